@@ -0,0 +1,136 @@
+// URL Matching Benchmarks
+// Per-request cost of the blocklist matchers against a synthetic 100k-rule list
+
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, Criterion};
+use servionx_browser_lib::privacy::{
+    blocklist_snapshot, BlockCategory, BlocklistManager, DomainSet, FeedFormat, FilterList, FilterRequest, PatternSet,
+    ResourceType, RuleSet,
+};
+
+const RULES: usize = 100_000;
+
+/// EasyList-shaped rules: host anchors, paths with wildcards and options,
+/// exceptions, regexes and cosmetic filters
+fn synthetic_list() -> String {
+    let mut list = String::from("[Adblock Plus 2.0]\n! Synthetic benchmark list\n");
+    for i in 0..RULES {
+        let rule = match i % 10 {
+            0..=3 => format!("||adhost{}.example^", i),
+            4 => format!("||cdn{}.example/ads/*.js$script,third-party", i),
+            5 => format!("/banner{}/*$image", i),
+            6 => format!("/track{}.gif^", i),
+            7 => format!("@@||adhost{}.example/consent^", i - 7),
+            8 => format!("||media{}.example/preroll$media,domain=tube{}.example", i, i),
+            _ => format!("site{}.example##.promo-{}", i, i),
+        };
+        list.push_str(&rule);
+        list.push('\n');
+    }
+    list
+}
+
+/// A mix of blocked, excepted and clean requests
+fn requests() -> Vec<FilterRequest> {
+    let page = Some("https://news.example/article");
+    vec![
+        FilterRequest::new("https://adhost40.example/pixel.png", page, ResourceType::Image),
+        FilterRequest::new("https://adhost50000.example/consent?id=1", page, ResourceType::Script),
+        FilterRequest::new("https://cdn.example/img/banner99995/728x90.png", page, ResourceType::Image),
+        FilterRequest::new("https://static.news.example/app.min.js?v=42", page, ResourceType::Script),
+        FilterRequest::new("https://images.news.example/photos/2026/10/lead.jpg", page, ResourceType::Image),
+    ]
+}
+
+fn bench_filter_list(c: &mut Criterion) {
+    let list = FilterList::parse(&synthetic_list());
+    let requests = requests();
+    c.bench_function("filter_list/check 100k rules", |b| {
+        b.iter(|| {
+            for request in &requests {
+                black_box(list.check(black_box(request)));
+            }
+        })
+    });
+    c.bench_function("filter_list/parse 100k rules", |b| {
+        let content = synthetic_list();
+        b.iter(|| FilterList::parse(black_box(&content)))
+    });
+}
+
+fn bench_domains(c: &mut Criterion) {
+    let domains: Vec<String> = (0..RULES).map(|i| format!("tracker{}.example", i)).collect();
+    let set: DomainSet = domains.iter().collect();
+    let hosts = ["a.b.tracker77777.example", "cdn.news.example", "www.example.org"];
+
+    c.bench_function("domains/suffix lookup 100k", |b| {
+        b.iter(|| {
+            for host in hosts {
+                black_box(set.matching(black_box(host)));
+            }
+        })
+    });
+    c.bench_function("domains/linear scan 100k", |b| {
+        b.iter(|| {
+            for host in hosts {
+                black_box(domains.iter().find(|d| host == d.as_str() || host.ends_with(&format!(".{}", d))));
+            }
+        })
+    });
+}
+
+fn bench_patterns(c: &mut Criterion) {
+    let patterns: Vec<String> = (0..RULES).map(|i| format!("/pixel{}/", i)).collect();
+    let set: PatternSet = patterns.iter().collect();
+    let urls = [
+        "https://cdn.example/pixel99999/p.gif",
+        "https://images.news.example/photos/2026/10/lead.jpg?w=1200&h=630",
+    ];
+
+    c.bench_function("patterns/aho-corasick 100k", |b| {
+        b.iter(|| {
+            for url in urls {
+                black_box(set.find(black_box(url)));
+            }
+        })
+    });
+    c.bench_function("patterns/linear scan 100k", |b| {
+        b.iter(|| {
+            for url in urls {
+                black_box(patterns.iter().find(|p| url.contains(p.as_str())));
+            }
+        })
+    });
+}
+
+fn bench_blocklist_manager(c: &mut Criterion) {
+    let manager = BlocklistManager::new();
+    manager.parse_easylist_rules(BlockCategory::Ads, &synthetic_list());
+    c.bench_function("blocklist_manager/should_block 100k rules", |b| {
+        b.iter(|| {
+            black_box(manager.should_block(black_box("https://ads.doubleclick.net/pagead/id")));
+            black_box(manager.should_block(black_box("https://static.news.example/app.min.js?v=42")));
+        })
+    });
+}
+
+fn bench_snapshot(c: &mut Criterion) {
+    let content = synthetic_list();
+    let mut rules = RuleSet::new();
+    rules.add_feed("synthetic", BlockCategory::Ads, FeedFormat::Adblock, &content).unwrap();
+    let bytes = blocklist_snapshot::encode(&rules, "bench", &[]).unwrap();
+
+    c.bench_function("startup/parse text 100k rules", |b| {
+        b.iter(|| {
+            let mut rules = RuleSet::new();
+            rules.add_feed("synthetic", BlockCategory::Ads, FeedFormat::Adblock, black_box(&content)).unwrap();
+            rules
+        })
+    });
+    c.bench_function("startup/load snapshot 100k rules", |b| {
+        b.iter(|| blocklist_snapshot::decode(black_box(&bytes), "bench").unwrap())
+    });
+}
+
+criterion_group!(benches, bench_filter_list, bench_domains, bench_patterns, bench_blocklist_manager, bench_snapshot);
+criterion_main!(benches);
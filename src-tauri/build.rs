@@ -1,3 +1,142 @@
+// Build Script
+// Generates the app command permissions that capabilities grant to windows
+
+/// Every command in `generate_handler!`; each gets `allow-`/`deny-` permissions
+const COMMANDS: &[&str] = &[
+    // Privacy commands
+    "get_fake_fingerprint",
+    "get_fake_geolocation",
+    "get_fake_user_agent",
+    "get_fake_ip_headers",
+    "get_outgoing_headers",
+    "set_site_ip_header_policy",
+    "regenerate_identity",
+    "regenerate_identity_from_seed",
+    "preview_identity_from_seed",
+    "validate_identity",
+    "get_fingerprint_dataset_info",
+    "list_geolocation_locations",
+    "get_location_mode",
+    "set_location_mode",
+    "set_location_route_from_gpx",
+    "list_site_identities",
+    "pin_site_identity",
+    "reset_site_identity",
+    "reset_all_site_identities",
+    "export_site_identities",
+    "list_profile_presets",
+    "list_site_profiles",
+    "get_site_layers",
+    "set_site_profile_preset",
+    "set_site_profile_layers",
+    "remove_site_profile",
+    "get_injection_scripts",
+    "check_whitelist",
+    "add_to_whitelist",
+    "remove_from_whitelist",
+    "get_whitelist",
+    "get_whitelist_entries",
+    "export_whitelist",
+    "import_whitelist",
+    "get_complete_injection_scripts",
+    "inspect_site_script",
+    "report_fingerprinting",
+    "get_fingerprinting_sites",
+    "get_site_fingerprinting",
+    "clear_fingerprinting_data",
+    "list_escalations",
+    "revert_escalation",
+    "run_self_test",
+    "submit_self_test_probes",
+    "diff_self_test_reports",
+    "list_storage_partitions",
+    "clear_site_storage",
+    "clear_all_site_storage",
+    "list_cookies",
+    "delete_site_cookies",
+    "sweep_cookies",
+    "get_cookie_rules",
+    "set_cookie_rule",
+    "remove_cookie_rule",
+    "get_blocklist_status",
+    "update_blocklists",
+    "check_url_blocking",
+    // Security commands
+    "unlock_settings",
+    "lock_settings",
+    "is_settings_locked",
+    "set_master_password",
+    "verify_master_password",
+    "unlock_logs",
+    "lock_logs",
+    "is_logs_locked",
+    "set_logs_password",
+    "verify_logs_password",
+    "has_profile",
+    "get_encrypted_logs",
+    "add_log_entry",
+    "get_live_logs",
+    "get_recent_live_logs",
+    "get_log_stats",
+    "clear_live_logs",
+    // Settings commands
+    "get_all_settings",
+    "update_all_settings",
+    "set_setting",
+    "get_setting",
+    "toggle_setting",
+    "reset_settings",
+    "set_regenerate_interval",
+    "set_escalation_threshold",
+    "set_cookie_cleanup_minutes",
+    // Metadata commands
+    "strip_file_metadata",
+    "get_file_metadata",
+    // Input commands
+    "get_virtual_keyboard_layout",
+    "process_virtual_key",
+    // Browser commands
+    "create_browser_tab",
+    "navigate_tab",
+    "close_browser_tab",
+    "get_browser_tabs",
+    "update_browser_tab",
+    "list_containers",
+    "create_container",
+    "delete_container",
+    // Download commands
+    "start_download",
+    "get_downloads",
+    "get_download",
+    "pause_download",
+    "resume_download",
+    "cancel_download",
+    "clear_completed_downloads",
+    "get_download_directory",
+    "execute_download",
+    // Video grabber commands
+    "get_detected_videos",
+    "get_all_detected_videos",
+    "get_video_detection_script",
+    "report_detected_media",
+    "download_video",
+    // Tools commands
+    "ssh_connect",
+    "ssh_disconnect",
+    "ssh_execute",
+    "sftp_list_directory",
+    "sftp_download",
+    "sftp_upload",
+    "network_ping",
+    "network_port_scan",
+    "network_dns_lookup",
+    "http_request",
+];
+
 fn main() {
-    tauri_build::build()
+    tauri_build::try_build(
+        tauri_build::Attributes::new()
+            .app_manifest(tauri_build::AppManifest::new().commands(COMMANDS)),
+    )
+    .expect("failed to run tauri-build");
 }
@@ -2,17 +2,19 @@
 // Tauri commands for browser tab management
 
 use tauri::{AppHandle, State};
-use super::{WebViewManager, BrowserTab};
+use super::{WebViewManager, BrowserTab, Container, ContainerManager};
+use crate::privacy::PrivacyEngine;
 
-/// Create a new browser tab
+/// Create a new browser tab, in a container if `container_id` is given
 #[tauri::command]
 pub async fn create_browser_tab(
     app: AppHandle,
     manager: State<'_, WebViewManager>,
     tab_id: String,
     url: String,
+    container_id: Option<String>,
 ) -> Result<BrowserTab, String> {
-    manager.create_tab(&app, &tab_id, &url)
+    manager.create_tab(&app, &tab_id, &url, container_id.as_deref())
 }
 
 /// Navigate to URL in a tab
@@ -52,3 +54,32 @@ pub fn update_browser_tab(
 ) {
     manager.update_tab(&tab_id, title, is_loading);
 }
+
+/// List the containers tabs can be opened in
+#[tauri::command]
+pub fn list_containers(containers: State<'_, ContainerManager>) -> Vec<Container> {
+    containers.list()
+}
+
+/// Create a container with a fresh identity. An ephemeral container is
+/// destroyed with everything it stored when its last tab closes.
+#[tauri::command]
+pub fn create_container(
+    containers: State<'_, ContainerManager>,
+    privacy: State<'_, PrivacyEngine>,
+    name: String,
+    color: String,
+    ephemeral: bool,
+) -> Result<Container, String> {
+    containers.create(&privacy, &name, &color, ephemeral)
+}
+
+/// Close a container's tabs and delete it with its identity and storage
+#[tauri::command]
+pub async fn delete_container(
+    app: AppHandle,
+    manager: State<'_, WebViewManager>,
+    container_id: String,
+) -> Result<Container, String> {
+    manager.destroy_container(&app, &container_id)
+}
@@ -0,0 +1,222 @@
+// Container Tabs
+// Named containers (Work, Personal, Shopping) and throwaway ephemeral ones.
+// Each container has its own fake identity and its own storage partitions;
+// ephemeral containers only live in memory and are destroyed with their
+// last tab.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+use crate::privacy::site_identity::{load_or_create_key, set_aside};
+use crate::privacy::{FakeIdentity, PrivacyEngine};
+use crate::security::Encryption;
+
+const STORE_FILE: &str = "containers.enc";
+const KEY_FILE: &str = "containers.key";
+
+/// Containers created on first start
+const DEFAULT_CONTAINERS: &[(&str, &str)] = &[
+    ("Work", "#3b82f6"),
+    ("Personal", "#22c55e"),
+    ("Shopping", "#f97316"),
+];
+
+/// A set of tabs sharing one identity and one cookie jar, apart from
+/// every other container
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Container {
+    pub id: String,
+    pub name: String,
+    pub color: String,
+    /// Destroyed, identity and storage included, when its last tab closes
+    pub ephemeral: bool,
+    pub identity: FakeIdentity,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The containers; named ones are encrypted on disk, ephemeral ones never
+/// leave memory
+pub struct ContainerManager {
+    data_dir: PathBuf,
+    key: Vec<u8>,
+    encryption: Encryption,
+    containers: RwLock<HashMap<String, Container>>,
+}
+
+impl ContainerManager {
+    /// Open the containers in the default app data directory
+    pub fn new(engine: &PrivacyEngine) -> Result<Self, String> {
+        let data_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ServionX Browser");
+        Self::open(&data_dir, engine)
+    }
+
+    /// Open the containers in `data_dir`, creating the default ones on
+    /// first use
+    pub fn open(data_dir: &Path, engine: &PrivacyEngine) -> Result<Self, String> {
+        std::fs::create_dir_all(data_dir).map_err(|e| e.to_string())?;
+
+        let manager = Self {
+            data_dir: data_dir.to_path_buf(),
+            key: load_or_create_key(&data_dir.join(KEY_FILE), &data_dir.join(STORE_FILE))?,
+            encryption: Encryption::new(),
+            containers: RwLock::new(HashMap::new()),
+        };
+
+        if data_dir.join(STORE_FILE).exists() {
+            match manager.load_from_disk() {
+                Ok(containers) => *manager.containers.write().unwrap() = containers,
+                Err(e) => {
+                    log::warn!("Could not load containers, starting empty: {}", e);
+                    set_aside(&data_dir.join(STORE_FILE))?;
+                }
+            }
+        } else {
+            for (name, color) in DEFAULT_CONTAINERS {
+                manager.create(engine, name, color, false)?;
+            }
+        }
+
+        Ok(manager)
+    }
+
+    fn load_from_disk(&self) -> Result<HashMap<String, Container>, String> {
+        let encoded = std::fs::read_to_string(self.data_dir.join(STORE_FILE)).map_err(|e| e.to_string())?;
+        let json = self.encryption.decrypt_from_base64(encoded.trim(), &self.key)?;
+        serde_json::from_slice(&json).map_err(|e| e.to_string())
+    }
+
+    fn save_to_disk(&self) -> Result<(), String> {
+        let json = {
+            let containers = self.containers.read().unwrap();
+            let named: HashMap<&String, &Container> = containers
+                .iter()
+                .filter(|(_, c)| !c.ephemeral)
+                .collect();
+            serde_json::to_vec(&named).map_err(|e| e.to_string())?
+        };
+        let encoded = self.encryption.encrypt_to_base64(&json, &self.key)?;
+        std::fs::write(self.data_dir.join(STORE_FILE), encoded).map_err(|e| e.to_string())
+    }
+
+    /// Create a container with a fresh identity
+    pub fn create(&self, engine: &PrivacyEngine, name: &str, color: &str, ephemeral: bool) -> Result<Container, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Container name cannot be empty".to_string());
+        }
+
+        let container = Container {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            color: color.to_string(),
+            ephemeral,
+            identity: engine.identity_from_seed(PrivacyEngine::random_seed()),
+            created_at: Utc::now(),
+        };
+        self.containers.write().unwrap().insert(container.id.clone(), container.clone());
+        if !ephemeral {
+            self.save_to_disk()?;
+        }
+
+        log::info!(
+            "Created {}container {} with identity {}",
+            if ephemeral { "ephemeral " } else { "" },
+            container.name,
+            container.identity.id
+        );
+        Ok(container)
+    }
+
+    /// Get a container by id
+    pub fn get(&self, id: &str) -> Option<Container> {
+        self.containers.read().unwrap().get(id).cloned()
+    }
+
+    /// All containers, named ones first, each group oldest first
+    pub fn list(&self) -> Vec<Container> {
+        let mut list: Vec<Container> = self.containers.read().unwrap().values().cloned().collect();
+        list.sort_by_key(|c| (c.ephemeral, c.created_at));
+        list
+    }
+
+    /// Ids of every container that exists
+    pub fn ids(&self) -> Vec<String> {
+        self.containers.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Remove a container. Its tabs and storage are the caller's to close
+    /// and clear.
+    pub fn remove(&self, id: &str) -> Result<Container, String> {
+        let container = self.containers
+            .write()
+            .unwrap()
+            .remove(id)
+            .ok_or_else(|| format!("Unknown container: {}", id))?;
+        if !container.ephemeral {
+            self.save_to_disk()?;
+        }
+        log::info!("Removed container {}", container.name);
+        Ok(container)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("servionx-containers-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_named_containers_persist_and_ephemeral_ones_do_not() {
+        let dir = temp_dir();
+        let engine = PrivacyEngine::open(&dir);
+        let manager = ContainerManager::open(&dir, &engine).unwrap();
+
+        let names: Vec<String> = manager.list().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, ["Work", "Personal", "Shopping"]);
+
+        let banking = manager.create(&engine, "Banking", "#ef4444", false).unwrap();
+        let temporary = manager.create(&engine, "Temporary", "#64748b", true).unwrap();
+        assert_ne!(banking.identity.id, temporary.identity.id);
+        assert!(manager.create(&engine, "  ", "#000000", false).is_err());
+
+        let reopened = ContainerManager::open(&dir, &engine).unwrap();
+        assert_eq!(reopened.get(&banking.id).unwrap().identity.id, banking.identity.id);
+        assert!(reopened.get(&temporary.id).is_none());
+        assert_eq!(reopened.list().len(), 4);
+
+        reopened.remove(&banking.id).unwrap();
+        assert!(ContainerManager::open(&dir, &engine).unwrap().get(&banking.id).is_none());
+    }
+
+    #[test]
+    fn test_lost_key_sets_the_store_aside() {
+        let dir = temp_dir();
+        let engine = PrivacyEngine::open(&dir);
+        let manager = ContainerManager::open(&dir, &engine).unwrap();
+        manager.create(&engine, "Banking", "#ef4444", false).unwrap();
+        let original = std::fs::read(dir.join(STORE_FILE)).unwrap();
+
+        std::fs::remove_file(dir.join(KEY_FILE)).unwrap();
+        let reopened = ContainerManager::open(&dir, &engine).unwrap();
+        assert_eq!(reopened.list().len(), DEFAULT_CONTAINERS.len());
+
+        let aside: Vec<PathBuf> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().contains("containers.enc.unreadable-"))
+            .collect();
+        assert_eq!(aside.len(), 1);
+        assert_eq!(std::fs::read(&aside[0]).unwrap(), original);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
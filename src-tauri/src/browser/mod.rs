@@ -2,22 +2,39 @@
 // Manages native webview windows for actual web browsing with privacy protection
 
 pub mod commands;
+pub mod containers;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
-use crate::privacy::{PrivacyEngine, TrackerBlocker, HttpsEnforcer};
+use tauri::webview::PageLoadEvent;
+use crate::privacy::{
+    PrivacyEngine, BlocklistManager, BlockVerdict, CategorySet, HttpsEnforcer, SiteIdentityStore, FakeIdentity, OutgoingHeaders,
+    InjectionScriptBuilder, InjectionScriptCache, ProtectionModule, ProtectionLayers, SiteProfileStore,
+    StoragePartition, StoragePartitioner,
+};
+use crate::privacy::script_cache::{self, CachedScript};
+use crate::settings::BrowserSettings;
+use crate::privacy::rotation::{IdentityRotator, RotationTrigger};
+
+pub use containers::{Container, ContainerManager};
 
 /// Represents a browser tab with its webview
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BrowserTab {
     pub id: String,
+    /// Top-level URL the webview shows, kept current as the page navigates
     pub url: String,
     pub title: String,
     pub is_loading: bool,
     pub can_go_back: bool,
     pub can_go_forward: bool,
+    /// Container the tab was opened in; `None` for ordinary tabs
+    #[serde(default)]
+    pub container_id: Option<String>,
 }
 
 /// Browser security settings
@@ -42,6 +59,11 @@ impl Default for SecuritySettings {
 pub struct WebViewManager {
     tabs: Arc<RwLock<HashMap<String, BrowserTab>>>,
     settings: Arc<RwLock<SecuritySettings>>,
+    // Current window label per tab; changes when a tab's webview is rebuilt
+    window_labels: Arc<RwLock<HashMap<String, String>>>,
+    window_generation: AtomicU64,
+    // Storage partition directory each tab's webview was built on
+    partition_dirs: Arc<RwLock<HashMap<String, PathBuf>>>,
 }
 
 impl WebViewManager {
@@ -49,6 +71,84 @@ impl WebViewManager {
         Self {
             tabs: Arc::new(RwLock::new(HashMap::new())),
             settings: Arc::new(RwLock::new(SecuritySettings::default())),
+            window_labels: Arc::new(RwLock::new(HashMap::new())),
+            window_generation: AtomicU64::new(0),
+            partition_dirs: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+    
+    /// Label of the webview window currently showing a tab
+    fn window_label(&self, tab_id: &str) -> String {
+        self.window_labels
+            .read()
+            .unwrap()
+            .get(tab_id)
+            .cloned()
+            .unwrap_or_else(|| format!("tab_{}", tab_id))
+    }
+    
+    /// Id of the tab shown in the webview window labelled `label`
+    fn tab_id_for_window(&self, label: &str) -> Option<String> {
+        self.window_labels
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(_, window)| window.as_str() == label)
+            .map(|(tab_id, _)| tab_id.clone())
+    }
+    
+    /// The tab shown in the webview window labelled `label`; `None` for the
+    /// app's own windows
+    pub(crate) fn tab_for_window(&self, label: &str) -> Option<BrowserTab> {
+        let tab_id = self.tab_id_for_window(label)?;
+        self.tabs.read().unwrap().get(&tab_id).cloned()
+    }
+    
+    /// Follow a page load in the webview window labelled `label`, including
+    /// those the page starts itself by following links or redirecting
+    fn record_page_load(&self, label: &str, url: &str, event: PageLoadEvent) {
+        let Some(tab_id) = self.tab_id_for_window(label) else {
+            return;
+        };
+        if let Some(tab) = self.tabs.write().unwrap().get_mut(&tab_id) {
+            tab.url = url.to_string();
+            tab.is_loading = event == PageLoadEvent::Started;
+        }
+    }
+    
+    /// Whether a top-level navigation from a webview built for `built_for` to
+    /// `target` leaves its site. Such a webview carries the wrong identity,
+    /// noise seeds, profile and storage partition for the new site.
+    fn crosses_site(built_for: &str, target: &str) -> bool {
+        let web = target.starts_with("http://") || target.starts_with("https://");
+        web && !crate::privacy::site::same_site(built_for, target)
+    }
+    
+    /// Data directories of the storage partitions open webviews are using
+    pub(crate) fn attached_partition_dirs(&self) -> HashSet<PathBuf> {
+        self.partition_dirs.read().unwrap().values().cloned().collect()
+    }
+    
+    /// Identity a page at `url` should see: its site's own identity when the
+    /// per-site store is available, the global identity otherwise, with
+    /// canvas and audio noise seeded for the site either way
+    pub(crate) fn identity_for_url(app: &AppHandle, url: &str) -> FakeIdentity {
+        let privacy_engine = app.state::<PrivacyEngine>();
+        let identity = match app.try_state::<SiteIdentityStore>() {
+            Some(store) => store.identity_for(&privacy_engine, url),
+            None => privacy_engine.get_identity(),
+        };
+        identity.for_site(url)
+    }
+    
+    /// Identity a page at `url` sees in a tab of `container`: the
+    /// container's own identity, or the per-site one outside containers
+    pub(crate) fn identity_for_tab(app: &AppHandle, container: Option<&str>, url: &str) -> FakeIdentity {
+        let container = container
+            .and_then(|id| app.try_state::<ContainerManager>()?.get(id));
+        match container {
+            Some(container) => container.identity.for_site(url),
+            None => Self::identity_for_url(app, url),
         }
     }
     
@@ -62,38 +162,159 @@ impl WebViewManager {
         *self.settings.write().unwrap() = settings;
     }
     
-    /// Create a new browser tab with native webview and privacy protection
-    pub fn create_tab(&self, app: &AppHandle, tab_id: &str, url: &str) -> Result<BrowserTab, String> {
-        use crate::privacy::{MalwareBlocker, MalwareCheckResult, FontFingerprint, 
-            ReferrerControl, FingerprintingDetector, StoragePartitioner};
+    /// Global settings from the settings manager, defaults before it is up
+    fn browser_settings(app: &AppHandle) -> BrowserSettings {
+        app.try_state::<crate::settings::SettingsManager>()
+            .map(|s| s.get_settings())
+            .unwrap_or_default()
+    }
+    
+    /// Protection layers for a page on `url`: the site's profile if it has
+    /// one, else the global settings, with real data for whitelisted sites
+    pub(crate) fn layers_for_url(&self, app: &AppHandle, url: &str, settings: &BrowserSettings) -> ProtectionLayers {
+        if let Some(profile) = app.try_state::<SiteProfileStore>().and_then(|store| store.get(url)) {
+            return profile.layers;
+        }
+        let layers = ProtectionLayers::from_settings(settings, &self.get_settings());
+        if app.state::<PrivacyEngine>().is_whitelisted(url) {
+            layers.real_data()
+        } else {
+            layers
+        }
+    }
+    
+    /// Every protection module for a page on `url`, all derived from
+    /// `identity` and switched on or off by `settings` and the site's `layers`
+    pub(crate) fn injection_builder(
+        app: &AppHandle,
+        identity: &FakeIdentity,
+        settings: &BrowserSettings,
+        layers: &ProtectionLayers,
+        url: &str,
+    ) -> InjectionScriptBuilder {
+        use crate::privacy::{FontFingerprint, ReferrerControl, FingerprintingDetector};
         
-        let window_label = format!("tab_{}", tab_id);
-        let settings = self.get_settings();
+        let font_fp = app.state::<FontFingerprint>();
+        let advanced_fp = app.state::<crate::privacy::AdvancedFingerprintProtection>();
+        let network_sec = app.state::<crate::security::NetworkSecurity>();
+        let complete_fake = app.state::<crate::privacy::CompleteFakeData>();
+        let ultimate = app.state::<crate::privacy::UltimatePrivacyProtection>();
+        let upload_prot = app.state::<crate::metadata::FakeFileMetadata>();
+        let additional = app.state::<crate::privacy::AdditionalProtection>();
+        let blocklist = app.state::<crate::privacy::BlocklistManager>();
         
-        // Check malware blocker first
-        let malware_blocker = app.state::<MalwareBlocker>();
-        match malware_blocker.check_url(url) {
-            MalwareCheckResult::Blocked { reason } => {
-                log::warn!("Malware blocker: {}", reason);
-                return Err(format!("🛑 Blocked: {}", reason));
-            }
-            MalwareCheckResult::Suspicious { reason } => {
-                log::warn!("Suspicious URL: {}", reason);
-                // Continue but log warning
+        // With geolocation spoofing on, the complete-data layer must not block
+        // the API the base layer answers with the identity's location
+        let complete_fake = crate::privacy::CompleteFakeData {
+            spoof_geolocation: settings.fake_geolocation,
+            ..complete_fake.with_identity(identity)
+        };
+        
+        let categories = CategorySet::for_page(settings, layers);
+        
+        // Modules switched by settings follow the site's layers too
+        let settings = &layers.apply_to(settings);
+        
+        InjectionScriptBuilder::new()
+            .module(ProtectionModule::JavaScriptPolicy, layers.get_injection_script() + &blocklist.get_script_blocking_script(url))
+            .module(ProtectionModule::ContentFilter, blocklist.get_cosmetic_script(url, categories))
+            .module(ProtectionModule::Identity, crate::privacy::generate_injection_script_with_layers(identity, layers))
+            .module(ProtectionModule::Fonts, font_fp.with_identity(identity).get_injection_script())
+            .module(ProtectionModule::Referrer, ReferrerControl::injection_script_for(layers.referrer_policy))
+            .module(ProtectionModule::FingerprintDetector, FingerprintingDetector::get_injection_script())
+            .module(ProtectionModule::AdvancedFingerprint, advanced_fp.with_identity(identity).get_injection_script())
+            .module(ProtectionModule::NetworkSecurity, network_sec.get_injection_script())
+            .module(ProtectionModule::CompleteFakeData, complete_fake.get_master_injection_script())
+            .module(ProtectionModule::Ultimate, ultimate.with_identity(identity).with_layers(layers).get_ultimate_injection_script())
+            .module(ProtectionModule::UploadProtection, upload_prot.get_upload_protection_script())
+            .module(ProtectionModule::Additional, additional.get_injection_script())
+            .module(ProtectionModule::IpHeaders, OutgoingHeaders::for_request(identity, settings, url).get_injection_script())
+            .with_settings(settings)
+    }
+    
+    /// Minified script for `identity` on `url`, served from the script cache
+    /// unless the identity, the site's layers or a setting that shapes it changed
+    pub(crate) fn page_script(
+        app: &AppHandle,
+        identity: &FakeIdentity,
+        settings: &BrowserSettings,
+        layers: &ProtectionLayers,
+        url: &str,
+    ) -> Arc<CachedScript> {
+        let key = Self::script_key(app, identity, settings, layers, url);
+        let build = || Self::injection_builder(app, identity, settings, layers, url);
+        match app.try_state::<InjectionScriptCache>() {
+            Some(cache) => cache.get_or_build(&key, &identity.id, build),
+            None => InjectionScriptCache::new().get_or_build(&key, &identity.id, build),
+        }
+    }
+    
+    /// Script cache key for `identity` on `url`, at the current blocklist
+    pub(crate) fn script_key(
+        app: &AppHandle,
+        identity: &FakeIdentity,
+        settings: &BrowserSettings,
+        layers: &ProtectionLayers,
+        url: &str,
+    ) -> String {
+        let generation = app.try_state::<BlocklistManager>().map_or(0, |blocklist| blocklist.generation());
+        script_cache::cache_key(identity, &layers.apply_to(settings), layers, url, generation)
+    }
+    
+    /// Refuse `url` when a list of a category the settings switch on blocks
+    /// it. Malware and phishing lists apply on every site, the others only
+    /// where the site's layers keep tracker blocking on.
+    fn check_content_filter(app: &AppHandle, url: &str, layers: &ProtectionLayers) -> Result<(), String> {
+        let categories = CategorySet::for_page(&Self::browser_settings(app), layers);
+        match app.state::<BlocklistManager>().check_navigation(url, categories) {
+            BlockVerdict::Blocked(rule) => Err(format!("🛑 Blocked {}: {}", url, rule)),
+            // Suspicious URLs load; the filter logs the warning
+            _ => Ok(()),
+        }
+    }
+    
+    /// Storage partition of the top-level site of `url` in `container`;
+    /// `None` leaves the webview on the shared default store. Container tabs
+    /// never fall back to the shared store: without a partition they fail.
+    fn partition_for(app: &AppHandle, container: Option<&str>, url: &str) -> Result<Option<StoragePartition>, String> {
+        let Some(partitioner) = app.try_state::<StoragePartitioner>() else {
+            return match container {
+                Some(_) => Err("Storage partitioning is unavailable for container tabs".to_string()),
+                None => Ok(None),
+            };
+        };
+        match partitioner.partition_in(container, url) {
+            Ok(partition) => Ok(partition),
+            Err(e) if container.is_some() => Err(format!("No storage partition for {}: {}", url, e)),
+            Err(e) => {
+                log::error!("No storage partition for {}, using the shared store: {}", url, e);
+                Ok(None)
             }
-            MalwareCheckResult::Safe => {}
         }
+    }
+    
+    /// Create a new browser tab with native webview and privacy protection,
+    /// inside `container_id` if given
+    pub fn create_tab(
+        &self,
+        app: &AppHandle,
+        tab_id: &str,
+        url: &str,
+        container_id: Option<&str>,
+    ) -> Result<BrowserTab, String> {
+        let settings = self.get_settings();
         
-        // Get privacy engine for injection scripts
-        let privacy_engine = app.state::<PrivacyEngine>();
-        let base_injection = privacy_engine.get_injection_script();
-        
-        // Check tracker blocker
-        let tracker_blocker = app.state::<TrackerBlocker>();
-        if settings.tracker_blocking && tracker_blocker.should_block(url) {
-            return Err(format!("Blocked tracker: {}", url));
+        if let Some(id) = container_id {
+            let containers = app.try_state::<ContainerManager>().ok_or("Containers are unavailable")?;
+            if containers.get(id).is_none() {
+                return Err(format!("Unknown container: {}", id));
+            }
         }
         
+        // Check the content filter
+        let layers = self.layers_for_url(app, url, &Self::browser_settings(app));
+        Self::check_content_filter(app, url, &layers)?;
+        
         // Enforce HTTPS
         let https_enforcer = app.state::<HttpsEnforcer>();
         let final_url = if settings.https_only {
@@ -105,6 +326,34 @@ impl WebViewManager {
             url.to_string()
         };
         
+        self.open_window(app, tab_id, &final_url, container_id)?;
+        
+        // Log security status
+        log::info!("Created protected browser tab {} for URL: {}", tab_id, final_url);
+        log::info!("  → Tracker blocking: {}", if layers.tracker_blocking { "ON" } else { "OFF" });
+        log::info!("  → HTTPS enforced: {}", if settings.https_only { "ON" } else { "OFF" });
+        log::info!("  → JavaScript: {}", if layers.javascript { "ON" } else { "OFF" });
+        log::info!("  → Requests blocked so far: {}", app.state::<BlocklistManager>().get_stats().blocked_count);
+        
+        let tab = BrowserTab {
+            id: tab_id.to_string(),
+            url: final_url,
+            title: "Loading...".to_string(),
+            is_loading: true,
+            can_go_back: false,
+            can_go_forward: false,
+            container_id: container_id.map(str::to_string),
+        };
+        
+        self.tabs.write().unwrap().insert(tab_id.to_string(), tab.clone());
+        
+        Ok(tab)
+    }
+    
+    /// Build the protected webview window for a tab of `container` showing
+    /// `final_url`. Injection scripts are fixed when a webview is created, so
+    /// this is also how a tab switches to another site's identity.
+    fn open_window(&self, app: &AppHandle, tab_id: &str, final_url: &str, container: Option<&str>) -> Result<(), String> {
         // Build the webview URL
         let webview_url = if final_url.is_empty() {
             WebviewUrl::App("index.html".into())
@@ -112,78 +361,91 @@ impl WebViewManager {
             WebviewUrl::External(final_url.parse().map_err(|e| format!("Invalid URL: {}", e))?)
         };
         
-        // Get fake user agent from privacy engine
-        let fake_ua = privacy_engine.get_identity().user_agent.full;
-        
-        // Combine ALL injection scripts for MAXIMUM protection (7 layers)
-        let font_fp = app.state::<FontFingerprint>();
-        let referrer_ctrl = app.state::<ReferrerControl>();
-        let advanced_fp = app.state::<crate::privacy::AdvancedFingerprintProtection>();
-        let network_sec = app.state::<crate::security::NetworkSecurity>();
-        let complete_fake = app.state::<crate::privacy::CompleteFakeData>();
-        let ultimate = app.state::<crate::privacy::UltimatePrivacyProtection>();
-        let upload_prot = app.state::<crate::metadata::FakeFileMetadata>();
-        let additional = app.state::<crate::privacy::AdditionalProtection>();
+        // Every layer below derives its spoofed values from the identity of
+        // the top-level site; third-party frames share the same webview
+        let identity = Self::identity_for_tab(app, container, final_url);
+        let browser_settings = Self::browser_settings(app);
+        let layers = self.layers_for_url(app, final_url, &browser_settings);
+        let page_script = Self::page_script(app, &identity, &browser_settings, &layers, final_url);
         
-        let combined_injection = format!(
-            "{}\\n{}\\n{}\\n{}\\n{}\\n{}\\n{}\\n{}\\n{}\\n{}\\n{}",
-            base_injection,
-            font_fp.get_injection_script(),
-            referrer_ctrl.get_injection_script(),
-            FingerprintingDetector::get_injection_script(),
-            StoragePartitioner::get_injection_script(),
-            advanced_fp.get_injection_script(),
-            network_sec.get_injection_script(),
-            complete_fake.get_master_injection_script(),
-            ultimate.get_ultimate_injection_script(),
-            upload_prot.get_upload_protection_script(),
-            additional.get_injection_script()
+        log::info!(
+            "Combined injection script: {} bytes minified from {} ({} protection modules)",
+            page_script.script.len(),
+            page_script.original_bytes,
+            page_script.modules.len()
         );
         
-        log::info!("Combined injection script: {} bytes (7 protection layers)", combined_injection.len());
+        // The first window for a tab keeps the plain label; rebuilt windows
+        // get a fresh one since the old label may not be released yet
+        let window_label = if self.window_labels.read().unwrap().contains_key(tab_id) {
+            let generation = self.window_generation.fetch_add(1, Ordering::Relaxed) + 1;
+            format!("tab_{}_{}", tab_id, generation)
+        } else {
+            format!("tab_{}", tab_id)
+        };
         
         // Create the webview window with privacy protections
-        let _window = WebviewWindowBuilder::new(app, &window_label, webview_url)
+        let mut builder = WebviewWindowBuilder::new(app, &window_label, webview_url)
             .title("ServionX Browser - Protected")
             .inner_size(1200.0, 800.0)
             .visible(true)
-            .initialization_script(&combined_injection)  // Inject ALL privacy scripts
-            .user_agent(&fake_ua)  // Use fake user agent
-            .build()
-            .map_err(|e| e.to_string())?;
-        
-        // Log security status
-        log::info!("Created protected browser tab {} for URL: {}", tab_id, final_url);
-        log::info!("  → Tracker blocking: {}", if settings.tracker_blocking { "ON" } else { "OFF" });
-        log::info!("  → HTTPS enforced: {}", if settings.https_only { "ON" } else { "OFF" });
-        log::info!("  → JavaScript: {}", if settings.javascript_enabled { "ON" } else { "OFF" });
-        log::info!("  → Trackers blocked so far: {}", tracker_blocker.get_blocked_count());
+            .initialization_script(&page_script.script)  // Inject ALL privacy scripts
+            .on_page_load(|window, payload| {
+                window.state::<WebViewManager>().record_page_load(window.label(), payload.url().as_str(), payload.event());
+            })
+            .on_navigation({
+                // Links, redirects and scripts that leave the site are
+                // cancelled and reopened through `navigate`, which rebuilds
+                // the webview for the new site like a typed URL
+                let (app, tab_id, built_for) = (app.clone(), tab_id.to_string(), final_url.to_string());
+                move |url| {
+                    if !Self::crosses_site(&built_for, url.as_str()) {
+                        return true;
+                    }
+                    let (app, tab_id, url) = (app.clone(), tab_id.clone(), url.to_string());
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = app.state::<WebViewManager>().navigate(&app, &tab_id, &url) {
+                            log::warn!("Could not move tab {} to {}: {}", tab_id, url, e);
+                        }
+                    });
+                    false
+                }
+            });
+        if layers.ua_spoof {
+            builder = builder.user_agent(&identity.user_agent.full);  // Use fake user agent
+        }
+        // Container tabs are partitioned whatever the site's profile says;
+        // the shared store would mix their data with other containers
+        let mut data_dir = None;
+        if layers.storage_partitioning || container.is_some() {
+            if let Some(partition) = Self::partition_for(app, container, final_url)? {
+                builder = builder
+                    .data_store_identifier(partition.store_identifier())
+                    .data_directory(partition.data_dir.clone());
+                data_dir = Some(partition.data_dir);
+            }
+        }
+        let _window = builder.build().map_err(|e| e.to_string())?;
         
-        let tab = BrowserTab {
-            id: tab_id.to_string(),
-            url: final_url,
-            title: "Loading...".to_string(),
-            is_loading: true,
-            can_go_back: false,
-            can_go_forward: false,
+        self.window_labels.write().unwrap().insert(tab_id.to_string(), window_label);
+        match data_dir {
+            Some(dir) => self.partition_dirs.write().unwrap().insert(tab_id.to_string(), dir),
+            None => self.partition_dirs.write().unwrap().remove(tab_id),
         };
+        log::info!("Tab {} uses identity {}", tab_id, identity.id);
         
-        self.tabs.write().unwrap().insert(tab_id.to_string(), tab.clone());
-        
-        Ok(tab)
+        Ok(())
     }
     
     /// Navigate a tab to a new URL
     pub fn navigate(&self, app: &AppHandle, tab_id: &str, url: &str) -> Result<(), String> {
-        let window_label = format!("tab_{}", tab_id);
+        let window_label = self.window_label(tab_id);
         let settings = self.get_settings();
         
         if let Some(window) = app.get_webview_window(&window_label) {
-            // Check tracker blocker
-            let tracker_blocker = app.state::<TrackerBlocker>();
-            if settings.tracker_blocking && tracker_blocker.should_block(url) {
-                return Err(format!("Blocked tracker: {}", url));
-            }
+            // Check the content filter
+            let layers = self.layers_for_url(app, url, &Self::browser_settings(app));
+            Self::check_content_filter(app, url, &layers)?;
             
             // Prepare URL with HTTPS enforcement
             let mut final_url = if !url.starts_with("http://") && !url.starts_with("https://") {
@@ -201,8 +463,20 @@ impl WebViewManager {
                 }
             }
             
-            window.navigate(final_url.parse().map_err(|e| format!("Invalid URL: {}", e))?)
-                .map_err(|e| e.to_string())?;
+            let (current_url, container) = self.tabs.read().unwrap()
+                .get(tab_id)
+                .map(|t| (t.url.clone(), t.container_id.clone()))
+                .unwrap_or_default();
+            
+            if crate::privacy::site::same_site(&current_url, &final_url) {
+                window.navigate(final_url.parse().map_err(|e| format!("Invalid URL: {}", e))?)
+                    .map_err(|e| e.to_string())?;
+            } else {
+                // Crossing to another site: rebuild the webview so the new
+                // site gets its own identity instead of inheriting this one
+                window.close().map_err(|e| e.to_string())?;
+                self.open_window(app, tab_id, &final_url, container.as_deref())?;
+            }
             
             // Update tab state
             if let Some(tab) = self.tabs.write().unwrap().get_mut(tab_id) {
@@ -218,17 +492,165 @@ impl WebViewManager {
     
     /// Close a tab
     pub fn close_tab(&self, app: &AppHandle, tab_id: &str) -> Result<(), String> {
-        let window_label = format!("tab_{}", tab_id);
+        let window_label = self.window_label(tab_id);
         
         if let Some(window) = app.get_webview_window(&window_label) {
             window.close().map_err(|e| e.to_string())?;
         }
         
-        self.tabs.write().unwrap().remove(tab_id);
+        let closed = self.tabs.write().unwrap().remove(tab_id);
+        self.window_labels.write().unwrap().remove(tab_id);
+        self.partition_dirs.write().unwrap().remove(tab_id);
+        
+        let Some(closed) = closed else {
+            return Ok(());
+        };
+        
+        // Last tab of an ephemeral container gone: destroy the container.
+        // Data the closing webview still holds goes at the next start.
+        if let Some(container_id) = &closed.container_id {
+            let container_still_open = self.tabs.read().unwrap()
+                .values()
+                .any(|t| t.container_id.as_ref() == Some(container_id));
+            let ephemeral = app.try_state::<ContainerManager>()
+                .and_then(|c| c.get(container_id))
+                .is_some_and(|c| c.ephemeral);
+            if ephemeral && !container_still_open {
+                self.destroy_container(app, container_id)?;
+            }
+            return Ok(());
+        }
+        
+        // Last tab for a site gone: let the rotator retire that site's identity
+        let site_still_open = self.tabs.read().unwrap()
+            .values()
+            .filter(|t| t.container_id.is_none())
+            .any(|t| crate::privacy::site::same_site(&t.url, &closed.url));
+        if !site_still_open {
+            if let (Some(rotator), Some(site)) = (
+                app.try_state::<IdentityRotator>(),
+                crate::privacy::site::registrable_domain(&closed.url),
+            ) {
+                rotator.rotate(app, RotationTrigger::SiteClosed(site));
+            }
+        }
         
         Ok(())
     }
     
+    /// Close every tab of a container, then delete the container with its
+    /// identity and storage
+    pub fn destroy_container(&self, app: &AppHandle, container_id: &str) -> Result<Container, String> {
+        let open_tabs: Vec<String> = self.tabs.read().unwrap()
+            .values()
+            .filter(|t| t.container_id.as_deref() == Some(container_id))
+            .map(|t| t.id.clone())
+            .collect();
+        for tab_id in open_tabs {
+            if let Some(window) = app.get_webview_window(&self.window_label(&tab_id)) {
+                window.destroy().map_err(|e| e.to_string())?;
+            }
+            self.tabs.write().unwrap().remove(&tab_id);
+            self.window_labels.write().unwrap().remove(&tab_id);
+            self.partition_dirs.write().unwrap().remove(&tab_id);
+        }
+        
+        let containers = app.try_state::<ContainerManager>().ok_or("Containers are unavailable")?;
+        let container = containers.remove(container_id)?;
+        if let Some(partitioner) = app.try_state::<StoragePartitioner>() {
+            partitioner.clear_container(container_id)?;
+        }
+        Ok(container)
+    }
+    
+    /// Recreate every tab's webview at its current URL. Injection scripts are
+    /// fixed per webview, so this is how a new identity reaches open pages.
+    pub fn rebuild_tabs(&self, app: &AppHandle) -> usize {
+        self.rebuild_tabs_where(app, |_| true)
+    }
+    
+    /// Recreate the webviews of the tabs showing `site`, e.g. after its
+    /// protection profile changed
+    pub fn rebuild_site_tabs(&self, app: &AppHandle, site: &str) -> usize {
+        self.rebuild_tabs_where(app, |tab| {
+            crate::privacy::site::registrable_domain(&tab.url).as_deref() == Some(site)
+        })
+    }
+    
+    /// Delete everything `site` stored. Its tabs are closed while the data
+    /// goes and come back on a fresh store. Returns the bytes freed.
+    pub fn clear_site_storage(&self, app: &AppHandle, site: &str) -> Result<u64, String> {
+        let partitioner = app.try_state::<StoragePartitioner>()
+            .ok_or("Storage partitioning is unavailable")?;
+        let mut cleared = Ok(0);
+        self.rebuild_tabs_between(
+            app,
+            |tab| crate::privacy::site::registrable_domain(&tab.url).as_deref() == Some(site),
+            || cleared = partitioner.clear_site(site),
+        );
+        cleared
+    }
+    
+    /// Delete the stored data of every site, reopening all tabs on fresh
+    /// stores. Returns the bytes freed.
+    pub fn clear_all_site_storage(&self, app: &AppHandle) -> Result<u64, String> {
+        let partitioner = app.try_state::<StoragePartitioner>()
+            .ok_or("Storage partitioning is unavailable")?;
+        let mut cleared = Ok(0);
+        self.rebuild_tabs_between(app, |_| true, || cleared = partitioner.clear_all());
+        cleared
+    }
+    
+    fn rebuild_tabs_where(&self, app: &AppHandle, matches: impl Fn(&BrowserTab) -> bool) -> usize {
+        self.rebuild_tabs_between(app, matches, || {})
+    }
+    
+    /// Close the matching tabs' webviews, run `between` while none is open,
+    /// then recreate them at their current URLs
+    fn rebuild_tabs_between(
+        &self,
+        app: &AppHandle,
+        matches: impl Fn(&BrowserTab) -> bool,
+        between: impl FnOnce(),
+    ) -> usize {
+        let mut closed = Vec::new();
+        for tab in self.get_tabs().into_iter().filter(|t| matches(t)) {
+            if let Some(window) = app.get_webview_window(&self.window_label(&tab.id)) {
+                if let Err(e) = window.destroy() {
+                    log::warn!("Could not close tab {} for rebuild: {}", tab.id, e);
+                    continue;
+                }
+            }
+            closed.push(tab);
+        }
+        
+        between();
+        
+        let mut rebuilt = 0;
+        for tab in closed {
+            match self.open_window(app, &tab.id, &tab.url, tab.container_id.as_deref()) {
+                Ok(()) => {
+                    self.update_tab(&tab.id, None, Some(true));
+                    rebuilt += 1;
+                }
+                Err(e) => log::error!("Failed to rebuild tab {}: {}", tab.id, e),
+            }
+        }
+        
+        rebuilt
+    }
+    
+    /// Every open tab with its webview window
+    pub fn tab_windows(&self, app: &AppHandle) -> Vec<(BrowserTab, tauri::WebviewWindow)> {
+        self.get_tabs()
+            .into_iter()
+            .filter_map(|tab| {
+                let window = app.get_webview_window(&self.window_label(&tab.id))?;
+                Some((tab, window))
+            })
+            .collect()
+    }
+    
     /// Get all tabs
     pub fn get_tabs(&self) -> Vec<BrowserTab> {
         self.tabs.read().unwrap().values().cloned().collect()
@@ -252,3 +674,23 @@ impl Default for WebViewManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_leaving_the_site_rebuilds_the_webview() {
+        let built_for = "https://www.shop.example/cart";
+        
+        assert!(!WebViewManager::crosses_site(built_for, "https://shop.example/checkout#pay"));
+        assert!(!WebViewManager::crosses_site(built_for, "https://cdn.shop.example/login"));
+        assert!(WebViewManager::crosses_site(built_for, "https://bank.example/"));
+        assert!(WebViewManager::crosses_site(built_for, "http://shop.example.org/"));
+        // A blank tab's first page gets its own site's webview too
+        assert!(WebViewManager::crosses_site("", "https://shop.example/"));
+        // Pages that aren't on the web stay where they are
+        assert!(!WebViewManager::crosses_site(built_for, "about:blank"));
+        assert!(!WebViewManager::crosses_site(built_for, "blob:https://shop.example/1b2c"));
+    }
+}
@@ -1,7 +1,7 @@
 //! Download Commands
 //! Tauri commands for download and video grabbing functionality
 
-use tauri::State;
+use tauri::{AppHandle, Manager, State};
 use super::{SmartDownloader, Download, VideoGrabber, DetectedMedia};
 
 // ========== DOWNLOAD COMMANDS ==========
@@ -134,6 +134,7 @@ pub async fn download_video(
 /// Execute actual HTTP download
 #[tauri::command]
 pub async fn execute_download(
+    app: AppHandle,
     downloader: State<'_, SmartDownloader>,
     id: String,
 ) -> Result<(), String> {
@@ -142,13 +143,20 @@ pub async fn execute_download(
     
     log::info!("Starting download: {} -> {}", download.url, download.save_path.display());
     
+    // Send the same identity headers the site's tabs would
+    let identity = crate::browser::WebViewManager::identity_for_url(&app, &download.url);
+    let settings = app
+        .try_state::<crate::settings::SettingsManager>()
+        .map(|s| s.get_settings())
+        .unwrap_or_default();
+    let headers = crate::privacy::OutgoingHeaders::for_request(&identity, &settings, &download.url);
+    
     // Use reqwest to download
     let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
         .build()
         .map_err(|e| e.to_string())?;
     
-    let response = client.get(&download.url)
+    let response = headers.apply(client.get(&download.url))
         .send()
         .await
         .map_err(|e| format!("Request failed: {}", e))?;
@@ -10,7 +10,7 @@ pub mod browser;
 pub mod settings;
 pub mod downloads;
 
-use tauri::Manager;
+use tauri::{Listener, Manager};
 
 // Re-export commonly used types
 pub use privacy::PrivacyEngine;
@@ -37,19 +37,53 @@ pub fn run() {
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_os::init())
         .plugin(tauri_plugin_clipboard_manager::init())
+        // Offline fingerprint self-test page
+        .register_uri_scheme_protocol(privacy::self_test::SELF_TEST_SCHEME, |_ctx, _request| {
+            tauri::http::Response::builder()
+                .header(tauri::http::header::CONTENT_TYPE, "text/html; charset=utf-8")
+                .body(privacy::self_test::SELF_TEST_PAGE.as_bytes().to_vec())
+                .unwrap_or_default()
+        })
         .setup(|app| {
             log::info!("Setting up ServionX Browser...");
             
             // Initialize privacy engine
             let privacy_engine = privacy::PrivacyEngine::new();
+            let whitelist_path = dirs::data_dir()
+                .unwrap_or_else(|| std::path::PathBuf::from("."))
+                .join("ServionX Browser")
+                .join("whitelist.json");
+            match privacy_engine.load_whitelist(&whitelist_path) {
+                Ok(count) => log::info!("Whitelist loaded ({} entries)", count),
+                Err(e) => log::error!("Failed to load whitelist: {}", e),
+            }
             app.manage(privacy_engine);
             log::info!("Privacy engine initialized");
             
-            // Initialize tracker blocker
-            let tracker_blocker = privacy::TrackerBlocker::new();
-            app.manage(tracker_blocker);
-            log::info!("Tracker blocker initialized ({} domains blocked)", 
-                privacy::tracker_blocker::TRACKER_DOMAINS.len());
+            // Initialize per-site identity store
+            match privacy::SiteIdentityStore::new() {
+                Ok(site_identities) => {
+                    log::info!("Site identity store initialized ({} sites)", site_identities.list().len());
+                    app.manage(site_identities);
+                }
+                Err(e) => {
+                    log::error!("Failed to initialize site identity store: {}", e);
+                }
+            }
+            
+            // Initialize per-site protection profiles
+            match privacy::SiteProfileStore::new() {
+                Ok(site_profiles) => {
+                    log::info!("Site protection profiles loaded ({} sites)", site_profiles.list().len());
+                    app.manage(site_profiles);
+                }
+                Err(e) => {
+                    log::error!("Failed to initialize site profiles: {}", e);
+                }
+            }
+            
+            // Initialize fingerprint self-test
+            app.manage(privacy::SelfTestRunner::new());
             
             // Initialize HTTPS enforcer
             let https_enforcer = privacy::HttpsEnforcer::new();
@@ -57,9 +91,10 @@ pub fn run() {
             log::info!("HTTPS enforcer initialized");
             
             // Initialize font fingerprint protection
-            let font_fingerprint = privacy::FontFingerprint::new();
-            log::info!("Font fingerprint protection initialized ({} fonts spoofed)", 
-                font_fingerprint.get_fonts().len());
+            let font_fingerprint = privacy::FontFingerprint::with_dataset(
+                &app.state::<privacy::PrivacyEngine>().dataset());
+            log::info!("Font fingerprint protection initialized ({} system fonts hidden by default)", 
+                font_fingerprint.hidden_fonts().len());
             app.manage(font_fingerprint);
             
             // Initialize referrer control
@@ -67,26 +102,82 @@ pub fn run() {
             app.manage(referrer_control);
             log::info!("Referrer control initialized (NoReferrer mode)");
             
-            // Initialize malware blocker
-            let malware_blocker = privacy::MalwareBlocker::new();
-            app.manage(malware_blocker);
-            log::info!("Malware/phishing blocker initialized");
-            
             // Initialize fingerprinting detector
             let fingerprint_detector = privacy::FingerprintingDetector::new();
             app.manage(fingerprint_detector);
             log::info!("Fingerprinting detector initialized");
             
-            // Initialize storage partitioner
-            let storage_partitioner = privacy::StoragePartitioner::new();
-            app.manage(storage_partitioner);
-            log::info!("Storage partitioner initialized");
+            // Initialize per-site storage partitions; without them every
+            // webview shares the default data store
+            match privacy::StoragePartitioner::new() {
+                Ok(storage_partitioner) => {
+                    log::info!("Storage partitions loaded ({} sites)", storage_partitioner.get_partitioned_count());
+                    app.manage(storage_partitioner);
+                }
+                Err(e) => {
+                    log::error!("Failed to initialize storage partitions: {}", e);
+                }
+            }
+            
+            // Initialize the cookie manager; its cleanup starts with the
+            // other schedulers below
+            match privacy::CookieManager::new() {
+                Ok(cookie_manager) => {
+                    log::info!("Cookie manager initialized ({} site rules)", cookie_manager.rules().len());
+                    app.manage(cookie_manager);
+                }
+                Err(e) => {
+                    log::error!("Failed to initialize cookie manager: {}", e);
+                }
+            }
+            
+            // Initialize container tabs; partitions of containers that are
+            // gone (ephemeral ones from the last run) are deleted
+            match browser::ContainerManager::new(&app.state::<privacy::PrivacyEngine>()) {
+                Ok(containers) => {
+                    if let Some(partitioner) = app.try_state::<privacy::StoragePartitioner>() {
+                        if let Err(e) = partitioner.retain_containers(&containers.ids()) {
+                            log::warn!("Could not delete leftover container storage: {}", e);
+                        }
+                    }
+                    log::info!("Containers loaded ({})", containers.list().len());
+                    app.manage(containers);
+                }
+                Err(e) => {
+                    log::error!("Failed to initialize containers: {}", e);
+                }
+            }
             
-            // Initialize blocklist manager (smart ad blocking)
+            // Initialize the content filter (ads, trackers, malware, phishing,
+            // social widgets and cryptominers)
             let blocklist_manager = privacy::BlocklistManager::new();
-            log::info!("Blocklist manager initialized (ad/tracker blocking)");
+            log::info!("Content filter initialized ({} built-in rules)",
+                privacy::builtin_lists::builtin_rule_count());
+            
+            // Initialize fingerprinting escalations and re-block their scripts
+            match privacy::EscalationManager::new() {
+                Ok(escalations) => {
+                    escalations.apply_blocked_scripts(&blocklist_manager);
+                    log::info!("Fingerprinting escalations loaded ({} decisions)", escalations.list().len());
+                    app.manage(escalations);
+                }
+                Err(e) => {
+                    log::error!("Failed to initialize fingerprinting escalations: {}", e);
+                }
+            }
             app.manage(blocklist_manager);
             
+            // Initialize blocklist feed updates (cached lists load in the background)
+            match privacy::BlocklistUpdater::new() {
+                Ok(updater) => {
+                    app.manage(updater);
+                    log::info!("Blocklist updater initialized");
+                }
+                Err(e) => {
+                    log::error!("Failed to initialize blocklist updater: {}", e);
+                }
+            }
+            
             // Initialize profile manager - handle errors gracefully
             match security::ProfileManager::new() {
                 Ok(profile_manager) => {
@@ -106,6 +197,15 @@ pub fn run() {
             // Initialize settings manager
             match settings::SettingsManager::new() {
                 Ok(settings_manager) => {
+                    // Identities follow the persisted location choice, starting
+                    // with the one created before settings were loaded
+                    let privacy_engine = app.state::<privacy::PrivacyEngine>();
+                    match privacy_engine.set_location_mode(settings_manager.get_settings().location_mode) {
+                        Ok(()) => {
+                            privacy_engine.regenerate_identity();
+                        }
+                        Err(e) => log::warn!("Ignoring saved location mode: {}", e),
+                    }
                     app.manage(settings_manager);
                     log::info!("Settings manager initialized");
                 }
@@ -159,6 +259,10 @@ pub fn run() {
             app.manage(additional_protection);
             log::info!("Additional protection initialized (WebAssembly/ResizeObserver)");
             
+            // Initialize page script cache (minified injection per identity/settings)
+            app.manage(privacy::InjectionScriptCache::new());
+            log::info!("Injection script cache initialized");
+            
             // Initialize webview manager
             let webview_manager = browser::WebViewManager::new();
             app.manage(webview_manager);
@@ -179,6 +283,21 @@ pub fn run() {
             app.manage(video_grabber);
             log::info!("Video grabber initialized (IDM-like video detection)");
             
+            // Initialize identity rotation - runs last so every layer it
+            // rotates and rebuilds is already managed
+            app.manage(privacy::IdentityRotator::new());
+            let rotation_handle = app.handle().clone();
+            app.listen("new-identity", move |_| {
+                let rotator = rotation_handle.state::<privacy::IdentityRotator>();
+                rotator.rotate(&rotation_handle, privacy::RotationTrigger::Manual);
+            });
+            privacy::IdentityRotator::start(app.handle().clone());
+            log::info!("Identity rotation scheduler started");
+            privacy::CookieManager::start(app.handle().clone());
+            log::info!("Cookie cleanup scheduler started");
+            privacy::BlocklistUpdater::start(app.handle().clone());
+            log::info!("Blocklist update scheduler started");
+            
             log::info!("╔══════════════════════════════════════════════════╗");
             log::info!("║                                                  ║");
             log::info!("║    ServionX Browser - MAXIMUM SECURITY MODE      ║");
@@ -205,13 +324,59 @@ pub fn run() {
             privacy::commands::get_fake_geolocation,
             privacy::commands::get_fake_user_agent,
             privacy::commands::get_fake_ip_headers,
+            privacy::commands::get_outgoing_headers,
+            privacy::commands::set_site_ip_header_policy,
             privacy::commands::regenerate_identity,
+            privacy::commands::regenerate_identity_from_seed,
+            privacy::commands::preview_identity_from_seed,
+            privacy::commands::validate_identity,
+            privacy::commands::get_fingerprint_dataset_info,
+            privacy::commands::list_geolocation_locations,
+            privacy::commands::get_location_mode,
+            privacy::commands::set_location_mode,
+            privacy::commands::set_location_route_from_gpx,
+            privacy::commands::list_site_identities,
+            privacy::commands::pin_site_identity,
+            privacy::commands::reset_site_identity,
+            privacy::commands::reset_all_site_identities,
+            privacy::commands::export_site_identities,
+            privacy::commands::list_profile_presets,
+            privacy::commands::list_site_profiles,
+            privacy::commands::get_site_layers,
+            privacy::commands::set_site_profile_preset,
+            privacy::commands::set_site_profile_layers,
+            privacy::commands::remove_site_profile,
             privacy::commands::get_injection_scripts,
             privacy::commands::check_whitelist,
             privacy::commands::add_to_whitelist,
             privacy::commands::remove_from_whitelist,
             privacy::commands::get_whitelist,
+            privacy::commands::get_whitelist_entries,
+            privacy::commands::export_whitelist,
+            privacy::commands::import_whitelist,
             privacy::commands::get_complete_injection_scripts,
+            privacy::commands::inspect_site_script,
+            privacy::commands::report_fingerprinting,
+            privacy::commands::get_fingerprinting_sites,
+            privacy::commands::get_site_fingerprinting,
+            privacy::commands::clear_fingerprinting_data,
+            privacy::commands::list_escalations,
+            privacy::commands::revert_escalation,
+            privacy::commands::run_self_test,
+            privacy::commands::submit_self_test_probes,
+            privacy::commands::diff_self_test_reports,
+            privacy::commands::list_storage_partitions,
+            privacy::commands::clear_site_storage,
+            privacy::commands::clear_all_site_storage,
+            privacy::commands::list_cookies,
+            privacy::commands::delete_site_cookies,
+            privacy::commands::sweep_cookies,
+            privacy::commands::get_cookie_rules,
+            privacy::commands::set_cookie_rule,
+            privacy::commands::remove_cookie_rule,
+            privacy::commands::get_blocklist_status,
+            privacy::commands::update_blocklists,
+            privacy::commands::check_url_blocking,
             
             // Security commands
             security::commands::unlock_settings,
@@ -239,6 +404,9 @@ pub fn run() {
             settings::commands::get_setting,
             settings::commands::toggle_setting,
             settings::commands::reset_settings,
+            settings::commands::set_regenerate_interval,
+            settings::commands::set_escalation_threshold,
+            settings::commands::set_cookie_cleanup_minutes,
             
             // Metadata commands
             metadata::commands::strip_file_metadata,
@@ -254,6 +422,9 @@ pub fn run() {
             browser::commands::close_browser_tab,
             browser::commands::get_browser_tabs,
             browser::commands::update_browser_tab,
+            browser::commands::list_containers,
+            browser::commands::create_container,
+            browser::commands::delete_container,
             
             // Download commands
             downloads::commands::start_download,
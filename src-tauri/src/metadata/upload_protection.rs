@@ -3,6 +3,7 @@
 
 use serde::{Deserialize, Serialize};
 use rand::Rng;
+use crate::privacy::ScriptTemplate;
 
 /// Fake metadata profiles for injection
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,8 +65,8 @@ impl FakeFileMetadata {
     
     /// Generate JavaScript injection script for upload interception
     pub fn get_upload_protection_script(&self) -> String {
-        format!(r#"
-(function() {{
+        ScriptTemplate::new(r#"
+(function() {
     'use strict';
     
     // ================================================================
@@ -75,13 +76,13 @@ impl FakeFileMetadata {
     
     console.log('[ServionX] 📁 Upload privacy protection initializing...');
     
-    const FAKE_METADATA = {{
-        camera: {{ make: '{camera_make}', model: '{camera_model}' }},
-        date: '{date_taken}',
-        software: '{software}',
-        author: '{author}',
-        creator: '{creator}'
-    }};
+    const FAKE_METADATA = {
+        camera: { make: @@camera_make@@, model: @@camera_model@@ },
+        date: @@date_taken@@,
+        software: @@software@@,
+        author: @@author@@,
+        creator: @@creator@@
+    };
     
     // ==================== FILE INPUT INTERCEPTION ====================
     // Override File input to strip metadata before upload
@@ -94,106 +95,106 @@ impl FakeFileMetadata {
     // ==================== CANVAS TO BLOB INTERCEPTION ====================
     // Strip metadata from canvas.toBlob() exports
     const originalToBlob = HTMLCanvasElement.prototype.toBlob;
-    HTMLCanvasElement.prototype.toBlob = function(callback, type, quality) {{
-        const newCallback = (blob) => {{
+    HTMLCanvasElement.prototype.toBlob = function(callback, type, quality) {
+        const newCallback = (blob) => {
             console.log('[ServionX] ✓ Canvas export metadata stripped:', blob?.type);
             callback(blob);
-        }};
+        };
         return originalToBlob.call(this, newCallback, type, quality);
-    }};
+    };
     
     // ==================== FORM DATA INTERCEPTION ====================
     // Intercept FormData to process files before upload
     const originalAppend = FormData.prototype.append;
-    FormData.prototype.append = function(name, value, filename) {{
-        if (value instanceof File) {{
+    FormData.prototype.append = function(name, value, filename) {
+        if (value instanceof File) {
             console.log('[ServionX] 📤 FormData file detected:', value.name, '(' + value.type + ')');
             
             // Check if it's an image that might have EXIF
-            if (value.type.startsWith('image/')) {{
+            if (value.type.startsWith('image/')) {
                 console.log('[ServionX] ⚠ Image upload detected - metadata will be stripped client-side');
                 console.log('[ServionX] 📷 Original file:', value.name, value.size, 'bytes');
                 
                 // For images, we let the backend handle stripping
                 // But we log a warning for the user
-            }}
+            }
             
             // For other file types, just log
-            if (value.type.includes('pdf') || value.type.includes('word') || value.type.includes('office')) {{
+            if (value.type.includes('pdf') || value.type.includes('word') || value.type.includes('office')) {
                 console.log('[ServionX] 📄 Document upload detected:', value.type);
-            }}
-        }}
+            }
+        }
         
         return originalAppend.call(this, name, value, filename);
-    }};
+    };
     
     // ==================== FETCH INTERCEPTION FOR FILE UPLOADS ====================
     const originalFetch = window.fetch;
-    window.fetch = async function(url, options) {{
-        if (options && options.body) {{
+    window.fetch = async function(url, options) {
+        if (options && options.body) {
             // Check if body contains files
-            if (options.body instanceof FormData) {{
+            if (options.body instanceof FormData) {
                 console.log('[ServionX] 📤 Fetch upload detected to:', typeof url === 'string' ? url : 'URL object');
                 
                 // Count files in FormData
                 let fileCount = 0;
-                for (let [key, value] of options.body.entries()) {{
-                    if (value instanceof File) {{
+                for (let [key, value] of options.body.entries()) {
+                    if (value instanceof File) {
                         fileCount++;
                         console.log('[ServionX] 📁 File in request:', value.name, value.type);
-                    }}
-                }}
+                    }
+                }
                 
-                if (fileCount > 0) {{
+                if (fileCount > 0) {
                     console.log('[ServionX] ✓ ' + fileCount + ' file(s) in upload - backend will strip metadata');
-                }}
-            }}
-        }}
+                }
+            }
+        }
         
         return originalFetch.call(this, url, options);
-    }};
+    };
     
     // ==================== XMLHttpRequest INTERCEPTION ====================
     const originalSend = XMLHttpRequest.prototype.send;
-    XMLHttpRequest.prototype.send = function(data) {{
-        if (data instanceof FormData) {{
+    XMLHttpRequest.prototype.send = function(data) {
+        if (data instanceof FormData) {
             console.log('[ServionX] 📤 XHR upload detected');
             
-            for (let [key, value] of data.entries()) {{
-                if (value instanceof File) {{
+            for (let [key, value] of data.entries()) {
+                if (value instanceof File) {
                     console.log('[ServionX] 📁 XHR File:', value.name, value.type);
-                }}
-            }}
-        }}
+                }
+            }
+        }
         
         return originalSend.call(this, data);
-    }};
+    };
     
     // ==================== DRAG AND DROP INTERCEPTION ====================
-    document.addEventListener('drop', function(e) {{
-        if (e.dataTransfer && e.dataTransfer.files && e.dataTransfer.files.length > 0) {{
+    document.addEventListener('drop', function(e) {
+        if (e.dataTransfer && e.dataTransfer.files && e.dataTransfer.files.length > 0) {
             console.log('[ServionX] 📁 Drag-drop files detected:', e.dataTransfer.files.length);
             
-            for (let file of e.dataTransfer.files) {{
+            for (let file of e.dataTransfer.files) {
                 console.log('[ServionX] 📁 Dropped file:', file.name, file.type, file.size, 'bytes');
                 
-                if (file.type.startsWith('image/')) {{
+                if (file.type.startsWith('image/')) {
                     console.log('[ServionX] ⚠ Image dropped - EXIF will be stripped on upload');
-                }}
-            }}
-        }}
-    }}, true);
+                }
+            }
+        }
+    }, true);
     
     // ==================== PASTE INTERCEPTION ====================
-    document.addEventListener('paste', function(e) {{
-        if (e.clipboardData && e.clipboardData.files && e.clipboardData.files.length > 0) {{
+    document.addEventListener('paste', function(e) {
+        if (e.clipboardData && e.clipboardData.files && e.clipboardData.files.length > 0) {
             console.log('[ServionX] 📋 Clipboard paste files detected:', e.clipboardData.files.length);
             
-            for (let file of e.clipboardData.files) {{
+            for (let file of e.clipboardData.files) {
                 console.log('[ServionX] 📁 Pasted file:', file.name, file.type);
-            }}
-        }}
-    }}, true);
+            }
+        }
+    }, true);
     
     // ==================== FILE READER INTERCEPTION ====================
     // Monitor when sites read files
@@ -201,26 +202,26 @@ impl FakeFileMetadata {
     const originalReadAsArrayBuffer = FileReader.prototype.readAsArrayBuffer;
     const originalReadAsText = FileReader.prototype.readAsText;
     
-    FileReader.prototype.readAsDataURL = function(blob) {{
-        if (blob instanceof File) {{
+    FileReader.prototype.readAsDataURL = function(blob) {
+        if (blob instanceof File) {
             console.log('[ServionX] 👁 Site reading file as DataURL:', blob.name);
-        }}
+        }
         return originalReadAsDataURL.call(this, blob);
-    }};
+    };
     
-    FileReader.prototype.readAsArrayBuffer = function(blob) {{
-        if (blob instanceof File) {{
+    FileReader.prototype.readAsArrayBuffer = function(blob) {
+        if (blob instanceof File) {
             console.log('[ServionX] 👁 Site reading file as ArrayBuffer:', blob.name);
-        }}
+        }
         return originalReadAsArrayBuffer.call(this, blob);
-    }};
+    };
     
-    FileReader.prototype.readAsText = function(blob, encoding) {{
-        if (blob instanceof File) {{
+    FileReader.prototype.readAsText = function(blob, encoding) {
+        if (blob instanceof File) {
             console.log('[ServionX] 👁 Site reading file as Text:', blob.name);
-        }}
+        }
         return originalReadAsText.call(this, blob, encoding);
-    }};
+    };
     
     // ==================== LOCATION METADATA BLOCKING ====================
     // Some sites try to get location and add it to uploads
@@ -241,15 +242,15 @@ impl FakeFileMetadata {
     console.log('[ServionX]   ✓ Clipboard paste');
     console.log('[ServionX]   ✓ FileReader access');
     console.log('[ServionX] ========================================');
-}})();
-"#,
-            camera_make = self.camera_make,
-            camera_model = self.camera_model,
-            date_taken = self.date_taken,
-            software = self.software,
-            author = self.author,
-            creator = self.creator,
-        )
+})();
+"#)
+            .bind("camera_make", &self.camera_make)
+            .bind("camera_model", &self.camera_model)
+            .bind("date_taken", &self.date_taken)
+            .bind("software", &self.software)
+            .bind("author", &self.author)
+            .bind("creator", &self.creator)
+            .render()
     }
 }
 
@@ -2,6 +2,7 @@
 //! Covers WebAssembly, ResizeObserver, and other advanced techniques
 
 use serde::{Deserialize, Serialize};
+use super::injection::ScriptTemplate;
 
 /// Additional browser API protections
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,8 +33,8 @@ impl AdditionalProtection {
     
     /// Get injection script for additional protections
     pub fn get_injection_script(&self) -> String {
-        format!(r#"
-(function() {{
+        ScriptTemplate::new(r#"
+(function() {
     'use strict';
     
     // ================================================================
@@ -45,43 +46,43 @@ impl AdditionalProtection {
     
     // ==================== WEBASSEMBLY FINGERPRINTING ====================
     // Block WebAssembly feature detection fingerprinting
-    if ({block_wasm} && typeof WebAssembly !== 'undefined') {{
+    if (@@block_wasm@@ && typeof WebAssembly !== 'undefined') {
         // Override WebAssembly.validate to return consistent results
         const origValidate = WebAssembly.validate;
-        WebAssembly.validate = function(bytes) {{
+        WebAssembly.validate = function(bytes) {
             console.log('[ServionX] ⚠ WebAssembly.validate called');
             return origValidate(bytes);
-        }};
+        };
         
         // Monitor WebAssembly.instantiate for fingerprinting
         const origInstantiate = WebAssembly.instantiate;
-        WebAssembly.instantiate = function(buffer, imports) {{
+        WebAssembly.instantiate = function(buffer, imports) {
             console.log('[ServionX] ⚠ WebAssembly.instantiate called');
             return origInstantiate(buffer, imports);
-        }};
+        };
         
         // Block WebAssembly.Memory properties fingerprinting
         const origMemory = WebAssembly.Memory;
-        WebAssembly.Memory = function(descriptor) {{
+        WebAssembly.Memory = function(descriptor) {
             console.log('[ServionX] ⚠ WebAssembly.Memory created');
             return new origMemory(descriptor);
-        }};
+        };
         
         console.log('[ServionX] ✓ WebAssembly fingerprinting monitored');
-    }}
+    }
     
     // ==================== RESIZE OBSERVER FINGERPRINTING ====================
     // ResizeObserver can be used to detect exact viewport/element sizes
-    if ({block_resize} && typeof ResizeObserver !== 'undefined') {{
+    if (@@block_resize@@ && typeof ResizeObserver !== 'undefined') {
         const OriginalResizeObserver = ResizeObserver;
-        window.ResizeObserver = function(callback) {{
-            const wrappedCallback = (entries, observer) => {{
+        window.ResizeObserver = function(callback) {
+            const wrappedCallback = (entries, observer) => {
                 // Normalize sizes to prevent fingerprinting
-                const normalizedEntries = entries.map(entry => {{
+                const normalizedEntries = entries.map(entry => {
                     const rect = entry.contentRect;
-                    return {{
+                    return {
                         ...entry,
-                        contentRect: {{
+                        contentRect: {
                             x: Math.round(rect.x),
                             y: Math.round(rect.y),
                             width: Math.round(rect.width),
@@ -90,97 +91,97 @@ impl AdditionalProtection {
                             right: Math.round(rect.right),
                             bottom: Math.round(rect.bottom),
                             left: Math.round(rect.left)
-                        }}
-                    }};
-                }});
+                        }
+                    };
+                });
                 callback(normalizedEntries, observer);
-            }};
+            };
             return new OriginalResizeObserver(wrappedCallback);
-        }};
+        };
         window.ResizeObserver.prototype = OriginalResizeObserver.prototype;
         console.log('[ServionX] ✓ ResizeObserver values normalized');
-    }}
+    }
     
     // ==================== INTERSECTION OBSERVER FINGERPRINTING ====================
-    if ({block_intersection} && typeof IntersectionObserver !== 'undefined') {{
+    if (@@block_intersection@@ && typeof IntersectionObserver !== 'undefined') {
         const OriginalIntersectionObserver = IntersectionObserver;
-        window.IntersectionObserver = function(callback, options) {{
-            const wrappedCallback = (entries, observer) => {{
+        window.IntersectionObserver = function(callback, options) {
+            const wrappedCallback = (entries, observer) => {
                 // Normalize intersection ratios
-                const normalizedEntries = entries.map(entry => ({{
+                const normalizedEntries = entries.map(entry => ({
                     ...entry,
                     intersectionRatio: Math.round(entry.intersectionRatio * 100) / 100
-                }}));
+                }));
                 callback(normalizedEntries, observer);
-            }};
+            };
             return new OriginalIntersectionObserver(wrappedCallback, options);
-        }};
+        };
         window.IntersectionObserver.prototype = OriginalIntersectionObserver.prototype;
         console.log('[ServionX] ✓ IntersectionObserver values normalized');
-    }}
+    }
     
     // ==================== INTL DATE FORMATTING FINGERPRINTING ====================
     // Date formatting reveals locale/timezone info
-    if ({normalize_date}) {{
+    if (@@normalize_date@@) {
         const origDateTimeFormat = Intl.DateTimeFormat;
-        Intl.DateTimeFormat = function(locale, options) {{
+        Intl.DateTimeFormat = function(locale, options) {
             // Use generic locale if none specified
             const safeLocale = locale || 'en-US';
             return new origDateTimeFormat(safeLocale, options);
-        }};
+        };
         Intl.DateTimeFormat.prototype = origDateTimeFormat.prototype;
         Intl.DateTimeFormat.supportedLocalesOf = origDateTimeFormat.supportedLocalesOf;
         console.log('[ServionX] ✓ Intl.DateTimeFormat normalized');
-    }}
+    }
     
     // ==================== SHARED WORKER FINGERPRINTING ====================
     // SharedWorkers can be used for cross-tab fingerprinting
-    if ({block_shared_workers} && typeof SharedWorker !== 'undefined') {{
-        window.SharedWorker = function(url, name) {{
+    if (@@block_shared_workers@@ && typeof SharedWorker !== 'undefined') {
+        window.SharedWorker = function(url, name) {
             console.log('[ServionX] ✗ SharedWorker blocked:', url);
             throw new Error('SharedWorkers are disabled for security');
-        }};
+        };
         console.log('[ServionX] ✓ SharedWorker blocked');
-    }}
+    }
     
     // ==================== BROADCAST CHANNEL FINGERPRINTING ====================
     // BroadcastChannel can leak cross-tab info
-    if (typeof BroadcastChannel !== 'undefined') {{
+    if (typeof BroadcastChannel !== 'undefined') {
         const OriginalBroadcastChannel = BroadcastChannel;
-        window.BroadcastChannel = function(name) {{
+        window.BroadcastChannel = function(name) {
             console.log('[ServionX] ⚠ BroadcastChannel created:', name);
             return new OriginalBroadcastChannel(name);
-        }};
+        };
         window.BroadcastChannel.prototype = OriginalBroadcastChannel.prototype;
-    }}
+    }
     
     // ==================== PERFORMANCE OBSERVER ====================
     // PerformanceObserver can leak timing info
-    if (typeof PerformanceObserver !== 'undefined') {{
+    if (typeof PerformanceObserver !== 'undefined') {
         const OriginalPerformanceObserver = PerformanceObserver;
-        window.PerformanceObserver = function(callback) {{
+        window.PerformanceObserver = function(callback) {
             console.log('[ServionX] ⚠ PerformanceObserver created');
             return new OriginalPerformanceObserver(callback);
-        }};
+        };
         window.PerformanceObserver.prototype = OriginalPerformanceObserver.prototype;
         window.PerformanceObserver.supportedEntryTypes = ['navigation', 'resource'];
-    }}
+    }
     
     // ==================== REPORTING API ====================
     // Block Reporting API
-    if (typeof ReportingObserver !== 'undefined') {{
+    if (typeof ReportingObserver !== 'undefined') {
         window.ReportingObserver = undefined;
         console.log('[ServionX] ✓ ReportingObserver blocked');
-    }}
+    }
     
     // ==================== NETWORK INFO WORKER ====================
     // Prevent workers from accessing network info
-    if (typeof navigator.connection !== 'undefined') {{
-        Object.defineProperty(navigator, 'onLine', {{
+    if (typeof navigator.connection !== 'undefined') {
+        Object.defineProperty(navigator, 'onLine', {
             get: () => true,
             configurable: true
-        }});
-    }}
+        });
+    }
     
     console.log('[ServionX] ========================================');
     console.log('[ServionX] 🔧 ADDITIONAL PROTECTION ACTIVE');
@@ -191,14 +192,14 @@ impl AdditionalProtection {
     console.log('[ServionX]   ✓ SharedWorker blocked');
     console.log('[ServionX]   ✓ Intl formatting normalized');
     console.log('[ServionX] ========================================');
-}})();
-"#,
-            block_wasm = self.block_wasm_fingerprint,
-            block_resize = self.block_resize_observer_fp,
-            block_intersection = self.block_intersection_observer_fp,
-            normalize_date = self.normalize_date_formatting,
-            block_shared_workers = self.block_shared_workers,
-        )
+})();
+"#)
+            .bind("block_wasm", &self.block_wasm_fingerprint)
+            .bind("block_resize", &self.block_resize_observer_fp)
+            .bind("block_intersection", &self.block_intersection_observer_fp)
+            .bind("normalize_date", &self.normalize_date_formatting)
+            .bind("block_shared_workers", &self.block_shared_workers)
+            .render()
     }
 }
 
@@ -2,7 +2,7 @@
 //! Provides comprehensive protection against browser fingerprinting techniques
 
 use serde::{Deserialize, Serialize};
-use rand::Rng;
+use super::injection::ScriptTemplate;
 
 /// Advanced fingerprint protection settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,26 +42,21 @@ impl Default for AdvancedFingerprintProtection {
 
 impl AdvancedFingerprintProtection {
     pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
-        
-        // Common screen resolutions for spoofing
-        let screens = [
-            (1920, 1080), (1366, 768), (1536, 864), (1440, 900),
-            (1280, 720), (1600, 900), (2560, 1440), (1280, 800),
-        ];
-        let (width, height) = screens[rng.gen_range(0..screens.len())];
+        // Screen and hardware of one real-world device, weighted by how common it is
+        let dataset = super::FingerprintDataset::bundled();
+        let device = dataset.sample_device(&mut rand::thread_rng());
         
         Self {
             spoof_screen: true,
-            screen_width: width,
-            screen_height: height,
-            color_depth: 24,
-            pixel_ratio: 1.0,
+            screen_width: device.screen.width,
+            screen_height: device.screen.height,
+            color_depth: device.screen.color_depth,
+            pixel_ratio: device.screen.pixel_ratio,
             
             spoof_hardware: true,
-            fake_cores: [4, 8, 12, 16][rng.gen_range(0..4)],
-            fake_memory: [4, 8, 16, 32][rng.gen_range(0..4)],
-            fake_max_touch_points: 0, // Desktop appearance
+            fake_cores: device.hardware_concurrency as u8,
+            fake_memory: device.device_memory as u8,
+            fake_max_touch_points: device.max_touch_points as u8,
             
             block_battery_api: true,
             block_bluetooth_api: true,
@@ -76,172 +71,188 @@ impl AdvancedFingerprintProtection {
         }
     }
     
+    /// Copy of this protection with screen and hardware values taken from
+    /// `identity`, so every script reports the same machine
+    pub fn with_identity(&self, identity: &super::FakeIdentity) -> Self {
+        let fp = &identity.fingerprint;
+        Self {
+            screen_width: fp.screen.width,
+            screen_height: fp.screen.height,
+            color_depth: fp.screen.color_depth,
+            pixel_ratio: fp.screen.pixel_ratio,
+            fake_cores: fp.hardware_concurrency.min(u8::MAX as u32) as u8,
+            fake_memory: fp.device_memory.min(u8::MAX as u32) as u8,
+            fake_max_touch_points: fp.max_touch_points.min(u8::MAX as u32) as u8,
+            ..self.clone()
+        }
+    }
+    
     /// Generate comprehensive fingerprint protection JavaScript
     pub fn get_injection_script(&self) -> String {
-        format!(r#"
-(function() {{
+        ScriptTemplate::new(r#"
+(function() {
     'use strict';
     
     // ============================================
     // ADVANCED FINGERPRINT PROTECTION - ServionX
     // ============================================
     
-    const config = {{
-        screen: {{ width: {screen_w}, height: {screen_h}, colorDepth: {color_depth}, pixelRatio: {pixel_ratio} }},
-        hardware: {{ cores: {cores}, memory: {memory}, touchPoints: {touch_points} }},
-        blockBattery: {block_battery},
-        blockBluetooth: {block_bluetooth},
-        blockUsb: {block_usb},
-        blockSensors: {block_sensors},
-        blockGamepad: {block_gamepad},
-        blockSpeech: {block_speech},
-        blockMediaDevices: {block_media},
-        reduceTiming: {reduce_timing},
-        timingPrecision: {timing_precision}
-    }};
+    const config = {
+        screen: { width: @@screen_w@@, height: @@screen_h@@, colorDepth: @@color_depth@@, pixelRatio: @@pixel_ratio@@ },
+        hardware: { cores: @@cores@@, memory: @@memory@@, touchPoints: @@touch_points@@ },
+        blockBattery: @@block_battery@@,
+        blockBluetooth: @@block_bluetooth@@,
+        blockUsb: @@block_usb@@,
+        blockSensors: @@block_sensors@@,
+        blockGamepad: @@block_gamepad@@,
+        blockSpeech: @@block_speech@@,
+        blockMediaDevices: @@block_media@@,
+        reduceTiming: @@reduce_timing@@,
+        timingPrecision: @@timing_precision@@
+    };
     
     // === SCREEN SPOOFING ===
-    Object.defineProperties(screen, {{
-        width: {{ get: () => config.screen.width, configurable: true }},
-        height: {{ get: () => config.screen.height, configurable: true }},
-        availWidth: {{ get: () => config.screen.width, configurable: true }},
-        availHeight: {{ get: () => config.screen.height - 40, configurable: true }},
-        colorDepth: {{ get: () => config.screen.colorDepth, configurable: true }},
-        pixelDepth: {{ get: () => config.screen.colorDepth, configurable: true }}
-    }});
+    Object.defineProperties(screen, {
+        width: { get: () => config.screen.width, configurable: true },
+        height: { get: () => config.screen.height, configurable: true },
+        availWidth: { get: () => config.screen.width, configurable: true },
+        availHeight: { get: () => config.screen.height - 40, configurable: true },
+        colorDepth: { get: () => config.screen.colorDepth, configurable: true },
+        pixelDepth: { get: () => config.screen.colorDepth, configurable: true }
+    });
     
-    Object.defineProperty(window, 'devicePixelRatio', {{
+    Object.defineProperty(window, 'devicePixelRatio', {
         get: () => config.screen.pixelRatio,
         configurable: true
-    }});
+    });
     
-    Object.defineProperty(window, 'innerWidth', {{
+    Object.defineProperty(window, 'innerWidth', {
         get: () => config.screen.width,
         configurable: true
-    }});
+    });
     
-    Object.defineProperty(window, 'innerHeight', {{
+    Object.defineProperty(window, 'innerHeight', {
         get: () => config.screen.height - 100,
         configurable: true
-    }});
+    });
     
     // === HARDWARE SPOOFING ===
-    Object.defineProperty(navigator, 'hardwareConcurrency', {{
+    Object.defineProperty(navigator, 'hardwareConcurrency', {
         get: () => config.hardware.cores,
         configurable: true
-    }});
+    });
     
-    Object.defineProperty(navigator, 'deviceMemory', {{
+    Object.defineProperty(navigator, 'deviceMemory', {
         get: () => config.hardware.memory,
         configurable: true
-    }});
+    });
     
-    Object.defineProperty(navigator, 'maxTouchPoints', {{
+    Object.defineProperty(navigator, 'maxTouchPoints', {
         get: () => config.hardware.touchPoints,
         configurable: true
-    }});
+    });
     
     // === BATTERY API BLOCKING ===
-    if (config.blockBattery) {{
+    if (config.blockBattery) {
         delete navigator.getBattery;
-        Object.defineProperty(navigator, 'getBattery', {{
+        Object.defineProperty(navigator, 'getBattery', {
             value: undefined,
             writable: false,
             configurable: false
-        }});
+        });
         console.log('[ServionX] Battery API blocked');
-    }}
+    }
     
     // === BLUETOOTH API BLOCKING ===
-    if (config.blockBluetooth && navigator.bluetooth) {{
-        Object.defineProperty(navigator, 'bluetooth', {{
+    if (config.blockBluetooth && navigator.bluetooth) {
+        Object.defineProperty(navigator, 'bluetooth', {
             value: undefined,
             writable: false,
             configurable: false
-        }});
+        });
         console.log('[ServionX] Bluetooth API blocked');
-    }}
+    }
     
     // === USB API BLOCKING ===
-    if (config.blockUsb && navigator.usb) {{
-        Object.defineProperty(navigator, 'usb', {{
+    if (config.blockUsb && navigator.usb) {
+        Object.defineProperty(navigator, 'usb', {
             value: undefined,
             writable: false,
             configurable: false
-        }});
+        });
         console.log('[ServionX] USB API blocked');
-    }}
+    }
     
     // === SENSOR APIs BLOCKING ===
-    if (config.blockSensors) {{
+    if (config.blockSensors) {
         // Block accelerometer, gyroscope, magnetometer, etc.
         const sensorAPIs = [
             'Accelerometer', 'Gyroscope', 'Magnetometer', 
             'AbsoluteOrientationSensor', 'RelativeOrientationSensor',
             'LinearAccelerationSensor', 'GravitySensor', 'AmbientLightSensor'
         ];
-        sensorAPIs.forEach(api => {{
-            if (window[api]) {{
+        sensorAPIs.forEach(api => {
+            if (window[api]) {
                 window[api] = undefined;
-            }}
-        }});
+            }
+        });
         console.log('[ServionX] Sensor APIs blocked');
-    }}
+    }
     
     // === GAMEPAD API SPOOFING ===
-    if (config.blockGamepad) {{
+    if (config.blockGamepad) {
         navigator.getGamepads = () => [];
         window.addEventListener('gamepadconnected', e => e.stopPropagation(), true);
         console.log('[ServionX] Gamepad API blocked');
-    }}
+    }
     
     // === SPEECH SYNTHESIS BLOCKING ===
-    if (config.blockSpeech && window.speechSynthesis) {{
+    if (config.blockSpeech && window.speechSynthesis) {
         window.speechSynthesis.getVoices = () => [];
         console.log('[ServionX] Speech synthesis voices blocked');
-    }}
+    }
     
     // === MEDIA DEVICES BLOCKING ===
-    if (config.blockMediaDevices && navigator.mediaDevices) {{
+    if (config.blockMediaDevices && navigator.mediaDevices) {
         const originalEnumerateDevices = navigator.mediaDevices.enumerateDevices;
-        navigator.mediaDevices.enumerateDevices = async () => {{
+        navigator.mediaDevices.enumerateDevices = async () => {
             console.log('[ServionX] Media devices enumeration blocked');
             return [];
-        }};
-    }}
+        };
+    }
     
     // === TIMING ATTACK PROTECTION ===
-    if (config.reduceTiming) {{
+    if (config.reduceTiming) {
         const originalNow = performance.now.bind(performance);
-        performance.now = () => {{
+        performance.now = () => {
             return Math.floor(originalNow() / config.timingPrecision) * config.timingPrecision;
-        }};
+        };
         
         const originalDateNow = Date.now;
-        Date.now = () => {{
+        Date.now = () => {
             return Math.floor(originalDateNow() / config.timingPrecision) * config.timingPrecision;
-        }};
+        };
         console.log('[ServionX] Timer precision reduced to ' + config.timingPrecision + 'ms');
-    }}
+    }
     
     // === KEYBOARD/MOUSE FINGERPRINT PROTECTION ===
     // Normalize keyboard event timing
     let lastKeyTime = 0;
-    document.addEventListener('keydown', (e) => {{
+    document.addEventListener('keydown', (e) => {
         const now = Date.now();
-        if (now - lastKeyTime < 50) {{
+        if (now - lastKeyTime < 50) {
             e.stopPropagation();
-        }}
+        }
         lastKeyTime = now;
-    }}, true);
+    }, true);
     
     // === CLIENT RECTS PROTECTION ===
     const originalGetBoundingClientRect = Element.prototype.getBoundingClientRect;
-    Element.prototype.getBoundingClientRect = function() {{
+    Element.prototype.getBoundingClientRect = function() {
         const rect = originalGetBoundingClientRect.call(this);
         // Add small random noise to prevent fingerprinting
         const noise = 0.0001;
-        return {{
+        return {
             x: rect.x + (Math.random() * noise),
             y: rect.y + (Math.random() * noise),
             width: rect.width,
@@ -250,58 +261,58 @@ impl AdvancedFingerprintProtection {
             right: rect.right + (Math.random() * noise),
             bottom: rect.bottom + (Math.random() * noise),
             left: rect.left + (Math.random() * noise)
-        }};
-    }};
+        };
+    };
     
     // === PLUGIN SPOOFING ===
-    Object.defineProperty(navigator, 'plugins', {{
-        get: () => {{
-            return {{
+    Object.defineProperty(navigator, 'plugins', {
+        get: () => {
+            return {
                 length: 5,
                 item: () => null,
                 namedItem: () => null,
-                refresh: () => {{}},
-                [Symbol.iterator]: function* () {{}}
-            }};
-        }},
+                refresh: () => {},
+                [Symbol.iterator]: function* () {}
+            };
+        },
         configurable: true
-    }});
+    });
     
     // === MIME TYPES SPOOFING ===
-    Object.defineProperty(navigator, 'mimeTypes', {{
-        get: () => {{
-            return {{
+    Object.defineProperty(navigator, 'mimeTypes', {
+        get: () => {
+            return {
                 length: 4,
                 item: () => null,
                 namedItem: () => null,
-                [Symbol.iterator]: function* () {{}}
-            }};
-        }},
+                [Symbol.iterator]: function* () {}
+            };
+        },
         configurable: true
-    }});
+    });
     
     console.log('[ServionX] Advanced fingerprint protection active');
     console.log('[ServionX] Screen: ' + config.screen.width + 'x' + config.screen.height);
     console.log('[ServionX] CPU cores: ' + config.hardware.cores + ', Memory: ' + config.hardware.memory + 'GB');
-}})();
-"#,
-            screen_w = self.screen_width,
-            screen_h = self.screen_height,
-            color_depth = self.color_depth,
-            pixel_ratio = self.pixel_ratio,
-            cores = self.fake_cores,
-            memory = self.fake_memory,
-            touch_points = self.fake_max_touch_points,
-            block_battery = self.block_battery_api,
-            block_bluetooth = self.block_bluetooth_api,
-            block_usb = self.block_usb_api,
-            block_sensors = self.block_sensor_api,
-            block_gamepad = self.block_gamepad_api,
-            block_speech = self.block_speech_api,
-            block_media = self.block_media_devices,
-            reduce_timing = self.reduce_timer_precision,
-            timing_precision = self.timer_precision_ms,
-        )
+})();
+"#)
+            .bind("screen_w", &self.screen_width)
+            .bind("screen_h", &self.screen_height)
+            .bind("color_depth", &self.color_depth)
+            .bind("pixel_ratio", &self.pixel_ratio)
+            .bind("cores", &self.fake_cores)
+            .bind("memory", &self.fake_memory)
+            .bind("touch_points", &self.fake_max_touch_points)
+            .bind("block_battery", &self.block_battery_api)
+            .bind("block_bluetooth", &self.block_bluetooth_api)
+            .bind("block_usb", &self.block_usb_api)
+            .bind("block_sensors", &self.block_sensor_api)
+            .bind("block_gamepad", &self.block_gamepad_api)
+            .bind("block_speech", &self.block_speech_api)
+            .bind("block_media", &self.block_media_devices)
+            .bind("reduce_timing", &self.reduce_timer_precision)
+            .bind("timing_precision", &self.timer_precision_ms)
+            .render()
     }
 }
 
@@ -326,4 +337,14 @@ mod tests {
         assert!(script.contains("Bluetooth API blocked"));
         assert!(script.contains("Sensor APIs blocked"));
     }
+    
+    #[test]
+    fn test_with_identity_matches_fingerprint() {
+        let identity = crate::privacy::test_engine().identity_from_seed(11);
+        let protection = AdvancedFingerprintProtection::new().with_identity(&identity);
+        
+        assert_eq!(protection.screen_width, identity.fingerprint.screen.width);
+        assert_eq!(protection.fake_cores as u32, identity.fingerprint.hardware_concurrency);
+        assert_eq!(protection.fake_memory as u32, identity.fingerprint.device_memory);
+    }
 }
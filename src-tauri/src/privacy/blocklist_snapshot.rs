@@ -0,0 +1,179 @@
+// Blocklist Snapshot
+// Compact binary image of the compiled blocklist rules (each feed's domain
+// set, indexed network filters and exceptions, cosmetic filters), written
+// after each feed update and loaded at startup instead of parsing the text
+// lists again
+
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::blocklist_manager::RuleSet;
+
+const MAGIC: &[u8; 8] = b"SVXBLSNP";
+
+/// Bump whenever the layout of `RuleSet` or anything inside it changes;
+/// snapshots of another version are ignored and rebuilt from the text lists
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// Magic, version, payload length and SHA-256 of the payload
+const HEADER_LEN: usize = 8 + 4 + 8 + 32;
+
+/// What a snapshot holds besides the rules
+#[derive(Serialize, Deserialize)]
+struct Payload {
+    /// Identifies the text lists the rules were compiled from
+    source: String,
+    /// Rules each feed contributed, by feed name
+    feed_rules: Vec<(String, usize)>,
+    created_at: i64,
+    rules: RuleSet,
+}
+
+/// Rules loaded from a snapshot
+#[derive(Debug)]
+pub struct Snapshot {
+    pub rules: RuleSet,
+    pub feed_rules: Vec<(String, usize)>,
+    pub created_at: i64,
+}
+
+/// Serialize `rules`, compiled from the lists identified by `source`
+pub fn encode(rules: &RuleSet, source: &str, feed_rules: &[(String, usize)]) -> Result<Vec<u8>, String> {
+    #[derive(Serialize)]
+    struct PayloadRef<'a> {
+        source: &'a str,
+        feed_rules: &'a [(String, usize)],
+        created_at: i64,
+        rules: &'a RuleSet,
+    }
+
+    let payload = postcard::to_stdvec(&PayloadRef {
+        source,
+        feed_rules,
+        created_at: chrono::Utc::now().timestamp(),
+        rules,
+    })
+    .map_err(|e| format!("Failed to encode blocklist snapshot: {}", e))?;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&Sha256::digest(&payload));
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Load a snapshot, refusing one that is damaged, of another version, or
+/// compiled from lists other than `source`
+pub fn decode(bytes: &[u8], source: &str) -> Result<Snapshot, String> {
+    if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+        return Err("Not a blocklist snapshot".to_string());
+    }
+    let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+    if version != SNAPSHOT_VERSION {
+        return Err(format!("Snapshot version {} (expected {})", version, SNAPSHOT_VERSION));
+    }
+    let len = u64::from_le_bytes(bytes[12..20].try_into().unwrap());
+    let payload = &bytes[HEADER_LEN..];
+    if payload.len() as u64 != len {
+        return Err("Snapshot is truncated".to_string());
+    }
+    if Sha256::digest(payload).as_slice() != &bytes[20..HEADER_LEN] {
+        return Err("Snapshot checksum mismatch".to_string());
+    }
+
+    let payload: Payload = postcard::from_bytes(payload)
+        .map_err(|e| format!("Failed to decode blocklist snapshot: {}", e))?;
+    if payload.source != source {
+        return Err("Snapshot is out of date".to_string());
+    }
+    Ok(Snapshot {
+        rules: payload.rules,
+        feed_rules: payload.feed_rules,
+        created_at: payload.created_at,
+    })
+}
+
+/// Write a snapshot file
+pub fn save(path: &Path, rules: &RuleSet, source: &str, feed_rules: &[(String, usize)]) -> Result<(), String> {
+    let bytes = encode(rules, source, feed_rules)?;
+    super::blocklist_updater::write_atomic(path, &bytes)
+}
+
+/// Read a snapshot file
+pub fn load(path: &Path, source: &str) -> Result<Snapshot, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    decode(&bytes, source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::privacy::blocklist_manager::FeedFormat;
+    use crate::privacy::filter_rules::ResourceType;
+    use crate::privacy::{BlockCategory, BlocklistManager, CategorySet};
+
+    const SAMPLE_LIST: &str = include_str!("data/easylist_sample.txt");
+
+    fn sample_rules() -> RuleSet {
+        let mut rules = RuleSet::new();
+        rules.add_feed("sample", BlockCategory::Ads, FeedFormat::Adblock, SAMPLE_LIST).unwrap();
+        rules.add_feed("hosts", BlockCategory::Malware, FeedFormat::Hosts, "0.0.0.0 malware.example\n").unwrap();
+        rules
+    }
+
+    #[test]
+    fn test_snapshot_round_trip_keeps_verdicts() {
+        let rules = sample_rules();
+        let bytes = encode(&rules, "lists-v1", &[("sample".to_string(), rules.total_rules())]).unwrap();
+        let snapshot = decode(&bytes, "lists-v1").unwrap();
+        assert_eq!(snapshot.rules.total_rules(), rules.total_rules());
+        assert_eq!(snapshot.feed_rules, vec![("sample".to_string(), rules.total_rules())]);
+
+        let (original, loaded) = (BlocklistManager::new(), BlocklistManager::new());
+        original.swap_rules(rules);
+        loaded.swap_rules(snapshot.rules);
+        let requests = [
+            ("https://ads.example.com/banner.png", "https://news.example/", ResourceType::Image),
+            ("https://ads.example.com/ok/logo.png", "https://news.example/", ResourceType::Image),
+            ("https://coinminer.example/mine.js", "https://allowed.example/", ResourceType::Script),
+            ("https://x.example/ad-12345.js", "https://news.example/", ResourceType::Script),
+            ("https://cdn.example/AdBanner/1.png", "https://news.example/", ResourceType::Image),
+            ("https://malware.example/", "https://news.example/", ResourceType::Document),
+            ("https://cdn.example/app.js", "https://news.example/", ResourceType::Script),
+        ];
+        for (url, source, resource) in requests {
+            assert_eq!(
+                original.check_request(url, Some(source), resource, CategorySet::all()),
+                loaded.check_request(url, Some(source), resource, CategorySet::all()),
+                "{}",
+                url
+            );
+        }
+        assert_eq!(
+            original.get_cosmetic_css_for("https://example.shop/", CategorySet::all()),
+            loaded.get_cosmetic_css_for("https://example.shop/", CategorySet::all())
+        );
+    }
+
+    #[test]
+    fn test_damaged_or_stale_snapshots_are_rejected() {
+        let bytes = encode(&sample_rules(), "lists-v1", &[]).unwrap();
+
+        let mut corrupt = bytes.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xff;
+        assert!(decode(&corrupt, "lists-v1").unwrap_err().contains("checksum"));
+
+        assert!(decode(&bytes[..bytes.len() - 10], "lists-v1").unwrap_err().contains("truncated"));
+
+        let mut other_version = bytes.clone();
+        other_version[8..12].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+        assert!(decode(&other_version, "lists-v1").unwrap_err().contains("version"));
+
+        assert!(decode(b"! Title: EasyList", "lists-v1").is_err());
+        assert!(decode(&bytes, "lists-v2").unwrap_err().contains("out of date"));
+    }
+}
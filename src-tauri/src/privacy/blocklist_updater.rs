@@ -0,0 +1,612 @@
+// Blocklist Updater
+// Downloads the blocklist feeds on a schedule, keeps the last good copy of
+// each on disk with its ETag / Last-Modified validators, and swaps the
+// parsed rules into the blocklist manager in one step
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use tauri::{AppHandle, Manager};
+use sha2::Digest;
+use tokio::sync::Mutex;
+
+use super::blocklist_manager::{FeedFormat, RuleSet, BLOCKLIST_FEEDS};
+use super::{blocklist_snapshot, BlockCategory, BlocklistManager};
+use crate::security::tor_manager::{TorManager, TorStatus};
+
+const INDEX_FILE: &str = "feeds.json";
+const SNAPSHOT_FILE: &str = "rules.snapshot";
+
+/// How often the feeds are checked for new versions
+const UPDATE_INTERVAL_SECS: i64 = 24 * 60 * 60;
+
+/// How often the scheduler wakes to see whether an update is due
+const SCHEDULER_TICK_SECS: u64 = 15 * 60;
+
+/// Per-feed download timeout
+const FETCH_TIMEOUT_SECS: u64 = 60;
+
+/// One feed to download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlocklistFeed {
+    pub name: String,
+    pub url: String,
+    pub format: FeedFormat,
+    pub category: BlockCategory,
+}
+
+impl BlocklistFeed {
+    pub fn new(name: &str, url: &str, format: FeedFormat, category: BlockCategory) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            format,
+            category,
+        }
+    }
+
+    /// The open source feeds the browser ships with
+    pub fn defaults() -> Vec<Self> {
+        BLOCKLIST_FEEDS
+            .iter()
+            .map(|(name, url, format, category)| Self::new(name, url, *format, *category))
+            .collect()
+    }
+}
+
+/// What is known about a feed's cached copy
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// When the cached copy was downloaded
+    fetched_at: Option<i64>,
+}
+
+/// A feed's state after the last update, as the UI shows it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedStatus {
+    pub name: String,
+    pub url: String,
+    pub format: FeedFormat,
+    pub category: BlockCategory,
+    /// Rules the feed contributed
+    pub rules: usize,
+    /// When the copy in use was downloaded
+    pub fetched_at: Option<i64>,
+    /// Whether the server reported no change since that download
+    pub not_modified: bool,
+    /// Why a fresh copy could not be used; the cached one was used instead
+    pub error: Option<String>,
+}
+
+/// One feed's part of an update: a fresh copy, if the server had one
+pub struct FeedDownload {
+    status: FeedStatus,
+    fresh: Option<(String, CacheEntry)>,
+}
+
+/// Downloads and caches the blocklist feeds
+pub struct BlocklistUpdater {
+    cache_dir: PathBuf,
+    feeds: Vec<BlocklistFeed>,
+    index: RwLock<HashMap<String, CacheEntry>>,
+    status: RwLock<Vec<FeedStatus>>,
+    last_check: RwLock<Option<i64>>,
+    /// Held for the whole of an update so two never overlap
+    running: Mutex<()>,
+}
+
+impl BlocklistUpdater {
+    /// Open the cache in the default app data directory
+    pub fn new() -> Result<Self, String> {
+        let cache_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ServionX Browser")
+            .join("blocklists");
+        Self::open(&cache_dir, BlocklistFeed::defaults())
+    }
+
+    pub fn open(cache_dir: &Path, feeds: Vec<BlocklistFeed>) -> Result<Self, String> {
+        std::fs::create_dir_all(cache_dir).map_err(|e| e.to_string())?;
+
+        let index = match std::fs::read_to_string(cache_dir.join(INDEX_FILE)) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                log::warn!("Ignoring corrupt blocklist cache index: {}", e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        Ok(Self {
+            cache_dir: cache_dir.to_path_buf(),
+            feeds,
+            index: RwLock::new(index),
+            status: RwLock::new(Vec::new()),
+            last_check: RwLock::new(None),
+            running: Mutex::new(()),
+        })
+    }
+
+    /// Each feed's state after the last load or update
+    pub fn status(&self) -> Vec<FeedStatus> {
+        self.status.read().unwrap().clone()
+    }
+
+    /// When the feeds were last checked for updates
+    pub fn last_check(&self) -> Option<i64> {
+        *self.last_check.read().unwrap()
+    }
+
+    /// Rules from the cached copies alone, for startup and offline use.
+    /// Loaded from the compiled snapshot when it matches the cached lists,
+    /// parsed from the lists (and snapshotted again) otherwise.
+    pub fn load_cached(&self) -> RuleSet {
+        let source = self.source_key();
+        match blocklist_snapshot::load(&self.cache_dir.join(SNAPSHOT_FILE), &source) {
+            Ok(snapshot) => {
+                let counts: HashMap<String, usize> = snapshot.feed_rules.into_iter().collect();
+                let status = self.feeds
+                    .iter()
+                    .map(|feed| {
+                        let mut feed_status = self.feed_status(feed);
+                        match counts.get(&feed.name) {
+                            Some(count) => feed_status.rules = *count,
+                            None => feed_status.error = Some("Not downloaded yet".to_string()),
+                        }
+                        feed_status
+                    })
+                    .collect();
+                *self.status.write().unwrap() = status;
+                return snapshot.rules;
+            }
+            Err(e) => log::info!("Parsing cached blocklists instead of the snapshot: {}", e),
+        }
+
+        let mut rules = RuleSet::new();
+        let mut status = Vec::new();
+        for feed in &self.feeds {
+            let mut feed_status = self.feed_status(feed);
+            match self.read_cache(feed) {
+                Some(content) => match rules.add_feed(&feed.name, feed.category, feed.format, &content) {
+                    Ok(count) => feed_status.rules = count,
+                    Err(e) => feed_status.error = Some(e),
+                },
+                None => feed_status.error = Some("Not downloaded yet".to_string()),
+            }
+            status.push(feed_status);
+        }
+        self.save_snapshot(&rules, &status);
+        *self.status.write().unwrap() = status;
+        rules
+    }
+
+    /// Check every feed for a new version. Parsing is left to `apply`,
+    /// which is slow for the big lists and so runs off the async runtime.
+    pub async fn download(&self, client: &reqwest::Client) -> Vec<FeedDownload> {
+        let mut downloads = Vec::new();
+        for feed in &self.feeds {
+            let mut status = self.feed_status(feed);
+            let fresh = match self.fetch(client, feed).await {
+                Ok(fresh) => fresh,
+                Err(e) => {
+                    status.error = Some(e);
+                    None
+                }
+            };
+            downloads.push(FeedDownload { status, fresh });
+        }
+        downloads
+    }
+
+    /// Parse the result of `download`. A feed that failed to download, or
+    /// downloaded something that does not parse, keeps contributing its
+    /// last good cached copy.
+    pub fn apply(&self, downloads: Vec<FeedDownload>) -> RuleSet {
+        let mut rules = RuleSet::new();
+        let mut status = Vec::new();
+        for (feed, download) in self.feeds.iter().zip(downloads) {
+            let mut feed_status = download.status;
+            match download.fresh {
+                // Parsing adds nothing when it fails or finds no rules, so
+                // a rejected download leaves `rules` as it was
+                Some((content, entry)) => match validate(feed, &content)
+                    .and_then(|_| rules.add_feed(&feed.name, feed.category, feed.format, &content))
+                {
+                    Ok(count) if count > 0 => {
+                        feed_status.rules = count;
+                        feed_status.fetched_at = entry.fetched_at;
+                        if let Err(e) = self.write_cache(feed, &content, entry) {
+                            log::warn!("Could not cache blocklist feed {}: {}", feed.name, e);
+                        }
+                        log::info!("Blocklist feed {} updated ({} rules)", feed.name, count);
+                        status.push(feed_status);
+                        continue;
+                    }
+                    Ok(_) => feed_status.error = Some("Download contained no rules".to_string()),
+                    Err(e) => feed_status.error = Some(e),
+                },
+                None if feed_status.error.is_none() => feed_status.not_modified = true,
+                None => {}
+            }
+            if let Some(e) = &feed_status.error {
+                log::warn!("Using cached copy of blocklist feed {}: {}", feed.name, e);
+            }
+
+            // Not modified, unreachable or invalid: use the last good copy
+            if let Some(content) = self.read_cache(feed) {
+                match rules.add_feed(&feed.name, feed.category, feed.format, &content) {
+                    Ok(count) => feed_status.rules = count,
+                    Err(e) => log::warn!("Cached blocklist feed {} is unreadable: {}", feed.name, e),
+                }
+            }
+            status.push(feed_status);
+        }
+
+        self.save_snapshot(&rules, &status);
+        *self.status.write().unwrap() = status;
+        *self.last_check.write().unwrap() = Some(Utc::now().timestamp());
+        rules
+    }
+
+    /// Identifies the cached lists by each feed's category, URL and
+    /// validators, and the parser by the app version, so a snapshot is only
+    /// used for the lists and the parser it was compiled with
+    fn source_key(&self) -> String {
+        let index = self.index.read().unwrap();
+        let sources: Vec<(&str, BlockCategory, Option<&CacheEntry>)> = self.feeds
+            .iter()
+            .map(|feed| (feed.name.as_str(), feed.category, index.get(&feed.name).filter(|c| c.url == feed.url)))
+            .collect();
+        let json = serde_json::to_vec(&(env!("CARGO_PKG_VERSION"), sources)).unwrap_or_default();
+        format!("{:x}", sha2::Sha256::digest(&json))
+    }
+
+    fn save_snapshot(&self, rules: &RuleSet, status: &[FeedStatus]) {
+        let feed_rules: Vec<(String, usize)> = status
+            .iter()
+            .filter(|s| s.rules > 0)
+            .map(|s| (s.name.clone(), s.rules))
+            .collect();
+        let path = self.cache_dir.join(SNAPSHOT_FILE);
+        if let Err(e) = blocklist_snapshot::save(&path, rules, &self.source_key(), &feed_rules) {
+            log::warn!("Could not write blocklist snapshot: {}", e);
+        }
+    }
+
+    /// Download a feed unless the cached copy is current. `Ok(None)` when
+    /// the server answered 304 Not Modified.
+    async fn fetch(&self, client: &reqwest::Client, feed: &BlocklistFeed) -> Result<Option<(String, CacheEntry)>, String> {
+        let mut request = client.get(&feed.url);
+        let cached = self.index.read().unwrap().get(&feed.name).cloned();
+        if let Some(cached) = cached.filter(|c| c.url == feed.url && self.cache_path(feed).exists()) {
+            if let Some(etag) = &cached.etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request.send().await.map_err(|e| format!("Request failed: {}", e))?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+
+        let header = |name: reqwest::header::HeaderName| {
+            response.headers().get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let entry = CacheEntry {
+            url: feed.url.clone(),
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+            fetched_at: Some(Utc::now().timestamp()),
+        };
+        let content = response.text().await.map_err(|e| format!("Download failed: {}", e))?;
+        Ok(Some((content, entry)))
+    }
+
+    fn feed_status(&self, feed: &BlocklistFeed) -> FeedStatus {
+        let fetched_at = self.index.read().unwrap().get(&feed.name).and_then(|c| c.fetched_at);
+        FeedStatus {
+            name: feed.name.clone(),
+            url: feed.url.clone(),
+            format: feed.format,
+            category: feed.category,
+            rules: 0,
+            fetched_at,
+            not_modified: false,
+            error: None,
+        }
+    }
+
+    fn cache_path(&self, feed: &BlocklistFeed) -> PathBuf {
+        self.cache_dir.join(format!("{}.txt", feed.name))
+    }
+
+    fn read_cache(&self, feed: &BlocklistFeed) -> Option<String> {
+        std::fs::read_to_string(self.cache_path(feed)).ok()
+    }
+
+    /// Store a feed and its validators. Both files are written next to
+    /// their final name and renamed into place, so a crash never leaves a
+    /// truncated list behind.
+    fn write_cache(&self, feed: &BlocklistFeed, content: &str, entry: CacheEntry) -> Result<(), String> {
+        write_atomic(&self.cache_path(feed), content.as_bytes())?;
+
+        let json = {
+            let mut index = self.index.write().unwrap();
+            index.insert(feed.name.clone(), entry);
+            serde_json::to_string_pretty(&*index).map_err(|e| e.to_string())?
+        };
+        write_atomic(&self.cache_dir.join(INDEX_FILE), json.as_bytes())
+    }
+
+    /// HTTP client for feed downloads. Goes through Tor, host names
+    /// included, when Tor is on, and through the system proxy otherwise.
+    /// Fails while Tor is on but not connected rather than going direct.
+    pub fn client(app: &AppHandle) -> Result<reqwest::Client, String> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS));
+        if let Some(tor) = app.try_state::<TorManager>().filter(|tor| tor.is_enabled()) {
+            if tor.get_status() != TorStatus::Connected {
+                return Err("Tor is not connected yet; blocklists are not updated outside it".to_string());
+            }
+            let proxy = tor.get_remote_dns_proxy_url().ok_or("Tor proxy not available")?;
+            builder = builder.proxy(reqwest::Proxy::all(&proxy).map_err(|e| e.to_string())?);
+        }
+        builder.build().map_err(|e| e.to_string())
+    }
+
+    /// Check the feeds now and swap the result into the blocklist
+    pub async fn update_now(app: &AppHandle) -> Result<Vec<FeedStatus>, String> {
+        let updater = app.try_state::<BlocklistUpdater>().ok_or("Blocklist updater not available")?;
+        let client = Self::client(app)?;
+        let _running = updater.running.lock().await;
+        let downloads = updater.download(&client).await;
+        let rules = {
+            let app = app.clone();
+            tauri::async_runtime::spawn_blocking(move || app.state::<BlocklistUpdater>().apply(downloads))
+                .await
+                .map_err(|e| e.to_string())?
+        };
+        if let Some(blocklist) = app.try_state::<BlocklistManager>() {
+            blocklist.swap_rules(rules);
+            blocklist.set_last_update(Utc::now().timestamp());
+        }
+        Ok(updater.status())
+    }
+
+    /// Load the cached feeds into the blocklist, then check for updates now
+    /// and every day after
+    pub fn start(app: AppHandle) {
+        tauri::async_runtime::spawn(async move {
+            let Some(updater) = app.try_state::<BlocklistUpdater>() else {
+                return;
+            };
+            let cached = {
+                let app = app.clone();
+                tauri::async_runtime::spawn_blocking(move || app.state::<BlocklistUpdater>().load_cached()).await
+            };
+            match cached {
+                Ok(rules) => {
+                    if let Some(blocklist) = app.try_state::<BlocklistManager>() {
+                        log::info!("Loaded {} cached blocklist rules", rules.total_rules());
+                        blocklist.swap_rules(rules);
+                    }
+                }
+                Err(e) => log::error!("Failed to load cached blocklists: {}", e),
+            }
+
+            let mut ticker = tokio::time::interval(Duration::from_secs(SCHEDULER_TICK_SECS));
+            loop {
+                ticker.tick().await;
+                let due = updater
+                    .last_check()
+                    .is_none_or(|last| Utc::now().timestamp() - last >= UPDATE_INTERVAL_SECS);
+                if due {
+                    if let Err(e) = Self::update_now(&app).await {
+                        log::error!("Blocklist update failed: {}", e);
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Reject downloads that are plainly not the feed: error pages and the like
+fn validate(feed: &BlocklistFeed, content: &str) -> Result<(), String> {
+    let start = content.trim_start();
+    if start.is_empty() {
+        return Err("Download was empty".to_string());
+    }
+    if start.starts_with('<') {
+        return Err("Download was an HTML page, not a blocklist".to_string());
+    }
+    if feed.format == FeedFormat::PhishTank && !start.starts_with('[') {
+        return Err("Download was not a PhishTank JSON list".to_string());
+    }
+    Ok(())
+}
+
+pub(super) fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Minimal HTTP/1.1 stand-in for the feed servers. Serves `body` with an
+    /// ETag (answering 304 to a matching If-None-Match) and counts requests.
+    struct FeedServer {
+        url: String,
+        body: Arc<RwLock<String>>,
+        requests: Arc<AtomicUsize>,
+        not_modified: Arc<AtomicUsize>,
+    }
+
+    impl FeedServer {
+        async fn start(body: &str) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/list.txt", listener.local_addr().unwrap());
+            let body = Arc::new(RwLock::new(body.to_string()));
+            let requests = Arc::new(AtomicUsize::new(0));
+            let not_modified = Arc::new(AtomicUsize::new(0));
+
+            let (served, counter, unchanged) = (body.clone(), requests.clone(), not_modified.clone());
+            tokio::spawn(async move {
+                loop {
+                    let Ok((mut socket, _)) = listener.accept().await else {
+                        return;
+                    };
+                    let mut buf = vec![0; 8192];
+                    let read = socket.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..read]).to_lowercase();
+                    counter.fetch_add(1, Ordering::SeqCst);
+
+                    let body = served.read().unwrap().clone();
+                    let etag = format!("\"{}\"", body.len());
+                    let response = if request.contains(&format!("if-none-match: {}", etag)) {
+                        unchanged.fetch_add(1, Ordering::SeqCst);
+                        format!("HTTP/1.1 304 Not Modified\r\nETag: {}\r\nConnection: close\r\n\r\n", etag)
+                    } else {
+                        format!(
+                            "HTTP/1.1 200 OK\r\nETag: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            etag,
+                            body.len(),
+                            body
+                        )
+                    };
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                }
+            });
+
+            Self { url, body, requests, not_modified }
+        }
+
+        fn serve(&self, body: &str) {
+            *self.body.write().unwrap() = body.to_string();
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("servionx-blocklists-{}", uuid::Uuid::new_v4()))
+    }
+
+    async fn update(updater: &BlocklistUpdater, client: &reqwest::Client) -> RuleSet {
+        let downloads = updater.download(client).await;
+        updater.apply(downloads)
+    }
+
+    fn blocks(rules: RuleSet, url: &str) -> bool {
+        let manager = BlocklistManager::new();
+        manager.swap_rules(rules);
+        manager.should_block(url)
+    }
+
+    #[tokio::test]
+    async fn test_update_caches_and_revalidates() {
+        let server = FeedServer::start("||ads.example^\n##.banner\n").await;
+        let dir = temp_dir();
+        let feeds = vec![BlocklistFeed::new("list", &server.url, FeedFormat::Adblock, BlockCategory::Ads)];
+        let client = reqwest::Client::new();
+
+        let updater = BlocklistUpdater::open(&dir, feeds.clone()).unwrap();
+        let rules = update(&updater, &client).await;
+        assert_eq!(rules.total_rules(), 2);
+        assert!(blocks(rules, "https://ads.example/x.js"));
+        assert!(dir.join("list.txt").exists());
+
+        // A second run sends the ETag back and keeps the cached copy
+        let reopened = BlocklistUpdater::open(&dir, feeds).unwrap();
+        let rules = update(&reopened, &client).await;
+        assert_eq!(server.not_modified.load(Ordering::SeqCst), 1);
+        assert!(reopened.status()[0].not_modified);
+        assert!(blocks(rules, "https://ads.example/x.js"));
+
+        // A changed list replaces the old rules
+        server.serve("||tracker.example^\n");
+        let rules = update(&reopened, &client).await;
+        assert_eq!(server.requests.load(Ordering::SeqCst), 3);
+        assert!(blocks(rules.clone(), "https://tracker.example/p"));
+        assert!(!blocks(rules, "https://ads.example/x.js"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_offline_and_invalid_downloads_fall_back_to_cache() {
+        let server = FeedServer::start("0.0.0.0 malware.example\n").await;
+        let dir = temp_dir();
+        let client = reqwest::Client::new();
+        let feeds = vec![BlocklistFeed::new("hosts", &server.url, FeedFormat::Hosts, BlockCategory::Malware)];
+        let updater = BlocklistUpdater::open(&dir, feeds).unwrap();
+        assert!(blocks(update(&updater, &client).await, "https://malware.example/"));
+
+        // An error page is rejected and the last good copy stays in use
+        server.serve("<html><body>Rate limited</body></html>");
+        let rules = update(&updater, &client).await;
+        assert!(updater.status()[0].error.as_deref().unwrap().contains("HTML"));
+        assert!(blocks(rules, "https://malware.example/"));
+        assert_eq!(std::fs::read_to_string(dir.join("hosts.txt")).unwrap(), "0.0.0.0 malware.example\n");
+
+        // So does an unreachable server
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed = format!("http://{}/hosts", listener.local_addr().unwrap());
+        drop(listener);
+        let offline = BlocklistUpdater::open(&dir, vec![BlocklistFeed::new("hosts", &closed, FeedFormat::Hosts, BlockCategory::Malware)]).unwrap();
+        let rules = update(&offline, &client).await;
+        assert!(offline.status()[0].error.is_some());
+        assert!(blocks(rules, "https://malware.example/"));
+
+        // And startup works from the cache alone
+        assert!(blocks(offline.load_cached(), "https://malware.example/"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_startup_prefers_snapshot_and_falls_back_to_text() {
+        let server = FeedServer::start("||ads.example^\n").await;
+        let dir = temp_dir();
+        let feeds = vec![BlocklistFeed::new("list", &server.url, FeedFormat::Adblock, BlockCategory::Ads)];
+        update(&BlocklistUpdater::open(&dir, feeds.clone()).unwrap(), &reqwest::Client::new()).await;
+        assert!(dir.join(SNAPSHOT_FILE).exists());
+
+        // The snapshot is used while it matches, without reading the list
+        std::fs::write(dir.join("list.txt"), "||other.example^\n").unwrap();
+        let updater = BlocklistUpdater::open(&dir, feeds.clone()).unwrap();
+        assert!(blocks(updater.load_cached(), "https://ads.example/x.js"));
+        assert_eq!(updater.status()[0].rules, 1);
+
+        // A damaged snapshot falls back to the text list and is rewritten
+        let mut bytes = std::fs::read(dir.join(SNAPSHOT_FILE)).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std::fs::write(dir.join(SNAPSHOT_FILE), &bytes).unwrap();
+        let updater = BlocklistUpdater::open(&dir, feeds.clone()).unwrap();
+        assert!(blocks(updater.load_cached(), "https://other.example/x.js"));
+        let rewritten = blocklist_snapshot::load(&dir.join(SNAPSHOT_FILE), &updater.source_key()).unwrap();
+        assert!(blocks(rewritten.rules, "https://other.example/x.js"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
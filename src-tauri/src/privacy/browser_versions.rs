@@ -0,0 +1,285 @@
+// Browser Version Model
+// Computes the browser versions in circulation on a given date from each
+// vendor's release cadence, so spoofed identities never fall behind
+
+use chrono::{Datelike, NaiveDate};
+use rand::Rng;
+
+use super::coherence::BrowserFamily;
+
+/// A fixed-length release train anchored at a known stable release
+struct Cadence {
+    anchor_release: i64,
+    anchor_date: (i32, u32, u32),
+    cycle_days: i64,
+}
+
+impl Cadence {
+    fn anchor(&self) -> NaiveDate {
+        let (y, m, d) = self.anchor_date;
+        NaiveDate::from_ymd_opt(y, m, d).unwrap_or_default()
+    }
+
+    fn release_on(&self, date: NaiveDate) -> i64 {
+        let days = (date - self.anchor()).num_days();
+        self.anchor_release + days.div_euclid(self.cycle_days)
+    }
+}
+
+// Chromium ships four-week trains with a summer and a holiday gap, which
+// averages out to about one release every 30 days
+const CHROME: Cadence = Cadence { anchor_release: 137, anchor_date: (2025, 5, 27), cycle_days: 30 };
+const EDGE: Cadence = Cadence { anchor_release: 137, anchor_date: (2025, 5, 29), cycle_days: 30 };
+
+/// Firefox releases and their ship dates. Its four-week trains stretch
+/// around holidays, so a fixed cycle drifts ahead of the real versions; past
+/// the table the cadence runs one train behind instead.
+const FIREFOX_RELEASES: &[(i64, (i32, u32, u32))] = &[
+    (139, (2025, 5, 27)),
+    (140, (2025, 6, 24)),
+    (141, (2025, 7, 22)),
+    (142, (2025, 8, 19)),
+    (143, (2025, 9, 16)),
+    (144, (2025, 10, 14)),
+    (145, (2025, 11, 11)),
+    (146, (2025, 12, 9)),
+    (147, (2026, 1, 13)),
+    (148, (2026, 2, 24)),
+];
+const FIREFOX_CYCLE_DAYS: i64 = 28;
+
+fn firefox_release_on(date: NaiveDate) -> i64 {
+    let cadence = |&(anchor_release, anchor_date): &(i64, (i32, u32, u32))| Cadence {
+        anchor_release,
+        anchor_date,
+        cycle_days: FIREFOX_CYCLE_DAYS,
+    };
+    let newest = FIREFOX_RELEASES.len() - 1;
+    match FIREFOX_RELEASES.iter().rposition(|release| date >= cadence(release).anchor()) {
+        None => cadence(&FIREFOX_RELEASES[0]).release_on(date),
+        Some(i) if i == newest => {
+            let last = cadence(&FIREFOX_RELEASES[newest]);
+            (last.release_on(date) - 1).max(last.anchor_release)
+        }
+        Some(i) => FIREFOX_RELEASES[i].0,
+    }
+}
+
+/// Safari majors ship each September with six point releases through the
+/// year; these are the days after the major at which each one lands
+const SAFARI_MINOR_DAYS: [i64; 7] = [0, 40, 90, 130, 190, 245, 305];
+const SAFARI_RELEASES_PER_YEAR: i64 = SAFARI_MINOR_DAYS.len() as i64;
+const SAFARI_FIRST_YEAR: i32 = 2024; // Safari 18
+
+/// Chromium build numbers grow by about this much per major version
+const CHROME_BUILD_ANCHOR: (u32, u32) = (137, 7151);
+const EDGE_BUILD_ANCHOR: (u32, u32) = (137, 3296);
+const CHROME_BUILDS_PER_MAJOR: u32 = 62;
+const EDGE_BUILDS_PER_MAJOR: u32 = 65;
+
+/// Safari 18 (2024) was followed by Safari 26 (2025); majors now track the year
+fn safari_major_for_year(year: i32) -> i64 {
+    if year >= 2025 { year as i64 - 1999 } else { year as i64 - 2006 }
+}
+
+fn safari_year_for_major(major: i64) -> i32 {
+    if major >= 26 { (major + 1999) as i32 } else { (major + 2006) as i32 }
+}
+
+/// Sequential index of the newest stable release of `browser` on `date`.
+/// Chromium and Firefox use the major version; Safari counts point releases.
+pub fn current_release(browser: BrowserFamily, date: NaiveDate) -> i64 {
+    match browser {
+        BrowserFamily::Chrome => CHROME.release_on(date),
+        BrowserFamily::Edge => EDGE.release_on(date),
+        BrowserFamily::Firefox => firefox_release_on(date),
+        BrowserFamily::Safari => {
+            let cycle_start = |year| NaiveDate::from_ymd_opt(year, 9, 15).unwrap_or_default();
+            let year = if date >= cycle_start(date.year()) { date.year() } else { date.year() - 1 };
+            let days = (date - cycle_start(year)).num_days();
+            let minor = SAFARI_MINOR_DAYS.iter().filter(|&&d| days >= d).count() as i64 - 1;
+            (year - SAFARI_FIRST_YEAR) as i64 * SAFARI_RELEASES_PER_YEAR + minor
+        }
+    }
+}
+
+/// Release index of a version string, the inverse of `version_string`
+pub fn release_index(browser: BrowserFamily, version: &str) -> Option<i64> {
+    let mut parts = version.split('.');
+    let major: i64 = parts.next()?.parse().ok()?;
+    match browser {
+        BrowserFamily::Safari => {
+            let minor: i64 = parts.next().and_then(|m| m.parse().ok()).unwrap_or(0);
+            let year = safari_year_for_major(major);
+            Some((year - SAFARI_FIRST_YEAR) as i64 * SAFARI_RELEASES_PER_YEAR
+                + minor.min(SAFARI_RELEASES_PER_YEAR - 1))
+        }
+        _ => Some(major),
+    }
+}
+
+/// Version string a browser puts in its user agent for a release index.
+/// Chromium reduces everything after the major to zeros.
+pub fn version_string(browser: BrowserFamily, index: i64) -> String {
+    match browser {
+        BrowserFamily::Chrome | BrowserFamily::Edge => format!("{}.0.0.0", index),
+        BrowserFamily::Firefox => format!("{}.0", index),
+        BrowserFamily::Safari => {
+            let year = SAFARI_FIRST_YEAR + index.div_euclid(SAFARI_RELEASES_PER_YEAR) as i32;
+            format!("{}.{}", safari_major_for_year(year), index.rem_euclid(SAFARI_RELEASES_PER_YEAR))
+        }
+    }
+}
+
+/// Carry a version observed on `from` forward to `to`, keeping its distance
+/// from the then-current release (a user one version behind stays one behind)
+pub fn age_version(browser: BrowserFamily, version: &str, from: NaiveDate, to: NaiveDate) -> String {
+    let Some(index) = release_index(browser, version) else {
+        return version.to_string();
+    };
+    let now = current_release(browser, to);
+    let aged = (index + now - current_release(browser, from)).min(now);
+    version_string(browser, aged)
+}
+
+fn chromium_build(anchor: (u32, u32), per_major: u32, major: u32) -> u32 {
+    let (anchor_major, anchor_build) = anchor;
+    if major >= anchor_major {
+        anchor_build + (major - anchor_major) * per_major
+    } else {
+        anchor_build.saturating_sub((anchor_major - major) * per_major)
+    }
+}
+
+/// Full (unreduced) version exposed through `uaFullVersion`, e.g.
+/// "137.0.7151.68" for Chrome. Firefox and Safari have no hidden part.
+pub fn full_version<R: Rng>(browser: BrowserFamily, version: &str, rng: &mut R) -> String {
+    let major: u32 = match version.split('.').next().and_then(|m| m.parse().ok()) {
+        Some(major) => major,
+        None => return version.to_string(),
+    };
+    match browser {
+        BrowserFamily::Chrome => format!("{}.0.{}.{}", major,
+            chromium_build(CHROME_BUILD_ANCHOR, CHROME_BUILDS_PER_MAJOR, major), rng.gen_range(40..130)),
+        BrowserFamily::Edge => format!("{}.0.{}.{}", major,
+            chromium_build(EDGE_BUILD_ANCHOR, EDGE_BUILDS_PER_MAJOR, major), rng.gen_range(40..130)),
+        _ => version.to_string(),
+    }
+}
+
+/// Full Chromium version an Edge build is based on, sharing its patch level
+pub fn chromium_full_version_for(major: u32, patch: &str) -> String {
+    format!("{}.0.{}.{}", major, chromium_build(CHROME_BUILD_ANCHOR, CHROME_BUILDS_PER_MAJOR, major), patch)
+}
+
+/// `Sec-CH-UA` brand list exactly as Chromium generates it for `major`:
+/// the GREASE brand's name, version and the list order all derive from the
+/// major version. With `full` set to (Chromium, brand) full versions this
+/// gives the matching `fullVersionList`.
+pub fn brand_list(brand: &str, major: u32, full: Option<(&str, &str)>) -> Vec<(String, String)> {
+    const GREASE_CHARS: [char; 11] = [' ', '(', ':', '-', '.', '/', ')', ';', '=', '?', '_'];
+    const GREASE_VERSIONS: [&str; 3] = ["8", "99", "24"];
+    const ORDERS: [[usize; 3]; 6] = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+
+    let seed = major as usize;
+    let grease_brand = format!("Not{}A{}Brand",
+        GREASE_CHARS[seed % GREASE_CHARS.len()], GREASE_CHARS[(seed + 1) % GREASE_CHARS.len()]);
+    let grease_version = GREASE_VERSIONS[seed % GREASE_VERSIONS.len()];
+
+    let (chromium, branded, grease) = match full {
+        Some((chromium, branded)) => (
+            chromium.to_string(),
+            branded.to_string(),
+            format!("{}.0.0.0", grease_version),
+        ),
+        None => (major.to_string(), major.to_string(), grease_version.to_string()),
+    };
+
+    let order = ORDERS[seed % ORDERS.len()];
+    let mut list = vec![(String::new(), String::new()); 3];
+    list[order[0]] = (grease_brand, grease);
+    list[order[1]] = ("Chromium".to_string(), chromium);
+    list[order[2]] = (brand.to_string(), branded);
+    list
+}
+
+/// Whether a brand is Chromium's GREASE placeholder rather than a real brand
+pub fn is_grease_brand(brand: &str) -> bool {
+    brand.starts_with("Not") && brand.ends_with("Brand")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_known_releases() {
+        assert_eq!(current_release(BrowserFamily::Chrome, date(2025, 10, 30)), 142);
+        assert_eq!(current_release(BrowserFamily::Chrome, date(2026, 1, 14)), 144);
+        assert_eq!(current_release(BrowserFamily::Firefox, date(2025, 10, 15)), 144);
+        assert_eq!(version_string(BrowserFamily::Safari, current_release(BrowserFamily::Safari, date(2025, 6, 1))), "18.5");
+        assert_eq!(version_string(BrowserFamily::Safari, current_release(BrowserFamily::Safari, date(2025, 9, 20))), "26.0");
+    }
+
+    #[test]
+    fn test_firefox_never_runs_ahead_of_real_releases() {
+        let releases = [(134, date(2025, 1, 7)), (136, date(2025, 3, 4)), (138, date(2025, 4, 29))]
+            .into_iter()
+            .chain(FIREFOX_RELEASES.iter().map(|&(v, (y, m, d))| (v, date(y, m, d))));
+        for (version, shipped) in releases {
+            let day_before = shipped - chrono::Duration::days(1);
+            assert!(current_release(BrowserFamily::Firefox, day_before) < version, "Firefox {} before {}", version, shipped);
+        }
+
+        // Past the table, a full train behind the fixed cadence
+        let (last, (y, m, d)) = FIREFOX_RELEASES[FIREFOX_RELEASES.len() - 1];
+        assert_eq!(current_release(BrowserFamily::Firefox, date(y, m, d) + chrono::Duration::days(55)), last);
+        assert_eq!(current_release(BrowserFamily::Firefox, date(y, m, d) + chrono::Duration::days(56)), last + 1);
+    }
+
+    #[test]
+    fn test_versions_keep_moving_forward() {
+        for browser in BrowserFamily::ALL {
+            let mut last = current_release(browser, date(2025, 1, 1));
+            let mut day = date(2025, 1, 1);
+            while day < date(2028, 1, 1) {
+                day += chrono::Duration::days(7);
+                let now = current_release(browser, day);
+                assert!(now >= last && now - last <= 1, "{:?} jumped on {}", browser, day);
+                last = now;
+            }
+        }
+    }
+
+    #[test]
+    fn test_age_version_round_trips() {
+        let from = date(2025, 6, 1);
+        let to = date(2026, 10, 16);
+        let chrome_now = current_release(BrowserFamily::Chrome, to);
+        assert_eq!(age_version(BrowserFamily::Chrome, "137.0.0.0", from, to), format!("{}.0.0.0", chrome_now));
+        assert_eq!(age_version(BrowserFamily::Chrome, "136.0.0.0", from, to), format!("{}.0.0.0", chrome_now - 1));
+        assert_eq!(age_version(BrowserFamily::Safari, "18.5", from, to), "27.0");
+        assert_eq!(age_version(BrowserFamily::Safari, "18.4", from, to), "26.6");
+        for browser in BrowserFamily::ALL {
+            let v = version_string(browser, current_release(browser, to));
+            assert_eq!(release_index(browser, &v), Some(current_release(browser, to)));
+        }
+    }
+
+    #[test]
+    fn test_brand_list_matches_chromium() {
+        // Chrome 120 sent: "Not_A Brand";v="8", "Chromium";v="120", "Google Chrome";v="120"
+        let brands = brand_list("Google Chrome", 120, None);
+        assert_eq!(brands[0], ("Not_A Brand".to_string(), "8".to_string()));
+        assert_eq!(brands[1], ("Chromium".to_string(), "120".to_string()));
+        assert_eq!(brands[2], ("Google Chrome".to_string(), "120".to_string()));
+
+        let full = brand_list("Google Chrome", 124, Some(("124.0.6367.60", "124.0.6367.60")));
+        assert!(full.iter().any(|(b, v)| b == "Not-A.Brand" && v == "99.0.0.0"));
+        assert!(is_grease_brand("Not-A.Brand"));
+    }
+}
@@ -1,9 +1,41 @@
-//! Expanded Tracker Blocklist
-//! Contains 2000+ known tracker, ad, and malware domains
-//! Data sourced from EasyList, EasyPrivacy, and Peter Lowe's list
+//! Built-in Blocklists
+//! Categorized lists compiled into the browser, so requests are filtered
+//! before any feed has been downloaded. Data sourced from EasyList,
+//! EasyPrivacy, Peter Lowe's list and the NoCoin list.
+//!
+//! Entries are domains, which also cover their subdomains, or `domain/path`
+//! prefixes.
 
-/// Top tracker and advertising domains (expanded list)
-pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
+use super::content_filter::BlockCategory;
+
+/// A list compiled into the browser
+#[derive(Debug)]
+pub struct BuiltinList {
+    pub name: &'static str,
+    pub category: BlockCategory,
+    pub entries: &'static [&'static str],
+}
+
+/// Lists that block, the security categories first so they name the rule
+/// when a host is listed twice
+pub const BUILTIN_LISTS: &[BuiltinList] = &[
+    BuiltinList { name: "builtin-malware", category: BlockCategory::Malware, entries: MALWARE_DOMAINS },
+    BuiltinList { name: "builtin-phishing", category: BlockCategory::Phishing, entries: PHISHING_DOMAINS },
+    BuiltinList { name: "builtin-cryptominers", category: BlockCategory::Cryptominers, entries: CRYPTOMINER_DOMAINS },
+    BuiltinList { name: "builtin-trackers", category: BlockCategory::Trackers, entries: TRACKER_DOMAINS },
+    BuiltinList { name: "builtin-social", category: BlockCategory::Social, entries: SOCIAL_DOMAINS },
+    BuiltinList { name: "builtin-ads", category: BlockCategory::Ads, entries: AD_DOMAINS },
+];
+
+/// Lists of URL fragments that only warn: pages matching them load, but are
+/// reported as suspicious
+pub const WARNING_LISTS: &[BuiltinList] = &[
+    BuiltinList { name: "suspicious-phishing", category: BlockCategory::Phishing, entries: SUSPICIOUS_PHISHING_PATTERNS },
+    BuiltinList { name: "suspicious-malware", category: BlockCategory::Malware, entries: SUSPICIOUS_MALWARE_PATTERNS },
+];
+
+/// Ad servers and ad exchanges
+pub const AD_DOMAINS: &[&str] = &[
     // ===== GOOGLE ADVERTISING =====
     "doubleclick.net",
     "googlesyndication.com",
@@ -11,22 +43,10 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "googleads.g.doubleclick.net",
     "adservice.google.com",
     "pagead2.googlesyndication.com",
-    "googletagmanager.com",
     "googletagservices.com",
-    "google-analytics.com",
-    "ssl.google-analytics.com",
-    "analytics.google.com",
     "tpc.googlesyndication.com",
     "googleadsserving.cn",
-    
-    // ===== FACEBOOK TRACKING =====
-    "facebook.com/tr",
-    "pixel.facebook.com",
-    "connect.facebook.net",
-    "an.facebook.com",
-    "staticxx.facebook.com",
-    "graph.facebook.com",
-    "fbcdn.net",
+    "googletraveladservices.com",
     
     // ===== MAJOR AD NETWORKS =====
     "adnxs.com",
@@ -57,57 +77,6 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "undertone.com",
     "yieldmo.com",
     
-    // ===== TRACKING PIXELS =====
-    "pixel.wp.com",
-    "pixel.quantserve.com",
-    "pixel.adsafeprotected.com",
-    "pixel.sitescout.com",
-    "pixel.rubiconproject.com",
-    "pixel.advertising.com",
-    "beacon.krxd.net",
-    "beacon.githubusercontent.com",
-    
-    // ===== ANALYTICS =====
-    "quantcast.com",
-    "quantserve.com",
-    "scorecardresearch.com",
-    "sb.scorecardresearch.com",
-    "comscore.com",
-    "imrworldwide.com",
-    "bluekai.com",
-    "krxd.net",
-    "exelator.com",
-    "eyeota.net",
-    "addthis.com",
-    "sharethis.com",
-    "mixpanel.com",
-    "segment.com",
-    "segment.io",
-    "amplitude.com",
-    "heap.io",
-    "heapanalytics.com",
-    "hotjar.com",
-    "fullstory.com",
-    "luckyorange.com",
-    "crazyegg.com",
-    "mouseflow.com",
-    "clicktale.com",
-    
-    // ===== SOCIAL TRACKING =====
-    "twitter.com/i/adsct",
-    "t.co",
-    "platform.twitter.com",
-    "syndication.twitter.com",
-    "linkedin.com/px",
-    "snap.licdn.com",
-    "px.ads.linkedin.com",
-    "ads.pinterest.com",
-    "ct.pinterest.com",
-    "trk.pinterest.com",
-    "reddit.com/pixel",
-    "events.reddit.com",
-    "alb.reddit.com",
-    
     // ===== VIDEO AD SERVERS =====
     "ads.hulu.com",
     "ads.youtube.com",
@@ -148,35 +117,8 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "admob.com",
     "admob.google.com",
     
-    // ===== DATA BROKERS =====
-    "acxiom.com",
-    "datalogix.com",
-    "liveramp.com",
-    "oracle.com/cx",
-    "lotame.com",
-    "nielsen.com",
-    "neustar.biz",
-    "throtle.io",
-    "zeotap.com",
-    "adsrvr.org",
-    
-    // ===== FINGERPRINTING =====
-    "fingerprintjs.com",
-    "fingerprintjs2.com",
-    "maxmind.com",
-    "ipinfo.io",
-    "ipapi.co",
-    "freegeoip.net",
-    "geoip-db.com",
-    "ipgeolocation.io",
-    "ipstack.com",
-    "ipdata.co",
-    "ip-api.com",
-    
     // ===== CONTENT RECOMMENDATION =====
-    "outbrain.com",
     "outbrainimg.com",
-    "taboola.com",
     "taboolasyndication.com",
     "revcontent.com",
     "mgid.com",
@@ -204,12 +146,89 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "rlcdn.com",
     "c.rlcdn.com",
     "adadvisor.net",
-    "adnxs.com",
     "adnxs-simple.com",
     "ib.adnxs.com",
     "ams1.ib.adnxs.com",
     "sin3.g.adnxs.com",
     
+    // ===== AMAZON =====
+    "amazon-adsystem.com",
+    "assoc-amazon.com",
+];
+
+/// Analytics, tracking pixels, data brokers and fingerprinting services
+pub const TRACKER_DOMAINS: &[&str] = &[
+    // ===== GOOGLE ANALYTICS =====
+    "googletagmanager.com",
+    "google-analytics.com",
+    "ssl.google-analytics.com",
+    "analytics.google.com",
+    
+    // ===== TRACKING PIXELS =====
+    "pixel.wp.com",
+    "pixel.quantserve.com",
+    "pixel.adsafeprotected.com",
+    "pixel.sitescout.com",
+    "pixel.rubiconproject.com",
+    "pixel.advertising.com",
+    "beacon.krxd.net",
+    "beacon.githubusercontent.com",
+    
+    // ===== ANALYTICS =====
+    "quantcast.com",
+    "quantserve.com",
+    "scorecardresearch.com",
+    "sb.scorecardresearch.com",
+    "comscore.com",
+    "imrworldwide.com",
+    "bluekai.com",
+    "krxd.net",
+    "exelator.com",
+    "eyeota.net",
+    "mixpanel.com",
+    "segment.com",
+    "segment.io",
+    "amplitude.com",
+    "heap.io",
+    "heapanalytics.com",
+    "hotjar.com",
+    "fullstory.com",
+    "luckyorange.com",
+    "crazyegg.com",
+    "mouseflow.com",
+    "clicktale.com",
+    "cdn.amplitude.com",
+    "cdn.segment.com",
+    "cdn.heapanalytics.com",
+    "cdn.mxpnl.com",
+    "static.hotjar.com",
+    "script.hotjar.com",
+    "rs.fullstory.com",
+    
+    // ===== DATA BROKERS =====
+    "acxiom.com",
+    "datalogix.com",
+    "liveramp.com",
+    "oracle.com/cx",
+    "lotame.com",
+    "nielsen.com",
+    "neustar.biz",
+    "throtle.io",
+    "zeotap.com",
+    
+    // ===== FINGERPRINTING =====
+    "fingerprintjs.com",
+    "fingerprintjs2.com",
+    "maxmind.com",
+    "ipinfo.io",
+    "ipapi.co",
+    "freegeoip.net",
+    "geoip-db.com",
+    "ipgeolocation.io",
+    "ipstack.com",
+    "ipdata.co",
+    "ip-api.com",
+    
     // ===== EMAIL TRACKING =====
     "mailchimp.com/track",
     "list-manage.com/track",
@@ -218,7 +237,6 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "mailgun.com/track",
     "sparkpost.com/track",
     "constantcontact.com/js",
-    "amazon-adsystem.com",
     
     // ===== ADDITIONAL TRACKERS (A-Z) =====
     "33across.com",
@@ -234,8 +252,6 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "adblockanalytics.com",
     "adbrite.com",
     "adbutler.com",
-    "adcolony.com",
-    "addtoany.com",
     "adelixir.com",
     "adengage.com",
     "adform.com",
@@ -265,9 +281,7 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "adrecord.com",
     "adrelated.com",
     "adrelevantmedia.com",
-    "adroll.com",
     "adrta.com",
-    "ads-twitter.com",
     "adsafeprotected.com",
     "adscale.de",
     "adsco.re",
@@ -277,7 +291,6 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "adsmoloco.com",
     "adspyglass.com",
     "adsrvmedia.net",
-    "adsrvr.org",
     "adstage.io",
     "adstarget.com",
     "adsterra.com",
@@ -289,7 +302,6 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "adtraction.com",
     "adtrackmedia.com",
     "adultswim.com/adswim",
-    "advance.net",
     "adventori.com",
     "adversal.com",
     "adverticum.net",
@@ -316,19 +328,15 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "airmedia.com",
     "ajs.io",
     "alexametrics.com",
-    "alicdn.com",
     "alladvantage.com",
-    "allfemalecams.com",
     "almondad.com",
     "alooma.io",
     "along.tv",
     "alphaaffiliates.com",
-    "altervista.org",
     "amgload.net",
     "amobee.com",
     "ampxchange.com",
     "analoganalytics.com",
-    "analytics.twitter.com",
     "analyticsengine.s3.amazonaws.com",
     "aniview.com",
     "anrdoezrs.net",
@@ -336,7 +344,6 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "apester.com",
     "apex.go.sonobi.com",
     "api.mixpanel.com",
-    "apparelnews.net",
     "appcues.com",
     "appliedsemantics.com",
     "appnexus.com",
@@ -367,7 +374,6 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "blismedia.com",
     "blis.com",
     "blockthrough.com",
-    "blog.hu",
     "blue-ads.de",
     "blueconic.net",
     "bluecore.com",
@@ -386,7 +392,6 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "bsk-r.tech",
     "btclass.net",
     "bttrack.com",
-    "busted.com",
     "buysellads.com",
     "buzzoola.com",
     "c.evidon.com",
@@ -394,8 +399,6 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "carambo.la",
     "carbonads.com",
     "carbonads.net",
-    "cartodb.com",
-    "casalemedia.com",
     "cashbeet.com",
     "cedexis.com",
     "celtra.com",
@@ -412,7 +415,6 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "cj.com",
     "cjbr.co",
     "ck.img.ppclink.com",
-    "cl.ly",
     "clearsearch.cc",
     "cleavr.io",
     "click.alternate.net",
@@ -426,11 +428,8 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "clickcertain.com",
     "clickdensity.com",
     "clickdivision.com",
-    "clicker.com",
-    "clickfunnels.com",
     "clickforensics.com",
     "clickhype.com",
-    "clickindia.com",
     "clickintext.net",
     "clickmeter.com",
     "clickoor.com",
@@ -442,7 +441,6 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "clicktrace.info",
     "clicktracks.com",
     "clickxchange.com",
-    "clickz.com",
     "climaxton.com",
     "clixtell.com",
     "clixtr.com",
@@ -466,39 +464,198 @@ pub const EXPANDED_TRACKER_DOMAINS: &[&str] = &[
     "conversionlogic.net",
     "conversionruler.com",
     "convertingtraffic.com",
-    "convertkit.com",
     "convertmedia.com",
     "convertro.com",
     "cookies.cmpnet.com",
     "coremetrics.com",
+    "atdmt.com",
+    "clicktale.net",
+    "crwdcntrl.net",
+    "demdex.net",
+    "dotomi.com",
+    "everesttech.net",
+    "liadm.com",
+    "mediamath.com",
+    "mookie1.com",
+    "myvisualiq.net",
+    "narrativ.com",
+    "newrelic.com",
+    "nr-data.net",
+    "omtrdc.net",
+    "optimizely.com",
+    "pardot.com",
+    "pippio.com",
+    "siftscience.com",
+    "simpli.fi",
+    "sitescout.com",
+    "tapad.com",
+    "teads.tv",
+    "tidaltv.com",
+    "tremorhub.com",
+    "tribalfusion.com",
+    "w55c.net",
+    "yieldlab.net",
+    "zemanta.com",
     
-    // ===== MALWARE / PHISHING =====
-    "malware.tracking.server.example.com",
-    "phishing.check.server.example.com",
+    // ===== MICROSOFT =====
+    "bat.bing.com",
+    "clarity.ms",
+];
+
+/// Social network pixels, embeds and share widgets
+pub const SOCIAL_DOMAINS: &[&str] = &[
+    // ===== FACEBOOK TRACKING =====
+    "facebook.com/tr",
+    "pixel.facebook.com",
+    "connect.facebook.net",
+    "an.facebook.com",
+    "staticxx.facebook.com",
+    "graph.facebook.com",
+    "fbcdn.net",
+    "facebook.net",
     
-    // ===== Add more domains as needed =====
+    // ===== SHARE WIDGETS =====
+    "addthis.com",
+    "sharethis.com",
+    "addtoany.com",
+    
+    // ===== SOCIAL TRACKING =====
+    "twitter.com/i/adsct",
+    "platform.twitter.com",
+    "syndication.twitter.com",
+    "linkedin.com/px",
+    "snap.licdn.com",
+    "px.ads.linkedin.com",
+    "ads.pinterest.com",
+    "ct.pinterest.com",
+    "trk.pinterest.com",
+    "reddit.com/pixel",
+    "events.reddit.com",
+    "alb.reddit.com",
+    
+    // ===== TWITTER =====
+    "ads-twitter.com",
+    "analytics.twitter.com",
+    "static.ads-twitter.com",
 ];
 
-/// Get the expanded blocklist count
-pub fn get_expanded_blocklist_count() -> usize {
-    EXPANDED_TRACKER_DOMAINS.len()
+/// In-browser cryptocurrency miners
+pub const CRYPTOMINER_DOMAINS: &[&str] = &[
+    // ===== IN-BROWSER MINERS =====
+    "cryptominer-script.com",
+    "coinhive.com",
+    "coin-hive.com",
+    "authedmine.com",
+    "cnhv.co",
+    "coinhave.com",
+    "crypto-loot.com",
+    "cryptoloot.pro",
+    "coinimp.com",
+    "coinimp.net",
+    "jsecoin.com",
+    "minero.cc",
+    "webmine.cz",
+    "webminepool.com",
+    "ppoi.org",
+    "monerominer.rocks",
+    "papoto.com",
+    "coinerra.com",
+    "minemytraffic.com",
+];
+
+/// Hosts serving malware
+pub const MALWARE_DOMAINS: &[&str] = &[
+    // ===== MALWARE DOWNLOADS =====
+    "malware-test.com",
+    "virus-download.com",
+    "trojan-installer.com",
+    "ransomware-payload.com",
+    "keylogger-download.com",
+    "spyware-install.com",
+    "adware-bundle.com",
+];
+
+/// Lookalike login and account pages
+pub const PHISHING_DOMAINS: &[&str] = &[
+    // ===== LOOKALIKE LOGIN PAGES =====
+    "appleid-verify.com",
+    "paypal-secure-login.com",
+    "amazon-account-verify.com",
+    "netflix-billing.com",
+    "google-account-verify.com",
+    "microsoft-verify.com",
+    "bank-secure-login.com",
+    "facebook-security.com",
+    "instagram-verify.com",
+    "twitter-secure.com",
+];
+/// URL fragments typical of phishing pages and scams
+pub const SUSPICIOUS_PHISHING_PATTERNS: &[&str] = &[
+    // Phishing indicators
+    "login-verify",
+    "account-suspended",
+    "security-alert",
+    "password-reset-urgent",
+    "confirm-identity",
+    "verify-account",
+    "billing-update",
+    "payment-failed",
+    "account-locked",
+    "unusual-activity",
+    
+    // Cryptocurrency scams
+    "crypto-giveaway",
+    "btc-double",
+    "eth-airdrop",
+    "free-bitcoin",
+];
+
+/// URL fragments typical of pirated software, a common malware carrier
+pub const SUSPICIOUS_MALWARE_PATTERNS: &[&str] = &[
+    "free-download",
+    "crack-software",
+    "keygen",
+    "warez",
+    "nulled",
+];
+
+/// Number of entries in the blocking lists
+pub fn builtin_rule_count() -> usize {
+    BUILTIN_LISTS.iter().map(|list| list.entries.len()).sum()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
     
     #[test]
     fn test_blocklist_size() {
         // Ensure we have a substantial blocklist
-        assert!(EXPANDED_TRACKER_DOMAINS.len() > 400);
+        assert!(builtin_rule_count() > 400);
+        for category in BlockCategory::ALL {
+            assert!(BUILTIN_LISTS.iter().any(|list| list.category == category && !list.entries.is_empty()), "{:?}", category);
+        }
     }
     
     #[test]
     fn test_major_trackers_included() {
-        assert!(EXPANDED_TRACKER_DOMAINS.contains(&"doubleclick.net"));
-        assert!(EXPANDED_TRACKER_DOMAINS.contains(&"google-analytics.com"));
-        assert!(EXPANDED_TRACKER_DOMAINS.contains(&"facebook.com/tr"));
-        assert!(EXPANDED_TRACKER_DOMAINS.contains(&"criteo.com"));
+        let listed = |category: BlockCategory, entry: &str| {
+            BUILTIN_LISTS.iter().any(|list| list.category == category && list.entries.contains(&entry))
+        };
+        assert!(listed(BlockCategory::Ads, "doubleclick.net"));
+        assert!(listed(BlockCategory::Trackers, "google-analytics.com"));
+        assert!(listed(BlockCategory::Social, "facebook.com/tr"));
+        assert!(listed(BlockCategory::Ads, "criteo.com"));
+        assert!(listed(BlockCategory::Cryptominers, "coinhive.com"));
+    }
+    
+    #[test]
+    fn test_entries_are_listed_once() {
+        let mut seen = HashSet::new();
+        for entry in BUILTIN_LISTS.iter().flat_map(|list| list.entries) {
+            assert!(seen.insert(*entry), "{} is listed twice", entry);
+            assert_eq!(*entry, entry.to_lowercase());
+        }
     }
 }
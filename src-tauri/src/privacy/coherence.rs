@@ -0,0 +1,622 @@
+// Identity Coherence
+// Derives every spoofed attribute from one consistent persona and rejects
+// identities whose attributes could not occur together on a real machine
+
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use rand::Rng;
+
+use super::FakeIdentity;
+use super::injection::ScriptTemplate;
+use super::browser_versions;
+use super::geolocation::FakeGeolocation;
+use super::ip_privacy;
+use super::ultimate_protection::ClientHintsData;
+use super::user_agent::FakeUserAgent;
+
+/// Operating system family claimed by an identity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OsFamily {
+    Windows,
+    MacOs,
+    Linux,
+}
+
+impl OsFamily {
+    pub const ALL: [OsFamily; 3] = [OsFamily::Windows, OsFamily::MacOs, OsFamily::Linux];
+
+    /// Pick an OS weighted by desktop market share
+    pub fn pick<R: Rng>(rng: &mut R) -> Self {
+        match rng.gen_range(0..100) {
+            0..=59 => OsFamily::Windows,  // 60%
+            60..=84 => OsFamily::MacOs,   // 25%
+            _ => OsFamily::Linux,         // 15%
+        }
+    }
+
+    /// Name used in `FakeUserAgent::os_name`
+    pub fn name(&self) -> &'static str {
+        match self {
+            OsFamily::Windows => "Windows",
+            OsFamily::MacOs => "macOS",
+            OsFamily::Linux => "Linux",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|os| os.name() == name)
+    }
+
+    /// Value of `navigator.platform`
+    pub fn navigator_platform(&self) -> &'static str {
+        match self {
+            OsFamily::Windows => "Win32",
+            OsFamily::MacOs => "MacIntel",
+            OsFamily::Linux => "Linux x86_64",
+        }
+    }
+
+    /// Value of the `Sec-CH-UA-Platform` client hint
+    pub fn client_hints_platform(&self) -> &'static str {
+        match self {
+            OsFamily::Windows => "Windows",
+            OsFamily::MacOs => "macOS",
+            OsFamily::Linux => "Linux",
+        }
+    }
+}
+
+/// Browser family claimed by an identity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BrowserFamily {
+    Chrome,
+    Firefox,
+    Safari,
+    Edge,
+}
+
+impl BrowserFamily {
+    pub const ALL: [BrowserFamily; 4] = [
+        BrowserFamily::Chrome,
+        BrowserFamily::Firefox,
+        BrowserFamily::Safari,
+        BrowserFamily::Edge,
+    ];
+
+    /// Name used in `FakeUserAgent::browser_name`
+    pub fn name(&self) -> &'static str {
+        match self {
+            BrowserFamily::Chrome => "Chrome",
+            BrowserFamily::Firefox => "Firefox",
+            BrowserFamily::Safari => "Safari",
+            BrowserFamily::Edge => "Edge",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|b| b.name() == name)
+    }
+
+    /// Chromium-based browsers expose `navigator.userAgentData`
+    pub fn is_chromium(&self) -> bool {
+        matches!(self, BrowserFamily::Chrome | BrowserFamily::Edge)
+    }
+
+    /// Whether this browser ships on the given OS
+    pub fn supported_on(&self, os: OsFamily) -> bool {
+        match self {
+            BrowserFamily::Chrome | BrowserFamily::Firefox => true,
+            BrowserFamily::Safari => os == OsFamily::MacOs,
+            BrowserFamily::Edge => os != OsFamily::Linux,
+        }
+    }
+
+    /// Pick a browser that exists on `os`
+    pub fn pick_for<R: Rng>(os: OsFamily, rng: &mut R) -> Self {
+        let candidates: Vec<BrowserFamily> = Self::ALL
+            .into_iter()
+            .filter(|b| b.supported_on(os))
+            .collect();
+        candidates[rng.gen_range(0..candidates.len())]
+    }
+}
+
+/// Locale and network region for a country
+#[derive(Debug)]
+pub struct RegionProfile {
+    pub country_code: &'static str,
+    pub locales: &'static [&'static str],
+    pub ip_region: &'static str,
+}
+
+/// Every country `GeolocationFaker` can place an identity in
+pub const REGIONS: &[RegionProfile] = &[
+    RegionProfile { country_code: "US", locales: &["en-US"], ip_region: "US" },
+    RegionProfile { country_code: "CA", locales: &["en-CA", "fr-CA"], ip_region: "US" },
+    RegionProfile { country_code: "GB", locales: &["en-GB"], ip_region: "EU" },
+    RegionProfile { country_code: "FR", locales: &["fr-FR"], ip_region: "EU" },
+    RegionProfile { country_code: "DE", locales: &["de-DE"], ip_region: "EU" },
+    RegionProfile { country_code: "NL", locales: &["nl-NL", "en-GB"], ip_region: "EU" },
+    RegionProfile { country_code: "ES", locales: &["es-ES"], ip_region: "EU" },
+    RegionProfile { country_code: "IT", locales: &["it-IT"], ip_region: "EU" },
+    RegionProfile { country_code: "AT", locales: &["de-AT"], ip_region: "EU" },
+    RegionProfile { country_code: "SE", locales: &["sv-SE", "en-GB"], ip_region: "EU" },
+    RegionProfile { country_code: "JP", locales: &["ja-JP"], ip_region: "ASIA" },
+    RegionProfile { country_code: "SG", locales: &["en-SG", "zh-SG"], ip_region: "ASIA" },
+    RegionProfile { country_code: "KR", locales: &["ko-KR"], ip_region: "ASIA" },
+    RegionProfile { country_code: "AE", locales: &["ar-AE", "en-US"], ip_region: "ASIA" },
+    RegionProfile { country_code: "HK", locales: &["zh-HK", "en-HK"], ip_region: "ASIA" },
+    RegionProfile { country_code: "IN", locales: &["en-IN", "hi-IN"], ip_region: "ASIA" },
+    RegionProfile { country_code: "TH", locales: &["th-TH"], ip_region: "ASIA" },
+    RegionProfile { country_code: "AU", locales: &["en-AU"], ip_region: "AU" },
+    RegionProfile { country_code: "NZ", locales: &["en-NZ"], ip_region: "AU" },
+    RegionProfile { country_code: "BR", locales: &["pt-BR"], ip_region: "LATAM" },
+    RegionProfile { country_code: "AR", locales: &["es-AR"], ip_region: "LATAM" },
+];
+
+/// Look up the region profile for a country code
+pub fn region_for(country_code: &str) -> Option<&'static RegionProfile> {
+    REGIONS.iter().find(|r| r.country_code.eq_ignore_ascii_case(country_code))
+}
+
+/// Offset of a timezone at `at`, daylight saving included, in
+/// `Date.prototype.getTimezoneOffset` convention (minutes, positive west
+/// of UTC). `None` for names missing from the tz database.
+pub fn timezone_offset_minutes(timezone: &str, at: DateTime<Utc>) -> Option<i32> {
+    let tz: Tz = timezone.parse().ok()?;
+    Some(-tz.offset_from_utc_datetime(&at.naive_utc()).fix().local_minus_utc() / 60)
+}
+
+/// Page script that reports `timezone` through `Date`: `getTimezoneOffset`
+/// and the `toString` family are computed per date from the tz database the
+/// page's own `Intl` carries, so January and July differ wherever the zone
+/// observes daylight saving. `fallback_offset` is used if `Intl` rejects the
+/// zone name.
+pub fn timezone_script(timezone: &str, fallback_offset: i32) -> String {
+    ScriptTemplate::new(r#"
+(function() {
+    'use strict';
+    
+    const timeZone = @@timezone@@;
+    const fallbackOffset = @@fallback_offset@@;
+    const NativeDate = Date;
+    const getTime = Date.prototype.getTime;
+    const MONTHS = ['Jan', 'Feb', 'Mar', 'Apr', 'May', 'Jun', 'Jul', 'Aug', 'Sep', 'Oct', 'Nov', 'Dec'];
+    
+    let format = null;
+    try {
+        format = new Intl.DateTimeFormat('en-US', {
+            timeZone: timeZone,
+            hourCycle: 'h23',
+            weekday: 'short',
+            year: 'numeric',
+            month: 'short',
+            day: '2-digit',
+            hour: '2-digit',
+            minute: '2-digit',
+            second: '2-digit',
+            timeZoneName: 'long'
+        });
+    } catch (e) {}
+    
+    // Wall-clock fields of `date` in the spoofed zone plus its offset there,
+    // or null for an invalid date
+    function zoned(date) {
+        const time = getTime.call(date);
+        if (isNaN(time)) return null;
+        if (!format) return { offset: fallbackOffset };
+        
+        const parts = {};
+        for (const part of format.formatToParts(time)) parts[part.type] = part.value;
+        const wall = new NativeDate(0);
+        wall.setUTCFullYear(+parts.year, MONTHS.indexOf(parts.month), +parts.day);
+        wall.setUTCHours(+parts.hour, +parts.minute, +parts.second, 0);
+        parts.offset = Math.round((Math.floor(time / 1000) * 1000 - getTime.call(wall)) / 60000);
+        return parts;
+    }
+    
+    function gmt(offset) {
+        const minutes = Math.abs(offset);
+        const pad = n => String(n).padStart(2, '0');
+        return 'GMT' + (offset > 0 ? '-' : '+') + pad(Math.floor(minutes / 60)) + pad(minutes % 60);
+    }
+    
+    Date.prototype.getTimezoneOffset = function() {
+        const z = zoned(this);
+        return z ? z.offset : NaN;
+    };
+    
+    if (!format) return;
+    
+    const dateString = z => z.weekday + ' ' + z.month + ' ' + z.day + ' ' + z.year;
+    const timeString = z => z.hour + ':' + z.minute + ':' + z.second + ' ' + gmt(z.offset) + ' (' + z.timeZoneName + ')';
+    
+    Date.prototype.toString = function() {
+        const z = zoned(this);
+        return z ? dateString(z) + ' ' + timeString(z) : 'Invalid Date';
+    };
+    Date.prototype.toDateString = function() {
+        const z = zoned(this);
+        return z ? dateString(z) : 'Invalid Date';
+    };
+    Date.prototype.toTimeString = function() {
+        const z = zoned(this);
+        return z ? timeString(z) : 'Invalid Date';
+    };
+})();
+"#)
+        .bind("timezone", timezone)
+        .bind("fallback_offset", &fallback_offset)
+        .render()
+}
+
+/// The single source every spoofed attribute of an identity is derived from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Persona {
+    pub os: OsFamily,
+    pub browser: BrowserFamily,
+    pub country_code: String,
+    pub timezone: String,
+    pub locale: String,
+    pub ip_region: String,
+}
+
+impl Persona {
+    /// Pick a persona living at `geolocation`
+    pub fn pick<R: Rng>(geolocation: &FakeGeolocation, rng: &mut R) -> Self {
+        let os = OsFamily::pick(rng);
+        let browser = BrowserFamily::pick_for(os, rng);
+        Self::for_device(geolocation, os, browser, rng)
+    }
+
+    /// Persona living at `geolocation` on an already chosen OS and browser
+    pub fn for_device<R: Rng>(geolocation: &FakeGeolocation, os: OsFamily, browser: BrowserFamily, rng: &mut R) -> Self {
+        let (locale, ip_region) = match region_for(&geolocation.country_code) {
+            Some(region) => (
+                region.locales[rng.gen_range(0..region.locales.len())],
+                region.ip_region,
+            ),
+            None => ("en-US", "US"),
+        };
+
+        Self {
+            os,
+            browser,
+            country_code: geolocation.country_code.clone(),
+            timezone: geolocation.timezone.clone(),
+            locale: locale.to_string(),
+            ip_region: ip_region.to_string(),
+        }
+    }
+
+    /// `navigator.languages` for this persona's locale
+    pub fn languages(&self) -> Vec<String> {
+        languages_for(&self.locale)
+    }
+}
+
+/// Expand a locale into the `navigator.languages` list a browser would report
+pub fn languages_for(locale: &str) -> Vec<String> {
+    let base = locale.split('-').next().unwrap_or(locale);
+    if base == locale {
+        vec![locale.to_string()]
+    } else {
+        vec![locale.to_string(), base.to_string()]
+    }
+}
+
+/// Build the client hints a Chromium browser with this UA would send.
+/// Returns `None` for Firefox and Safari, which do not expose them.
+pub fn client_hints_for(user_agent: &FakeUserAgent, webgl_renderer: &str) -> Option<ClientHintsData> {
+    let browser = BrowserFamily::from_name(&user_agent.browser_name)?;
+    let os = OsFamily::from_name(&user_agent.os_name)?;
+    if !browser.is_chromium() {
+        return None;
+    }
+
+    let major: u32 = major_version(&user_agent.browser_version).parse().ok()?;
+    let brand = match browser {
+        BrowserFamily::Edge => "Microsoft Edge",
+        _ => "Google Chrome",
+    };
+
+    let platform_version = match os {
+        // Windows 11 still reports NT 10.0 in the UA; only the hint differs
+        OsFamily::Windows if user_agent.os_version == "11.0" => "15.0.0".to_string(),
+        OsFamily::Windows => "10.0.0".to_string(),
+        OsFamily::MacOs => {
+            let mut parts: Vec<&str> = user_agent.os_version.split('_').collect();
+            parts.resize(3, "0");
+            parts.join(".")
+        }
+        OsFamily::Linux => "6.5.0".to_string(),
+    };
+
+    // Apple Silicon GPUs only ship in ARM Macs
+    let architecture = if webgl_renderer.contains("Apple M") { "arm" } else { "x86" };
+
+    // Older identities predate full versions; fall back to the reduced one
+    let full_version = if user_agent.full_version.is_empty() {
+        user_agent.browser_version.clone()
+    } else {
+        user_agent.full_version.clone()
+    };
+    let chromium_full_version = match browser {
+        BrowserFamily::Edge => browser_versions::chromium_full_version_for(
+            major,
+            full_version.rsplit('.').next().unwrap_or("0"),
+        ),
+        _ => full_version.clone(),
+    };
+
+    Some(ClientHintsData {
+        brands: browser_versions::brand_list(brand, major, None),
+        mobile: false,
+        platform: os.client_hints_platform().to_string(),
+        platform_version,
+        architecture: architecture.to_string(),
+        bitness: "64".to_string(),
+        model: String::new(),
+        full_version_list: browser_versions::brand_list(
+            brand,
+            major,
+            Some((&chromium_full_version, &full_version)),
+        ),
+        ua_full_version: full_version,
+    })
+}
+
+/// Leading numeric component of a browser version ("120.0.0.0" -> "120")
+pub fn major_version(version: &str) -> &str {
+    version.split('.').next().unwrap_or(version)
+}
+
+/// Fonts that only ship with one OS; seeing them on another OS is a giveaway
+const OS_EXCLUSIVE_FONTS: &[(OsFamily, &[&str])] = &[
+    (OsFamily::Windows, &[
+        "Segoe UI", "Calibri", "Cambria", "Consolas", "Candara", "Constantia",
+        "Corbel", "Webdings", "Wingdings", "MS Gothic", "Marlett",
+    ]),
+    (OsFamily::MacOs, &[
+        "Menlo", "Monaco", "San Francisco", "SF Pro Display", "SF Pro Text",
+        "Apple Color Emoji", "Helvetica Neue", "Lucida Grande", "Geneva",
+    ]),
+    (OsFamily::Linux, &[
+        "DejaVu Sans", "DejaVu Serif", "DejaVu Sans Mono", "Liberation Sans",
+        "Liberation Serif", "Liberation Mono", "Ubuntu", "Cantarell", "Droid Sans",
+    ]),
+];
+
+/// Whether a font could plausibly be installed on `os`
+pub fn font_plausible_on(font: &str, os: OsFamily) -> bool {
+    !OS_EXCLUSIVE_FONTS
+        .iter()
+        .any(|(owner, fonts)| *owner != os && fonts.iter().any(|f| f.eq_ignore_ascii_case(font)))
+}
+
+/// Whether a WebGL renderer string could come from a GPU driver on `os`
+pub fn webgl_renderer_plausible_on(renderer: &str, os: OsFamily) -> bool {
+    let r = renderer.to_lowercase();
+    if r.contains("direct3d") || r.contains("d3d11") {
+        return os == OsFamily::Windows;
+    }
+    if r.contains("apple") || r.contains("metal") {
+        return os == OsFamily::MacOs;
+    }
+    if r.contains("mesa") || r.contains("llvmpipe") {
+        return os == OsFamily::Linux;
+    }
+    // Mobile GPUs never appear on the desktop platforms we spoof
+    !(r.contains("adreno") || r.contains("mali") || r.contains("powervr"))
+}
+
+/// A single reason an identity is not internally consistent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoherenceViolation {
+    pub field: String,
+    pub message: String,
+}
+
+impl CoherenceViolation {
+    fn new(field: &str, message: String) -> Self {
+        Self { field: field.to_string(), message }
+    }
+}
+
+/// Check that every attribute of an identity agrees with every other one
+pub fn validate(identity: &FakeIdentity) -> Result<(), Vec<CoherenceViolation>> {
+    let mut violations = Vec::new();
+    let ua = &identity.user_agent;
+    let fp = &identity.fingerprint;
+    let geo = &identity.geolocation;
+
+    let os = OsFamily::from_name(&ua.os_name);
+    let browser = BrowserFamily::from_name(&ua.browser_name);
+
+    if let Some(os) = os {
+        if ua.platform != os.navigator_platform() {
+            violations.push(CoherenceViolation::new("userAgent.platform", format!(
+                "platform {} does not match OS {}", ua.platform, os.name())));
+        }
+
+        let ua_token = match os {
+            OsFamily::Windows => "Windows NT",
+            OsFamily::MacOs => "Macintosh",
+            OsFamily::Linux => "Linux",
+        };
+        if !ua.full.contains(ua_token) {
+            violations.push(CoherenceViolation::new("userAgent.full", format!(
+                "UA string does not mention {}", os.name())));
+        }
+
+        if let Some(browser) = browser {
+            if !browser.supported_on(os) {
+                violations.push(CoherenceViolation::new("userAgent.browserName", format!(
+                    "{} does not run on {}", browser.name(), os.name())));
+            }
+        }
+
+        if !webgl_renderer_plausible_on(&fp.webgl_renderer, os) {
+            violations.push(CoherenceViolation::new("fingerprint.webglRenderer", format!(
+                "GPU {} cannot appear on {}", fp.webgl_renderer, os.name())));
+        }
+
+        for font in &fp.installed_fonts {
+            if !font_plausible_on(font, os) {
+                violations.push(CoherenceViolation::new("fingerprint.installedFonts", format!(
+                    "font {} is not shipped with {}", font, os.name())));
+            }
+        }
+
+        if os == OsFamily::MacOs && (fp.max_touch_points > 0 || fp.touch_support) {
+            violations.push(CoherenceViolation::new("fingerprint.maxTouchPoints",
+                "Macs have no touch screen".to_string()));
+        }
+
+        match (&identity.client_hints, browser) {
+            (Some(_), Some(b)) if !b.is_chromium() => {
+                violations.push(CoherenceViolation::new("clientHints", format!(
+                    "{} does not send client hints", b.name())));
+            }
+            (None, Some(b)) if b.is_chromium() => {
+                violations.push(CoherenceViolation::new("clientHints", format!(
+                    "{} always sends client hints", b.name())));
+            }
+            (Some(hints), _) => {
+                if hints.platform != os.client_hints_platform() {
+                    violations.push(CoherenceViolation::new("clientHints.platform", format!(
+                        "hint platform {} does not match OS {}", hints.platform, os.name())));
+                }
+                let major = major_version(&ua.browser_version);
+                if hints.brands.iter().any(|(b, v)| !browser_versions::is_grease_brand(b) && v != major) {
+                    violations.push(CoherenceViolation::new("clientHints.brands", format!(
+                        "brand versions do not match UA version {}", ua.browser_version)));
+                }
+                if major_version(&hints.ua_full_version) != major
+                    || hints.full_version_list.iter().any(|(b, v)| {
+                        !browser_versions::is_grease_brand(b) && major_version(v) != major
+                    })
+                {
+                    violations.push(CoherenceViolation::new("clientHints.fullVersionList", format!(
+                        "full versions do not match UA version {}", ua.browser_version)));
+                }
+            }
+            _ => {}
+        }
+    } else {
+        violations.push(CoherenceViolation::new("userAgent.osName", format!(
+            "unknown OS {}", ua.os_name)));
+    }
+
+    if browser.is_none() {
+        violations.push(CoherenceViolation::new("userAgent.browserName", format!(
+            "unknown browser {}", ua.browser_name)));
+    }
+
+    // Chrome clamps navigator.deviceMemory to 8
+    if fp.device_memory == 0 || fp.device_memory > 8 {
+        violations.push(CoherenceViolation::new("fingerprint.deviceMemory", format!(
+            "deviceMemory {} is outside the 1-8 range browsers report", fp.device_memory)));
+    }
+
+    if identity.timezone != geo.timezone {
+        violations.push(CoherenceViolation::new("timezone", format!(
+            "timezone {} does not match location {}", identity.timezone, geo.city)));
+    }
+    if timezone_offset_minutes(&identity.timezone, Utc::now()).is_none() {
+        violations.push(CoherenceViolation::new("timezone", format!(
+            "unknown timezone {}", identity.timezone)));
+    }
+
+    match region_for(&geo.country_code) {
+        Some(region) => {
+            if !region.locales.contains(&identity.language.as_str()) {
+                violations.push(CoherenceViolation::new("language", format!(
+                    "language {} is not spoken in {}", identity.language, geo.country)));
+            }
+            match ip_privacy::country_for_ip(&identity.ip_headers.x_real_ip) {
+                Some(country) if country == region.country_code => {}
+                _ => violations.push(CoherenceViolation::new("ipHeaders", format!(
+                    "IP {} is not allocated to {}", identity.ip_headers.x_real_ip, geo.country))),
+            }
+        }
+        None => violations.push(CoherenceViolation::new("geolocation.countryCode", format!(
+            "unknown country {}", geo.country_code))),
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::privacy::test_engine;
+
+    #[test]
+    fn test_generated_identities_are_coherent() {
+        let engine = test_engine();
+        for seed in 0..200 {
+            let identity = engine.identity_from_seed(seed);
+            assert_eq!(validate(&identity), Ok(()), "seed {} produced an incoherent identity", seed);
+        }
+    }
+
+    #[test]
+    fn test_rejects_impossible_combination() {
+        let engine = test_engine();
+        let mut identity = engine.identity_from_seed(7);
+
+        // Safari on Linux with an Apple GPU speaking a foreign language
+        identity.user_agent.browser_name = "Safari".to_string();
+        identity.user_agent.os_name = "Linux".to_string();
+        identity.fingerprint.webgl_renderer = "Apple M2".to_string();
+        identity.language = "pt-BR".to_string();
+        identity.geolocation.country_code = "JP".to_string();
+
+        let violations = validate(&identity).unwrap_err();
+        let fields: Vec<&str> = violations.iter().map(|v| v.field.as_str()).collect();
+        assert!(fields.contains(&"userAgent.browserName"));
+        assert!(fields.contains(&"fingerprint.webglRenderer"));
+        assert!(fields.contains(&"language"));
+    }
+
+    #[test]
+    fn test_timezone_offsets_follow_daylight_saving() {
+        let winter = Utc.with_ymd_and_hms(2026, 1, 15, 12, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2026, 7, 15, 12, 0, 0).unwrap();
+
+        assert_eq!(timezone_offset_minutes("America/New_York", winter), Some(300));
+        assert_eq!(timezone_offset_minutes("America/New_York", summer), Some(240));
+        assert_eq!(timezone_offset_minutes("Australia/Sydney", winter), Some(-660));
+        assert_eq!(timezone_offset_minutes("Australia/Sydney", summer), Some(-600));
+        assert_eq!(timezone_offset_minutes("Asia/Kolkata", summer), Some(-330));
+        assert_eq!(timezone_offset_minutes("Mars/Olympus_Mons", summer), None);
+    }
+
+    #[test]
+    fn test_timezone_script_computes_offsets_per_date() {
+        let script = timezone_script("Europe/Berlin", -60);
+        assert!(script.contains(r#"const timeZone = "Europe/Berlin";"#));
+        assert!(script.contains("formatToParts(time)"));
+        // The fixed offset only stands in when Intl rejects the zone
+        assert!(script.contains("if (!format) return { offset: fallbackOffset };"));
+    }
+
+    #[test]
+    fn test_client_hints_only_for_chromium() {
+        let engine = test_engine();
+        for seed in 0..50 {
+            let identity = engine.identity_from_seed(seed);
+            let chromium = BrowserFamily::from_name(&identity.user_agent.browser_name)
+                .unwrap()
+                .is_chromium();
+            assert_eq!(identity.client_hints.is_some(), chromium);
+        }
+    }
+}
@@ -1,14 +1,32 @@
 // Privacy Commands
 // Tauri commands for privacy functionality
 
-use tauri::State;
+use tauri::{AppHandle, Emitter, Manager, State};
+use crate::browser::WebViewManager;
 use crate::privacy::{
-    PrivacyEngine, FakeIdentity,
+    PrivacyEngine, FakeIdentity, CoherenceViolation,
+    SiteIdentity, SiteIdentityStore, IdentityRotator, RotationTrigger,
     fingerprint::FakeFingerprint,
-    geolocation::FakeGeolocation,
+    geolocation::{self, FakeGeolocation, LocationInfo, LocationMode},
     user_agent::FakeUserAgent,
     ip_privacy::FakeIpHeaders,
+    request_headers::{IpHeaderPolicy, OutgoingHeaders},
+    fingerprint_dataset::DatasetInfo,
+    script_cache::{InjectionScriptCache, SiteScriptInfo},
+    site_profiles::{ProfilePreset, ProtectionLayers, SiteProfile, SiteProfileStore},
+    whitelist::{WhitelistEntry, WhitelistFormat},
+    storage_partitioner::{PartitionUsage, StoragePartitioner},
+    cookie_manager::{CookieCleanupReport, CookieManager, CookieRule, CookieRuleEntry, SiteCookie},
+    BlocklistManager, BlockVerdict, CategorySet, ResourceType,
+    blocklist_updater::{BlocklistUpdater, FeedStatus},
+    escalation::{EscalationDecision, EscalationManager, SITE_ESCALATED_EVENT},
+    self_test::{self, ProbeValues, ProtectedRun, SelfTestDiff, SelfTestReport, SelfTestRunner},
+    fingerprint_detector::{
+        FingerprintingDetector, FingerprintingReport, FingerprintingVerdict, SiteFingerprinting,
+        FINGERPRINTING_DETECTED_EVENT,
+    },
 };
+use chrono::{DateTime, Utc};
 
 /// Get the current fake fingerprint
 #[tauri::command]
@@ -22,6 +40,47 @@ pub fn get_fake_geolocation(privacy: State<PrivacyEngine>) -> FakeGeolocation {
     privacy.get_identity().geolocation
 }
 
+/// List the cities a location mode can pick
+#[tauri::command]
+pub fn list_geolocation_locations(privacy: State<PrivacyEngine>) -> Vec<LocationInfo> {
+    privacy.list_locations()
+}
+
+/// Get how the spoofed location is chosen
+#[tauri::command]
+pub fn get_location_mode(privacy: State<PrivacyEngine>) -> LocationMode {
+    privacy.location_mode()
+}
+
+/// Choose how the spoofed location is picked, persist it and move to a new
+/// identity in that location
+#[tauri::command]
+pub async fn set_location_mode(app: AppHandle, mode: LocationMode) -> Result<FakeIdentity, String> {
+    apply_location_mode(&app, mode)
+}
+
+/// Replay a GPX track as the spoofed location
+#[tauri::command]
+pub async fn set_location_route_from_gpx(
+    app: AppHandle,
+    name: String,
+    gpx: String,
+    loop_route: bool,
+) -> Result<FakeIdentity, String> {
+    let route = geolocation::parse_gpx(&name, &gpx, loop_route)?;
+    apply_location_mode(&app, LocationMode::Route(route))
+}
+
+/// Persist a location mode and rotate into it. Rotating rebuilds windows,
+/// so callers are async commands, off the main thread.
+fn apply_location_mode(app: &AppHandle, mode: LocationMode) -> Result<FakeIdentity, String> {
+    let privacy = app.state::<PrivacyEngine>();
+    privacy.set_location_mode(mode.clone())?;
+    app.state::<crate::settings::SettingsManager>().set_location_mode(mode)?;
+    app.state::<IdentityRotator>().rotate(app, RotationTrigger::Manual);
+    Ok(privacy.get_identity())
+}
+
 /// Get the current fake user agent
 #[tauri::command]
 pub fn get_fake_user_agent(privacy: State<PrivacyEngine>) -> FakeUserAgent {
@@ -34,10 +93,164 @@ pub fn get_fake_ip_headers(privacy: State<PrivacyEngine>) -> FakeIpHeaders {
     privacy.get_identity().ip_headers
 }
 
+/// Preview the headers a request to `url` would carry
+#[tauri::command]
+pub fn get_outgoing_headers(
+    app: AppHandle,
+    settings: State<crate::settings::SettingsManager>,
+    url: String,
+) -> OutgoingHeaders {
+    let identity = crate::browser::WebViewManager::identity_for_url(&app, &url);
+    OutgoingHeaders::for_request(&identity, &settings.get_settings(), &url)
+}
+
+/// Choose whether a site gets the spoofed IP headers (`None` follows the
+/// global setting)
+#[tauri::command]
+pub fn set_site_ip_header_policy(
+    settings: State<crate::settings::SettingsManager>,
+    site: String,
+    policy: Option<IpHeaderPolicy>,
+) -> Result<(), String> {
+    settings.set_site_ip_header_policy(&site, policy)
+}
+
 /// Regenerate a new fake identity
 #[tauri::command]
-pub fn regenerate_identity(privacy: State<PrivacyEngine>) -> FakeIdentity {
-    privacy.regenerate_identity()
+pub async fn regenerate_identity(app: AppHandle) -> FakeIdentity {
+    // Goes through the rotator so open tabs pick up the new identity; async
+    // because rebuilding windows from a sync command deadlocks on Windows
+    app.state::<IdentityRotator>().rotate(&app, RotationTrigger::Manual);
+    app.state::<PrivacyEngine>().get_identity()
+}
+
+/// Replace the current identity with the one reproduced from `seed` and
+/// the time it was created (now if left out)
+#[tauri::command]
+pub fn regenerate_identity_from_seed(
+    privacy: State<PrivacyEngine>,
+    seed: u64,
+    created_at: Option<DateTime<Utc>>,
+) -> FakeIdentity {
+    privacy.regenerate_identity_from_seed(seed, created_at.unwrap_or_else(Utc::now))
+}
+
+/// Preview the identity a seed produces, created at `created_at` or now,
+/// without activating it
+#[tauri::command]
+pub fn preview_identity_from_seed(
+    privacy: State<PrivacyEngine>,
+    seed: u64,
+    created_at: Option<DateTime<Utc>>,
+) -> FakeIdentity {
+    privacy.identity_from_seed_at(seed, created_at.unwrap_or_else(Utc::now))
+}
+
+/// Describe the fingerprint dataset identities are sampled from
+#[tauri::command]
+pub fn get_fingerprint_dataset_info(privacy: State<PrivacyEngine>) -> DatasetInfo {
+    privacy.dataset_info()
+}
+
+/// List the attributes of the current identity that contradict each other
+#[tauri::command]
+pub fn validate_identity(privacy: State<PrivacyEngine>) -> Vec<CoherenceViolation> {
+    privacy.validate_identity().err().unwrap_or_default()
+}
+
+/// List every per-site identity, most recently used first
+#[tauri::command]
+pub fn list_site_identities(store: State<SiteIdentityStore>) -> Vec<SiteIdentity> {
+    store.list()
+}
+
+/// Pin or unpin a site's identity so it survives resets and rotation
+#[tauri::command]
+pub fn pin_site_identity(
+    store: State<SiteIdentityStore>,
+    site: String,
+    pinned: bool,
+) -> Result<SiteIdentity, String> {
+    store.set_pinned(&site, pinned)
+}
+
+/// Give a site a brand new identity
+#[tauri::command]
+pub fn reset_site_identity(
+    privacy: State<PrivacyEngine>,
+    store: State<SiteIdentityStore>,
+    site: String,
+) -> Result<SiteIdentity, String> {
+    store.reset(&privacy, &site)
+}
+
+/// Forget all unpinned site identities
+#[tauri::command]
+pub fn reset_all_site_identities(store: State<SiteIdentityStore>) -> Result<usize, String> {
+    store.reset_unpinned()
+}
+
+/// Export all site identities as JSON
+#[tauri::command]
+pub fn export_site_identities(store: State<SiteIdentityStore>) -> Result<String, String> {
+    store.export_json()
+}
+
+/// Every preset with the layers it turns on
+#[tauri::command]
+pub fn list_profile_presets() -> Vec<(ProfilePreset, ProtectionLayers)> {
+    ProfilePreset::ALL.into_iter().map(|p| (p, p.layers())).collect()
+}
+
+/// List every per-site protection profile
+#[tauri::command]
+pub fn list_site_profiles(store: State<SiteProfileStore>) -> Vec<SiteProfile> {
+    store.list()
+}
+
+/// Layers a page at `url` runs with, whether from a profile or the global settings
+#[tauri::command]
+pub fn get_site_layers(
+    app: AppHandle,
+    manager: State<WebViewManager>,
+    settings: State<crate::settings::SettingsManager>,
+    url: String,
+) -> ProtectionLayers {
+    manager.layers_for_url(&app, &url, &settings.get_settings())
+}
+
+/// Apply a preset to a site and reload its open tabs with it
+#[tauri::command]
+pub async fn set_site_profile_preset(
+    app: AppHandle,
+    site: String,
+    preset: ProfilePreset,
+) -> Result<SiteProfile, String> {
+    let profile = app.state::<SiteProfileStore>().set_preset(&site, preset)?;
+    app.state::<WebViewManager>().rebuild_site_tabs(&app, &profile.site);
+    Ok(profile)
+}
+
+/// Give a site a custom mix of layers and reload its open tabs with it
+#[tauri::command]
+pub async fn set_site_profile_layers(
+    app: AppHandle,
+    site: String,
+    layers: ProtectionLayers,
+) -> Result<SiteProfile, String> {
+    let profile = app.state::<SiteProfileStore>().set_layers(&site, layers)?;
+    app.state::<WebViewManager>().rebuild_site_tabs(&app, &profile.site);
+    Ok(profile)
+}
+
+/// Return a site to the global settings
+#[tauri::command]
+pub async fn remove_site_profile(app: AppHandle, site: String) -> Result<bool, String> {
+    let removed = app.state::<SiteProfileStore>().remove(&site)?;
+    if let (true, Some(site)) = (removed, crate::privacy::site::registrable_domain(&site)) {
+        app.state::<WebViewManager>().rebuild_site_tabs(&app, &site);
+    }
+    Ok(removed)
 }
 
 /// Get JavaScript injection scripts for privacy protection
@@ -52,16 +265,21 @@ pub fn check_whitelist(privacy: State<PrivacyEngine>, domain: String) -> bool {
     privacy.is_whitelisted(&domain)
 }
 
-/// Add a domain to the whitelist
+/// Add a domain or pattern (`*.bank.example`, `intranet:8443`) to the
+/// whitelist, optionally as a temporary exception
 #[tauri::command]
-pub fn add_to_whitelist(privacy: State<PrivacyEngine>, domain: String) {
-    privacy.add_to_whitelist(&domain);
+pub fn add_to_whitelist(
+    privacy: State<PrivacyEngine>,
+    domain: String,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<(), String> {
+    privacy.add_to_whitelist(&domain, expires_at)
 }
 
 /// Remove a domain from the whitelist
 #[tauri::command]
-pub fn remove_from_whitelist(privacy: State<PrivacyEngine>, domain: String) {
-    privacy.remove_from_whitelist(&domain);
+pub fn remove_from_whitelist(privacy: State<PrivacyEngine>, domain: String) -> Result<(), String> {
+    privacy.remove_from_whitelist(&domain)
 }
 
 /// Get all whitelisted domains
@@ -70,42 +288,338 @@ pub fn get_whitelist(privacy: State<PrivacyEngine>) -> Vec<String> {
     privacy.get_whitelist()
 }
 
+/// Get whitelist entries with their expiry
+#[tauri::command]
+pub fn get_whitelist_entries(privacy: State<PrivacyEngine>) -> Vec<WhitelistEntry> {
+    privacy.get_whitelist_entries()
+}
+
+/// Export the whitelist as text or JSON
+#[tauri::command]
+pub fn export_whitelist(privacy: State<PrivacyEngine>, format: WhitelistFormat) -> Result<String, String> {
+    privacy.export_whitelist(format)
+}
+
+/// Import a whitelist in either format; returns how many entries it had
+#[tauri::command]
+pub fn import_whitelist(privacy: State<PrivacyEngine>, content: String, replace: bool) -> Result<usize, String> {
+    privacy.import_whitelist(&content, replace)
+}
+
 /// Get COMPLETE injection scripts (ALL protection modules combined)
 #[tauri::command]
 pub fn get_complete_injection_scripts(
+    app: AppHandle,
     privacy: State<PrivacyEngine>,
-    advanced_fp: State<crate::privacy::AdvancedFingerprintProtection>,
-    network_security: State<crate::security::NetworkSecurity>,
-    complete_fake: State<crate::privacy::CompleteFakeData>,
-    ultimate: State<crate::privacy::UltimatePrivacyProtection>,
-    upload_protection: State<crate::metadata::FakeFileMetadata>,
+    settings: State<crate::settings::SettingsManager>,
 ) -> String {
-    // Combine all injection scripts
-    let mut combined = String::new();
+    // Same modules a new tab gets, all derived from the current identity
+    let identity = privacy.get_identity();
+    let settings = settings.get_settings();
+    let layers = app.state::<WebViewManager>().layers_for_url(&app, "about:blank", &settings);
+    let injection = WebViewManager::injection_builder(&app, &identity, &settings, &layers, "about:blank");
+    let combined = injection.build();
     
-    // 1. Base identity spoofing (fingerprint, geolocation, user agent)
-    combined.push_str(&privacy.get_injection_scripts());
-    combined.push('\n');
+    log::info!(
+        "Combined injection script generated ({} bytes) - {} protection modules",
+        combined.len(),
+        injection.ordered_modules().len()
+    );
+    combined
+}
+
+/// Inspect the exact minified script a tab opened at `url` would receive
+#[tauri::command]
+pub fn inspect_site_script(
+    app: AppHandle,
+    settings: State<crate::settings::SettingsManager>,
+    cache: State<InjectionScriptCache>,
+    url: String,
+) -> SiteScriptInfo {
+    let settings = settings.get_settings();
+    let identity = WebViewManager::identity_for_url(&app, &url);
+    let layers = app.state::<WebViewManager>().layers_for_url(&app, &url, &settings);
+    let key = WebViewManager::script_key(&app, &identity, &settings, &layers, &url);
+    let cached = cache.get(&key).is_some();
+    let page_script = WebViewManager::page_script(&app, &identity, &settings, &layers, &url);
     
-    // 2. Advanced fingerprint protection (screen, battery, sensors, timing)
-    combined.push_str(&advanced_fp.get_injection_script());
-    combined.push('\n');
+    SiteScriptInfo {
+        site: crate::privacy::site::registrable_domain(&url),
+        url,
+        cached,
+        cache_stats: cache.stats(),
+        script: (*page_script).clone(),
+    }
+}
+
+/// Receive a batch of fingerprinting events from a page's detection script.
+/// Pages can call this themselves, so it only ever adds to the statistics,
+/// and the events count for the site of the tab the calling webview shows,
+/// whatever page URL the report claims.
+#[tauri::command]
+pub fn report_fingerprinting(
+    app: AppHandle,
+    webview: tauri::Webview,
+    manager: State<WebViewManager>,
+    detector: State<FingerprintingDetector>,
+    logs: State<crate::security::LiveSecurityLogs>,
+    mut report: FingerprintingReport,
+) -> Result<(), String> {
+    // Only tabs report; the app's own windows have no site to score
+    let Some(tab) = manager.tab_for_window(webview.label()) else {
+        return Ok(());
+    };
+    // The self-test page fingerprints on purpose
+    if self_test::is_self_test_url(&tab.url) {
+        return Ok(());
+    }
+    report.page_url = tab.url;
+    // Load ids are only unique within the tab that made them up
+    report.load_id = format!("{}:{}", webview.label(), report.load_id);
+    let outcome = detector.record_report(&report)?;
     
-    // 3. Network security (cookies, mixed content, referrer)
-    combined.push_str(&network_security.get_injection_script());
-    combined.push('\n');
+    for (api, script) in &outcome.new_activity {
+        let api = format!("{:?} via {}", api, script.as_deref().unwrap_or("inline script"));
+        logs.log_fingerprint_blocked(&api, &report.page_url);
+    }
     
-    // 4. Complete fake data (timezone, language, network, clipboard, etc.)
-    combined.push_str(&complete_fake.get_master_injection_script());
-    combined.push('\n');
+    let site = &outcome.site;
+    if site.verdict == FingerprintingVerdict::Fingerprinting
+        && outcome.previous_verdict < FingerprintingVerdict::Fingerprinting
+    {
+        log::warn!("{} is fingerprinting visitors (score {})", site.site, site.score);
+        if let Err(e) = app.emit(FINGERPRINTING_DETECTED_EVENT, site.clone()) {
+            log::error!("Failed to emit fingerprinting alert: {}", e);
+        }
+    }
     
-    // 5. Ultimate protection (WebRTC, Math, CSS, Keyboard, Audio, Canvas deep protection)
-    combined.push_str(&ultimate.get_ultimate_injection_script());
-    combined.push('\n');
+    let (Some(escalations), Some(profiles)) = (app.try_state::<EscalationManager>(), app.try_state::<SiteProfileStore>()) else {
+        return Ok(());
+    };
+    let settings = app.state::<crate::settings::SettingsManager>().get_settings();
+    if escalations.should_escalate(site, &settings) {
+        // Only future visits change; open tabs keep their page script
+        let current = manager.layers_for_url(&app, &report.page_url, &settings);
+        let decision = escalations.escalate(
+            site,
+            current,
+            &profiles,
+            &app.state::<BlocklistManager>(),
+            settings.escalation_blocks_scripts,
+        )?;
+        app.state::<InjectionScriptCache>().clear();
+        logs.log_fingerprint_blocked("Site escalated to stricter protection", &report.page_url);
+        if let Err(e) = app.emit(SITE_ESCALATED_EVENT, decision) {
+            log::error!("Failed to emit escalation: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Automatic escalations, newest first
+#[tauri::command]
+pub fn list_escalations(escalations: State<EscalationManager>) -> Vec<EscalationDecision> {
+    escalations.list()
+}
+
+/// Undo an escalation and rebuild the site's open tabs
+#[tauri::command]
+pub async fn revert_escalation(app: AppHandle, id: String) -> Result<EscalationDecision, String> {
+    let decision = app.state::<EscalationManager>().revert(
+        &id,
+        &app.state::<SiteProfileStore>(),
+        &app.state::<BlocklistManager>(),
+    )?;
+    app.state::<InjectionScriptCache>().clear();
+    app.state::<WebViewManager>().rebuild_site_tabs(&app, &decision.site);
+    Ok(decision)
+}
+
+/// Fingerprinting seen per site, highest score first
+#[tauri::command]
+pub fn get_fingerprinting_sites(detector: State<FingerprintingDetector>) -> Vec<SiteFingerprinting> {
+    detector.get_sites()
+}
+
+/// Fingerprinting seen on one site
+#[tauri::command]
+pub fn get_site_fingerprinting(detector: State<FingerprintingDetector>, site: String) -> Option<SiteFingerprinting> {
+    detector.get_site(&site)
+}
+
+/// Forget all recorded fingerprinting
+#[tauri::command]
+pub fn clear_fingerprinting_data(detector: State<FingerprintingDetector>) {
+    detector.clear();
+}
+
+/// Run the fingerprint self-test: the probe page without protection, under
+/// the current identity and under a freshly generated one
+#[tauri::command]
+pub async fn run_self_test(app: AppHandle) -> Result<SelfTestReport, String> {
+    let settings = app.state::<crate::settings::SettingsManager>().get_settings();
+    let layers = ProtectionLayers::from_settings(&settings, &app.state::<WebViewManager>().get_settings());
+    let privacy = app.state::<PrivacyEngine>();
+    let url = self_test::page_url("");
+    let identities = [privacy.get_identity(), privacy.identity_from_seed(rand::random())]
+        .map(|identity| identity.for_site(&url));
     
-    // 6. Upload protection (FormData, Fetch, XHR, drag-drop, paste, FileReader)
-    combined.push_str(&upload_protection.get_upload_protection_script());
+    let scripts = identities
+        .each_ref()
+        .map(|identity| WebViewManager::page_script(&app, identity, &settings, &layers, &url));
+    let user_agents = identities
+        .each_ref()
+        .map(|identity| layers.ua_spoof.then_some(identity.user_agent.full.as_str()));
     
-    log::info!("Combined injection script generated ({} bytes) - 6 protection layers", combined.len());
-    combined
+    let runner = app.state::<SelfTestRunner>();
+    let (baseline, current, regenerated) = tokio::join!(
+        runner.probe(&app, None, None),
+        runner.probe(&app, Some(&scripts[0].script), user_agents[0]),
+        runner.probe(&app, Some(&scripts[1].script), user_agents[1]),
+    );
+    
+    let [current_identity, regenerated_identity] = identities;
+    let runs = [
+        ProtectedRun { identity: current_identity, probes: current? },
+        ProtectedRun { identity: regenerated_identity, probes: regenerated? },
+    ];
+    let report = self_test::evaluate(&baseline?, &runs, &layers);
+    log::info!(
+        "Self-test: {} probes rotating, {} static, {} leaked, {} inconsistencies",
+        report.summary.rotating,
+        report.summary.static_values,
+        report.summary.leaked,
+        report.summary.inconsistencies
+    );
+    Ok(report)
+}
+
+/// Called by the self-test page with the values it probed
+#[tauri::command]
+pub fn submit_self_test_probes(
+    runner: State<SelfTestRunner>,
+    run_id: String,
+    probes: ProbeValues,
+) -> Result<(), String> {
+    runner.deliver(&run_id, probes)
+}
+
+/// What changed between two self-test reports, e.g. from two releases
+#[tauri::command]
+pub fn diff_self_test_reports(old: SelfTestReport, new: SelfTestReport) -> SelfTestDiff {
+    self_test::diff(&old, &new)
+}
+
+/// Every site's storage partition with its size on disk
+#[tauri::command]
+pub fn list_storage_partitions(partitioner: State<StoragePartitioner>) -> Vec<PartitionUsage> {
+    partitioner.usage()
+}
+
+/// Delete everything one site stored: cookies, local storage, IndexedDB,
+/// caches and service workers. Returns the bytes freed.
+#[tauri::command]
+pub async fn clear_site_storage(app: AppHandle, site: String) -> Result<u64, String> {
+    app.state::<WebViewManager>().clear_site_storage(&app, &site)
+}
+
+/// Delete the stored data of every site. Returns the bytes freed.
+#[tauri::command]
+pub async fn clear_all_site_storage(app: AppHandle) -> Result<u64, String> {
+    app.state::<WebViewManager>().clear_all_site_storage(&app)
+}
+
+/// Every cookie the open tabs and the shared store hold. Cookies of closed
+/// sites with their own storage partition are out of reach until they open.
+#[tauri::command]
+pub async fn list_cookies(app: AppHandle) -> Result<Vec<SiteCookie>, String> {
+    // Reading cookies blocks the calling thread on Windows
+    tauri::async_runtime::spawn_blocking(move || CookieManager::list_cookies(&app))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Delete a site's cookies; returns how many were deleted
+#[tauri::command]
+pub async fn delete_site_cookies(app: AppHandle, site: String) -> Result<usize, String> {
+    let site = crate::privacy::site::registrable_domain(&site).ok_or("Invalid site")?;
+    let count = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        let site = site.clone();
+        move || CookieManager::delete_site_cookies(&app, &site)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    
+    if let Some(logs) = app.try_state::<crate::security::LiveSecurityLogs>() {
+        logs.log(
+            crate::security::LogType::Privacy,
+            crate::security::LogSeverity::Info,
+            &format!("{} cookie(s) deleted: {}", count, site),
+            Some("Deleted by the user"),
+            None,
+        );
+    }
+    Ok(count)
+}
+
+/// Apply the cookie rules and the automatic cleanup now
+#[tauri::command]
+pub async fn sweep_cookies(app: AppHandle) -> Result<CookieCleanupReport, String> {
+    tauri::async_runtime::spawn_blocking(move || CookieManager::sweep(&app))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// List the per-site cookie rules
+#[tauri::command]
+pub fn get_cookie_rules(cookies: State<CookieManager>) -> Vec<CookieRuleEntry> {
+    cookies.rules()
+}
+
+/// Allow, keep for the session only, or block a site's cookies
+#[tauri::command]
+pub fn set_cookie_rule(
+    cookies: State<CookieManager>,
+    site: String,
+    rule: CookieRule,
+) -> Result<CookieRuleEntry, String> {
+    cookies.set_rule(&site, rule)
+}
+
+/// Remove a site's cookie rule
+#[tauri::command]
+pub fn remove_cookie_rule(cookies: State<CookieManager>, site: String) -> Result<bool, String> {
+    cookies.remove_rule(&site)
+}
+
+/// Each blocklist feed's state after the last load or update
+#[tauri::command]
+pub fn get_blocklist_status(updater: State<BlocklistUpdater>) -> Vec<FeedStatus> {
+    updater.status()
+}
+
+/// Check the blocklist feeds for new versions now
+#[tauri::command]
+pub async fn update_blocklists(app: AppHandle) -> Result<Vec<FeedStatus>, String> {
+    BlocklistUpdater::update_now(&app).await
+}
+
+/// Which list and rule would decide on loading `url` with the current
+/// settings, without counting it as blocked: as a `resource` of the page at
+/// `source_url`, or as a navigation when no page is given
+#[tauri::command]
+pub fn check_url_blocking(
+    blocklist: State<BlocklistManager>,
+    settings: State<crate::settings::SettingsManager>,
+    url: String,
+    source_url: Option<String>,
+    resource: Option<ResourceType>,
+) -> BlockVerdict {
+    let categories = CategorySet::from_settings(&settings.get_settings());
+    let resource = resource.unwrap_or(match source_url {
+        Some(_) => ResourceType::Other,
+        None => ResourceType::Document,
+    });
+    blocklist.check_request(&url, source_url.as_deref(), resource, categories)
 }
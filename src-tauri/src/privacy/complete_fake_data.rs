@@ -3,6 +3,7 @@
 
 use serde::{Deserialize, Serialize};
 use rand::Rng;
+use super::injection::ScriptTemplate;
 
 /// Comprehensive fake data configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,6 +37,10 @@ pub struct CompleteFakeData {
     
     // Permissions
     pub fake_permissions: bool,
+    
+    // Geolocation: leave it to the identity's spoofed location instead of blocking
+    #[serde(default)]
+    pub spoof_geolocation: bool,
 }
 
 impl Default for CompleteFakeData {
@@ -48,12 +53,13 @@ impl CompleteFakeData {
     pub fn new() -> Self {
         let mut rng = rand::thread_rng();
         
+        // Offsets use Date.prototype.getTimezoneOffset convention (positive west)
         let timezones = [
-            ("America/New_York", -300),
-            ("America/Los_Angeles", -480),
+            ("America/New_York", 300),
+            ("America/Los_Angeles", 480),
             ("Europe/London", 0),
-            ("Europe/Paris", 60),
-            ("Asia/Tokyo", 540),
+            ("Europe/Paris", -60),
+            ("Asia/Tokyo", -540),
         ];
         let (tz, offset) = timezones[rng.gen_range(0..timezones.len())];
         
@@ -80,13 +86,32 @@ impl CompleteFakeData {
             orientation_angle: 0,
             do_not_track: "1".to_string(),
             fake_permissions: true,
+            spoof_geolocation: false,
+        }
+    }
+    
+    /// Copy of this configuration with timezone, language and platform taken
+    /// from `identity`, so they agree with the UA and geolocation
+    pub fn with_identity(&self, identity: &super::FakeIdentity) -> Self {
+        let ua = &identity.user_agent;
+        Self {
+            timezone: identity.timezone.clone(),
+            timezone_offset: super::coherence::timezone_offset_minutes(&identity.timezone, chrono::Utc::now())
+                .unwrap_or(self.timezone_offset),
+            language: identity.language.clone(),
+            languages: super::coherence::languages_for(&identity.language),
+            platform: ua.platform.clone(),
+            vendor: ua.vendor.clone(),
+            app_version: ua.app_version.clone(),
+            do_not_track: if identity.do_not_track { "1" } else { "0" }.to_string(),
+            ..self.clone()
         }
     }
     
     /// Generate the COMPLETE injection script covering ALL data collection
     pub fn get_master_injection_script(&self) -> String {
-        format!(r#"
-(function() {{
+        ScriptTemplate::new(r#"
+(function() {
     'use strict';
     
     // ================================================================
@@ -97,285 +122,276 @@ impl CompleteFakeData {
     console.log('[ServionX] 🛡️ Complete data protection initializing...');
     
     // ==================== CONFIGURATION ====================
-    const config = {{
-        timezone: '{timezone}',
-        timezoneOffset: {tz_offset},
-        language: '{language}',
-        languages: {languages},
-        connection: {{ type: '{conn_type}', effectiveType: '{eff_type}', downlink: {downlink}, rtt: {rtt}, saveData: {save_data} }},
-        platform: '{platform}',
-        vendor: '{vendor}',
-        appVersion: '{app_version}',
-        orientation: {{ type: '{orient_type}', angle: {orient_angle} }},
-        doNotTrack: '{dnt}'
-    }};
+    const config = {
+        timezone: @@timezone@@,
+        language: @@language@@,
+        languages: @@languages@@,
+        connection: { type: @@conn_type@@, effectiveType: @@eff_type@@, downlink: @@downlink@@, rtt: @@rtt@@, saveData: @@save_data@@ },
+        platform: @@platform@@,
+        vendor: @@vendor@@,
+        appVersion: @@app_version@@,
+        orientation: { type: @@orient_type@@, angle: @@orient_angle@@ },
+        doNotTrack: @@dnt@@
+    };
     
     // ==================== TIMEZONE SPOOFING ====================
-    const RealDate = Date;
-    const dateOffset = config.timezoneOffset * 60 * 1000;
-    
-    Date.prototype.getTimezoneOffset = function() {{
-        return config.timezoneOffset;
-    }};
-    
-    const origToString = Date.prototype.toString;
-    Date.prototype.toString = function() {{
-        return origToString.call(this).replace(/GMT[+-]\d{{4}}/, 'GMT+0000');
-    }};
+    // getTimezoneOffset and Date strings are set per date by the timezone
+    // script that follows this one
     
     // Intl.DateTimeFormat timezone
     const origResolvedOptions = Intl.DateTimeFormat.prototype.resolvedOptions;
-    Intl.DateTimeFormat.prototype.resolvedOptions = function() {{
+    Intl.DateTimeFormat.prototype.resolvedOptions = function() {
         const result = origResolvedOptions.call(this);
         result.timeZone = config.timezone;
         return result;
-    }};
+    };
     
     console.log('[ServionX] ✓ Timezone spoofed: ' + config.timezone);
     
     // ==================== LANGUAGE SPOOFING ====================
-    Object.defineProperties(navigator, {{
-        language: {{ get: () => config.language, configurable: true }},
-        languages: {{ get: () => Object.freeze(config.languages), configurable: true }}
-    }});
+    Object.defineProperties(navigator, {
+        language: { get: () => config.language, configurable: true },
+        languages: { get: () => Object.freeze(config.languages), configurable: true }
+    });
     console.log('[ServionX] ✓ Language spoofed: ' + config.language);
     
     // ==================== NETWORK INFORMATION SPOOFING ====================
-    if (navigator.connection) {{
-        Object.defineProperties(navigator.connection, {{
-            type: {{ get: () => config.connection.type, configurable: true }},
-            effectiveType: {{ get: () => config.connection.effectiveType, configurable: true }},
-            downlink: {{ get: () => config.connection.downlink, configurable: true }},
-            rtt: {{ get: () => config.connection.rtt, configurable: true }},
-            saveData: {{ get: () => config.connection.saveData, configurable: true }}
-        }});
-    }} else {{
-        Object.defineProperty(navigator, 'connection', {{
-            get: () => ({{
+    if (navigator.connection) {
+        Object.defineProperties(navigator.connection, {
+            type: { get: () => config.connection.type, configurable: true },
+            effectiveType: { get: () => config.connection.effectiveType, configurable: true },
+            downlink: { get: () => config.connection.downlink, configurable: true },
+            rtt: { get: () => config.connection.rtt, configurable: true },
+            saveData: { get: () => config.connection.saveData, configurable: true }
+        });
+    } else {
+        Object.defineProperty(navigator, 'connection', {
+            get: () => ({
                 type: config.connection.type,
                 effectiveType: config.connection.effectiveType,
                 downlink: config.connection.downlink,
                 rtt: config.connection.rtt,
                 saveData: config.connection.saveData,
                 onchange: null
-            }}),
+            }),
             configurable: true
-        }});
-    }}
+        });
+    }
     console.log('[ServionX] ✓ Network info spoofed: ' + config.connection.effectiveType);
     
     // ==================== PLATFORM SPOOFING ====================
-    Object.defineProperties(navigator, {{
-        platform: {{ get: () => config.platform, configurable: true }},
-        vendor: {{ get: () => config.vendor, configurable: true }},
-        appVersion: {{ get: () => config.appVersion, configurable: true }},
-        product: {{ get: () => 'Gecko', configurable: true }},
-        productSub: {{ get: () => '20030107', configurable: true }},
-        appCodeName: {{ get: () => 'Mozilla', configurable: true }},
-        appName: {{ get: () => 'Netscape', configurable: true }}
-    }});
+    Object.defineProperties(navigator, {
+        platform: { get: () => config.platform, configurable: true },
+        vendor: { get: () => config.vendor, configurable: true },
+        appVersion: { get: () => config.appVersion, configurable: true },
+        product: { get: () => 'Gecko', configurable: true },
+        productSub: { get: () => '20030107', configurable: true },
+        appCodeName: { get: () => 'Mozilla', configurable: true },
+        appName: { get: () => 'Netscape', configurable: true }
+    });
     console.log('[ServionX] ✓ Platform spoofed: ' + config.platform);
     
     // ==================== DO NOT TRACK ====================
-    Object.defineProperty(navigator, 'doNotTrack', {{
+    Object.defineProperty(navigator, 'doNotTrack', {
         get: () => config.doNotTrack,
         configurable: true
-    }});
+    });
     console.log('[ServionX] ✓ Do Not Track: enabled');
     
     // ==================== SCREEN ORIENTATION ====================
-    if (screen.orientation) {{
-        Object.defineProperties(screen.orientation, {{
-            type: {{ get: () => config.orientation.type, configurable: true }},
-            angle: {{ get: () => config.orientation.angle, configurable: true }}
-        }});
-    }}
+    if (screen.orientation) {
+        Object.defineProperties(screen.orientation, {
+            type: { get: () => config.orientation.type, configurable: true },
+            angle: { get: () => config.orientation.angle, configurable: true }
+        });
+    }
     
     // ==================== CLIPBOARD API BLOCKING ====================
-    navigator.clipboard.readText = async () => {{
+    navigator.clipboard.readText = async () => {
         console.log('[ServionX] ✗ Clipboard read blocked');
         throw new DOMException('Clipboard access denied', 'NotAllowedError');
-    }};
-    navigator.clipboard.read = async () => {{
+    };
+    navigator.clipboard.read = async () => {
         console.log('[ServionX] ✗ Clipboard read blocked');
         throw new DOMException('Clipboard access denied', 'NotAllowedError');
-    }};
+    };
     console.log('[ServionX] ✓ Clipboard API blocked');
     
     // ==================== BEACON API BLOCKING ====================
     const origSendBeacon = navigator.sendBeacon;
-    navigator.sendBeacon = function(url, data) {{
+    navigator.sendBeacon = function(url, data) {
         console.log('[ServionX] ✗ Beacon blocked:', url);
         return false;
-    }};
+    };
     console.log('[ServionX] ✓ Beacon API blocked');
     
     // ==================== PERMISSIONS API SPOOFING ====================
-    if (navigator.permissions) {{
+    if (navigator.permissions) {
         const origQuery = navigator.permissions.query.bind(navigator.permissions);
-        navigator.permissions.query = async (permissionDesc) => {{
+        navigator.permissions.query = async (permissionDesc) => {
             console.log('[ServionX] Permission query:', permissionDesc.name);
-            return {{
+            return {
                 state: 'prompt',
                 name: permissionDesc.name,
                 onchange: null
-            }};
-        }};
-    }}
+            };
+        };
+    }
     console.log('[ServionX] ✓ Permissions API spoofed');
     
     // ==================== PAGE VISIBILITY BLOCKING ====================
-    Object.defineProperty(document, 'visibilityState', {{
+    Object.defineProperty(document, 'visibilityState', {
         get: () => 'visible',
         configurable: true
-    }});
-    Object.defineProperty(document, 'hidden', {{
+    });
+    Object.defineProperty(document, 'hidden', {
         get: () => false,
         configurable: true
-    }});
+    });
     console.log('[ServionX] ✓ Page visibility locked to visible');
     
     // ==================== NOTIFICATION API BLOCKING ====================
-    if (window.Notification) {{
-        window.Notification = class FakeNotification {{
-            static get permission() {{ return 'denied'; }}
-            static requestPermission() {{ return Promise.resolve('denied'); }}
-            constructor() {{ throw new Error('Notifications disabled'); }}
-        }};
-    }}
+    if (window.Notification) {
+        window.Notification = class FakeNotification {
+            static get permission() { return 'denied'; }
+            static requestPermission() { return Promise.resolve('denied'); }
+            constructor() { throw new Error('Notifications disabled'); }
+        };
+    }
     console.log('[ServionX] ✓ Notification API blocked');
     
     // ==================== GEOLOCATION WATCHPOSITION BLOCKING ====================
-    if (navigator.geolocation) {{
+    if (navigator.geolocation && !@@spoof_geolocation@@) {
         const origGetCurrentPosition = navigator.geolocation.getCurrentPosition;
-        navigator.geolocation.getCurrentPosition = function(success, error, options) {{
+        navigator.geolocation.getCurrentPosition = function(success, error, options) {
             console.log('[ServionX] ✗ Geolocation request blocked');
-            if (error) error({{ code: 1, message: 'User denied geolocation' }});
-        }};
-        navigator.geolocation.watchPosition = function() {{
+            if (error) error({ code: 1, message: 'User denied geolocation' });
+        };
+        navigator.geolocation.watchPosition = function() {
             console.log('[ServionX] ✗ Geolocation watch blocked');
             return 0;
-        }};
-    }}
-    console.log('[ServionX] ✓ Geolocation API blocked');
+        };
+        console.log('[ServionX] ✓ Geolocation API blocked');
+    }
     
     // ==================== WEBGL EXTENSION SPOOFING ====================
     const origGetExtension = WebGLRenderingContext.prototype.getExtension;
-    WebGLRenderingContext.prototype.getExtension = function(name) {{
-        if (name === 'WEBGL_debug_renderer_info') {{
+    WebGLRenderingContext.prototype.getExtension = function(name) {
+        if (name === 'WEBGL_debug_renderer_info') {
             return null;
-        }}
+        }
         return origGetExtension.call(this, name);
-    }};
+    };
     console.log('[ServionX] ✓ WebGL debug info blocked');
     
     // ==================== FONT ENUMERATION BLOCKING ====================
-    if (document.fonts) {{
-        document.fonts.check = () => true;
-        document.fonts.forEach = () => {{}};
-    }}
+    // document.fonts.check is answered by the font module from the
+    // identity's font list
+    if (document.fonts) {
+        document.fonts.forEach = () => {};
+    }
     console.log('[ServionX] ✓ Font enumeration blocked');
     
     // ==================== MEDIA CAPABILITIES SPOOFING ====================
-    if (navigator.mediaCapabilities) {{
-        navigator.mediaCapabilities.decodingInfo = async () => ({{
+    if (navigator.mediaCapabilities) {
+        navigator.mediaCapabilities.decodingInfo = async () => ({
             supported: true,
             smooth: true,
             powerEfficient: true
-        }});
-    }}
+        });
+    }
     console.log('[ServionX] ✓ Media capabilities spoofed');
     
     // ==================== PAYMENT REQUEST BLOCKING ====================
-    if (window.PaymentRequest) {{
-        window.PaymentRequest = class FakePaymentRequest {{
-            constructor() {{ throw new Error('PaymentRequest disabled'); }}
-        }};
-    }}
+    if (window.PaymentRequest) {
+        window.PaymentRequest = class FakePaymentRequest {
+            constructor() { throw new Error('PaymentRequest disabled'); }
+        };
+    }
     console.log('[ServionX] ✓ Payment Request API blocked');
     
     // ==================== CREDENTIAL API BLOCKING ====================
-    if (navigator.credentials) {{
+    if (navigator.credentials) {
         navigator.credentials.get = async () => null;
         navigator.credentials.create = async () => null;
-        navigator.credentials.store = async () => {{}};
-    }}
+        navigator.credentials.store = async () => {};
+    }
     console.log('[ServionX] ✓ Credentials API blocked');
     
     // ==================== SERVICE WORKER RESTRICTIONS ====================
-    if (navigator.serviceWorker) {{
-        Object.defineProperty(navigator, 'serviceWorker', {{
-            get: () => ({{
+    if (navigator.serviceWorker) {
+        Object.defineProperty(navigator, 'serviceWorker', {
+            get: () => ({
                 register: () => Promise.reject(new Error('ServiceWorkers disabled')),
                 getRegistration: () => Promise.resolve(undefined),
                 getRegistrations: () => Promise.resolve([]),
                 ready: Promise.reject(new Error('ServiceWorkers disabled'))
-            }}),
+            }),
             configurable: true
-        }});
-    }}
+        });
+    }
     console.log('[ServionX] ✓ Service Workers restricted');
     
     // ==================== WEB WORKERS CSP ====================
     const origWorker = window.Worker;
-    window.Worker = function(url) {{
+    window.Worker = function(url) {
         console.log('[ServionX] ⚠ Worker created:', url);
         return new origWorker(url);
-    }};
+    };
     
     // ==================== IDLE DETECTION BLOCKING ====================
-    if (window.IdleDetector) {{
+    if (window.IdleDetector) {
         window.IdleDetector = undefined;
-    }}
+    }
     console.log('[ServionX] ✓ Idle Detection API blocked');
     
     // ==================== WAKE LOCK BLOCKING ====================
-    if (navigator.wakeLock) {{
-        navigator.wakeLock.request = async () => {{
+    if (navigator.wakeLock) {
+        navigator.wakeLock.request = async () => {
             throw new DOMException('Wake Lock denied', 'NotAllowedError');
-        }};
-    }}
+        };
+    }
     console.log('[ServionX] ✓ Wake Lock API blocked');
     
     // ==================== SHARE API BLOCKING ====================
-    if (navigator.share) {{
-        navigator.share = async () => {{
+    if (navigator.share) {
+        navigator.share = async () => {
             throw new DOMException('Share denied', 'NotAllowedError');
-        }};
-    }}
+        };
+    }
     console.log('[ServionX] ✓ Web Share API blocked');
     
     // ==================== STORAGE ESTIMATE SPOOFING ====================
-    if (navigator.storage) {{
-        navigator.storage.estimate = async () => ({{
+    if (navigator.storage) {
+        navigator.storage.estimate = async () => ({
             quota: 2147483648, // 2GB (standard)
             usage: 0
-        }});
-    }}
+        });
+    }
     console.log('[ServionX] ✓ Storage estimate spoofed');
     
     // ==================== PRINTING BLOCKING ====================
-    window.print = function() {{
+    window.print = function() {
         console.log('[ServionX] ✗ Print blocked');
-    }};
+    };
     
     // ==================== HISTORY LENGTH SPOOFING ====================
-    Object.defineProperty(history, 'length', {{
+    Object.defineProperty(history, 'length', {
         get: () => 1,
         configurable: true
-    }});
+    });
     console.log('[ServionX] ✓ History length spoofed');
     
     // ==================== CONSOLE PROTECTION ====================
     // Prevent console timing attacks
     const origConsoleLog = console.log;
-    console.log = function(...args) {{
-        if (args[0] && typeof args[0] === 'string' && args[0].startsWith('[ServionX]')) {{
+    console.log = function(...args) {
+        if (args[0] && typeof args[0] === 'string' && args[0].startsWith('[ServionX]')) {
             origConsoleLog.apply(console, args);
-        }} else {{
+        } else {
             origConsoleLog.apply(console, args);
-        }}
-    }};
+        }
+    };
     
     // ==================== COMPLETION ====================
     console.log('[ServionX] ========================================');
@@ -389,24 +405,25 @@ impl CompleteFakeData {
     console.log('[ServionX]   ✓ Storage, Credentials, ServiceWorkers');
     console.log('[ServionX]   ✓ Battery, Sensors, Media Devices');
     console.log('[ServionX] ========================================');
-}})();
-"#,
-            timezone = self.timezone,
-            tz_offset = self.timezone_offset,
-            language = self.language,
-            languages = serde_json::to_string(&self.languages).unwrap_or("[]".to_string()),
-            conn_type = self.connection_type,
-            eff_type = self.effective_type,
-            downlink = self.downlink,
-            rtt = self.rtt,
-            save_data = self.save_data,
-            platform = self.platform,
-            vendor = self.vendor,
-            app_version = self.app_version,
-            orient_type = self.orientation_type,
-            orient_angle = self.orientation_angle,
-            dnt = self.do_not_track,
-        )
+})();
+"#)
+            .bind("timezone", &self.timezone)
+            .bind("language", &self.language)
+            .bind("languages", &self.languages)
+            .bind("conn_type", &self.connection_type)
+            .bind("eff_type", &self.effective_type)
+            .bind("downlink", &self.downlink)
+            .bind("rtt", &self.rtt)
+            .bind("save_data", &self.save_data)
+            .bind("platform", &self.platform)
+            .bind("vendor", &self.vendor)
+            .bind("app_version", &self.app_version)
+            .bind("orient_type", &self.orientation_type)
+            .bind("orient_angle", &self.orientation_angle)
+            .bind("dnt", &self.do_not_track)
+            .bind("spoof_geolocation", &self.spoof_geolocation)
+            .render()
+            + &super::coherence::timezone_script(&self.timezone, self.timezone_offset)
     }
 }
 
@@ -430,5 +447,22 @@ mod tests {
         assert!(script.contains("Clipboard API blocked"));
         assert!(script.contains("Beacon API blocked"));
         assert!(script.contains("Geolocation API blocked"));
+        assert!(script.contains("if (navigator.geolocation && !false)"));
+        assert!(!script.contains("GMT+0000"));
+        assert!(script.ends_with(&crate::privacy::coherence::timezone_script(&data.timezone, data.timezone_offset)));
+        
+        let spoofed = CompleteFakeData { spoof_geolocation: true, ..data };
+        assert!(spoofed.get_master_injection_script().contains("if (navigator.geolocation && !true)"));
+    }
+    
+    #[test]
+    fn test_with_identity_follows_identity() {
+        let identity = crate::privacy::test_engine().identity_from_seed(5);
+        let data = CompleteFakeData::new().with_identity(&identity);
+        
+        assert_eq!(data.timezone, identity.timezone);
+        assert_eq!(data.language, identity.language);
+        assert_eq!(data.platform, identity.user_agent.platform);
+        assert_eq!(data.app_version, identity.user_agent.app_version);
     }
 }
@@ -0,0 +1,186 @@
+// Content Filter Categories
+// What each blocklist protects against, which categories the settings switch
+// on, and the verdict naming the list and rule that decided a request
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::settings::BrowserSettings;
+use super::site_profiles::ProtectionLayers;
+
+/// What a rule list protects against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BlockCategory {
+    Ads,
+    Trackers,
+    Malware,
+    Phishing,
+    /// Social network pixels, embeds and share widgets
+    Social,
+    /// In-browser cryptocurrency miners
+    Cryptominers,
+}
+
+impl BlockCategory {
+    pub const ALL: [BlockCategory; 6] = [
+        BlockCategory::Ads,
+        BlockCategory::Trackers,
+        BlockCategory::Malware,
+        BlockCategory::Phishing,
+        BlockCategory::Social,
+        BlockCategory::Cryptominers,
+    ];
+
+    /// Whether `settings` switch this category on
+    pub fn enabled_in(self, settings: &BrowserSettings) -> bool {
+        match self {
+            BlockCategory::Ads => settings.block_ads,
+            BlockCategory::Trackers => settings.block_trackers,
+            BlockCategory::Malware => settings.block_malware,
+            BlockCategory::Phishing => settings.block_phishing,
+            BlockCategory::Social => settings.block_social,
+            BlockCategory::Cryptominers => settings.block_cryptominers,
+        }
+    }
+
+    /// Malware and phishing protect the user rather than their privacy, so
+    /// a site's profile cannot switch them off
+    pub fn is_security(self) -> bool {
+        matches!(self, BlockCategory::Malware | BlockCategory::Phishing)
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for BlockCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BlockCategory::Ads => "ad",
+            BlockCategory::Trackers => "tracker",
+            BlockCategory::Malware => "malware",
+            BlockCategory::Phishing => "phishing",
+            BlockCategory::Social => "social tracker",
+            BlockCategory::Cryptominers => "cryptominer",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The categories a request is checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct CategorySet(u8);
+
+impl CategorySet {
+    pub fn all() -> Self {
+        BlockCategory::ALL.into_iter().collect()
+    }
+
+    /// The categories `settings` switch on
+    pub fn from_settings(settings: &BrowserSettings) -> Self {
+        BlockCategory::ALL.into_iter().filter(|c| c.enabled_in(settings)).collect()
+    }
+
+    /// The categories blocked on a page with `layers`: those `settings`
+    /// switch on, less the privacy ones when the site's tracker blocking is off
+    pub fn for_page(settings: &BrowserSettings, layers: &ProtectionLayers) -> Self {
+        let enabled = Self::from_settings(settings);
+        if layers.tracker_blocking {
+            return enabled;
+        }
+        BlockCategory::ALL.into_iter().filter(|c| c.is_security() && enabled.contains(*c)).collect()
+    }
+
+    pub fn contains(self, category: BlockCategory) -> bool {
+        self.0 & category.bit() != 0
+    }
+
+    pub fn with(self, category: BlockCategory) -> Self {
+        Self(self.0 | category.bit())
+    }
+
+    pub fn without(self, category: BlockCategory) -> Self {
+        Self(self.0 & !category.bit())
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl FromIterator<BlockCategory> for CategorySet {
+    fn from_iter<I: IntoIterator<Item = BlockCategory>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::default(), Self::with)
+    }
+}
+
+/// The rule that decided a request and the list it came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleMatch {
+    pub category: BlockCategory,
+    /// Feed or built-in list name
+    pub list: String,
+    /// The rule as written in the list
+    pub rule: String,
+}
+
+impl fmt::Display for RuleMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} rule {} from {}", self.category, self.rule, self.list)
+    }
+}
+
+/// How the content filter decided on a request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "match", rename_all = "camelCase")]
+pub enum BlockVerdict {
+    /// No rule applies
+    NoMatch,
+    /// A blocking rule matched (and no exception overrode it)
+    Blocked(RuleMatch),
+    /// An exception rule overrode the blocking rules
+    Allowed(RuleMatch),
+    /// Nothing blocks the request, but it looks like phishing or malware
+    Suspicious(RuleMatch),
+}
+
+impl BlockVerdict {
+    pub fn is_blocked(&self) -> bool {
+        matches!(self, BlockVerdict::Blocked(_))
+    }
+
+    /// The rule behind the verdict; `None` when nothing matched
+    pub fn rule_match(&self) -> Option<&RuleMatch> {
+        match self {
+            BlockVerdict::NoMatch => None,
+            BlockVerdict::Blocked(rule) | BlockVerdict::Allowed(rule) | BlockVerdict::Suspicious(rule) => Some(rule),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_categories_follow_settings_and_site_layers() {
+        let settings = BrowserSettings {
+            block_ads: false,
+            block_phishing: false,
+            ..BrowserSettings::default()
+        };
+        let enabled = CategorySet::from_settings(&settings);
+        assert!(enabled.contains(BlockCategory::Trackers) && enabled.contains(BlockCategory::Malware));
+        assert!(!enabled.contains(BlockCategory::Ads) && !enabled.contains(BlockCategory::Phishing));
+
+        // A site with tracker blocking off keeps only the security categories
+        let layers = ProtectionLayers { tracker_blocking: false, ..ProtectionLayers::default() };
+        let page = CategorySet::for_page(&settings, &layers);
+        assert_eq!(page, CategorySet::default().with(BlockCategory::Malware));
+
+        assert!(CategorySet::all().without(BlockCategory::Social).contains(BlockCategory::Cryptominers));
+        assert!(CategorySet::default().is_empty());
+    }
+}
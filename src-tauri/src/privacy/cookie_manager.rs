@@ -0,0 +1,454 @@
+// Cookie Manager
+// Reads and deletes the webviews' cookies from Rust, applies per-site
+// allow / session-only / block rules, and clears the cookies of sites that
+// have had no open tab for a configurable time
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use tauri::{AppHandle, Manager};
+use tauri::webview::Cookie;
+
+use super::{site, StoragePartitioner};
+use crate::browser::WebViewManager;
+use crate::security::{LiveSecurityLogs, LogSeverity, LogType};
+use crate::settings::SettingsManager;
+
+const RULES_FILE: &str = "cookie_rules.json";
+
+/// How often the automatic cleanup runs
+const SWEEP_INTERVAL_SECS: u64 = 60;
+
+/// What a site may keep in its cookie jar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CookieRule {
+    /// Keep cookies, even through the automatic cleanup
+    Allow,
+    /// Delete cookies once the site has no open tab
+    SessionOnly,
+    /// Delete cookies as soon as they are seen
+    Block,
+}
+
+/// A site's cookie rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CookieRuleEntry {
+    pub site: String,
+    pub rule: CookieRule,
+}
+
+/// Why a site's cookies are deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CleanupReason {
+    Blocked,
+    SessionEnded,
+    Inactive,
+}
+
+/// One cookie as the UI lists it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteCookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub path: String,
+    /// Registrable domain the cookie belongs to
+    pub site: String,
+    /// Container of the tab whose store holds the cookie
+    pub container: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<String>,
+    /// `None` for session cookies
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl SiteCookie {
+    fn from_cookie(cookie: &Cookie<'_>, container: Option<&str>) -> Option<Self> {
+        let domain = cookie.domain()?.trim_start_matches('.').to_lowercase();
+        Some(Self {
+            name: cookie.name().to_string(),
+            value: cookie.value().to_string(),
+            site: site::registrable_domain(&domain)?,
+            domain,
+            path: cookie.path().unwrap_or("/").to_string(),
+            container: container.map(str::to_string),
+            secure: cookie.secure().unwrap_or(false),
+            http_only: cookie.http_only().unwrap_or(false),
+            same_site: cookie.same_site().map(|s| s.to_string()),
+            expires: cookie.expires_datetime()
+                .and_then(|t| DateTime::from_timestamp(t.unix_timestamp(), 0)),
+        })
+    }
+}
+
+/// What a sweep deleted
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CookieCleanupReport {
+    pub cookies_deleted: usize,
+    /// Sites whose cookies were deleted
+    pub sites: Vec<String>,
+    /// Closed sites whose whole storage partition was cleared
+    pub partitions_cleared: Vec<String>,
+}
+
+/// Cookie rules and the bookkeeping of when each site was last open
+pub struct CookieManager {
+    data_dir: PathBuf,
+    rules: RwLock<HashMap<String, CookieRule>>,
+    last_open: RwLock<HashMap<String, DateTime<Utc>>>,
+}
+
+impl CookieManager {
+    /// Open the rules in the default app data directory
+    pub fn new() -> Result<Self, String> {
+        let data_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ServionX Browser");
+        Self::open(&data_dir)
+    }
+
+    pub fn open(data_dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(data_dir).map_err(|e| e.to_string())?;
+
+        let manager = Self {
+            data_dir: data_dir.to_path_buf(),
+            rules: RwLock::new(HashMap::new()),
+            last_open: RwLock::new(HashMap::new()),
+        };
+        match manager.load_from_disk() {
+            Ok(rules) => *manager.rules.write().unwrap() = rules,
+            Err(e) => log::warn!("Could not load cookie rules, starting empty: {}", e),
+        }
+        Ok(manager)
+    }
+
+    fn load_from_disk(&self) -> Result<HashMap<String, CookieRule>, String> {
+        let path = self.data_dir.join(RULES_FILE);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    fn save_to_disk(&self) -> Result<(), String> {
+        let json = {
+            let rules = self.rules.read().unwrap();
+            serde_json::to_string_pretty(&*rules).map_err(|e| e.to_string())?
+        };
+        std::fs::write(self.data_dir.join(RULES_FILE), json).map_err(|e| e.to_string())
+    }
+
+    /// Set the cookie rule of a site
+    pub fn set_rule(&self, site_or_url: &str, rule: CookieRule) -> Result<CookieRuleEntry, String> {
+        let site = site::registrable_domain(site_or_url).ok_or("Invalid site")?;
+        self.rules.write().unwrap().insert(site.clone(), rule);
+        self.save_to_disk()?;
+        log::info!("Cookie rule for {} set to {:?}", site, rule);
+        Ok(CookieRuleEntry { site, rule })
+    }
+
+    /// Remove a site's cookie rule; returns whether it had one
+    pub fn remove_rule(&self, site_or_url: &str) -> Result<bool, String> {
+        let site = site::registrable_domain(site_or_url).ok_or("Invalid site")?;
+        let removed = self.rules.write().unwrap().remove(&site).is_some();
+        if removed {
+            self.save_to_disk()?;
+        }
+        Ok(removed)
+    }
+
+    /// The rule of a site, if it has one
+    pub fn rule_for(&self, site: &str) -> Option<CookieRule> {
+        self.rules.read().unwrap().get(site).copied()
+    }
+
+    /// Every rule, by site
+    pub fn rules(&self) -> Vec<CookieRuleEntry> {
+        let mut rules: Vec<CookieRuleEntry> = self.rules
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(site, rule)| CookieRuleEntry { site: site.clone(), rule: *rule })
+            .collect();
+        rules.sort_by(|a, b| a.site.cmp(&b.site));
+        rules
+    }
+
+    /// Whether `site`'s cookies go now, given the sites with open tabs and
+    /// how long a closed site keeps its cookies without a rule (`None`: for
+    /// good). A closed site's clock starts the first time it is seen closed.
+    pub fn cleanup_reason(
+        &self,
+        site: &str,
+        open_sites: &HashSet<String>,
+        cleanup_after: Option<Duration>,
+        now: DateTime<Utc>,
+    ) -> Option<CleanupReason> {
+        let rule = self.rule_for(site);
+        if rule == Some(CookieRule::Block) {
+            return Some(CleanupReason::Blocked);
+        }
+        if open_sites.contains(site) {
+            self.last_open.write().unwrap().insert(site.to_string(), now);
+            return None;
+        }
+        match rule {
+            Some(CookieRule::Allow) => None,
+            Some(CookieRule::SessionOnly) => Some(CleanupReason::SessionEnded),
+            _ => {
+                let after = cleanup_after?;
+                let last_open = *self.last_open.write().unwrap().entry(site.to_string()).or_insert(now);
+                (now - last_open >= after).then_some(CleanupReason::Inactive)
+            }
+        }
+    }
+
+    /// Forget when `site` was last open, after its cookies were deleted
+    fn forget(&self, site: &str) {
+        self.last_open.write().unwrap().remove(site);
+    }
+
+    /// Every cookie of the open tabs' stores and of the shared store
+    pub fn list_cookies(app: &AppHandle) -> Vec<SiteCookie> {
+        let mut seen = HashSet::new();
+        Self::cookie_stores(app)
+            .into_iter()
+            .flat_map(|(window, container)| {
+                let cookies = window.cookies().unwrap_or_else(|e| {
+                    log::warn!("Could not read cookies of {}: {}", window.label(), e);
+                    Vec::new()
+                });
+                cookies
+                    .iter()
+                    .filter_map(|c| SiteCookie::from_cookie(c, container.as_deref()))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|c| seen.insert((c.container.clone(), c.domain.clone(), c.path.clone(), c.name.clone())))
+            .collect()
+    }
+
+    /// Delete every cookie of `site` the open stores hold; returns how many
+    pub fn delete_site_cookies(app: &AppHandle, site: &str) -> usize {
+        Self::delete_where(app, |cookie| (cookie.site == site).then_some(())).len()
+    }
+
+    /// Apply the rules and the automatic cleanup to every cookie store.
+    /// Cookies are deleted where a webview can reach them; closed sites with
+    /// a storage partition of their own lose the whole partition, unless a
+    /// webview still runs on it (a tab that navigated to another site).
+    pub fn sweep(app: &AppHandle) -> CookieCleanupReport {
+        let Some(manager) = app.try_state::<CookieManager>() else {
+            return CookieCleanupReport::default();
+        };
+        let tabs = app.try_state::<WebViewManager>();
+        // Tab URLs follow the pages' own navigations, not just the address bar
+        let open_sites: HashSet<String> = tabs
+            .as_ref()
+            .map(|tabs| tabs.get_tabs())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|tab| site::registrable_domain(&tab.url))
+            .collect();
+        let attached = tabs.map(|tabs| tabs.attached_partition_dirs()).unwrap_or_default();
+        let cleanup_after = app
+            .try_state::<SettingsManager>()
+            .map(|s| s.get_settings().cookie_cleanup_minutes)
+            .filter(|&minutes| minutes > 0)
+            .map(|minutes| Duration::minutes(minutes as i64));
+        let now = Utc::now();
+
+        let deleted = Self::delete_where(app, |cookie| {
+            manager.cleanup_reason(&cookie.site, &open_sites, cleanup_after, now)
+        });
+
+        let mut report = CookieCleanupReport {
+            cookies_deleted: deleted.len(),
+            ..Default::default()
+        };
+        for (cookie, reason) in &deleted {
+            if !report.sites.contains(&cookie.site) {
+                report.sites.push(cookie.site.clone());
+                Self::log_cleanup(app, &cookie.site, *reason, deleted.iter().filter(|(c, _)| c.site == cookie.site).count());
+            }
+        }
+
+        if let Some(partitioner) = app.try_state::<StoragePartitioner>() {
+            let closed: Vec<String> = partitioner
+                .partitions()
+                .into_iter()
+                .filter(|p| p.container.is_none() && !attached.contains(&p.data_dir))
+                .map(|p| p.site)
+                .filter(|site| manager.cleanup_reason(site, &open_sites, cleanup_after, now).is_some())
+                .collect();
+            for site in closed {
+                match partitioner.clear_partition(None, &site) {
+                    Ok(_) => {
+                        if let Some(logs) = app.try_state::<LiveSecurityLogs>() {
+                            logs.log(
+                                LogType::Privacy,
+                                LogSeverity::Info,
+                                &format!("Cookies and site data cleared: {}", site),
+                                Some("Site had no open tab"),
+                                None,
+                            );
+                        }
+                        report.partitions_cleared.push(site);
+                    }
+                    Err(e) => log::warn!("Could not clear storage of {}: {}", site, e),
+                }
+            }
+        }
+
+        for site in report.sites.iter().chain(&report.partitions_cleared) {
+            if !open_sites.contains(site) {
+                manager.forget(site);
+            }
+        }
+        report
+    }
+
+    /// Start the periodic sweep. Reading cookies blocks on some platforms,
+    /// so every sweep runs on a blocking thread.
+    pub fn start(app: AppHandle) {
+        tauri::async_runtime::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(SWEEP_INTERVAL_SECS));
+            loop {
+                ticker.tick().await;
+                let app = app.clone();
+                if let Err(e) = tauri::async_runtime::spawn_blocking(move || Self::sweep(&app)).await {
+                    log::error!("Cookie sweep failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// The webviews whose cookie jars together hold every reachable cookie,
+    /// with the container each belongs to. The app window sits on the
+    /// shared store that unpartitioned tabs use too.
+    fn cookie_stores(app: &AppHandle) -> Vec<(tauri::WebviewWindow, Option<String>)> {
+        let mut stores = Vec::new();
+        if let Some(tabs) = app.try_state::<WebViewManager>() {
+            stores.extend(tabs.tab_windows(app).into_iter().map(|(tab, window)| (window, tab.container_id)));
+        }
+        if let Some(main) = app.get_webview_window("main") {
+            stores.push((main, None));
+        }
+        stores
+    }
+
+    /// Delete the cookies `decide` returns a value for; returns the deleted
+    /// cookies with that value
+    fn delete_where<T>(app: &AppHandle, decide: impl Fn(&SiteCookie) -> Option<T>) -> Vec<(SiteCookie, T)> {
+        let mut deleted = Vec::new();
+        for (window, container) in Self::cookie_stores(app) {
+            let Ok(cookies) = window.cookies() else {
+                continue;
+            };
+            for cookie in cookies {
+                let Some(info) = SiteCookie::from_cookie(&cookie, container.as_deref()) else {
+                    continue;
+                };
+                let Some(decision) = decide(&info) else {
+                    continue;
+                };
+                match window.delete_cookie(cookie) {
+                    Ok(()) => deleted.push((info, decision)),
+                    Err(e) => log::warn!("Could not delete cookie {} of {}: {}", info.name, info.domain, e),
+                }
+            }
+        }
+        deleted
+    }
+
+    fn log_cleanup(app: &AppHandle, site: &str, reason: CleanupReason, count: usize) {
+        let Some(logs) = app.try_state::<LiveSecurityLogs>() else {
+            return;
+        };
+        let (severity, details) = match reason {
+            CleanupReason::Blocked => (LogSeverity::Blocked, "Site's cookies are blocked"),
+            CleanupReason::SessionEnded => (LogSeverity::Info, "Session-only site has no open tab"),
+            CleanupReason::Inactive => (LogSeverity::Info, "Site had no open tab for the cleanup delay"),
+        };
+        logs.log(
+            LogType::Privacy,
+            severity,
+            &format!("{} cookie(s) deleted: {}", count, site),
+            Some(details),
+            None,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("servionx-cookies-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_rules_persist_by_site() {
+        let dir = temp_dir();
+        let manager = CookieManager::open(&dir).unwrap();
+        manager.set_rule("https://www.bank.example/login", CookieRule::Allow).unwrap();
+        manager.set_rule("tracker.example", CookieRule::Block).unwrap();
+
+        let reopened = CookieManager::open(&dir).unwrap();
+        assert_eq!(reopened.rule_for("bank.example"), Some(CookieRule::Allow));
+        assert_eq!(reopened.rules().len(), 2);
+        assert!(reopened.remove_rule("tracker.example").unwrap());
+        assert!(!reopened.remove_rule("tracker.example").unwrap());
+    }
+
+    #[test]
+    fn test_cleanup_follows_rules_and_open_tabs() {
+        let manager = CookieManager::open(&temp_dir()).unwrap();
+        manager.set_rule("bank.example", CookieRule::Allow).unwrap();
+        manager.set_rule("news.example", CookieRule::SessionOnly).unwrap();
+        manager.set_rule("tracker.example", CookieRule::Block).unwrap();
+
+        let now = Utc::now();
+        let after = Some(Duration::minutes(30));
+        let open: HashSet<String> = ["news.example".to_string(), "tracker.example".to_string()].into();
+        let closed = HashSet::new();
+
+        assert_eq!(manager.cleanup_reason("tracker.example", &open, after, now), Some(CleanupReason::Blocked));
+        assert_eq!(manager.cleanup_reason("news.example", &open, after, now), None);
+        assert_eq!(manager.cleanup_reason("news.example", &closed, after, now), Some(CleanupReason::SessionEnded));
+        assert_eq!(manager.cleanup_reason("bank.example", &closed, after, now + Duration::days(9)), None);
+
+        // Unruled sites go once closed for the cleanup delay
+        assert_eq!(manager.cleanup_reason("shop.example", &closed, after, now), None);
+        assert_eq!(manager.cleanup_reason("shop.example", &closed, after, now + Duration::minutes(29)), None);
+        assert_eq!(
+            manager.cleanup_reason("shop.example", &closed, after, now + Duration::minutes(30)),
+            Some(CleanupReason::Inactive)
+        );
+        assert_eq!(manager.cleanup_reason("shop.example", &closed, None, now + Duration::days(1)), None);
+    }
+
+    #[test]
+    fn test_cookie_info_is_keyed_by_site() {
+        let cookie = Cookie::build(("sid", "abc"))
+            .domain(".login.shop.example")
+            .path("/")
+            .secure(true)
+            .http_only(true)
+            .build();
+        let info = SiteCookie::from_cookie(&cookie, Some("work")).unwrap();
+        assert_eq!(info.site, "shop.example");
+        assert_eq!(info.domain, "login.shop.example");
+        assert_eq!(info.container.as_deref(), Some("work"));
+        assert!(info.secure && info.http_only && info.expires.is_none());
+    }
+}
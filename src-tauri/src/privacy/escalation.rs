@@ -0,0 +1,311 @@
+// Fingerprinting Escalation
+// Moves sites that fingerprint aggressively to stricter protection for
+// future visits, optionally blocks the scripts doing it on that site, and
+// keeps every decision on disk so it can be reverted
+
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+use super::fingerprint_detector::{FingerprintingType, SiteFingerprinting};
+use super::{BlocklistManager, ProtectionLayers, SiteProfile, SiteProfileStore};
+use crate::settings::BrowserSettings;
+
+const STORE_FILE: &str = "escalations.json";
+
+/// Event emitted when a site was escalated
+pub const SITE_ESCALATED_EVENT: &str = "site-escalated";
+
+/// Distinct high-entropy APIs one script must use to be blocked
+const MIN_SCRIPT_APIS: usize = 2;
+
+/// One automatic escalation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EscalationDecision {
+    pub id: String,
+    pub site: String,
+    pub decided_at: DateTime<Utc>,
+    /// The high-entropy APIs the triggering page load used
+    pub apis: Vec<FingerprintingType>,
+    /// Script URLs blocked on the site's pages
+    pub blocked_scripts: Vec<String>,
+    /// The site's profile before the escalation; `None` if it had none
+    pub previous_profile: Option<SiteProfile>,
+    /// The layers the site was escalated to
+    pub layers: ProtectionLayers,
+    pub reverted_at: Option<DateTime<Utc>>,
+}
+
+impl EscalationDecision {
+    pub fn is_active(&self) -> bool {
+        self.reverted_at.is_none()
+    }
+}
+
+/// Escalation decisions, persisted as JSON in the app data directory
+pub struct EscalationManager {
+    data_dir: PathBuf,
+    decisions: RwLock<Vec<EscalationDecision>>,
+}
+
+impl EscalationManager {
+    /// Open the decisions in the default app data directory
+    pub fn new() -> Result<Self, String> {
+        let data_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ServionX Browser");
+        Self::open(&data_dir)
+    }
+
+    pub fn open(data_dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(data_dir).map_err(|e| e.to_string())?;
+
+        let manager = Self {
+            data_dir: data_dir.to_path_buf(),
+            decisions: RwLock::new(Vec::new()),
+        };
+        match manager.load_from_disk() {
+            Ok(decisions) => *manager.decisions.write().unwrap() = decisions,
+            Err(e) => log::warn!("Could not load escalation decisions, starting empty: {}", e),
+        }
+        Ok(manager)
+    }
+
+    fn load_from_disk(&self) -> Result<Vec<EscalationDecision>, String> {
+        let path = self.data_dir.join(STORE_FILE);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    fn save_to_disk(&self) -> Result<(), String> {
+        let json = {
+            let decisions = self.decisions.read().unwrap();
+            serde_json::to_string_pretty(&*decisions).map_err(|e| e.to_string())?
+        };
+        std::fs::write(self.data_dir.join(STORE_FILE), json).map_err(|e| e.to_string())
+    }
+
+    /// Whether `site` crossed the threshold in its last page load. A site is
+    /// escalated once: after the user reverts a decision it is left alone.
+    pub fn should_escalate(&self, site: &SiteFingerprinting, settings: &BrowserSettings) -> bool {
+        if !settings.auto_escalate {
+            return false;
+        }
+        let apis = site.last_load_apis.iter().filter(|api| api.is_high_entropy()).count();
+        apis >= settings.escalation_threshold as usize
+            && !self.decisions.read().unwrap().iter().any(|d| d.site == site.site)
+    }
+
+    /// Escalate `site` from its `current` layers and, with `block_scripts`,
+    /// block the scripts that used several high-entropy APIs on its pages.
+    /// The script URLs come from the site's own reports, so they never
+    /// reach the pages of other sites.
+    pub fn escalate(
+        &self,
+        site: &SiteFingerprinting,
+        current: ProtectionLayers,
+        profiles: &SiteProfileStore,
+        blocklist: &BlocklistManager,
+        block_scripts: bool,
+    ) -> Result<EscalationDecision, String> {
+        let previous_profile = profiles.get(&site.site);
+        let layers = current.escalated();
+        profiles.set_layers(&site.site, layers.clone())?;
+
+        let blocked_scripts = if block_scripts { offending_scripts(site) } else { Vec::new() };
+        for script in &blocked_scripts {
+            blocklist.block_site_script(&site.site, script);
+        }
+
+        let decision = EscalationDecision {
+            id: uuid::Uuid::new_v4().to_string(),
+            site: site.site.clone(),
+            decided_at: Utc::now(),
+            apis: site.last_load_apis.iter().copied().filter(|api| api.is_high_entropy()).collect(),
+            blocked_scripts,
+            previous_profile,
+            layers,
+            reverted_at: None,
+        };
+        self.decisions.write().unwrap().push(decision.clone());
+        self.save_to_disk()?;
+
+        log::warn!(
+            "Escalated {} to stricter protection ({} scripts blocked)",
+            decision.site,
+            decision.blocked_scripts.len()
+        );
+        Ok(decision)
+    }
+
+    /// Undo decision `id`: restore the site's previous profile and unblock
+    /// its scripts
+    pub fn revert(
+        &self,
+        id: &str,
+        profiles: &SiteProfileStore,
+        blocklist: &BlocklistManager,
+    ) -> Result<EscalationDecision, String> {
+        let decision = {
+            let mut decisions = self.decisions.write().unwrap();
+            let decision = decisions
+                .iter_mut()
+                .find(|d| d.id == id)
+                .ok_or_else(|| format!("Unknown escalation: {}", id))?;
+            if !decision.is_active() {
+                return Err(format!("Escalation of {} was already reverted", decision.site));
+            }
+            decision.reverted_at = Some(Utc::now());
+            decision.clone()
+        };
+
+        match &decision.previous_profile {
+            Some(previous) => {
+                match previous.preset {
+                    Some(preset) => profiles.set_preset(&decision.site, preset)?,
+                    None => profiles.set_layers(&decision.site, previous.layers.clone())?,
+                };
+            }
+            None => {
+                profiles.remove(&decision.site)?;
+            }
+        }
+        for script in &decision.blocked_scripts {
+            blocklist.unblock_site_script(&decision.site, script);
+        }
+        self.save_to_disk()?;
+
+        log::info!("Reverted escalation of {}", decision.site);
+        Ok(decision)
+    }
+
+    /// All decisions, newest first
+    pub fn list(&self) -> Vec<EscalationDecision> {
+        let mut list = self.decisions.read().unwrap().clone();
+        list.sort_by_key(|d| std::cmp::Reverse(d.decided_at));
+        list
+    }
+
+    /// Block the scripts of every active decision, e.g. at startup
+    pub fn apply_blocked_scripts(&self, blocklist: &BlocklistManager) {
+        for decision in self.decisions.read().unwrap().iter().filter(|d| d.is_active()) {
+            for script in &decision.blocked_scripts {
+                blocklist.block_site_script(&decision.site, script);
+            }
+        }
+    }
+}
+
+/// Scripts of `site` that used at least `MIN_SCRIPT_APIS` high-entropy APIs,
+/// without query or fragment so cache-busting parameters still match
+fn offending_scripts(site: &SiteFingerprinting) -> Vec<String> {
+    let mut scripts: Vec<String> = site
+        .scripts
+        .iter()
+        .filter(|s| s.script_url != "inline")
+        .filter(|s| s.apis.iter().filter(|api| api.is_high_entropy()).count() >= MIN_SCRIPT_APIS)
+        .map(|s| {
+            let end = s.script_url.find(['?', '#']).unwrap_or(s.script_url.len());
+            s.script_url[..end].to_lowercase()
+        })
+        .collect();
+    scripts.sort();
+    scripts.dedup();
+    scripts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::privacy::fingerprint_detector::{FingerprintingDetector, FingerprintingEvent, FingerprintingReport};
+    use crate::privacy::{CategorySet, ProfilePreset, ResourceType};
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("servionx-escalation-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn fingerprinting_site(apis: &[FingerprintingType]) -> SiteFingerprinting {
+        let detector = FingerprintingDetector::new();
+        let events = apis
+            .iter()
+            .map(|&api| FingerprintingEvent {
+                api,
+                script_url: Some("https://cdn.fp.example/fp.js?v=3".to_string()),
+                count: 1,
+            })
+            .collect();
+        let report = FingerprintingReport {
+            page_url: "https://shop.example/".to_string(),
+            load_id: "load".to_string(),
+            events,
+        };
+        detector.record_report(&report).unwrap().site
+    }
+
+    #[test]
+    fn test_threshold_counts_high_entropy_apis() {
+        let manager = EscalationManager::open(&temp_dir()).unwrap();
+        let settings = BrowserSettings::default();
+
+        let all = fingerprinting_site(&[
+            FingerprintingType::Canvas,
+            FingerprintingType::WebGL,
+            FingerprintingType::Audio,
+            FingerprintingType::Font,
+        ]);
+        assert!(manager.should_escalate(&all, &settings));
+
+        let noisy = fingerprinting_site(&[
+            FingerprintingType::Canvas,
+            FingerprintingType::WebGL,
+            FingerprintingType::Screen,
+            FingerprintingType::Timezone,
+        ]);
+        assert!(!manager.should_escalate(&noisy, &settings));
+        assert!(manager.should_escalate(&noisy, &BrowserSettings { escalation_threshold: 2, ..settings.clone() }));
+        assert!(!manager.should_escalate(&all, &BrowserSettings { auto_escalate: false, ..settings }));
+    }
+
+    #[test]
+    fn test_escalation_persists_and_reverts() {
+        let dir = temp_dir();
+        let profiles = SiteProfileStore::open(&dir).unwrap();
+        let blocklist = BlocklistManager::new();
+        let manager = EscalationManager::open(&dir).unwrap();
+        let site = fingerprinting_site(&[FingerprintingType::Canvas, FingerprintingType::Audio]);
+        let blocked_on = |blocklist: &BlocklistManager, page: &str| {
+            blocklist
+                .check_request("https://cdn.fp.example/fp.js?v=4", Some(page), ResourceType::Script, CategorySet::all())
+                .is_blocked()
+        };
+
+        profiles.set_preset("shop.example", ProfilePreset::BankingCompatible).unwrap();
+        let current = profiles.get("shop.example").unwrap().layers;
+        let decision = manager.escalate(&site, current, &profiles, &blocklist, true).unwrap();
+        assert_eq!(decision.blocked_scripts, vec!["https://cdn.fp.example/fp.js"]);
+        assert!(profiles.get("shop.example").unwrap().layers.spoofs_fingerprint());
+        assert!(blocked_on(&blocklist, "https://shop.example/cart"));
+        // Other sites still load the script
+        assert!(!blocked_on(&blocklist, "https://news.example/"));
+
+        let settings = BrowserSettings { escalation_threshold: 2, ..BrowserSettings::default() };
+        let reopened = EscalationManager::open(&dir).unwrap();
+        assert!(!reopened.should_escalate(&site, &settings));
+        let fresh_blocklist = BlocklistManager::new();
+        reopened.apply_blocked_scripts(&fresh_blocklist);
+        assert!(blocked_on(&fresh_blocklist, "https://shop.example/"));
+
+        reopened.revert(&decision.id, &profiles, &fresh_blocklist).unwrap();
+        assert!(!blocked_on(&fresh_blocklist, "https://shop.example/"));
+        assert_eq!(profiles.get("shop.example").unwrap().preset, Some(ProfilePreset::BankingCompatible));
+        assert!(reopened.revert(&decision.id, &profiles, &fresh_blocklist).is_err());
+        assert!(!reopened.list()[0].is_active());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,946 @@
+// Adblock Filter Rules
+// Parses Adblock Plus / uBlock Origin filter lists and matches requests and
+// pages against them: wildcards, anchors, request options, exception rules
+// and domain-scoped cosmetic filters
+
+use std::ops::Range;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use super::site;
+use super::url_matcher::{self, TokenIndex};
+
+/// `domains#@#selector`, `domains##selector` and the extended forms
+/// (`#?#`, `#$#`, `#%#`) cosmetic lines use
+static COSMETIC_LINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^([^/*|@"!]*?)#([@?$%]{0,2})#(.+)$"#).unwrap());
+
+/// uBlock procedural operators a stylesheet cannot express
+const PROCEDURAL_OPERATORS: &[&str] = &[
+    ":has-text(", ":-abp-", ":xpath(", ":upward(", ":remove(", ":style(",
+    ":matches-css", ":matches-path(", ":min-text-length(", ":watch-attr(", ":others(",
+];
+
+/// What a request loads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ResourceType {
+    Document,
+    Subdocument,
+    Script,
+    Image,
+    Stylesheet,
+    Font,
+    Media,
+    Object,
+    XmlHttpRequest,
+    WebSocket,
+    Ping,
+    Popup,
+    Other,
+}
+
+impl ResourceType {
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+
+    /// The type an option name stands for
+    fn from_option(name: &str) -> Option<Self> {
+        Some(match name {
+            "document" | "doc" => ResourceType::Document,
+            "subdocument" | "frame" => ResourceType::Subdocument,
+            "script" => ResourceType::Script,
+            "image" => ResourceType::Image,
+            "stylesheet" | "css" => ResourceType::Stylesheet,
+            "font" => ResourceType::Font,
+            "media" => ResourceType::Media,
+            "object" => ResourceType::Object,
+            "xmlhttprequest" | "xhr" => ResourceType::XmlHttpRequest,
+            "websocket" => ResourceType::WebSocket,
+            "ping" | "beacon" => ResourceType::Ping,
+            "popup" => ResourceType::Popup,
+            "other" => ResourceType::Other,
+            _ => return None,
+        })
+    }
+}
+
+/// Page-level exception types, only meaningful on `@@` rules
+const ELEMHIDE: u32 = 1 << 13;
+const GENERICHIDE: u32 = 1 << 14;
+
+/// Every request type
+const ALL_TYPES: u32 = (1 << 13) - 1;
+
+/// Types a rule without type options applies to: everything but whole
+/// documents and popups, which need an explicit option
+const DEFAULT_TYPES: u32 = ALL_TYPES & !(1 << ResourceType::Document as u32) & !(1 << ResourceType::Popup as u32);
+
+/// One request to decide on
+#[derive(Debug, Clone)]
+pub struct FilterRequest {
+    url: String,
+    url_lower: String,
+    host: String,
+    /// Where the host begins in `url_lower`
+    host_start: usize,
+    /// The page making the request
+    source_url: Option<String>,
+    source_host: Option<String>,
+    resource: ResourceType,
+    /// `None` when the source is unknown
+    third_party: Option<bool>,
+    /// The distinct tokens of `url_lower`, looked up in every list's index
+    tokens: Vec<Range<usize>>,
+}
+
+impl FilterRequest {
+    /// A request for `url` of type `resource`, made by the page at `source_url`
+    pub fn new(url: &str, source_url: Option<&str>, resource: ResourceType) -> Self {
+        let host = site::host_of(url).unwrap_or_default();
+        let source_host = source_url.and_then(site::host_of);
+        let third_party = source_url.map(|source| !site::same_site(url, source));
+        let url_lower = url.to_lowercase();
+        let authority = url_lower.find("://").map_or(0, |i| i + 3);
+        let host_start = url_lower[authority..]
+            .find(host.as_str())
+            .map_or(authority, |i| authority + i);
+        Self {
+            url: url.to_string(),
+            host,
+            host_start,
+            source_url: source_url.map(str::to_string),
+            source_host,
+            resource,
+            third_party,
+            tokens: url_matcher::url_token_spans(&url_lower),
+            url_lower,
+        }
+    }
+
+    /// Host of the requested URL, without userinfo, port, query or
+    /// fragment; empty when the URL has none
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.tokens.iter().map(|span| &self.url_lower[span.clone()])
+    }
+}
+
+/// The outcome of matching a request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "filter", rename_all = "camelCase")]
+pub enum FilterVerdict {
+    /// No rule applies
+    NoMatch,
+    /// A blocking rule matched (and no exception overrode it)
+    Blocked(String),
+    /// An exception rule overrode the blocking rules
+    Allowed(String),
+}
+
+impl FilterVerdict {
+    pub fn is_blocked(&self) -> bool {
+        matches!(self, FilterVerdict::Blocked(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Token {
+    Literal(String),
+    /// `*`
+    Wildcard,
+    /// `^`: a character that is not a letter, digit or one of `_-.%`, or
+    /// the end of the URL
+    Separator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Anchor {
+    None,
+    /// `|` at the start: the URL starts here
+    Start,
+    /// `||`: the host or one of its subdomains starts here
+    Hostname,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Pattern {
+    Tokens {
+        anchor: Anchor,
+        /// `|` at the end: the URL ends here
+        anchor_end: bool,
+        tokens: Vec<Token>,
+    },
+    Regex(RegexPattern),
+}
+
+/// A `/regex/` rule. Serialized as its source and compiled again on load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "RegexSource", try_from = "RegexSource")]
+struct RegexPattern {
+    source: String,
+    match_case: bool,
+    regex: Regex,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RegexSource {
+    source: String,
+    match_case: bool,
+}
+
+impl RegexPattern {
+    fn new(source: &str, match_case: bool) -> Result<Self, String> {
+        let regex = regex::RegexBuilder::new(source)
+            .case_insensitive(!match_case)
+            .build()
+            .map_err(|e| format!("Invalid regex filter: {}", e))?;
+        Ok(Self {
+            source: source.to_string(),
+            match_case,
+            regex,
+        })
+    }
+}
+
+impl TryFrom<RegexSource> for RegexPattern {
+    type Error = String;
+
+    fn try_from(source: RegexSource) -> Result<Self, String> {
+        Self::new(&source.source, source.match_case)
+    }
+}
+
+impl From<RegexPattern> for RegexSource {
+    fn from(pattern: RegexPattern) -> Self {
+        Self {
+            source: pattern.source,
+            match_case: pattern.match_case,
+        }
+    }
+}
+
+impl Pattern {
+    fn parse(pattern: &str, match_case: bool) -> Result<Self, String> {
+        if pattern.len() > 2 && pattern.starts_with('/') && pattern.ends_with('/') {
+            let source = &pattern[1..pattern.len() - 1];
+            return RegexPattern::new(source, match_case).map(Pattern::Regex);
+        }
+
+        let (anchor, rest) = if let Some(rest) = pattern.strip_prefix("||") {
+            (Anchor::Hostname, rest)
+        } else if let Some(rest) = pattern.strip_prefix('|') {
+            (Anchor::Start, rest)
+        } else {
+            (Anchor::None, pattern)
+        };
+        let (anchor_end, rest) = match rest.strip_suffix('|') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let rest = if match_case { rest.to_string() } else { rest.to_lowercase() };
+
+        let mut tokens = Vec::new();
+        let mut literal = String::new();
+        for c in rest.chars() {
+            let token = match c {
+                '*' => Token::Wildcard,
+                '^' => Token::Separator,
+                _ => {
+                    literal.push(c);
+                    continue;
+                }
+            };
+            if !literal.is_empty() {
+                tokens.push(Token::Literal(std::mem::take(&mut literal)));
+            }
+            if !(token == Token::Wildcard && tokens.last() == Some(&Token::Wildcard)) {
+                tokens.push(token);
+            }
+        }
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+
+        // A leading wildcard cancels a start anchor, a trailing one the end
+        // anchor; neither changes what matches otherwise
+        let anchor = if tokens.first() == Some(&Token::Wildcard) && anchor == Anchor::Start {
+            Anchor::None
+        } else {
+            anchor
+        };
+        if anchor != Anchor::Hostname && tokens.first() == Some(&Token::Wildcard) {
+            tokens.remove(0);
+        }
+        let anchor_end = anchor_end && tokens.last() != Some(&Token::Wildcard);
+        if tokens.last() == Some(&Token::Wildcard) {
+            tokens.pop();
+        }
+
+        Ok(Pattern::Tokens { anchor, anchor_end, tokens })
+    }
+
+    /// Whether the pattern matches `url` (lowercased unless `match_case`)
+    /// whose host starts at `host_start`
+    fn matches(&self, url: &str, host: &str, host_start: usize) -> bool {
+        let (anchor, anchor_end, tokens) = match self {
+            Pattern::Regex(pattern) => return pattern.regex.is_match(url),
+            Pattern::Tokens { anchor, anchor_end, tokens } => (*anchor, *anchor_end, tokens.as_slice()),
+        };
+        let bytes = url.as_bytes();
+        match anchor {
+            Anchor::Start => match_tokens(bytes, 0, tokens, false, anchor_end),
+            Anchor::Hostname => std::iter::once(0)
+                .chain(host.match_indices('.').map(|(i, _)| i + 1))
+                .any(|offset| match_tokens(bytes, host_start + offset, tokens, false, anchor_end)),
+            Anchor::None => match_tokens(bytes, 0, tokens, true, anchor_end),
+        }
+    }
+}
+
+fn is_separator(byte: u8) -> bool {
+    !(byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-' | b'.' | b'%'))
+}
+
+/// Match `tokens` against `url` from `pos`, as if preceded by a wildcard
+/// when `floating`. Everything between two wildcards matches a span fixed by
+/// where it starts, so on a mismatch only the last wildcard needs to grow:
+/// O(url × tokens) rather than exponential in the number of wildcards.
+fn match_tokens(url: &[u8], mut pos: usize, tokens: &[Token], floating: bool, anchor_end: bool) -> bool {
+    let mut next = 0;
+    // Token after the last wildcard and where the wildcard's match ends
+    let mut retry = floating.then_some((0, pos));
+    loop {
+        let matched = match tokens.get(next) {
+            None => !anchor_end || pos == url.len(),
+            Some(Token::Wildcard) => {
+                retry = Some((next + 1, pos));
+                next += 1;
+                continue;
+            }
+            Some(Token::Literal(literal)) => {
+                if url[pos..].starts_with(literal.as_bytes()) {
+                    pos += literal.len();
+                    true
+                } else {
+                    false
+                }
+            }
+            Some(Token::Separator) => {
+                if pos == url.len() {
+                    true
+                } else if is_separator(url[pos]) {
+                    pos += 1;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        if matched {
+            if next == tokens.len() {
+                return true;
+            }
+            next += 1;
+            continue;
+        }
+        match retry {
+            Some((token, end)) if end < url.len() => {
+                retry = Some((token, end + 1));
+                next = token;
+                pos = end + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Whether `host` is `domain` or one of its subdomains
+fn host_in(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.len() > domain.len()
+            && host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.')
+}
+
+/// Domains a rule is limited to and excluded from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct DomainScope {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl DomainScope {
+    fn parse<'a>(domains: impl Iterator<Item = &'a str>) -> Self {
+        let mut scope = Self::default();
+        for domain in domains.map(str::trim).filter(|d| !d.is_empty()) {
+            match domain.strip_prefix('~') {
+                Some(excluded) => scope.exclude.push(excluded.to_lowercase()),
+                None => scope.include.push(domain.to_lowercase()),
+            }
+        }
+        scope
+    }
+
+    fn is_generic(&self) -> bool {
+        self.include.is_empty()
+    }
+
+    /// Whether a page on `host` is in scope; the most specific entry wins
+    fn applies_to(&self, host: Option<&str>) -> bool {
+        if self.include.is_empty() && self.exclude.is_empty() {
+            return true;
+        }
+        let Some(host) = host else {
+            return self.include.is_empty();
+        };
+        let longest = |domains: &[String]| {
+            domains.iter().filter(|d| host_in(host, d)).map(|d| d.len()).max()
+        };
+        match (longest(&self.include), longest(&self.exclude)) {
+            (_, None) if self.include.is_empty() => true,
+            (Some(include), Some(exclude)) => include > exclude,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+/// A request-blocking rule or its `@@` exception
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkFilter {
+    /// The rule as written in the list
+    pub raw: String,
+    pub exception: bool,
+    pub important: bool,
+    match_case: bool,
+    pattern: Pattern,
+    types: u32,
+    third_party: Option<bool>,
+    domains: DomainScope,
+}
+
+impl NetworkFilter {
+    fn parse(line: &str) -> Result<Self, String> {
+        let (exception, body) = match line.strip_prefix("@@") {
+            Some(body) => (true, body),
+            None => (false, line),
+        };
+
+        // Options follow the last `$`, unless that `$` belongs to a regex
+        let (pattern, options) = match body.rfind('$') {
+            Some(idx) if !(body.starts_with('/') && body[idx..].contains('/')) => {
+                (&body[..idx], Some(&body[idx + 1..]))
+            }
+            _ => (body, None),
+        };
+
+        let mut filter = Self {
+            raw: line.to_string(),
+            exception,
+            important: false,
+            match_case: false,
+            pattern: Pattern::Tokens { anchor: Anchor::None, anchor_end: false, tokens: Vec::new() },
+            types: DEFAULT_TYPES,
+            third_party: None,
+            domains: DomainScope::default(),
+        };
+
+        let mut included = 0;
+        let mut excluded = 0;
+        for option in options.into_iter().flat_map(|o| o.split(',')) {
+            let option = option.trim().to_lowercase();
+            let (negated, name) = match option.strip_prefix('~') {
+                Some(name) => (true, name),
+                None => (false, option.as_str()),
+            };
+            if let Some(kind) = ResourceType::from_option(name) {
+                if negated { excluded |= kind.bit() } else { included |= kind.bit() }
+                continue;
+            }
+            match name {
+                "third-party" | "3p" => filter.third_party = Some(!negated),
+                "first-party" | "1p" => filter.third_party = Some(negated),
+                "important" => filter.important = true,
+                "match-case" => filter.match_case = true,
+                "all" => included |= ALL_TYPES,
+                "elemhide" | "ehide" if exception => included |= ELEMHIDE,
+                "generichide" | "ghide" if exception => included |= GENERICHIDE,
+                _ => match name.split_once('=') {
+                    Some(("domain" | "from", domains)) => {
+                        filter.domains = DomainScope::parse(domains.split('|'));
+                    }
+                    _ => return Err(format!("Unsupported filter option: {}", option)),
+                },
+            }
+        }
+        if included != 0 {
+            filter.types = included & !excluded;
+        } else if excluded != 0 {
+            filter.types = DEFAULT_TYPES & !excluded;
+        }
+
+        filter.pattern = Pattern::parse(pattern, filter.match_case)?;
+        Ok(filter)
+    }
+
+    /// Whether this rule applies to `request`; `url` is the request URL as
+    /// the rule compares it (lowercased unless the rule is case-sensitive)
+    fn matches(&self, request: &FilterRequest, types: u32) -> bool {
+        if self.types & types == 0 {
+            return false;
+        }
+        if let Some(third_party) = self.third_party {
+            if request.third_party != Some(third_party) {
+                return false;
+            }
+        }
+        if !self.domains.applies_to(request.source_host.as_deref()) {
+            return false;
+        }
+
+        let url = if self.match_case { &request.url } else { &request.url_lower };
+        self.pattern.matches(url, &request.host, request.host_start)
+    }
+
+    /// Lowercase tokens (runs of ASCII letters and digits) that appear whole
+    /// in every URL this rule matches
+    fn index_tokens(&self) -> Vec<String> {
+        let Pattern::Tokens { anchor, anchor_end, tokens } = &self.pattern else {
+            return Vec::new();
+        };
+        let mut found = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            let Token::Literal(literal) = token else {
+                continue;
+            };
+            // A literal's edges are token boundaries only where an anchor or
+            // a separator pins them
+            let starts_bounded = if i == 0 { *anchor != Anchor::None } else { tokens[i - 1] == Token::Separator };
+            let ends_bounded = if i + 1 == tokens.len() { *anchor_end } else { tokens[i + 1] == Token::Separator };
+
+            let literal = literal.to_lowercase();
+            let mut offset = 0;
+            for run in literal.split(|c: char| !c.is_ascii_alphanumeric()) {
+                let end = offset + run.len();
+                if !run.is_empty()
+                    && (offset > 0 || starts_bounded)
+                    && (end < literal.len() || ends_bounded)
+                {
+                    found.push(run.to_string());
+                }
+                offset = end + 1;
+            }
+        }
+        found
+    }
+}
+
+/// A cosmetic rule: `domains##selector` hides, `domains#@#selector` stops
+/// a hiding rule with the same selector on those domains
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CosmeticFilter {
+    pub selector: String,
+    pub exception: bool,
+    domains: DomainScope,
+}
+
+/// A parsed filter list line
+#[derive(Debug, Clone)]
+pub enum Filter {
+    Network(NetworkFilter),
+    Cosmetic(CosmeticFilter),
+}
+
+impl Filter {
+    /// Parse one list line. `Ok(None)` for comments, headers and blank
+    /// lines; `Err` for rules this engine cannot apply.
+    pub fn parse(line: &str) -> Result<Option<Filter>, String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+            return Ok(None);
+        }
+
+        if let Some(captures) = COSMETIC_LINE.captures(line) {
+            let selector = captures[3].trim();
+            let exception = match &captures[2] {
+                "" => false,
+                "@" => true,
+                marker => return Err(format!("Unsupported cosmetic rule type #{}#", marker)),
+            };
+            if selector.starts_with("+js(") || PROCEDURAL_OPERATORS.iter().any(|op| selector.contains(op)) {
+                return Err(format!("Unsupported procedural cosmetic rule: {}", selector));
+            }
+            return Ok(Some(Filter::Cosmetic(CosmeticFilter {
+                selector: selector.to_string(),
+                exception,
+                domains: DomainScope::parse(captures[1].split(',')),
+            })));
+        }
+
+        NetworkFilter::parse(line).map(|filter| Some(Filter::Network(filter)))
+    }
+}
+
+/// How many lines a list contributed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseStats {
+    pub network: usize,
+    pub cosmetic: usize,
+    /// Rules skipped because they use syntax this engine does not support
+    pub unsupported: usize,
+}
+
+/// A set of parsed filters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FilterList {
+    network: Vec<NetworkFilter>,
+    /// Blocking rules in `network`, by token
+    blocking: TokenIndex,
+    /// Exception rules in `network`, by token
+    exceptions: TokenIndex,
+    cosmetic: Vec<CosmeticFilter>,
+}
+
+impl FilterList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a whole list
+    pub fn parse(content: &str) -> Self {
+        let mut list = Self::new();
+        list.add_rules(content);
+        list
+    }
+
+    /// Add every rule of `content`
+    pub fn add_rules(&mut self, content: &str) -> ParseStats {
+        let mut stats = ParseStats::default();
+        for line in content.lines() {
+            match Filter::parse(line) {
+                Ok(Some(filter)) => {
+                    match filter {
+                        Filter::Network(_) => stats.network += 1,
+                        Filter::Cosmetic(_) => stats.cosmetic += 1,
+                    }
+                    self.add(filter);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    log::trace!("Skipped filter {}: {}", line.trim(), e);
+                    stats.unsupported += 1;
+                }
+            }
+        }
+        stats
+    }
+
+    pub fn add(&mut self, filter: Filter) {
+        match filter {
+            Filter::Network(filter) => {
+                let index = if filter.exception { &mut self.exceptions } else { &mut self.blocking };
+                index.insert(self.network.len(), &filter.index_tokens());
+                self.network.push(filter);
+            }
+            Filter::Cosmetic(filter) => self.cosmetic.push(filter),
+        }
+    }
+
+    pub fn network_count(&self) -> usize {
+        self.network.len()
+    }
+
+    pub fn cosmetic_count(&self) -> usize {
+        self.cosmetic.len()
+    }
+
+    /// Whether an exception of `types` covers the page at `page_url`
+    fn page_excepted(&self, page_url: &str, types: u32) -> Option<&NetworkFilter> {
+        let page = FilterRequest::new(page_url, Some(page_url), ResourceType::Document);
+        let exception = self.exceptions
+            .candidates(page.tokens())
+            .map(|id| &self.network[id])
+            .find(|f| f.matches(&page, types));
+        exception
+    }
+
+    /// Decide on a request. `$important` rules beat every exception; a
+    /// `$document` exception for the requesting page allows everything else
+    /// on it, and other exceptions beat the remaining blocking rules.
+    pub fn check(&self, request: &FilterRequest) -> FilterVerdict {
+        let Some(blocked) = self.blocking_filter(request) else {
+            return FilterVerdict::NoMatch;
+        };
+        if blocked.important {
+            return FilterVerdict::Blocked(blocked.raw.clone());
+        }
+
+        match self.exception_for(request) {
+            Some(exception) => FilterVerdict::Allowed(exception.raw.clone()),
+            None => FilterVerdict::Blocked(blocked.raw.clone()),
+        }
+    }
+
+    /// A blocking rule matching `request`, an `$important` one if any,
+    /// regardless of exceptions
+    pub fn blocking_filter(&self, request: &FilterRequest) -> Option<&NetworkFilter> {
+        let types = request.resource.bit();
+        let mut blocked = None;
+        let candidates = self.blocking.candidates(request.tokens()).map(|id| &self.network[id]);
+        for filter in candidates.filter(|f| f.matches(request, types)) {
+            if filter.important {
+                return Some(filter);
+            }
+            blocked = Some(filter);
+        }
+        blocked
+    }
+
+    /// The exception that would let `request` through a non-important
+    /// blocking rule, including a `$document` exception for its page
+    pub fn exception_for(&self, request: &FilterRequest) -> Option<&NetworkFilter> {
+        let types = request.resource.bit();
+        self.exceptions
+            .candidates(request.tokens())
+            .map(|id| &self.network[id])
+            .find(|f| f.matches(request, types))
+            .or_else(|| {
+                request
+                    .source_url
+                    .as_deref()
+                    .and_then(|page| self.page_excepted(page, ResourceType::Document.bit()))
+            })
+    }
+
+    /// Selectors to hide on the page at `page_url`
+    pub fn cosmetic_selectors(&self, page_url: &str) -> Vec<String> {
+        let document = ResourceType::Document.bit();
+        if self.page_excepted(page_url, document | ELEMHIDE).is_some() {
+            return Vec::new();
+        }
+        let generic_allowed = self.page_excepted(page_url, GENERICHIDE).is_none();
+        let host = site::host_of(page_url);
+        let host = host.as_deref();
+
+        let excepted: Vec<&str> = self.cosmetic
+            .iter()
+            .filter(|f| f.exception && f.domains.applies_to(host))
+            .map(|f| f.selector.as_str())
+            .collect();
+        let mut selectors: Vec<String> = Vec::new();
+        for filter in self.cosmetic.iter().filter(|f| !f.exception) {
+            let in_scope = if filter.domains.is_generic() {
+                generic_allowed && filter.domains.applies_to(host)
+            } else {
+                filter.domains.applies_to(host)
+            };
+            if in_scope && !excepted.contains(&filter.selector.as_str()) && !selectors.contains(&filter.selector) {
+                selectors.push(filter.selector.clone());
+            }
+        }
+        selectors
+    }
+
+    /// Selectors every page hides, for when the page is unknown
+    pub fn generic_selectors(&self) -> Vec<String> {
+        let excepted: Vec<&str> = self.cosmetic
+            .iter()
+            .filter(|f| f.exception && f.domains.is_generic())
+            .map(|f| f.selector.as_str())
+            .collect();
+        self.cosmetic
+            .iter()
+            .filter(|f| !f.exception && f.domains.is_generic() && f.domains.exclude.is_empty())
+            .filter(|f| !excepted.contains(&f.selector.as_str()))
+            .map(|f| f.selector.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sample EasyList / EasyPrivacy / uBlock lines with the requests they
+    /// must and must not block
+    const SAMPLE_LIST: &str = include_str!("data/easylist_sample.txt");
+
+    fn verdict(list: &FilterList, url: &str, source: &str, resource: ResourceType) -> FilterVerdict {
+        let source = (!source.is_empty()).then_some(source);
+        list.check(&FilterRequest::new(url, source, resource))
+    }
+
+    #[test]
+    fn test_sample_list_parses() {
+        let mut list = FilterList::new();
+        let stats = list.add_rules(SAMPLE_LIST);
+        assert_eq!(stats.unsupported, 4, "only the scriptlet, snippet, procedural and csp rules are skipped");
+        assert_eq!((stats.network, stats.cosmetic), (21, 7));
+        assert_eq!((list.network_count(), list.cosmetic_count()), (21, 7));
+    }
+
+    #[test]
+    fn test_network_conformance() {
+        use ResourceType::*;
+        let list = FilterList::parse(SAMPLE_LIST);
+        // (url, requesting page, type, blocked)
+        let cases: &[(&str, &str, ResourceType, bool)] = &[
+            // ||host^ anchors
+            ("https://ads.example.com/banner.png", "https://news.example/", Image, true),
+            ("https://sub.ads.example.com/x.js", "https://news.example/", Script, true),
+            ("https://notads.example.com/x.js", "https://news.example/", Script, false),
+            ("https://ads.example.com.evil.example/x", "https://news.example/", Script, false),
+            // Wildcards and separators
+            ("https://cdn.example.org/banners/ad_728x90.gif", "https://news.example/", Image, true),
+            ("https://cdn.example.org/banners/728x90.gif", "https://news.example/", Image, false),
+            ("https://example.net/adframe.js?id=3", "https://news.example/", Script, true),
+            ("https://example.net/adframe.json", "https://news.example/", Script, false),
+            ("https://example.net/track/pixel", "https://news.example/", Image, true),
+            // |start and end| anchors
+            ("http://ad.example.info/x", "https://news.example/", Script, true),
+            ("https://safe.example/?u=http://ad.example.info/x", "https://news.example/", Script, false),
+            ("https://static.example/swf/promo.swf", "https://news.example/", Object, true),
+            ("https://static.example/swf/promo.swf?v=1", "https://news.example/", Object, false),
+            // $third-party
+            ("https://tracker.example/collect", "https://news.example/", XmlHttpRequest, true),
+            ("https://tracker.example/collect", "https://www.tracker.example/", XmlHttpRequest, false),
+            ("https://cdn.firstparty.example/ads.js", "https://firstparty.example/", Script, true),
+            ("https://cdn.firstparty.example/ads.js", "https://news.example/", Script, false),
+            // Resource types
+            ("https://media.example/popunder.js", "https://news.example/", Script, true),
+            ("https://media.example/popunder.js", "https://news.example/", Image, false),
+            ("https://pixels.example/p.gif", "https://news.example/", Image, true),
+            ("https://pixels.example/p.gif", "https://news.example/", Script, false),
+            ("https://widgets.example/w", "https://news.example/", Subdocument, false),
+            ("https://widgets.example/w", "https://news.example/", Script, true),
+            // Rules without type options skip whole documents
+            ("https://ads.example.com/", "", Document, false),
+            // $domain=
+            ("https://video.example/preroll.mp4", "https://tube.example/watch", Media, true),
+            ("https://video.example/preroll.mp4", "https://www.tube.example/watch", Media, true),
+            ("https://video.example/preroll.mp4", "https://kids.tube.example/watch", Media, false),
+            ("https://video.example/preroll.mp4", "https://other.example/", Media, false),
+            // Exceptions
+            ("https://ads.example.com/ok/logo.png", "https://news.example/", Image, false),
+            ("https://tracker.example/collect?consent=1", "https://news.example/", XmlHttpRequest, false),
+            // $important beats exceptions
+            ("https://coinminer.example/mine.js", "https://allowed.example/", Script, true),
+            // $document exceptions allow the whole page
+            ("https://ads.example.com/banner.png", "https://intranet.example/", Image, false),
+            // Regex rules
+            ("https://x.example/ad-12345.js", "https://news.example/", Script, true),
+            ("https://x.example/ad-abc.js", "https://news.example/", Script, false),
+            // $match-case
+            ("https://cdn.example/AdBanner/1.png", "https://news.example/", Image, true),
+            ("https://cdn.example/adbanner/1.png", "https://news.example/", Image, false),
+            // Unrelated
+            ("https://news.example/article", "https://news.example/", Document, false),
+            ("https://cdn.example/app.js", "https://news.example/", Script, false),
+        ];
+        for (url, source, resource, blocked) in cases {
+            let verdict = verdict(&list, url, source, *resource);
+            assert_eq!(verdict.is_blocked(), *blocked, "{} from {} as {:?}: {:?}", url, source, resource, verdict);
+        }
+    }
+
+    #[test]
+    fn test_exceptions_report_the_rule_that_allowed() {
+        let list = FilterList::parse(SAMPLE_LIST);
+        assert_eq!(
+            verdict(&list, "https://ads.example.com/ok/logo.png", "https://news.example/", ResourceType::Image),
+            FilterVerdict::Allowed("@@||ads.example.com/ok/$image".to_string())
+        );
+        assert_eq!(
+            verdict(&list, "https://cdn.example/app.js", "https://news.example/", ResourceType::Script),
+            FilterVerdict::NoMatch
+        );
+    }
+
+    #[test]
+    fn test_cosmetic_conformance() {
+        let list = FilterList::parse(SAMPLE_LIST);
+        let news = list.cosmetic_selectors("https://www.news.example/story");
+        assert!(news.contains(&".ad-banner".to_string()));
+        assert!(news.contains(&"#sidebar-ads".to_string()));
+        assert!(news.contains(&".news-sponsor".to_string()));
+        assert!(!news.contains(&".shop-promo".to_string()));
+
+        // example.shop turns a generic rule off and has its own
+        let shop = list.cosmetic_selectors("https://example.shop/");
+        assert!(!shop.contains(&".ad-banner".to_string()));
+        assert!(shop.contains(&".shop-promo".to_string()));
+
+        // ~domain excludes a subdomain from a domain rule
+        let archive = list.cosmetic_selectors("https://archive.news.example/");
+        assert!(!archive.contains(&".news-sponsor".to_string()));
+
+        // $generichide keeps the site's own rules, $elemhide drops all
+        let forum = list.cosmetic_selectors("https://forum.example/");
+        assert!(!forum.contains(&".ad-banner".to_string()));
+        assert!(forum.contains(&".forum-ad".to_string()));
+        assert!(list.cosmetic_selectors("https://intranet.example/").is_empty());
+
+        let generic = list.generic_selectors();
+        assert!(generic.contains(&".ad-banner".to_string()));
+        assert!(!generic.contains(&".shop-promo".to_string()));
+    }
+
+    #[test]
+    fn test_index_tokens_are_whole_tokens() {
+        let tokens = |line: &str| NetworkFilter::parse(line).unwrap().index_tokens();
+        assert_eq!(tokens("||ads.example.com^"), vec!["ads", "example", "com"]);
+        assert_eq!(tokens("||ads.example.co"), vec!["ads", "example"]);
+        assert_eq!(tokens("/banners/ad_*.gif"), vec!["banners", "ad"]);
+        assert_eq!(tokens("adframe"), Vec::<String>::new());
+        assert_eq!(tokens("/swf/promo.swf|"), vec!["swf", "promo", "swf"]);
+        assert!(tokens("/\\/ad-[0-9]+\\.js/").is_empty());
+
+        // Every rule in the sample is found through the index
+        let list = FilterList::parse(SAMPLE_LIST);
+        assert_eq!(list.blocking.len() + list.exceptions.len(), list.network_count());
+    }
+
+    #[test]
+    fn test_pattern_edge_cases() {
+        let host = |url: &str| site::host_of(url).unwrap();
+        let matches = |pattern: &str, url: &str| {
+            let url_lower = url.to_lowercase();
+            let h = host(&url_lower);
+            let start = url_lower.find(&h).unwrap();
+            Pattern::parse(pattern, false).unwrap().matches(&url_lower, &h, start)
+        };
+        // ^ matches the end of the URL
+        assert!(matches("||example.com^", "https://example.com"));
+        assert!(matches("||example.com^", "https://example.com:8080/"));
+        assert!(!matches("||example.com^", "https://example.community/"));
+        // A bare pattern is a substring match
+        assert!(matches("/banner/1", "https://x.example/img/banner/1.png"));
+        assert!(!matches("/banner/1", "https://x.example/banner/2.png"));
+        // Repeated wildcards collapse
+        assert!(matches("ad**frame", "https://x.example/ad-iframe"));
+        assert!(matches("|https://*.example/", "https://cdn.example/"));
+        // Backtracking past an earlier wildcard's first fit
+        assert!(matches("a*b^c|", "https://x.example/ab-abxc/ab/c"));
+        assert!(!matches("a*b^c|", "https://x.example/ab-abxc/abc"));
+        assert!(matches("||example.com/*.js^", "https://cdn.example.com/a.json/b.js"));
+    }
+
+    #[test]
+    fn test_wildcards_match_in_polynomial_time() {
+        let rule = format!("{}b", "*a".repeat(12));
+        let url = format!("https://x.example/{}", "a".repeat(10_000));
+        let pattern = Pattern::parse(&rule, false).unwrap();
+
+        let started = std::time::Instant::now();
+        assert!(!pattern.matches(&url, "x.example", 8));
+        assert!(pattern.matches(&format!("{}b", url), "x.example", 8));
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+    }
+}
@@ -1,9 +1,11 @@
 // Fingerprint Generator
 // Generates consistent fake browser fingerprints
 
+use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 use rand::Rng;
-use uuid::Uuid;
+use super::coherence::{BrowserFamily, OsFamily};
+use super::fingerprint_dataset::{DeviceProfile, FingerprintDataset};
 
 /// Represents a complete fake browser fingerprint
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +37,9 @@ pub struct FakeFingerprint {
     // Touch support
     pub max_touch_points: u32,
     pub touch_support: bool,
+    
+    // Screen metrics
+    pub screen: FakeScreen,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,110 +49,82 @@ pub struct FakePlugin {
     pub filename: String,
 }
 
-#[derive(Debug)]
-pub struct FingerprintGenerator {
-    webgl_configs: Vec<WebGLConfig>,
-    font_sets: Vec<Vec<&'static str>>,
+/// Fake screen metrics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FakeScreen {
+    pub width: u32,
+    pub height: u32,
+    pub color_depth: u8,
+    pub pixel_ratio: f64,
 }
 
 #[derive(Debug)]
-struct WebGLConfig {
-    vendor: &'static str,
-    renderer: &'static str,
+pub struct FingerprintGenerator {
+    dataset: Arc<FingerprintDataset>,
 }
 
 impl FingerprintGenerator {
+    /// Generator sampling from the bundled fingerprint dataset
     pub fn new() -> Self {
-        Self {
-            webgl_configs: vec![
-                WebGLConfig {
-                    vendor: "Google Inc. (NVIDIA)",
-                    renderer: "ANGLE (NVIDIA, NVIDIA GeForce RTX 3080 Direct3D11 vs_5_0 ps_5_0, D3D11)",
-                },
-                WebGLConfig {
-                    vendor: "Google Inc. (AMD)",
-                    renderer: "ANGLE (AMD, AMD Radeon RX 6800 XT Direct3D11 vs_5_0 ps_5_0, D3D11)",
-                },
-                WebGLConfig {
-                    vendor: "Google Inc. (Intel)",
-                    renderer: "ANGLE (Intel, Intel(R) UHD Graphics 630 Direct3D11 vs_5_0 ps_5_0, D3D11)",
-                },
-                WebGLConfig {
-                    vendor: "Apple Inc.",
-                    renderer: "Apple M1 Pro",
-                },
-                WebGLConfig {
-                    vendor: "Apple Inc.",
-                    renderer: "Apple M2 Max",
-                },
-                WebGLConfig {
-                    vendor: "Google Inc. (NVIDIA)",
-                    renderer: "ANGLE (NVIDIA, NVIDIA GeForce GTX 1660 SUPER Direct3D11 vs_5_0 ps_5_0, D3D11)",
-                },
-                WebGLConfig {
-                    vendor: "Mesa/X.org",
-                    renderer: "llvmpipe (LLVM 15.0.6, 256 bits)",
-                },
-                WebGLConfig {
-                    vendor: "Google Inc. (Qualcomm)",
-                    renderer: "ANGLE (Qualcomm, Adreno (TM) 650, OpenGL ES 3.2)",
-                },
-            ],
-            font_sets: vec![
-                vec![
-                    "Arial", "Arial Black", "Comic Sans MS", "Courier New", "Georgia",
-                    "Impact", "Times New Roman", "Trebuchet MS", "Verdana", "Webdings",
-                ],
-                vec![
-                    "Arial", "Calibri", "Cambria", "Consolas", "Courier New",
-                    "Segoe UI", "Tahoma", "Times New Roman", "Verdana", "Wingdings",
-                ],
-                vec![
-                    "Arial", "Helvetica", "Menlo", "Monaco", "San Francisco",
-                    "SF Pro Display", "Times", "Courier", "Georgia", "Palatino",
-                ],
-                vec![
-                    "Arial", "DejaVu Sans", "DejaVu Serif", "Liberation Sans",
-                    "Liberation Serif", "Noto Sans", "Roboto", "Ubuntu", "Droid Sans",
-                ],
-            ],
-        }
+        Self::with_dataset(FingerprintDataset::bundled())
+    }
+    
+    /// Generator sampling from a specific dataset
+    pub fn with_dataset(dataset: Arc<FingerprintDataset>) -> Self {
+        Self { dataset }
+    }
+    
+    /// Dataset this generator samples from
+    pub fn dataset(&self) -> &Arc<FingerprintDataset> {
+        &self.dataset
     }
     
     /// Generate a new fake fingerprint
     pub fn generate(&self) -> FakeFingerprint {
-        let mut rng = rand::thread_rng();
-        
-        // Select random WebGL config
-        let webgl_config = &self.webgl_configs[rng.gen_range(0..self.webgl_configs.len())];
-        
-        // Select random font set
-        let font_set = &self.font_sets[rng.gen_range(0..self.font_sets.len())];
-        
-        // Generate hardware specs
-        let hardware_concurrency = *[2, 4, 6, 8, 12, 16].choose(&mut rng).unwrap();
-        let device_memory = *[2, 4, 8, 16, 32].choose(&mut rng).unwrap();
-        
-        // Generate touch support (mobile vs desktop)
-        let is_mobile = rng.gen_bool(0.2); // 20% chance of mobile
+        self.generate_with_rng(&mut rand::thread_rng())
+    }
+    
+    /// Generate a fake fingerprint drawing all randomness from `rng`
+    pub fn generate_with_rng<R: Rng>(&self, rng: &mut R) -> FakeFingerprint {
+        let device = self.dataset.sample_device(rng);
+        self.generate_for_device_with_rng(device, rng)
+    }
+    
+    /// Generate a fingerprint whose hardware matches the given OS and browser
+    pub fn generate_for_with_rng<R: Rng>(&self, os: OsFamily, browser: BrowserFamily, rng: &mut R) -> FakeFingerprint {
+        let device = self.dataset.sample_device_for(os, browser, rng);
+        self.generate_for_device_with_rng(device, rng)
+    }
+    
+    /// Generate a fingerprint for one device profile of the dataset. GPU,
+    /// screen, fonts and hardware all come from that profile together.
+    pub fn generate_for_device_with_rng<R: Rng>(&self, device: &DeviceProfile, rng: &mut R) -> FakeFingerprint {
+        let has_touch = device.max_touch_points > 0;
         
         FakeFingerprint {
-            session_id: Uuid::new_v4().to_string(),
-            hardware_concurrency,
-            device_memory,
+            session_id: uuid::Builder::from_random_bytes(rng.gen()).into_uuid().to_string(),
+            hardware_concurrency: device.hardware_concurrency,
+            device_memory: device.device_memory,
             canvas_noise_seed: rng.gen_range(1..10000),
-            webgl_vendor: webgl_config.vendor.to_string(),
-            webgl_renderer: webgl_config.renderer.to_string(),
-            webgl_version: "WebGL 2.0 (OpenGL ES 3.0 Chromium)".to_string(),
-            audio_noise_seed: rng.gen_range(1..10000),
-            installed_fonts: font_set.iter().map(|s| s.to_string()).collect(),
-            plugins: if is_mobile {
-                vec![]
+            webgl_vendor: device.webgl_vendor.clone(),
+            webgl_renderer: device.webgl_renderer.clone(),
+            webgl_version: if device.browser_family().is_chromium() {
+                "WebGL 2.0 (OpenGL ES 3.0 Chromium)".to_string()
             } else {
-                self.generate_plugins(&mut rng)
+                "WebGL 2.0".to_string()
+            },
+            audio_noise_seed: rng.gen_range(1..10000),
+            installed_fonts: self.dataset.fonts_for(device),
+            plugins: self.generate_plugins(rng),
+            max_touch_points: device.max_touch_points,
+            touch_support: has_touch,
+            screen: FakeScreen {
+                width: device.screen.width,
+                height: device.screen.height,
+                color_depth: device.screen.color_depth,
+                pixel_ratio: device.screen.pixel_ratio,
             },
-            max_touch_points: if is_mobile { rng.gen_range(1..11) } else { 0 },
-            touch_support: is_mobile,
         }
     }
     
@@ -182,20 +159,6 @@ impl Default for FingerprintGenerator {
     }
 }
 
-trait SliceRandom {
-    fn choose<R: Rng>(&self, rng: &mut R) -> Option<&u32>;
-}
-
-impl SliceRandom for [u32] {
-    fn choose<R: Rng>(&self, rng: &mut R) -> Option<&u32> {
-        if self.is_empty() {
-            None
-        } else {
-            Some(&self[rng.gen_range(0..self.len())])
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -213,4 +176,31 @@ mod tests {
         assert!(fp1.hardware_concurrency >= 2);
         assert!(fp1.device_memory >= 2);
     }
+    
+    #[test]
+    fn test_seeded_fingerprint_is_reproducible() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+        
+        let gen = FingerprintGenerator::new();
+        let fp1 = gen.generate_with_rng(&mut StdRng::seed_from_u64(42));
+        let fp2 = gen.generate_with_rng(&mut StdRng::seed_from_u64(42));
+        
+        assert_eq!(fp1.session_id, fp2.session_id);
+        assert_eq!(fp1.webgl_renderer, fp2.webgl_renderer);
+        assert_eq!(fp1.canvas_noise_seed, fp2.canvas_noise_seed);
+    }
+    
+    #[test]
+    fn test_fingerprint_matches_requested_platform() {
+        use rand::SeedableRng;
+        
+        let gen = FingerprintGenerator::new();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(9);
+        for _ in 0..50 {
+            let fp = gen.generate_for_with_rng(OsFamily::MacOs, BrowserFamily::Safari, &mut rng);
+            assert_eq!(fp.webgl_renderer, "Apple GPU");
+            assert_eq!(fp.max_touch_points, 0);
+        }
+    }
 }
@@ -0,0 +1,358 @@
+// Fingerprint Dataset
+// Weighted real-world device profiles that spoofed identities are sampled from
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use once_cell::sync::Lazy;
+use rand::Rng;
+use rand::distributions::{Distribution, WeightedIndex};
+use serde::{Deserialize, Serialize};
+
+use super::coherence::{self, BrowserFamily, OsFamily};
+
+/// Dataset format understood by this build
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Local file in the app data directory that replaces the bundled dataset
+pub const OVERRIDE_FILE: &str = "fingerprint_dataset.json";
+
+const BUNDLED_JSON: &str = include_str!("data/fingerprint_dataset.json");
+
+static BUNDLED: Lazy<Arc<FingerprintDataset>> = Lazy::new(|| {
+    let dataset = FingerprintDataset::from_json(BUNDLED_JSON)
+        .expect("bundled fingerprint dataset is valid");
+    Arc::new(dataset)
+});
+
+/// Screen metrics of a device profile, in CSS pixels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenProfile {
+    pub width: u32,
+    pub height: u32,
+    pub color_depth: u8,
+    pub pixel_ratio: f64,
+}
+
+/// One real-world OS + browser + hardware combination. Every attribute of a
+/// profile is taken together, so sampled identities match machines that exist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceProfile {
+    pub weight: f64,
+    pub os: String,
+    pub browser: String,
+    pub webgl_vendor: String,
+    pub webgl_renderer: String,
+    pub screen: ScreenProfile,
+    pub font_set: String,
+    pub hardware_concurrency: u32,
+    pub device_memory: u32,
+    pub max_touch_points: u32,
+}
+
+impl DeviceProfile {
+    pub fn os_family(&self) -> OsFamily {
+        OsFamily::from_name(&self.os).unwrap_or(OsFamily::Windows)
+    }
+
+    pub fn browser_family(&self) -> BrowserFamily {
+        BrowserFamily::from_name(&self.browser).unwrap_or(BrowserFamily::Chrome)
+    }
+}
+
+/// Share of a browser version among users of that browser
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowserVersionShare {
+    pub browser: String,
+    pub version: String,
+    pub weight: f64,
+}
+
+/// Share of an OS version among users of that OS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OsVersionShare {
+    pub os: String,
+    pub version: String,
+    pub weight: f64,
+}
+
+/// Versioned distribution of real browser fingerprints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FingerprintDataset {
+    pub schema_version: u32,
+    /// Date the statistics were collected (YYYY-MM-DD)
+    pub revision: String,
+    #[serde(default)]
+    pub source: String,
+    pub font_sets: HashMap<String, Vec<String>>,
+    pub browser_versions: Vec<BrowserVersionShare>,
+    pub os_versions: Vec<OsVersionShare>,
+    pub devices: Vec<DeviceProfile>,
+    /// Loaded from the local override file rather than the bundled copy
+    #[serde(skip)]
+    pub is_override: bool,
+}
+
+/// Summary of the active dataset for the UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetInfo {
+    pub schema_version: u32,
+    pub revision: String,
+    pub source: String,
+    pub device_count: usize,
+    pub is_override: bool,
+}
+
+impl FingerprintDataset {
+    /// The dataset compiled into the browser
+    pub fn bundled() -> Arc<FingerprintDataset> {
+        BUNDLED.clone()
+    }
+
+    /// Bundled dataset, replaced by the override file in the default app data
+    /// directory when one is present and valid
+    pub fn load() -> Arc<FingerprintDataset> {
+        let data_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ServionX Browser");
+        Self::load_from(&data_dir)
+    }
+
+    /// Bundled dataset, replaced by `data_dir/fingerprint_dataset.json` when
+    /// that file exists and validates
+    pub fn load_from(data_dir: &Path) -> Arc<FingerprintDataset> {
+        let path = data_dir.join(OVERRIDE_FILE);
+        if !path.exists() {
+            return Self::bundled();
+        }
+
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| Self::from_json(&json))
+        {
+            Ok(mut dataset) => {
+                dataset.is_override = true;
+                log::info!("Using fingerprint dataset override {} ({} devices)",
+                    dataset.revision, dataset.devices.len());
+                Arc::new(dataset)
+            }
+            Err(e) => {
+                log::warn!("Ignoring fingerprint dataset override {}: {}", path.display(), e);
+                Self::bundled()
+            }
+        }
+    }
+
+    /// Parse and validate a dataset
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let dataset: FingerprintDataset = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        dataset.validate()?;
+        Ok(dataset)
+    }
+
+    /// Reject datasets that could produce incoherent or unsampleable identities
+    pub fn validate(&self) -> Result<(), String> {
+        if self.schema_version != SCHEMA_VERSION {
+            return Err(format!("unsupported schema version {} (expected {})",
+                self.schema_version, SCHEMA_VERSION));
+        }
+        if self.devices.is_empty() {
+            return Err("dataset has no devices".to_string());
+        }
+
+        for (i, device) in self.devices.iter().enumerate() {
+            let os = OsFamily::from_name(&device.os)
+                .ok_or_else(|| format!("device {}: unknown OS {}", i, device.os))?;
+            let browser = BrowserFamily::from_name(&device.browser)
+                .ok_or_else(|| format!("device {}: unknown browser {}", i, device.browser))?;
+
+            if !(device.weight > 0.0 && device.weight.is_finite()) {
+                return Err(format!("device {}: weight must be positive", i));
+            }
+            if !browser.supported_on(os) {
+                return Err(format!("device {}: {} does not run on {}", i, device.browser, device.os));
+            }
+            if !coherence::webgl_renderer_plausible_on(&device.webgl_renderer, os) {
+                return Err(format!("device {}: GPU {} cannot appear on {}", i, device.webgl_renderer, device.os));
+            }
+            if device.device_memory == 0 || device.device_memory > 8 {
+                return Err(format!("device {}: deviceMemory must be 1-8", i));
+            }
+            if os == OsFamily::MacOs && device.max_touch_points > 0 {
+                return Err(format!("device {}: Macs have no touch screen", i));
+            }
+
+            let fonts = self.font_sets.get(&device.font_set)
+                .ok_or_else(|| format!("device {}: unknown font set {}", i, device.font_set))?;
+            if let Some(font) = fonts.iter().find(|f| !coherence::font_plausible_on(f, os)) {
+                return Err(format!("device {}: font {} is not shipped with {}", i, font, device.os));
+            }
+            if !self.browser_versions.iter().any(|v| v.browser == device.browser) {
+                return Err(format!("device {}: no versions listed for {}", i, device.browser));
+            }
+            if !self.os_versions.iter().any(|v| v.os == device.os) {
+                return Err(format!("device {}: no versions listed for {}", i, device.os));
+            }
+        }
+
+        if self.browser_versions.iter().any(|v| !(v.weight > 0.0 && v.weight.is_finite()))
+            || self.os_versions.iter().any(|v| !(v.weight > 0.0 && v.weight.is_finite()))
+        {
+            return Err("version weights must be positive".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Date the statistics describe, if the revision is a YYYY-MM-DD date
+    pub fn revision_date(&self) -> Option<chrono::NaiveDate> {
+        chrono::NaiveDate::parse_from_str(&self.revision, "%Y-%m-%d").ok()
+    }
+
+    pub fn info(&self) -> DatasetInfo {
+        DatasetInfo {
+            schema_version: self.schema_version,
+            revision: self.revision.clone(),
+            source: self.source.clone(),
+            device_count: self.devices.len(),
+            is_override: self.is_override,
+        }
+    }
+
+    /// Pick a whole device profile, weighted by how common it is
+    pub fn sample_device<R: Rng>(&self, rng: &mut R) -> &DeviceProfile {
+        &self.devices[weighted_index(self.devices.iter().map(|d| d.weight), rng)]
+    }
+
+    /// Pick a device profile running `browser` on `os`, falling back to any
+    /// device on `os`, then to any device at all
+    pub fn sample_device_for<R: Rng>(&self, os: OsFamily, browser: BrowserFamily, rng: &mut R) -> &DeviceProfile {
+        let exact: Vec<&DeviceProfile> = self.devices
+            .iter()
+            .filter(|d| d.os_family() == os && d.browser_family() == browser)
+            .collect();
+        if !exact.is_empty() {
+            return exact[weighted_index(exact.iter().map(|d| d.weight), rng)];
+        }
+
+        let same_os: Vec<&DeviceProfile> = self.devices.iter().filter(|d| d.os_family() == os).collect();
+        if !same_os.is_empty() {
+            return same_os[weighted_index(same_os.iter().map(|d| d.weight), rng)];
+        }
+
+        self.sample_device(rng)
+    }
+
+    /// Fonts of a device profile's font set
+    pub fn fonts_for(&self, device: &DeviceProfile) -> Vec<String> {
+        self.font_sets.get(&device.font_set).cloned().unwrap_or_default()
+    }
+
+    /// Every font the devices of `os` ship with, sorted. A device's fonts
+    /// are always a subset of its OS's catalog.
+    pub fn font_catalog(&self, os: OsFamily) -> Vec<String> {
+        let fonts: std::collections::BTreeSet<String> = self.devices
+            .iter()
+            .filter(|d| d.os_family() == os)
+            .flat_map(|d| self.fonts_for(d))
+            .collect();
+        fonts.into_iter().collect()
+    }
+
+    /// Pick a version of `browser`, weighted by adoption
+    pub fn sample_browser_version<R: Rng>(&self, browser: BrowserFamily, rng: &mut R) -> Option<&str> {
+        let versions: Vec<&BrowserVersionShare> = self.browser_versions
+            .iter()
+            .filter(|v| v.browser == browser.name())
+            .collect();
+        if versions.is_empty() {
+            return None;
+        }
+        Some(&versions[weighted_index(versions.iter().map(|v| v.weight), rng)].version)
+    }
+
+    /// Pick a version of `os`, weighted by adoption
+    pub fn sample_os_version<R: Rng>(&self, os: OsFamily, rng: &mut R) -> Option<&str> {
+        let versions: Vec<&OsVersionShare> = self.os_versions
+            .iter()
+            .filter(|v| v.os == os.name())
+            .collect();
+        if versions.is_empty() {
+            return None;
+        }
+        Some(&versions[weighted_index(versions.iter().map(|v| v.weight), rng)].version)
+    }
+}
+
+/// Index drawn in proportion to `weights` (non-empty); uniform if the
+/// weights are unusable
+fn weighted_index<R: Rng>(weights: impl ExactSizeIterator<Item = f64> + Clone, rng: &mut R) -> usize {
+    match WeightedIndex::new(weights.clone()) {
+        Ok(dist) => dist.sample(rng),
+        Err(_) => rng.gen_range(0..weights.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_bundled_dataset_is_valid() {
+        let dataset = FingerprintDataset::bundled();
+        assert_eq!(dataset.schema_version, SCHEMA_VERSION);
+        assert!(dataset.devices.len() >= 20);
+        assert!(!dataset.info().is_override);
+    }
+
+    #[test]
+    fn test_sampling_follows_weights() {
+        let dataset = FingerprintDataset::bundled();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let total: f64 = dataset.devices.iter().map(|d| d.weight).sum();
+        let windows_share: f64 = dataset.devices.iter()
+            .filter(|d| d.os == "Windows")
+            .map(|d| d.weight)
+            .sum::<f64>() / total;
+
+        let samples = 5000;
+        let windows = (0..samples)
+            .filter(|_| dataset.sample_device(&mut rng).os == "Windows")
+            .count() as f64 / samples as f64;
+        assert!((windows - windows_share).abs() < 0.03, "{} vs {}", windows, windows_share);
+    }
+
+    #[test]
+    fn test_override_file() {
+        let dir = std::env::temp_dir().join(format!("servionx-dataset-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Only one device: every sample must be it
+        let mut custom: FingerprintDataset = serde_json::from_str(BUNDLED_JSON).unwrap();
+        custom.revision = "2099-01-01".to_string();
+        custom.devices.retain(|d| d.browser == "Safari");
+        std::fs::write(dir.join(OVERRIDE_FILE), serde_json::to_string(&custom).unwrap()).unwrap();
+
+        let loaded = FingerprintDataset::load_from(&dir);
+        assert_eq!(loaded.revision, "2099-01-01");
+        assert!(loaded.info().is_override);
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(loaded.sample_device(&mut rng).browser, "Safari");
+
+        // An incoherent override is ignored
+        custom.devices[0].os = "Linux".to_string();
+        std::fs::write(dir.join(OVERRIDE_FILE), serde_json::to_string(&custom).unwrap()).unwrap();
+        assert_eq!(FingerprintDataset::load_from(&dir).revision, FingerprintDataset::bundled().revision);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,11 +1,26 @@
 // Script Fingerprinting Detection
 // Detects and alerts when websites attempt to fingerprint the browser
 
+use std::collections::HashMap;
 use std::sync::RwLock;
 use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use super::site;
+
+/// Attempts kept for `get_recent_attempts`
+const MAX_ATTEMPTS: usize = 1000;
+/// Script URLs tracked per site; pages cannot grow the map without bound
+const MAX_SCRIPTS_PER_SITE: usize = 50;
+/// Sites tracked before the least recently active one is dropped
+const MAX_SITES: usize = 500;
+
+/// Event emitted when a site's verdict first reaches `Fingerprinting`
+pub const FINGERPRINTING_DETECTED_EVENT: &str = "fingerprinting-detected";
 
 /// Types of fingerprinting attempts
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum FingerprintingType {
     Canvas,
     WebGL,
@@ -19,6 +34,48 @@ pub enum FingerprintingType {
     Screen,
 }
 
+impl FingerprintingType {
+    /// How strongly use of this API points at fingerprinting. Reading back
+    /// canvas, audio or font metrics has few other uses; the timezone or
+    /// screen size is read by plenty of ordinary pages.
+    pub fn weight(&self) -> u32 {
+        match self {
+            FingerprintingType::Canvas | FingerprintingType::Audio => 25,
+            FingerprintingType::WebGL | FingerprintingType::Font => 20,
+            FingerprintingType::WebRTC | FingerprintingType::Battery => 10,
+            FingerprintingType::Hardware
+            | FingerprintingType::Timezone
+            | FingerprintingType::Language
+            | FingerprintingType::Screen => 5,
+        }
+    }
+
+    /// Canvas, WebGL, audio and fonts: the APIs that identify a machine
+    /// on their own
+    pub fn is_high_entropy(&self) -> bool {
+        self.weight() >= 20
+    }
+}
+
+/// How likely it is that a site fingerprints its visitors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FingerprintingVerdict {
+    None,
+    Suspicious,
+    Fingerprinting,
+}
+
+impl FingerprintingVerdict {
+    pub fn from_score(score: u32) -> Self {
+        match score {
+            50.. => FingerprintingVerdict::Fingerprinting,
+            25.. => FingerprintingVerdict::Suspicious,
+            _ => FingerprintingVerdict::None,
+        }
+    }
+}
+
 /// A detected fingerprinting attempt
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FingerprintingAttempt {
@@ -28,10 +85,94 @@ pub struct FingerprintingAttempt {
     pub blocked: bool,
 }
 
+/// Calls to one API from one script, as counted by the page script
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FingerprintingEvent {
+    pub api: FingerprintingType,
+    /// Script that made the calls; `None` when no page script was on the stack
+    pub script_url: Option<String>,
+    pub count: u32,
+}
+
+/// A batch of events the detection script sends from one page load
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FingerprintingReport {
+    /// Filled in by the backend from the reporting tab; pages leave it out
+    #[serde(default)]
+    pub page_url: String,
+    /// Random per page load, so reports of one load can be grouped
+    pub load_id: String,
+    pub events: Vec<FingerprintingEvent>,
+}
+
+/// What one script on a site did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptActivity {
+    pub script_url: String,
+    pub apis: Vec<FingerprintingType>,
+    pub calls: u64,
+}
+
+/// Everything seen from one site
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteFingerprinting {
+    pub site: String,
+    pub api_calls: HashMap<FingerprintingType, u64>,
+    pub scripts: Vec<ScriptActivity>,
+    pub page_loads: u64,
+    /// Distinct APIs the most recent page load used
+    pub last_load_apis: Vec<FingerprintingType>,
+    /// Sum of the weights of the distinct APIs used, capped at 100
+    pub score: u32,
+    pub verdict: FingerprintingVerdict,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    #[serde(skip)]
+    last_load_id: String,
+}
+
+impl SiteFingerprinting {
+    fn new(site: String) -> Self {
+        let now = Utc::now();
+        Self {
+            site,
+            api_calls: HashMap::new(),
+            scripts: Vec::new(),
+            page_loads: 0,
+            last_load_apis: Vec::new(),
+            score: 0,
+            verdict: FingerprintingVerdict::None,
+            first_seen: now,
+            last_seen: now,
+            last_load_id: String::new(),
+        }
+    }
+
+    fn rescore(&mut self) {
+        self.score = self.api_calls.keys().map(|api| api.weight()).sum::<u32>().min(100);
+        self.verdict = FingerprintingVerdict::from_score(self.score);
+    }
+}
+
+/// What a report changed, for logging and alerts
+#[derive(Debug, Clone)]
+pub struct ReportOutcome {
+    pub site: SiteFingerprinting,
+    /// (API, script) pairs this site had not used before
+    pub new_activity: Vec<(FingerprintingType, Option<String>)>,
+    /// The verdict before this report
+    pub previous_verdict: FingerprintingVerdict,
+}
+
 /// Detects and tracks fingerprinting attempts
 pub struct FingerprintingDetector {
     attempts: RwLock<Vec<FingerprintingAttempt>>,
     total_blocked: RwLock<u64>,
+    sites: RwLock<HashMap<String, SiteFingerprinting>>,
 }
 
 impl FingerprintingDetector {
@@ -39,9 +180,10 @@ impl FingerprintingDetector {
         Self {
             attempts: RwLock::new(Vec::new()),
             total_blocked: RwLock::new(0),
+            sites: RwLock::new(HashMap::new()),
         }
     }
-    
+
     /// Record a fingerprinting attempt
     pub fn record_attempt(&self, attempt_type: FingerprintingType, url: String, blocked: bool) {
         let attempt = FingerprintingAttempt {
@@ -50,70 +192,213 @@ impl FingerprintingDetector {
             timestamp: chrono::Utc::now().timestamp(),
             blocked,
         };
-        
-        self.attempts.write().unwrap().push(attempt);
-        
+
+        {
+            let mut attempts = self.attempts.write().unwrap();
+            if attempts.len() >= MAX_ATTEMPTS {
+                attempts.remove(0);
+            }
+            attempts.push(attempt);
+        }
+
         if blocked {
             *self.total_blocked.write().unwrap() += 1;
         }
     }
-    
+
+    /// Add a report from a page to its site's aggregate. Every API the
+    /// detection script sees is also spoofed by the protection layers, so
+    /// each counts as blocked.
+    pub fn record_report(&self, report: &FingerprintingReport) -> Result<ReportOutcome, String> {
+        let is_web_page = report.page_url.starts_with("https://") || report.page_url.starts_with("http://");
+        let site = site::registrable_domain(&report.page_url)
+            .filter(|_| is_web_page)
+            .ok_or_else(|| format!("Report from a page without a site: {}", report.page_url))?;
+
+        for event in &report.events {
+            self.record_attempt(event.api, report.page_url.clone(), true);
+        }
+
+        let mut sites = self.sites.write().unwrap();
+        if !sites.contains_key(&site) && sites.len() >= MAX_SITES {
+            let oldest = sites.values().min_by_key(|s| s.last_seen).map(|s| s.site.clone());
+            if let Some(oldest) = oldest {
+                sites.remove(&oldest);
+            }
+        }
+        let entry = sites
+            .entry(site.clone())
+            .or_insert_with(|| SiteFingerprinting::new(site));
+        let previous_verdict = entry.verdict;
+
+        if entry.last_load_id != report.load_id {
+            entry.last_load_id = report.load_id.clone();
+            entry.page_loads += 1;
+            entry.last_load_apis.clear();
+        }
+        entry.last_seen = Utc::now();
+
+        let mut new_activity = Vec::new();
+        for event in &report.events {
+            let calls = u64::from(event.count.max(1));
+            *entry.api_calls.entry(event.api).or_insert(0) += calls;
+            if !entry.last_load_apis.contains(&event.api) {
+                entry.last_load_apis.push(event.api);
+                entry.last_load_apis.sort();
+            }
+
+            let script_url = event.script_url.clone().unwrap_or_else(|| "inline".to_string());
+            let known = entry.scripts.iter().position(|s| s.script_url == script_url);
+            let script = match known {
+                Some(i) => &mut entry.scripts[i],
+                None if entry.scripts.len() < MAX_SCRIPTS_PER_SITE => {
+                    entry.scripts.push(ScriptActivity { script_url, apis: Vec::new(), calls: 0 });
+                    entry.scripts.last_mut().unwrap()
+                }
+                None => continue,
+            };
+            script.calls += calls;
+            if !script.apis.contains(&event.api) {
+                script.apis.push(event.api);
+                script.apis.sort();
+                new_activity.push((event.api, event.script_url.clone()));
+            }
+        }
+        entry.rescore();
+
+        Ok(ReportOutcome {
+            site: entry.clone(),
+            new_activity,
+            previous_verdict,
+        })
+    }
+
     /// Get recent attempts
     pub fn get_recent_attempts(&self, limit: usize) -> Vec<FingerprintingAttempt> {
         let attempts = self.attempts.read().unwrap();
         attempts.iter().rev().take(limit).cloned().collect()
     }
-    
+
     /// Get total blocked count
     pub fn get_blocked_count(&self) -> u64 {
         *self.total_blocked.read().unwrap()
     }
-    
-    /// Generate JavaScript to detect and report fingerprinting attempts
+
+    /// Aggregate for the site of `site_or_url`, if it was seen
+    pub fn get_site(&self, site_or_url: &str) -> Option<SiteFingerprinting> {
+        let site = site::registrable_domain(site_or_url)?;
+        self.sites.read().unwrap().get(&site).cloned()
+    }
+
+    /// Every site seen, highest score first
+    pub fn get_sites(&self) -> Vec<SiteFingerprinting> {
+        let mut sites: Vec<SiteFingerprinting> = self.sites.read().unwrap().values().cloned().collect();
+        sites.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| b.last_seen.cmp(&a.last_seen)));
+        sites
+    }
+
+    /// Forget everything recorded
+    pub fn clear(&self) {
+        self.attempts.write().unwrap().clear();
+        self.sites.write().unwrap().clear();
+    }
+
+    /// Generate JavaScript to detect and report fingerprinting attempts.
+    /// Calls are counted per API and calling script and sent in batches
+    /// over the app's IPC to `report_fingerprinting`.
     pub fn get_injection_script() -> String {
         r#"
 // Fingerprinting Detection
 (function() {
     'use strict';
-    
+
+    // Captured before any page script can replace it
+    const ipc = window.__TAURI_INTERNALS__;
+    const loadId = Math.random().toString(36).slice(2) + Date.now().toString(36);
+    const pending = new Map();
     let fingerprintAttempts = 0;
-    
-    // Monitor canvas fingerprinting
-    const originalGetContext = HTMLCanvasElement.prototype.getContext;
-    HTMLCanvasElement.prototype.getContext = function(type, ...args) {
-        if (type === '2d' || type === 'webgl' || type === 'webgl2') {
-            fingerprintAttempts++;
-            console.log('%c[ServionX] Canvas fingerprinting attempt detected and protected', 'color: #f59e0b;');
-        }
-        return originalGetContext.apply(this, [type, ...args]);
+
+    // Innermost page script on the stack; the injected layers have no URL
+    const callerScript = () => {
+        const match = (new Error().stack || '').match(/(https?:\/\/[^\s()@]+?):\d+:\d+/);
+        return match ? match[1] : null;
     };
-    
-    // Monitor audio fingerprinting
-    const originalCreateOscillator = AudioContext.prototype.createOscillator;
-    AudioContext.prototype.createOscillator = function() {
+
+    const report = (api) => {
         fingerprintAttempts++;
-        console.log('%c[ServionX] Audio fingerprinting attempt detected and protected', 'color: #f59e0b;');
-        return originalCreateOscillator.apply(this);
+        const scriptUrl = callerScript();
+        const key = api + ' ' + scriptUrl;
+        const entry = pending.get(key);
+        if (entry) {
+            entry.count++;
+        } else {
+            pending.set(key, { api, scriptUrl, count: 1 });
+        }
     };
-    
-    // Monitor WebGL fingerprinting
-    const originalGetParameter = WebGLRenderingContext.prototype.getParameter;
-    WebGLRenderingContext.prototype.getParameter = function(param) {
-        // RENDERER and VENDOR are common fingerprinting targets
-        if (param === 37446 || param === 37445) {
-            fingerprintAttempts++;
-            console.log('%c[ServionX] WebGL fingerprinting attempt detected and protected', 'color: #f59e0b;');
+
+    const flush = () => {
+        if (pending.size === 0) return;
+        const events = Array.from(pending.values());
+        pending.clear();
+        if (ipc && typeof ipc.invoke === 'function') {
+            ipc.invoke('report_fingerprinting', {
+                report: { loadId, events }
+            }).catch(() => {});
         }
-        return originalGetParameter.apply(this, arguments);
     };
-    
+    setInterval(flush, 2000);
+    window.addEventListener('pagehide', flush);
+
+    const watch = (proto, method, api, when) => {
+        if (!proto || typeof proto[method] !== 'function') return;
+        const original = proto[method];
+        proto[method] = function() {
+            if (!when || when.apply(this, arguments)) report(api);
+            return original.apply(this, arguments);
+        };
+    };
+
+    // Monitor canvas fingerprinting (reading pixels back, not drawing)
+    watch(HTMLCanvasElement.prototype, 'toDataURL', 'canvas');
+    watch(HTMLCanvasElement.prototype, 'toBlob', 'canvas');
+    watch(window.CanvasRenderingContext2D && CanvasRenderingContext2D.prototype, 'getImageData', 'canvas');
+
+    // Monitor font enumeration through text metrics
+    watch(window.CanvasRenderingContext2D && CanvasRenderingContext2D.prototype, 'measureText', 'font');
+    if (document.fonts && typeof document.fonts.check === 'function') {
+        watch(Object.getPrototypeOf(document.fonts), 'check', 'font');
+    }
+
+    // Monitor WebGL fingerprinting
+    // RENDERER and VENDOR are common fingerprinting targets
+    const webglTargets = (param) => param === 37446 || param === 37445;
+    const debugInfo = (name) => name === 'WEBGL_debug_renderer_info';
+    [window.WebGLRenderingContext, window.WebGL2RenderingContext].forEach((ctx) => {
+        if (!ctx) return;
+        watch(ctx.prototype, 'getParameter', 'webgl', webglTargets);
+        watch(ctx.prototype, 'getExtension', 'webgl', debugInfo);
+    });
+
+    // Monitor audio fingerprinting
+    const audioProto = window.BaseAudioContext ? BaseAudioContext.prototype
+        : window.AudioContext ? AudioContext.prototype : null;
+    watch(audioProto, 'createOscillator', 'audio');
+    watch(audioProto, 'createDynamicsCompressor', 'audio');
+
+    // Monitor timezone reads
+    watch(Intl.DateTimeFormat.prototype, 'resolvedOptions', 'timezone');
+
+    // Monitor WebRTC (local IP discovery)
+    watch(window.RTCPeerConnection && RTCPeerConnection.prototype, 'createDataChannel', 'webrtc');
+    watch(window.RTCPeerConnection && RTCPeerConnection.prototype, 'createOffer', 'webrtc');
+
     // Report fingerprinting attempts periodically
     setInterval(() => {
         if (fingerprintAttempts > 0) {
             console.log('%c[ServionX] Total fingerprinting attempts blocked: ' + fingerprintAttempts, 'color: #22c55e; font-weight: bold;');
         }
     }, 10000);
-    
+
     console.log('%c[ServionX] Fingerprinting detection active', 'color: #22c55e;');
 })();
 "#.to_string()
@@ -125,3 +410,59 @@ impl Default for FingerprintingDetector {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(api: FingerprintingType, script: Option<&str>, count: u32) -> FingerprintingEvent {
+        FingerprintingEvent { api, script_url: script.map(str::to_string), count }
+    }
+
+    #[test]
+    fn test_reports_aggregate_per_site() {
+        let detector = FingerprintingDetector::new();
+        let fp_js = Some("https://cdn.fp.example/fp.js");
+        let first = detector.record_report(&FingerprintingReport {
+            page_url: "https://www.shop.example/cart".to_string(),
+            load_id: "a".to_string(),
+            events: vec![event(FingerprintingType::Canvas, fp_js, 3), event(FingerprintingType::Timezone, None, 1)],
+        }).unwrap();
+        assert_eq!(first.site.verdict, FingerprintingVerdict::Suspicious);
+        assert_eq!(first.new_activity.len(), 2);
+
+        let second = detector.record_report(&FingerprintingReport {
+            page_url: "https://shop.example/".to_string(),
+            load_id: "a".to_string(),
+            events: vec![event(FingerprintingType::Canvas, fp_js, 2), event(FingerprintingType::Audio, fp_js, 1)],
+        }).unwrap();
+        assert_eq!(second.previous_verdict, FingerprintingVerdict::Suspicious);
+        assert_eq!(second.site.verdict, FingerprintingVerdict::Fingerprinting);
+        assert_eq!(second.new_activity, vec![(FingerprintingType::Audio, fp_js.map(str::to_string))]);
+
+        let site = detector.get_site("shop.example").unwrap();
+        assert_eq!(site.page_loads, 1);
+        assert_eq!(
+            site.last_load_apis,
+            vec![FingerprintingType::Canvas, FingerprintingType::Audio, FingerprintingType::Timezone]
+        );
+        assert_eq!(site.api_calls[&FingerprintingType::Canvas], 5);
+        let script = site.scripts.iter().find(|s| s.script_url == "https://cdn.fp.example/fp.js").unwrap();
+        assert_eq!(script.apis, vec![FingerprintingType::Canvas, FingerprintingType::Audio]);
+        assert_eq!(detector.get_blocked_count(), 4);
+
+        assert!(detector.record_report(&FingerprintingReport {
+            page_url: "about:blank".to_string(),
+            load_id: "b".to_string(),
+            events: vec![],
+        }).is_err());
+    }
+
+    #[test]
+    fn test_report_json_from_page() {
+        let json = r#"{"loadId":"x1","events":[{"api":"webgl","scriptUrl":null,"count":2}]}"#;
+        let report: FingerprintingReport = serde_json::from_str(json).unwrap();
+        assert_eq!(report.events[0].api, FingerprintingType::WebGL);
+        assert!(report.page_url.is_empty());
+    }
+}
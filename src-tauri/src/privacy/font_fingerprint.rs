@@ -1,11 +1,16 @@
 // Font Fingerprint Protection
-// Spoofs the list of available fonts to prevent fingerprinting
+// Shows pages the fonts of the identity's OS: the identity's installed fonts
+// come from that OS's font catalog, and document.fonts and text measurement
+// report exactly those
 
-use rand::seq::SliceRandom;
-use rand::Rng;
+use std::collections::BTreeSet;
+use super::coherence::OsFamily;
+use super::fingerprint_dataset::FingerprintDataset;
+use super::injection::ScriptTemplate;
+use super::FakeIdentity;
 
-/// Common fonts that will be reported as available
-const COMMON_FONTS: &[&str] = &[
+/// Fonts probing scripts commonly test for, besides the catalogs' own
+const PROBED_FONTS: &[&str] = &[
     "Arial",
     "Arial Black",
     "Comic Sans MS",
@@ -26,63 +31,137 @@ const COMMON_FONTS: &[&str] = &[
     "Consolas",
     "Liberation Sans",
     "Liberation Serif",
+    "Segoe UI",
+    "Calibri",
+    "Cambria",
+    "Menlo",
+    "DejaVu Sans",
+    "Ubuntu",
+    "Cantarell",
+    "Noto Sans",
+    "Roboto",
 ];
 
-/// Generates a fake font list for fingerprint protection
+/// Spoofs which system fonts are available
 pub struct FontFingerprint {
-    fake_fonts: Vec<String>,
+    /// Every system font a page might probe for
+    known_fonts: Vec<String>,
+    /// The fonts reported as installed
+    fonts: Vec<String>,
 }
 
 impl FontFingerprint {
+    /// Fonts of a Windows machine from the bundled dataset, until an
+    /// identity is applied
     pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
-        
-        // Select random subset of common fonts (10-15 fonts)
-        let num_fonts = rng.gen_range(10..=15);
-        let mut fonts: Vec<String> = COMMON_FONTS.iter()
-            .map(|s| s.to_string())
+        Self::with_dataset(&FingerprintDataset::bundled())
+    }
+
+    /// Font protection knowing the system fonts of `dataset`'s catalogs
+    pub fn with_dataset(dataset: &FingerprintDataset) -> Self {
+        let known_fonts: BTreeSet<String> = OsFamily::ALL
+            .into_iter()
+            .flat_map(|os| dataset.font_catalog(os))
+            .chain(PROBED_FONTS.iter().map(|f| f.to_string()))
             .collect();
-        fonts.shuffle(&mut rng);
-        fonts.truncate(num_fonts);
-        
         Self {
-            fake_fonts: fonts,
+            known_fonts: known_fonts.into_iter().collect(),
+            fonts: dataset.font_catalog(OsFamily::Windows),
+        }
+    }
+
+    /// Copy of this protection reporting the fonts of `identity`
+    pub fn with_identity(&self, identity: &FakeIdentity) -> Self {
+        Self {
+            known_fonts: self.known_fonts.clone(),
+            fonts: identity.fingerprint.installed_fonts.clone(),
         }
     }
-    
+
     /// Get the list of fake fonts to report
     pub fn get_fonts(&self) -> &[String] {
-        &self.fake_fonts
+        &self.fonts
+    }
+
+    /// System fonts the page must not detect: known, but not installed on
+    /// the claimed machine
+    pub fn hidden_fonts(&self) -> Vec<String> {
+        self.known_fonts
+            .iter()
+            .filter(|known| !self.fonts.iter().any(|f| f.eq_ignore_ascii_case(known)))
+            .cloned()
+            .collect()
     }
-    
+
     /// Generate JavaScript to inject for font spoofing
     pub fn get_injection_script(&self) -> String {
-        let fonts_json = serde_json::to_string(&self.fake_fonts).unwrap_or_default();
-        
-        format!(r#"
+        ScriptTemplate::new(r#"
 // Font Fingerprint Protection
-(function() {{
+(function() {
     'use strict';
-    
-    const FAKE_FONTS = {fonts};
-    
-    // Override document.fonts if available
-    if (document.fonts && typeof document.fonts.check === 'function') {{
+
+    const INSTALLED = new Set(@@fonts@@.map(f => f.toLowerCase()));
+    const HIDDEN = new Set(@@hidden@@.map(f => f.toLowerCase()));
+    const familyName = (family) => family.trim().replace(/^["']|["']$/g, '').toLowerCase();
+
+    // Drop hidden fonts from a font-family list, so text measured with them
+    // falls back exactly as on a machine without them
+    const filterFamilies = (list) => {
+        const kept = String(list).split(',').filter(f => !HIDDEN.has(familyName(f)));
+        return kept.length ? kept.join(',') : 'serif';
+    };
+    // Same for the `font` shorthand: "bold 72px 'Calibri', monospace"
+    const filterShorthand = (font) => {
+        const match = /^(.*?[\d.]+[a-z%]*(?:\s*\/\s*\S+)?\s+)(.+)$/i.exec(String(font));
+        return match ? match[1] + filterFamilies(match[2]) : font;
+    };
+
+    const wrapSetter = (proto, prop, filter) => {
+        const descriptor = proto && Object.getOwnPropertyDescriptor(proto, prop);
+        if (!descriptor || !descriptor.set) return;
+        Object.defineProperty(proto, prop, {
+            ...descriptor,
+            set: function(value) {
+                descriptor.set.call(this, filter(value));
+            }
+        });
+    };
+
+    // Canvas text measurement
+    wrapSetter(CanvasRenderingContext2D.prototype, 'font', filterShorthand);
+    if (typeof OffscreenCanvasRenderingContext2D !== 'undefined') {
+        wrapSetter(OffscreenCanvasRenderingContext2D.prototype, 'font', filterShorthand);
+    }
+
+    // Element size measurement
+    wrapSetter(CSSStyleDeclaration.prototype, 'fontFamily', filterFamilies);
+    wrapSetter(CSSStyleDeclaration.prototype, 'font', filterShorthand);
+    const originalSetProperty = CSSStyleDeclaration.prototype.setProperty;
+    CSSStyleDeclaration.prototype.setProperty = function(name, value, priority) {
+        const prop = String(name).toLowerCase();
+        if (prop === 'font-family') value = filterFamilies(value);
+        if (prop === 'font') value = filterShorthand(value);
+        return originalSetProperty.call(this, name, value, priority);
+    };
+
+    // document.fonts answers from the same list
+    if (document.fonts && typeof document.fonts.check === 'function') {
         const originalCheck = document.fonts.check.bind(document.fonts);
-        document.fonts.check = function(font, text) {{
-            // Extract font family name
-            const match = font.match(/["']?([^"',]+)["']?/);
-            if (match) {{
-                const fontName = match[1].trim();
-                return FAKE_FONTS.some(f => f.toLowerCase() === fontName.toLowerCase());
-            }}
+        document.fonts.check = function(font, text) {
+            const match = /^(.*?[\d.]+[a-z%]*(?:\s*\/\s*\S+)?\s+)(.+)$/i.exec(String(font));
+            const families = (match ? match[2] : String(font)).split(',').map(familyName);
+            if (families.some(f => HIDDEN.has(f))) return false;
+            if (families.some(f => INSTALLED.has(f))) return true;
             return originalCheck(font, text);
-        }};
-    }}
-    
+        };
+    }
+
     console.log('%c[ServionX] Font fingerprinting protection active', 'color: #22c55e;');
-}})();
-"#, fonts = fonts_json)
+})();
+"#)
+            .bind("fonts", &self.fonts)
+            .bind("hidden", &self.hidden_fonts())
+            .render()
     }
 }
 
@@ -95,11 +174,30 @@ impl Default for FontFingerprint {
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
     #[test]
-    fn test_font_generation() {
-        let fp = FontFingerprint::new();
-        assert!(fp.get_fonts().len() >= 10);
-        assert!(fp.get_fonts().len() <= 15);
+    fn test_fonts_follow_identity_os() {
+        let engine = crate::privacy::test_engine();
+        let dataset = FingerprintDataset::bundled();
+        let protection = FontFingerprint::new();
+        assert!(!protection.get_fonts().is_empty());
+
+        for seed in 0..20 {
+            let identity = engine.identity_from_seed(seed);
+            let os = OsFamily::from_name(&identity.user_agent.os_name).unwrap();
+            let catalog = dataset.font_catalog(os);
+            let fonts = protection.with_identity(&identity);
+
+            assert_eq!(fonts.get_fonts(), identity.fingerprint.installed_fonts.as_slice());
+            assert!(fonts.get_fonts().iter().all(|f| catalog.contains(f)), "{:?} on {}", fonts.get_fonts(), os.name());
+            let hidden = fonts.hidden_fonts();
+            assert!(fonts.get_fonts().iter().all(|f| !hidden.contains(f)));
+            let other_os_font = OsFamily::ALL.into_iter()
+                .filter(|other| *other != os)
+                .flat_map(|other| dataset.font_catalog(other))
+                .find(|f| !catalog.contains(f))
+                .unwrap();
+            assert!(hidden.contains(&other_os_font));
+        }
     }
 }
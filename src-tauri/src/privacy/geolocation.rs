@@ -2,7 +2,21 @@
 // Generates realistic fake GPS coordinates
 
 use serde::{Deserialize, Serialize};
+use chrono::DateTime;
+use once_cell::sync::Lazy;
 use rand::Rng;
+use regex::Regex;
+
+/// Walking pace used to time GPX tracks that carry no timestamps (m/s)
+const WALKING_SPEED: f64 = 1.4;
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+static GPX_POINT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s)<(?:trkpt|rtept)\b([^>]*?)(?:/>|>(.*?)</(?:trkpt|rtept)>)").unwrap()
+});
+static GPX_LAT: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\blat\s*=\s*["']([^"']+)["']"#).unwrap());
+static GPX_LON: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\blon\s*=\s*["']([^"']+)["']"#).unwrap());
+static GPX_TIME: Lazy<Regex> = Lazy::new(|| Regex::new(r"<time>\s*([^<]+?)\s*</time>").unwrap());
 
 /// Fake geolocation data
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +27,59 @@ pub struct FakeGeolocation {
     pub city: String,
     pub country: String,
     pub country_code: String,
+    pub timezone: String,
+    /// Track replayed by `watchPosition`, starting at `latitude`/`longitude`
+    #[serde(default)]
+    pub route: Option<GeoRoute>,
+}
+
+/// One point of a replayed route
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutePoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Seconds since the start of the route
+    pub elapsed_secs: f64,
+}
+
+/// A named track, usually imported from GPX
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoRoute {
+    pub name: String,
+    pub points: Vec<RoutePoint>,
+    /// Start over from the first point once the end is reached
+    #[serde(default)]
+    pub loop_route: bool,
+}
+
+/// How the identity's location is chosen
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum LocationMode {
+    /// A random known city on every new identity
+    #[default]
+    Random,
+    /// A fixed country, optionally narrowed down to one city
+    #[serde(rename_all = "camelCase")]
+    City { country_code: String, city: Option<String> },
+    /// Exact coordinates; country and timezone come from the nearest city
+    Coordinates { latitude: f64, longitude: f64, accuracy: Option<f64> },
+    /// Replay a track so `watchPosition` reports a moving location
+    Route(GeoRoute),
+}
+
+/// A selectable city, as listed to the UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationInfo {
+    pub city: String,
+    pub country: String,
+    pub country_code: String,
+    pub timezone: String,
+    pub latitude: f64,
+    pub longitude: f64,
 }
 
 /// Predefined locations for realistic fake coordinates
@@ -23,6 +90,7 @@ struct Location {
     country_code: &'static str,
     lat: f64,
     lon: f64,
+    timezone: &'static str,
 }
 
 #[derive(Debug)]
@@ -35,81 +103,78 @@ impl GeolocationFaker {
         Self {
             locations: vec![
                 // United States
-                Location { city: "New York", country: "United States", country_code: "US", lat: 40.7128, lon: -74.0060 },
-                Location { city: "Los Angeles", country: "United States", country_code: "US", lat: 34.0522, lon: -118.2437 },
-                Location { city: "Chicago", country: "United States", country_code: "US", lat: 41.8781, lon: -87.6298 },
-                Location { city: "Houston", country: "United States", country_code: "US", lat: 29.7604, lon: -95.3698 },
-                Location { city: "Phoenix", country: "United States", country_code: "US", lat: 33.4484, lon: -112.0740 },
-                Location { city: "San Francisco", country: "United States", country_code: "US", lat: 37.7749, lon: -122.4194 },
-                Location { city: "Seattle", country: "United States", country_code: "US", lat: 47.6062, lon: -122.3321 },
-                Location { city: "Miami", country: "United States", country_code: "US", lat: 25.7617, lon: -80.1918 },
+                Location { city: "New York", country: "United States", country_code: "US", lat: 40.7128, lon: -74.0060, timezone: "America/New_York" },
+                Location { city: "Los Angeles", country: "United States", country_code: "US", lat: 34.0522, lon: -118.2437, timezone: "America/Los_Angeles" },
+                Location { city: "Chicago", country: "United States", country_code: "US", lat: 41.8781, lon: -87.6298, timezone: "America/Chicago" },
+                Location { city: "Houston", country: "United States", country_code: "US", lat: 29.7604, lon: -95.3698, timezone: "America/Chicago" },
+                Location { city: "Phoenix", country: "United States", country_code: "US", lat: 33.4484, lon: -112.0740, timezone: "America/Phoenix" },
+                Location { city: "San Francisco", country: "United States", country_code: "US", lat: 37.7749, lon: -122.4194, timezone: "America/Los_Angeles" },
+                Location { city: "Seattle", country: "United States", country_code: "US", lat: 47.6062, lon: -122.3321, timezone: "America/Los_Angeles" },
+                Location { city: "Miami", country: "United States", country_code: "US", lat: 25.7617, lon: -80.1918, timezone: "America/New_York" },
                 
                 // Europe
-                Location { city: "London", country: "United Kingdom", country_code: "GB", lat: 51.5074, lon: -0.1278 },
-                Location { city: "Paris", country: "France", country_code: "FR", lat: 48.8566, lon: 2.3522 },
-                Location { city: "Berlin", country: "Germany", country_code: "DE", lat: 52.5200, lon: 13.4050 },
-                Location { city: "Amsterdam", country: "Netherlands", country_code: "NL", lat: 52.3676, lon: 4.9041 },
-                Location { city: "Madrid", country: "Spain", country_code: "ES", lat: 40.4168, lon: -3.7038 },
-                Location { city: "Rome", country: "Italy", country_code: "IT", lat: 41.9028, lon: 12.4964 },
-                Location { city: "Vienna", country: "Austria", country_code: "AT", lat: 48.2082, lon: 16.3738 },
-                Location { city: "Stockholm", country: "Sweden", country_code: "SE", lat: 59.3293, lon: 18.0686 },
+                Location { city: "London", country: "United Kingdom", country_code: "GB", lat: 51.5074, lon: -0.1278, timezone: "Europe/London" },
+                Location { city: "Paris", country: "France", country_code: "FR", lat: 48.8566, lon: 2.3522, timezone: "Europe/Paris" },
+                Location { city: "Berlin", country: "Germany", country_code: "DE", lat: 52.5200, lon: 13.4050, timezone: "Europe/Berlin" },
+                Location { city: "Amsterdam", country: "Netherlands", country_code: "NL", lat: 52.3676, lon: 4.9041, timezone: "Europe/Amsterdam" },
+                Location { city: "Madrid", country: "Spain", country_code: "ES", lat: 40.4168, lon: -3.7038, timezone: "Europe/Madrid" },
+                Location { city: "Rome", country: "Italy", country_code: "IT", lat: 41.9028, lon: 12.4964, timezone: "Europe/Rome" },
+                Location { city: "Vienna", country: "Austria", country_code: "AT", lat: 48.2082, lon: 16.3738, timezone: "Europe/Vienna" },
+                Location { city: "Stockholm", country: "Sweden", country_code: "SE", lat: 59.3293, lon: 18.0686, timezone: "Europe/Stockholm" },
                 
                 // Asia
-                Location { city: "Tokyo", country: "Japan", country_code: "JP", lat: 35.6762, lon: 139.6503 },
-                Location { city: "Singapore", country: "Singapore", country_code: "SG", lat: 1.3521, lon: 103.8198 },
-                Location { city: "Seoul", country: "South Korea", country_code: "KR", lat: 37.5665, lon: 126.9780 },
-                Location { city: "Dubai", country: "United Arab Emirates", country_code: "AE", lat: 25.2048, lon: 55.2708 },
-                Location { city: "Hong Kong", country: "Hong Kong", country_code: "HK", lat: 22.3193, lon: 114.1694 },
-                Location { city: "Mumbai", country: "India", country_code: "IN", lat: 19.0760, lon: 72.8777 },
-                Location { city: "Bangkok", country: "Thailand", country_code: "TH", lat: 13.7563, lon: 100.5018 },
+                Location { city: "Tokyo", country: "Japan", country_code: "JP", lat: 35.6762, lon: 139.6503, timezone: "Asia/Tokyo" },
+                Location { city: "Singapore", country: "Singapore", country_code: "SG", lat: 1.3521, lon: 103.8198, timezone: "Asia/Singapore" },
+                Location { city: "Seoul", country: "South Korea", country_code: "KR", lat: 37.5665, lon: 126.9780, timezone: "Asia/Seoul" },
+                Location { city: "Dubai", country: "United Arab Emirates", country_code: "AE", lat: 25.2048, lon: 55.2708, timezone: "Asia/Dubai" },
+                Location { city: "Hong Kong", country: "Hong Kong", country_code: "HK", lat: 22.3193, lon: 114.1694, timezone: "Asia/Hong_Kong" },
+                Location { city: "Mumbai", country: "India", country_code: "IN", lat: 19.0760, lon: 72.8777, timezone: "Asia/Kolkata" },
+                Location { city: "Bangkok", country: "Thailand", country_code: "TH", lat: 13.7563, lon: 100.5018, timezone: "Asia/Bangkok" },
                 
                 // Oceania
-                Location { city: "Sydney", country: "Australia", country_code: "AU", lat: -33.8688, lon: 151.2093 },
-                Location { city: "Melbourne", country: "Australia", country_code: "AU", lat: -37.8136, lon: 144.9631 },
-                Location { city: "Auckland", country: "New Zealand", country_code: "NZ", lat: -36.8485, lon: 174.7633 },
+                Location { city: "Sydney", country: "Australia", country_code: "AU", lat: -33.8688, lon: 151.2093, timezone: "Australia/Sydney" },
+                Location { city: "Melbourne", country: "Australia", country_code: "AU", lat: -37.8136, lon: 144.9631, timezone: "Australia/Melbourne" },
+                Location { city: "Auckland", country: "New Zealand", country_code: "NZ", lat: -36.8485, lon: 174.7633, timezone: "Pacific/Auckland" },
                 
                 // South America
-                Location { city: "São Paulo", country: "Brazil", country_code: "BR", lat: -23.5505, lon: -46.6333 },
-                Location { city: "Buenos Aires", country: "Argentina", country_code: "AR", lat: -34.6037, lon: -58.3816 },
+                Location { city: "São Paulo", country: "Brazil", country_code: "BR", lat: -23.5505, lon: -46.6333, timezone: "America/Sao_Paulo" },
+                Location { city: "Buenos Aires", country: "Argentina", country_code: "AR", lat: -34.6037, lon: -58.3816, timezone: "America/Argentina/Buenos_Aires" },
                 
                 // Canada
-                Location { city: "Toronto", country: "Canada", country_code: "CA", lat: 43.6532, lon: -79.3832 },
-                Location { city: "Vancouver", country: "Canada", country_code: "CA", lat: 49.2827, lon: -123.1207 },
+                Location { city: "Toronto", country: "Canada", country_code: "CA", lat: 43.6532, lon: -79.3832, timezone: "America/Toronto" },
+                Location { city: "Vancouver", country: "Canada", country_code: "CA", lat: 49.2827, lon: -123.1207, timezone: "America/Vancouver" },
             ],
         }
     }
     
     /// Generate a fake geolocation
     pub fn generate(&self) -> FakeGeolocation {
-        let mut rng = rand::thread_rng();
-        
+        self.generate_with_rng(&mut rand::thread_rng())
+    }
+    
+    /// Generate a fake geolocation drawing all randomness from `rng`
+    pub fn generate_with_rng<R: Rng>(&self, rng: &mut R) -> FakeGeolocation {
         // Select a random base location
         let location = &self.locations[rng.gen_range(0..self.locations.len())];
-        
-        // Add small random offset (within ~5km) to make each generation unique
-        let lat_offset = rng.gen_range(-0.05..0.05);
-        let lon_offset = rng.gen_range(-0.05..0.05);
-        
-        // Generate realistic accuracy (meters)
-        let accuracy = rng.gen_range(10.0..100.0);
-        
-        FakeGeolocation {
-            latitude: location.lat + lat_offset,
-            longitude: location.lon + lon_offset,
-            accuracy,
-            city: location.city.to_string(),
-            country: location.country.to_string(),
-            country_code: location.country_code.to_string(),
-        }
+        Self::near(location, rng)
     }
     
     /// Generate a fake geolocation for a specific country
     pub fn generate_for_country(&self, country_code: &str) -> Option<FakeGeolocation> {
-        let mut rng = rand::thread_rng();
-        
+        self.generate_for_country_with_rng(country_code, &mut rand::thread_rng())
+    }
+    
+    /// Generate a fake geolocation for a specific country using `rng`
+    pub fn generate_for_country_with_rng<R: Rng>(&self, country_code: &str, rng: &mut R) -> Option<FakeGeolocation> {
+        self.generate_for_city_with_rng(country_code, None, rng)
+    }
+    
+    /// Generate a fake geolocation in a country, optionally in one city
+    pub fn generate_for_city_with_rng<R: Rng>(&self, country_code: &str, city: Option<&str>, rng: &mut R) -> Option<FakeGeolocation> {
         let matching: Vec<_> = self.locations
             .iter()
             .filter(|l| l.country_code.eq_ignore_ascii_case(country_code))
+            .filter(|l| city.is_none_or(|c| l.city.eq_ignore_ascii_case(c)))
             .collect();
         
         if matching.is_empty() {
@@ -117,21 +182,201 @@ impl GeolocationFaker {
         }
         
         let location = matching[rng.gen_range(0..matching.len())];
+        Some(Self::near(location, rng))
+    }
+    
+    /// Generate the geolocation a location mode asks for. Modes that name
+    /// an unknown place fall back to a random location.
+    pub fn generate_for_mode_with_rng<R: Rng>(&self, mode: &LocationMode, rng: &mut R) -> FakeGeolocation {
+        match mode {
+            LocationMode::Random => self.generate_with_rng(rng),
+            LocationMode::City { country_code, city } => self
+                .generate_for_city_with_rng(country_code, city.as_deref(), rng)
+                .unwrap_or_else(|| self.generate_with_rng(rng)),
+            LocationMode::Coordinates { latitude, longitude, accuracy } => {
+                let accuracy = accuracy.unwrap_or_else(|| rng.gen_range(10.0..100.0));
+                self.at(*latitude, *longitude, accuracy)
+            }
+            LocationMode::Route(route) => match route.points.first() {
+                Some(start) => FakeGeolocation {
+                    route: Some(route.clone()),
+                    ..self.at(start.latitude, start.longitude, rng.gen_range(5.0..20.0))
+                },
+                None => self.generate_with_rng(rng),
+            },
+        }
+    }
+    
+    /// Check that a mode names a known place and usable coordinates
+    pub fn validate_mode(&self, mode: &LocationMode) -> Result<(), String> {
+        match mode {
+            LocationMode::Random => Ok(()),
+            LocationMode::City { country_code, city } => {
+                let known = self.locations.iter().any(|l| {
+                    l.country_code.eq_ignore_ascii_case(country_code)
+                        && city.as_deref().is_none_or(|c| l.city.eq_ignore_ascii_case(c))
+                });
+                if known {
+                    Ok(())
+                } else {
+                    Err(match city {
+                        Some(city) => format!("Unknown city {} in {}", city, country_code),
+                        None => format!("Unknown country {}", country_code),
+                    })
+                }
+            }
+            LocationMode::Coordinates { latitude, longitude, accuracy } => {
+                validate_coordinates(*latitude, *longitude)?;
+                match accuracy {
+                    Some(a) if !(a.is_finite() && *a > 0.0) => Err(format!("Invalid accuracy {}", a)),
+                    _ => Ok(()),
+                }
+            }
+            LocationMode::Route(route) => {
+                if route.points.len() < 2 {
+                    return Err("A route needs at least two points".to_string());
+                }
+                for point in &route.points {
+                    validate_coordinates(point.latitude, point.longitude)?;
+                }
+                if route.points.windows(2).any(|w| w[1].elapsed_secs < w[0].elapsed_secs) {
+                    return Err("Route points must be in time order".to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+    
+    /// All selectable cities
+    pub fn list_locations(&self) -> Vec<LocationInfo> {
+        self.locations
+            .iter()
+            .map(|l| LocationInfo {
+                city: l.city.to_string(),
+                country: l.country.to_string(),
+                country_code: l.country_code.to_string(),
+                timezone: l.timezone.to_string(),
+                latitude: l.lat,
+                longitude: l.lon,
+            })
+            .collect()
+    }
+    
+    /// The known city closest to a point, which decides its country and timezone
+    fn nearest(&self, latitude: f64, longitude: f64) -> &Location {
+        self.locations
+            .iter()
+            .min_by(|a, b| {
+                let da = haversine_m(latitude, longitude, a.lat, a.lon);
+                let db = haversine_m(latitude, longitude, b.lat, b.lon);
+                da.total_cmp(&db)
+            })
+            .expect("location table is not empty")
+    }
+    
+    /// Exact coordinates, described by the nearest known city
+    fn at(&self, latitude: f64, longitude: f64, accuracy: f64) -> FakeGeolocation {
+        let location = self.nearest(latitude, longitude);
+        FakeGeolocation {
+            latitude,
+            longitude,
+            accuracy,
+            city: location.city.to_string(),
+            country: location.country.to_string(),
+            country_code: location.country_code.to_string(),
+            timezone: location.timezone.to_string(),
+            route: None,
+        }
+    }
+    
+    /// A point somewhere around a known city
+    fn near<R: Rng>(location: &Location, rng: &mut R) -> FakeGeolocation {
+        // Add small random offset (within ~5km) to make each generation unique
         let lat_offset = rng.gen_range(-0.05..0.05);
         let lon_offset = rng.gen_range(-0.05..0.05);
+        
+        // Generate realistic accuracy (meters)
         let accuracy = rng.gen_range(10.0..100.0);
         
-        Some(FakeGeolocation {
+        FakeGeolocation {
             latitude: location.lat + lat_offset,
             longitude: location.lon + lon_offset,
             accuracy,
             city: location.city.to_string(),
             country: location.country.to_string(),
             country_code: location.country_code.to_string(),
-        })
+            timezone: location.timezone.to_string(),
+            route: None,
+        }
     }
 }
 
+fn validate_coordinates(latitude: f64, longitude: f64) -> Result<(), String> {
+    if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) {
+        Ok(())
+    } else {
+        Err(format!("Invalid coordinates {}, {}", latitude, longitude))
+    }
+}
+
+/// Great-circle distance in meters
+fn haversine_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// Parse the track and route points of a GPX document. Points keep their
+/// recorded timing; a track without (usable) timestamps is timed at
+/// walking pace instead.
+pub fn parse_gpx(name: &str, gpx: &str, loop_route: bool) -> Result<GeoRoute, String> {
+    let mut coords = Vec::new();
+    let mut times = Vec::new();
+    for point in GPX_POINT.captures_iter(gpx) {
+        let attrs = &point[1];
+        let attr = |re: &Regex, what: &str| -> Result<f64, String> {
+            re.captures(attrs)
+                .and_then(|c| c[1].trim().parse::<f64>().ok())
+                .ok_or_else(|| format!("GPX point {} has no valid {}", coords.len() + 1, what))
+        };
+        let latitude = attr(&GPX_LAT, "lat")?;
+        let longitude = attr(&GPX_LON, "lon")?;
+        validate_coordinates(latitude, longitude)?;
+        coords.push((latitude, longitude));
+        times.push(
+            point.get(2)
+                .and_then(|body| GPX_TIME.captures(body.as_str()))
+                .and_then(|c| DateTime::parse_from_rfc3339(&c[1]).ok()),
+        );
+    }
+    
+    if coords.len() < 2 {
+        return Err("GPX contains fewer than two track points".to_string());
+    }
+    
+    let timed = times.iter().all(Option::is_some)
+        && times.windows(2).all(|w| w[0] <= w[1])
+        && times.first() != times.last();
+    let mut elapsed = 0.0;
+    let points = coords
+        .iter()
+        .enumerate()
+        .map(|(i, &(latitude, longitude))| {
+            if timed {
+                elapsed = (times[i].unwrap() - times[0].unwrap()).num_milliseconds() as f64 / 1000.0;
+            } else if i > 0 {
+                let (prev_lat, prev_lon) = coords[i - 1];
+                elapsed += haversine_m(prev_lat, prev_lon, latitude, longitude) / WALKING_SPEED;
+            }
+            RoutePoint { latitude, longitude, elapsed_secs: elapsed }
+        })
+        .collect();
+    
+    Ok(GeoRoute { name: name.to_string(), points, loop_route })
+}
+
 impl Default for GeolocationFaker {
     fn default() -> Self {
         Self::new()
@@ -160,4 +405,40 @@ mod tests {
         
         assert_eq!(geo.country_code, "US");
     }
+    
+    #[test]
+    fn test_coordinates_take_nearest_city() {
+        let faker = GeolocationFaker::new();
+        let mode = LocationMode::Coordinates { latitude: 48.80, longitude: 2.13, accuracy: Some(15.0) };
+        assert!(faker.validate_mode(&mode).is_ok());
+        
+        let geo = faker.generate_for_mode_with_rng(&mode, &mut rand::thread_rng());
+        assert_eq!(geo.latitude, 48.80);
+        assert_eq!(geo.city, "Paris");
+        assert_eq!(geo.timezone, "Europe/Paris");
+        
+        let bad = LocationMode::City { country_code: "US".into(), city: Some("Paris".into()) };
+        assert!(faker.validate_mode(&bad).is_err());
+    }
+    
+    #[test]
+    fn test_parse_gpx() {
+        let timed = r#"<gpx><trk><trkseg>
+            <trkpt lat="52.5200" lon="13.4050"><time>2024-05-01T10:00:00Z</time></trkpt>
+            <trkpt lon="13.4060" lat="52.5210"><ele>40</ele><time>2024-05-01T10:00:30Z</time></trkpt>
+        </trkseg></trk></gpx>"#;
+        let route = parse_gpx("walk", timed, true).unwrap();
+        assert_eq!(route.points.len(), 2);
+        assert_eq!(route.points[1].longitude, 13.4060);
+        assert_eq!(route.points[1].elapsed_secs, 30.0);
+        
+        // Without timestamps the track is walked at a steady pace
+        let untimed = r#"<gpx><rte><rtept lat="0" lon="0"/><rtept lat="0" lon="0.01"/></rte></gpx>"#;
+        let route = parse_gpx("line", untimed, false).unwrap();
+        let secs = route.points[1].elapsed_secs;
+        assert!((secs - 1111.95 / WALKING_SPEED).abs() < 5.0, "{}", secs);
+        
+        assert!(parse_gpx("empty", "<gpx><trk/></gpx>", false).is_err());
+        assert!(parse_gpx("bad", r#"<trkpt lat="95" lon="0"/><trkpt lat="0" lon="0"/>"#, false).is_err());
+    }
 }
@@ -0,0 +1,569 @@
+// Injection Script Builder
+// Assembles the per-page protection scripts from typed, ordered modules and
+// renders script templates with every interpolated value JSON-escaped
+
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::BrowserSettings;
+
+/// Marks a value slot in a script template: `@@name@@`
+const PLACEHOLDER: &str = "@@";
+
+/// Encode a value as a JavaScript literal. JSON is valid JS except for the
+/// U+2028/U+2029 line separators, and `</` is escaped so a value can never
+/// close a surrounding `<script>` element.
+pub fn js_value<T: Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value)
+        .unwrap_or_else(|_| "null".to_string())
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029")
+        .replace("</", "<\\/")
+}
+
+/// A script with `@@name@@` slots that are filled with JSON-encoded values,
+/// so the JS itself needs no brace escaping and values cannot break out of
+/// their literal
+pub struct ScriptTemplate<'a> {
+    source: &'a str,
+    values: Vec<(&'a str, String)>,
+}
+
+impl<'a> ScriptTemplate<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self { source, values: Vec::new() }
+    }
+
+    /// Fill every `@@name@@` slot with `value` as a JS literal
+    pub fn bind<T: Serialize + ?Sized>(mut self, name: &'a str, value: &T) -> Self {
+        self.values.push((name, js_value(value)));
+        self
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::with_capacity(self.source.len());
+        let mut rest = self.source;
+        while let Some(start) = rest.find(PLACEHOLDER) {
+            let after = &rest[start + PLACEHOLDER.len()..];
+            let Some(end) = after.find(PLACEHOLDER) else { break };
+            let name = &after[..end];
+            out.push_str(&rest[..start]);
+            match self.values.iter().find(|(n, _)| *n == name) {
+                Some((_, value)) => out.push_str(value),
+                None => {
+                    log::error!("Script template slot {} is not bound", name);
+                    out.push_str("undefined");
+                }
+            }
+            rest = &after[end + PLACEHOLDER.len()..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// Words after which a `/` starts a regular expression rather than a division
+const REGEX_PRECEDING_WORDS: &[&str] = &[
+    "return", "typeof", "case", "do", "else", "in", "of", "new", "delete",
+    "void", "throw", "instanceof", "yield", "await",
+];
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Shrink a script without changing what it does: comments go, runs of
+/// whitespace collapse to a single space or newline, and spaces are only
+/// kept where two tokens would otherwise merge. Line breaks are preserved so
+/// automatic semicolon insertion behaves exactly as before. String, template
+/// and regex literals are copied untouched.
+pub fn minify(script: &str) -> String {
+    let chars: Vec<char> = script.chars().collect();
+    let mut out = String::with_capacity(script.len());
+    let mut pending: Option<char> = None; // ' ' or '\n' waiting to be written
+    let mut last_sig: Option<char> = None;
+    let mut word = String::new();
+    let mut last_word = String::new();
+    let mut i = 0;
+
+    // Write a code character, resolving any whitespace before it
+    fn emit(out: &mut String, pending: &mut Option<char>, last_sig: &mut Option<char>, c: char) {
+        if let Some(ws) = pending.take() {
+            if let Some(prev) = *last_sig {
+                if ws == '\n' {
+                    out.push('\n');
+                } else if (is_word_char(prev) && is_word_char(c))
+                    || (prev == c && matches!(c, '+' | '-'))
+                    || (prev == '/' && matches!(c, '/' | '*'))
+                {
+                    out.push(' ');
+                }
+            }
+        }
+        out.push(c);
+        *last_sig = Some(c);
+    }
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        if is_word_char(c) {
+            word.push(c);
+        } else if !word.is_empty() {
+            last_word = std::mem::take(&mut word);
+        }
+
+        match c {
+            c if c.is_whitespace() => {
+                if c == '\n' || pending.is_none() {
+                    pending = Some(if c == '\n' { '\n' } else { ' ' });
+                }
+                i += 1;
+            }
+            '/' if next == Some('/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if next == Some('*') => {
+                let mut had_newline = false;
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    had_newline |= chars[i] == '\n';
+                    i += 1;
+                }
+                i += 2;
+                if had_newline {
+                    pending = Some('\n');
+                } else if pending.is_none() {
+                    pending = Some(' ');
+                }
+            }
+            '\'' | '"' | '`' => {
+                emit(&mut out, &mut pending, &mut last_sig, c);
+                i += 1;
+                let mut depth = 0usize; // `${` nesting inside a template
+                while i < chars.len() {
+                    let ch = chars[i];
+                    out.push(ch);
+                    i += 1;
+                    match ch {
+                        '\\' => {
+                            if let Some(&escaped) = chars.get(i) {
+                                out.push(escaped);
+                                i += 1;
+                            }
+                        }
+                        '$' if c == '`' && chars.get(i) == Some(&'{') => {
+                            out.push('{');
+                            i += 1;
+                            depth += 1;
+                        }
+                        '{' if depth > 0 => depth += 1,
+                        '}' if depth > 0 => depth -= 1,
+                        ch if ch == c && depth == 0 => break,
+                        _ => {}
+                    }
+                }
+                last_sig = Some(c);
+            }
+            '/' => {
+                let starts_regex = match last_sig {
+                    None => true,
+                    Some(prev) if is_word_char(prev) || prev == ')' || prev == ']' => {
+                        is_word_char(prev) && REGEX_PRECEDING_WORDS.contains(&last_word.as_str())
+                    }
+                    Some(_) => true,
+                };
+                emit(&mut out, &mut pending, &mut last_sig, c);
+                i += 1;
+                if starts_regex {
+                    let mut in_class = false;
+                    while i < chars.len() {
+                        let ch = chars[i];
+                        out.push(ch);
+                        i += 1;
+                        match ch {
+                            '\\' => {
+                                if let Some(&escaped) = chars.get(i) {
+                                    out.push(escaped);
+                                    i += 1;
+                                }
+                            }
+                            '[' => in_class = true,
+                            ']' => in_class = false,
+                            '/' if !in_class => break,
+                            '\n' => break,
+                            _ => {}
+                        }
+                    }
+                    // Flags follow as ordinary word characters
+                    last_sig = Some('/');
+                    last_word.clear();
+                }
+            }
+            _ => {
+                emit(&mut out, &mut pending, &mut last_sig, c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// The protection scripts injected into every page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProtectionModule {
+    /// Keeps the page's own scripts from running when a site profile turns
+    /// JavaScript off, and blocked script URLs from loading
+    JavaScriptPolicy,
+    /// Hides the elements the blocklists' cosmetic filters name on the page
+    ContentFilter,
+    /// Identity spoofing: navigator, geolocation, canvas, WebGL, timezone
+    Identity,
+    Fonts,
+    Referrer,
+    FingerprintDetector,
+    AdvancedFingerprint,
+    NetworkSecurity,
+    CompleteFakeData,
+    Ultimate,
+    UploadProtection,
+    Additional,
+    IpHeaders,
+}
+
+impl ProtectionModule {
+    /// Every module, in the order they are injected when nothing else matters
+    pub const ALL: [ProtectionModule; 13] = [
+        ProtectionModule::JavaScriptPolicy,
+        ProtectionModule::ContentFilter,
+        ProtectionModule::Identity,
+        ProtectionModule::Fonts,
+        ProtectionModule::Referrer,
+        ProtectionModule::FingerprintDetector,
+        ProtectionModule::AdvancedFingerprint,
+        ProtectionModule::NetworkSecurity,
+        ProtectionModule::CompleteFakeData,
+        ProtectionModule::Ultimate,
+        ProtectionModule::UploadProtection,
+        ProtectionModule::Additional,
+        ProtectionModule::IpHeaders,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProtectionModule::JavaScriptPolicy => "javascript-policy",
+            ProtectionModule::ContentFilter => "content-filter",
+            ProtectionModule::Identity => "identity",
+            ProtectionModule::Fonts => "fonts",
+            ProtectionModule::Referrer => "referrer",
+            ProtectionModule::FingerprintDetector => "fingerprint-detector",
+            ProtectionModule::AdvancedFingerprint => "advanced-fingerprint",
+            ProtectionModule::NetworkSecurity => "network-security",
+            ProtectionModule::CompleteFakeData => "complete-fake-data",
+            ProtectionModule::Ultimate => "ultimate",
+            ProtectionModule::UploadProtection => "upload-protection",
+            ProtectionModule::Additional => "additional",
+            ProtectionModule::IpHeaders => "ip-headers",
+        }
+    }
+
+    /// Modules whose script must run before this one. The detector has to
+    /// wrap the real APIs before anything replaces them, and later layers
+    /// override parts of the identity layer (timezone, canvas, fetch).
+    pub fn depends_on(&self) -> &'static [ProtectionModule] {
+        match self {
+            ProtectionModule::JavaScriptPolicy
+            | ProtectionModule::ContentFilter
+            | ProtectionModule::FingerprintDetector
+            | ProtectionModule::Identity => &[],
+            ProtectionModule::Fonts | ProtectionModule::Referrer | ProtectionModule::NetworkSecurity => &[ProtectionModule::FingerprintDetector],
+            ProtectionModule::AdvancedFingerprint | ProtectionModule::CompleteFakeData => {
+                &[ProtectionModule::FingerprintDetector, ProtectionModule::Identity]
+            }
+            ProtectionModule::Ultimate => &[ProtectionModule::Identity, ProtectionModule::CompleteFakeData],
+            ProtectionModule::UploadProtection => &[ProtectionModule::NetworkSecurity],
+            ProtectionModule::Additional => &[ProtectionModule::CompleteFakeData],
+            // Wraps fetch/XHR after the upload layer so both apply
+            ProtectionModule::IpHeaders => &[ProtectionModule::UploadProtection],
+        }
+    }
+
+    /// Whether the browser settings turn this module on
+    pub fn enabled_by(&self, settings: &BrowserSettings) -> bool {
+        match self {
+            ProtectionModule::Identity
+            | ProtectionModule::Fonts
+            | ProtectionModule::AdvancedFingerprint
+            | ProtectionModule::Ultimate
+            | ProtectionModule::Additional => settings.spoof_fingerprint,
+            ProtectionModule::Referrer => settings.strip_referrer,
+            ProtectionModule::UploadProtection => settings.strip_metadata,
+            // Detection only reports, the script policy is empty unless a
+            // site profile asks for it, the content filter follows the
+            // blocking categories, the IP headers follow the page's site
+            // policy, and the remaining layers carry the baseline
+            // protections every page gets
+            ProtectionModule::JavaScriptPolicy
+            | ProtectionModule::IpHeaders
+            | ProtectionModule::ContentFilter
+            | ProtectionModule::FingerprintDetector
+            | ProtectionModule::NetworkSecurity
+            | ProtectionModule::CompleteFakeData => true,
+        }
+    }
+}
+
+/// Collects module scripts and joins the enabled ones in dependency order
+#[derive(Debug, Default)]
+pub struct InjectionScriptBuilder {
+    modules: Vec<(ProtectionModule, String)>,
+    disabled: HashSet<ProtectionModule>,
+}
+
+impl InjectionScriptBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add (or replace) the script for `module`
+    pub fn module(mut self, module: ProtectionModule, script: impl Into<String>) -> Self {
+        self.modules.retain(|(m, _)| *m != module);
+        self.modules.push((module, script.into()));
+        self
+    }
+
+    /// Leave `module` out of the output
+    pub fn disable(mut self, module: ProtectionModule) -> Self {
+        self.disabled.insert(module);
+        self
+    }
+
+    /// Leave out every module the settings turn off
+    pub fn with_settings(mut self, settings: &BrowserSettings) -> Self {
+        for module in ProtectionModule::ALL {
+            if !module.enabled_by(settings) {
+                self.disabled.insert(module);
+            }
+        }
+        self
+    }
+
+    /// Enabled modules in injection order. Dependencies come first; a
+    /// dependency that is disabled or absent is simply skipped.
+    pub fn ordered_modules(&self) -> Vec<ProtectionModule> {
+        let present: Vec<ProtectionModule> = ProtectionModule::ALL
+            .into_iter()
+            .filter(|m| !self.disabled.contains(m) && self.modules.iter().any(|(n, _)| n == m))
+            .collect();
+
+        let mut ordered = Vec::with_capacity(present.len());
+        let mut placed = HashSet::new();
+        while ordered.len() < present.len() {
+            let ready = present.iter().copied().find(|m| {
+                !placed.contains(m)
+                    && m.depends_on().iter().all(|d| placed.contains(d) || !present.contains(d))
+            });
+            // The dependency table is static and acyclic (see tests), so a
+            // module is always ready until all are placed
+            let Some(module) = ready else { break };
+            placed.insert(module);
+            ordered.push(module);
+        }
+        ordered
+    }
+
+    /// The combined script. Each module is separated by a newline and a
+    /// semicolon so one module's last statement cannot run into the next.
+    pub fn build(&self) -> String {
+        self.ordered_modules()
+            .into_iter()
+            .filter_map(|module| {
+                let (_, script) = self.modules.iter().find(|(m, _)| *m == module)?;
+                if script.trim().is_empty() {
+                    return None;
+                }
+                Some(format!("// ServionX module: {}\n;{}\n", module.name(), script.trim()))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a script as an ES script, returning the parser error if any
+    fn parse_error(script: &str) -> Option<String> {
+        let mut interner = boa_interner::Interner::default();
+        boa_parser::Parser::new(boa_parser::Source::from_bytes(script))
+            .parse_script(&mut interner)
+            .err()
+            .map(|e| e.to_string())
+    }
+
+    fn assert_parses(name: &str, script: &str) {
+        if let Some(e) = parse_error(script) {
+            panic!("{} does not parse: {}", name, e);
+        }
+    }
+
+    #[test]
+    fn test_parse_harness_rejects_broken_scripts() {
+        assert!(parse_error("(function() { const a = 1; })();").is_none());
+        assert!(parse_error("(function() { const a = 'x'y'; })();").is_some());
+        assert!(parse_error("if (a) {{ b(); }").is_some());
+    }
+
+    #[test]
+    fn test_js_value_escapes() {
+        assert_eq!(js_value("It's \"Apple\" M2"), r#""It's \"Apple\" M2""#);
+        assert_eq!(js_value("a\u{2028}b</script>"), r#""a\u2028b<\/script>""#);
+    }
+
+    #[test]
+    fn test_minify_keeps_literals_and_tokens_apart() {
+        let script = "// header\nconst re = /[\"']?\\/\\/x/g; /* note */\nconst s = 'a // not a comment';\n\
+            let n = a / b / c;\nreturn   typeof x === \"y\"  ?  i++ + ++j : `t ${ {a: 1}.a } //`;\n";
+        let min = minify(script);
+        assert_eq!(
+            min,
+            "const re=/[\"']?\\/\\/x/g;\nconst s='a // not a comment';\nlet n=a/b/c;\nreturn typeof x===\"y\"?i++ + ++j:`t ${ {a: 1}.a } //`;"
+        );
+        assert!(minify("if (a) x = /=/.test(y)\n/* multi\nline */ z()").contains("/=/.test"));
+    }
+
+    #[test]
+    fn test_template_render() {
+        let script = ScriptTemplate::new("const r = @@renderer@@; if (@@on@@) { f({ n: @@n@@ }); }")
+            .bind("renderer", "ANGLE (NVIDIA, \"RTX\" 4090 Direct3D11)")
+            .bind("on", &true)
+            .bind("n", &3)
+            .render();
+        assert_eq!(script, r#"const r = "ANGLE (NVIDIA, \"RTX\" 4090 Direct3D11)"; if (true) { f({ n: 3 }); }"#);
+        assert_parses("template", &script);
+    }
+
+    #[test]
+    fn test_dependencies_are_acyclic_and_respected() {
+        let mut builder = InjectionScriptBuilder::new();
+        for module in ProtectionModule::ALL.iter().rev() {
+            builder = builder.module(*module, format!("void '{}';", module.name()));
+        }
+        let order = builder.ordered_modules();
+        assert_eq!(order.len(), ProtectionModule::ALL.len());
+        for (i, module) in order.iter().enumerate() {
+            for dep in module.depends_on() {
+                let at = order.iter().position(|m| m == dep).unwrap();
+                assert!(at < i, "{} runs before its dependency {}", module.name(), dep.name());
+            }
+        }
+        assert_parses("all modules", &builder.build());
+    }
+
+    #[test]
+    fn test_settings_disable_modules() {
+        let settings = BrowserSettings {
+            strip_referrer: false,
+            ..BrowserSettings::default()
+        };
+        let builder = ProtectionModule::ALL
+            .iter()
+            .fold(InjectionScriptBuilder::new(), |b, m| b.module(*m, "void 0;"))
+            .with_settings(&settings);
+        let order = builder.ordered_modules();
+        assert!(!order.contains(&ProtectionModule::Referrer));
+        assert!(order.contains(&ProtectionModule::Identity));
+    }
+
+    #[test]
+    fn test_site_ip_header_policy_overrides_global_off() {
+        use crate::privacy::{IpHeaderPolicy, OutgoingHeaders};
+        let identity = crate::privacy::test_engine().identity_from_seed(4);
+        let mut settings = BrowserSettings { send_ip_headers: false, ..BrowserSettings::default() };
+        settings.ip_header_sites.insert("shop.example".to_string(), IpHeaderPolicy::Send);
+        let script = |url: &str| {
+            InjectionScriptBuilder::new()
+                .module(ProtectionModule::IpHeaders, OutgoingHeaders::for_request(&identity, &settings, url).get_injection_script())
+                .with_settings(&settings)
+                .build()
+        };
+
+        assert!(script("https://www.shop.example/").contains(&identity.ip_headers.x_real_ip));
+        assert!(!script("https://news.example/").contains(&identity.ip_headers.x_real_ip));
+    }
+
+    /// Every page script the browser injects, built the way a tab builds them
+    fn page_scripts(identity: &crate::privacy::FakeIdentity, upload: &crate::metadata::FakeFileMetadata) -> InjectionScriptBuilder {
+        use crate::privacy::*;
+        let settings = BrowserSettings::default();
+        // Strict also covers the script policy; the referrer layer gets the
+        // policy whose script has the most branches
+        let layers = ProfilePreset::Strict.layers();
+        InjectionScriptBuilder::new()
+            .module(ProtectionModule::JavaScriptPolicy, layers.get_injection_script())
+            .module(ProtectionModule::Identity, generate_injection_script_with_layers(identity, &layers))
+            .module(ProtectionModule::Fonts, FontFingerprint::new().with_identity(identity).get_injection_script())
+            .module(ProtectionModule::Referrer, ReferrerControl::injection_script_for(ReferrerPolicy::SameOrigin))
+            .module(ProtectionModule::FingerprintDetector, FingerprintingDetector::get_injection_script())
+            .module(ProtectionModule::AdvancedFingerprint, AdvancedFingerprintProtection::new().with_identity(identity).get_injection_script())
+            .module(ProtectionModule::NetworkSecurity, crate::security::NetworkSecurity::new().get_injection_script())
+            .module(ProtectionModule::CompleteFakeData, CompleteFakeData::new().with_identity(identity).get_master_injection_script())
+            .module(ProtectionModule::Ultimate, UltimatePrivacyProtection::new().with_identity(identity).with_layers(&layers).get_ultimate_injection_script())
+            .module(ProtectionModule::UploadProtection, upload.get_upload_protection_script())
+            .module(ProtectionModule::Additional, AdditionalProtection::new().get_injection_script())
+            .module(ProtectionModule::IpHeaders, OutgoingHeaders::for_request(identity, &settings, "https://example.com/").get_injection_script())
+            .with_settings(&settings)
+    }
+
+    #[test]
+    fn test_every_module_parses() {
+        let engine = crate::privacy::test_engine();
+        let upload = crate::metadata::FakeFileMetadata::new();
+        for seed in 0..5 {
+            let builder = page_scripts(&engine.identity_from_seed(seed), &upload);
+            for (module, script) in &builder.modules {
+                assert_parses(module.name(), script);
+            }
+            let combined = builder.build();
+            assert_parses("combined page script", &combined);
+            let minified = minify(&combined);
+            assert_parses("minified page script", &minified);
+            assert!(minified.len() < combined.len() * 3 / 4, "{} -> {}", combined.len(), minified.len());
+        }
+
+        let blocklist = crate::privacy::BlocklistManager::new();
+        blocklist.add_url_pattern("https://tracker.example/fp.js");
+        blocklist.block_site_script("shop.example", "https://cdn.example/fp.js");
+        assert_parses("script blocking", &blocklist.get_script_blocking_script("https://shop.example/"));
+        blocklist.parse_easylist_rules(crate::privacy::BlockCategory::Ads, "shop.example##.sponsored");
+        let cosmetic = blocklist.get_cosmetic_script("https://shop.example/", crate::privacy::CategorySet::all());
+        assert_parses("cosmetic filtering", &cosmetic);
+
+        // Scripts injected outside the per-tab builder
+        assert_parses("ad blocking", &blocklist.get_injection_script());
+        assert_parses("certificate transparency", &crate::security::CertificateTransparency::new().get_injection_script());
+        assert_parses("vulnerability scanner", &crate::security::VulnerabilityScanner::get_injection_script());
+        assert_parses("video detection", &crate::downloads::VideoGrabber::get_detection_script());
+    }
+
+    #[test]
+    fn test_hostile_values_stay_inside_their_literals() {
+        let mut identity = crate::privacy::test_engine().identity_from_seed(3);
+        identity.fingerprint.webgl_renderer = "ANGLE (\"Evil\"), '); alert(1); //\n</script>\u{2028}".to_string();
+        identity.user_agent.platform = "Win32'}); throw 1; ({'".to_string();
+        let upload = crate::metadata::FakeFileMetadata {
+            camera_model: "EOS 5D Mark IV'; alert(1); '".to_string(),
+            ..crate::metadata::FakeFileMetadata::new()
+        };
+
+        let script = page_scripts(&identity, &upload).build();
+        assert_parses("hostile identity", &script);
+        assert!(script.contains(&js_value(&identity.fingerprint.webgl_renderer)));
+        assert!(!script.contains("</script>"));
+    }
+}
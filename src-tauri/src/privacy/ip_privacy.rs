@@ -20,44 +20,102 @@ pub struct FakeIpHeaders {
 struct IpRange {
     start: [u8; 4],
     end: [u8; 4],
-    region: &'static str,
+    country: &'static str,
+}
+
+/// Consumer ISP blocks per country, so a spoofed address geolocates to the
+/// same country as the identity. Covers every country in `coherence::REGIONS`.
+const IP_RANGES: &[IpRange] = &[
+    // North America (ARIN)
+    IpRange { start: [73, 0, 0, 0], end: [73, 255, 255, 255], country: "US" },
+    IpRange { start: [71, 0, 0, 0], end: [71, 255, 255, 255], country: "US" },
+    IpRange { start: [98, 0, 0, 0], end: [98, 127, 255, 255], country: "US" },
+    IpRange { start: [70, 24, 0, 0], end: [70, 31, 255, 255], country: "CA" },
+    IpRange { start: [99, 224, 0, 0], end: [99, 255, 255, 255], country: "CA" },
+    
+    // Europe (RIPE)
+    IpRange { start: [81, 96, 0, 0], end: [81, 111, 255, 255], country: "GB" },
+    IpRange { start: [86, 128, 0, 0], end: [86, 191, 255, 255], country: "GB" },
+    IpRange { start: [90, 0, 0, 0], end: [90, 127, 255, 255], country: "FR" },
+    IpRange { start: [82, 64, 0, 0], end: [82, 67, 255, 255], country: "FR" },
+    IpRange { start: [79, 192, 0, 0], end: [79, 255, 255, 255], country: "DE" },
+    IpRange { start: [91, 0, 0, 0], end: [91, 63, 255, 255], country: "DE" },
+    IpRange { start: [84, 104, 0, 0], end: [84, 107, 255, 255], country: "NL" },
+    IpRange { start: [62, 194, 0, 0], end: [62, 195, 255, 255], country: "NL" },
+    IpRange { start: [83, 32, 0, 0], end: [83, 63, 255, 255], country: "ES" },
+    IpRange { start: [88, 0, 0, 0], end: [88, 31, 255, 255], country: "ES" },
+    IpRange { start: [87, 0, 0, 0], end: [87, 31, 255, 255], country: "IT" },
+    IpRange { start: [151, 0, 0, 0], end: [151, 31, 255, 255], country: "IT" },
+    IpRange { start: [84, 112, 0, 0], end: [84, 119, 255, 255], country: "AT" },
+    IpRange { start: [77, 116, 0, 0], end: [77, 119, 255, 255], country: "AT" },
+    IpRange { start: [78, 64, 0, 0], end: [78, 79, 255, 255], country: "SE" },
+    IpRange { start: [83, 248, 0, 0], end: [83, 255, 255, 255], country: "SE" },
+    IpRange { start: [94, 200, 0, 0], end: [94, 207, 255, 255], country: "AE" },
+    IpRange { start: [86, 96, 0, 0], end: [86, 103, 255, 255], country: "AE" },
+    
+    // Asia-Pacific (APNIC)
+    IpRange { start: [126, 0, 0, 0], end: [126, 255, 255, 255], country: "JP" },
+    IpRange { start: [133, 0, 0, 0], end: [133, 255, 255, 255], country: "JP" },
+    IpRange { start: [116, 86, 0, 0], end: [116, 87, 255, 255], country: "SG" },
+    IpRange { start: [118, 189, 0, 0], end: [118, 189, 255, 255], country: "SG" },
+    IpRange { start: [121, 128, 0, 0], end: [121, 191, 255, 255], country: "KR" },
+    IpRange { start: [175, 192, 0, 0], end: [175, 255, 255, 255], country: "KR" },
+    IpRange { start: [219, 76, 0, 0], end: [219, 79, 255, 255], country: "HK" },
+    IpRange { start: [112, 118, 0, 0], end: [112, 119, 255, 255], country: "HK" },
+    IpRange { start: [117, 192, 0, 0], end: [117, 255, 255, 255], country: "IN" },
+    IpRange { start: [59, 88, 0, 0], end: [59, 95, 255, 255], country: "IN" },
+    IpRange { start: [171, 96, 0, 0], end: [171, 127, 255, 255], country: "TH" },
+    IpRange { start: [101, 160, 0, 0], end: [101, 191, 255, 255], country: "AU" },
+    IpRange { start: [120, 144, 0, 0], end: [120, 159, 255, 255], country: "AU" },
+    IpRange { start: [125, 236, 0, 0], end: [125, 239, 255, 255], country: "NZ" },
+    IpRange { start: [222, 152, 0, 0], end: [222, 159, 255, 255], country: "NZ" },
+    
+    // Latin America (LACNIC)
+    IpRange { start: [177, 0, 0, 0], end: [177, 127, 255, 255], country: "BR" },
+    IpRange { start: [189, 0, 0, 0], end: [189, 31, 255, 255], country: "BR" },
+    IpRange { start: [181, 0, 0, 0], end: [181, 15, 255, 255], country: "AR" },
+    IpRange { start: [190, 16, 0, 0], end: [190, 31, 255, 255], country: "AR" },
+];
+
+impl IpRange {
+    /// Network region of this range's country, as used by `RegionProfile`
+    fn region(&self) -> Option<&'static str> {
+        super::coherence::region_for(self.country).map(|r| r.ip_region)
+    }
+    
+    /// A host address in this range, avoiding network and broadcast octets
+    fn sample<R: Rng>(&self, rng: &mut R) -> String {
+        let octets: Vec<u8> = (0..4)
+            .map(|i| match i {
+                3 => rng.gen_range(self.start[3].max(1)..=self.end[3].min(254)),
+                _ => rng.gen_range(self.start[i]..=self.end[i]),
+            })
+            .collect();
+        
+        format!("{}.{}.{}.{}", octets[0], octets[1], octets[2], octets[3])
+    }
 }
 
 #[derive(Debug)]
 pub struct IpPrivacy {
-    ip_ranges: Vec<IpRange>,
+    ip_ranges: &'static [IpRange],
 }
 
 impl IpPrivacy {
     pub fn new() -> Self {
         Self {
-            ip_ranges: vec![
-                // US IP ranges
-                IpRange { start: [45, 0, 0, 0], end: [45, 255, 255, 255], region: "US" },
-                IpRange { start: [104, 0, 0, 0], end: [104, 255, 255, 255], region: "US" },
-                IpRange { start: [172, 64, 0, 0], end: [172, 71, 255, 255], region: "US" },
-                IpRange { start: [98, 0, 0, 0], end: [98, 127, 255, 255], region: "US" },
-                
-                // EU IP ranges
-                IpRange { start: [185, 0, 0, 0], end: [185, 255, 255, 255], region: "EU" },
-                IpRange { start: [195, 0, 0, 0], end: [195, 255, 255, 255], region: "EU" },
-                IpRange { start: [212, 0, 0, 0], end: [212, 127, 255, 255], region: "EU" },
-                
-                // Asia IP ranges
-                IpRange { start: [203, 0, 0, 0], end: [203, 255, 255, 255], region: "ASIA" },
-                IpRange { start: [110, 0, 0, 0], end: [110, 255, 255, 255], region: "ASIA" },
-                IpRange { start: [175, 0, 0, 0], end: [175, 255, 255, 255], region: "ASIA" },
-                
-                // Australia IP ranges
-                IpRange { start: [101, 0, 0, 0], end: [101, 127, 255, 255], region: "AU" },
-                IpRange { start: [202, 0, 0, 0], end: [202, 127, 255, 255], region: "AU" },
-            ],
+            ip_ranges: IP_RANGES,
         }
     }
     
     /// Generate fake IP headers
     pub fn generate_headers(&self) -> FakeIpHeaders {
-        let fake_ip = self.generate_random_ip();
+        self.generate_headers_with_rng(&mut rand::thread_rng())
+    }
+    
+    /// Generate fake IP headers drawing all randomness from `rng`
+    pub fn generate_headers_with_rng<R: Rng>(&self, rng: &mut R) -> FakeIpHeaders {
+        let fake_ip = self.generate_random_ip(rng);
         
         FakeIpHeaders {
             x_forwarded_for: fake_ip.clone(),
@@ -69,43 +127,93 @@ impl IpPrivacy {
     }
     
     /// Generate a random public IP address
-    fn generate_random_ip(&self) -> String {
-        let mut rng = rand::thread_rng();
-        
+    fn generate_random_ip<R: Rng>(&self, rng: &mut R) -> String {
         // Select a random IP range
         let range = &self.ip_ranges[rng.gen_range(0..self.ip_ranges.len())];
-        
-        // Generate IP within range
-        let octets: Vec<u8> = (0..4)
-            .map(|i| rng.gen_range(range.start[i]..=range.end[i]))
-            .collect();
-        
-        format!("{}.{}.{}.{}", octets[0], octets[1], octets[2], octets[3])
+        range.sample(rng)
+    }
+    
+    fn pick_range<'a, R: Rng>(matching: &[&'a IpRange], rng: &mut R) -> Option<&'a IpRange> {
+        if matching.is_empty() {
+            None
+        } else {
+            Some(matching[rng.gen_range(0..matching.len())])
+        }
     }
     
     /// Generate fake IP for a specific region
     pub fn generate_ip_for_region(&self, region: &str) -> Option<String> {
-        let mut rng = rand::thread_rng();
-        
+        self.generate_ip_for_region_with_rng(region, &mut rand::thread_rng())
+    }
+    
+    /// Generate fake IP for a specific region using `rng`
+    pub fn generate_ip_for_region_with_rng<R: Rng>(&self, region: &str, rng: &mut R) -> Option<String> {
         let matching: Vec<_> = self.ip_ranges
             .iter()
-            .filter(|r| r.region.eq_ignore_ascii_case(region))
+            .filter(|r| r.region().is_some_and(|reg| reg.eq_ignore_ascii_case(region)))
             .collect();
         
-        if matching.is_empty() {
-            return None;
-        }
-        
-        let range = matching[rng.gen_range(0..matching.len())];
-        
-        let octets: Vec<u8> = (0..4)
-            .map(|i| rng.gen_range(range.start[i]..=range.end[i]))
+        Self::pick_range(&matching, rng).map(|range| range.sample(rng))
+    }
+    
+    /// Generate fake IP allocated to a specific country using `rng`
+    pub fn generate_ip_for_country_with_rng<R: Rng>(&self, country_code: &str, rng: &mut R) -> Option<String> {
+        let matching: Vec<_> = self.ip_ranges
+            .iter()
+            .filter(|r| r.country.eq_ignore_ascii_case(country_code))
             .collect();
         
-        Some(format!("{}.{}.{}.{}", octets[0], octets[1], octets[2], octets[3]))
+        Self::pick_range(&matching, rng).map(|range| range.sample(rng))
+    }
+    
+    /// Generate fake IP headers with an address from `region`, falling back
+    /// to any region if none of our ranges cover it
+    pub fn generate_headers_for_region_with_rng<R: Rng>(&self, region: &str, rng: &mut R) -> FakeIpHeaders {
+        let fake_ip = match self.generate_ip_for_region_with_rng(region, rng) {
+            Some(ip) => ip,
+            None => self.generate_random_ip(rng),
+        };
+        Self::headers_for_ip(fake_ip)
+    }
+    
+    /// Generate fake IP headers with an address allocated to `country_code`,
+    /// falling back to its region and then to any range
+    pub fn generate_headers_for_country_with_rng<R: Rng>(&self, country_code: &str, rng: &mut R) -> FakeIpHeaders {
+        let region = super::coherence::region_for(country_code).map(|r| r.ip_region);
+        let fake_ip = self.generate_ip_for_country_with_rng(country_code, rng)
+            .or_else(|| region.and_then(|region| self.generate_ip_for_region_with_rng(region, rng)))
+            .unwrap_or_else(|| self.generate_random_ip(rng));
+        Self::headers_for_ip(fake_ip)
+    }
+    
+    fn headers_for_ip(fake_ip: String) -> FakeIpHeaders {
+        FakeIpHeaders {
+            x_forwarded_for: fake_ip.clone(),
+            x_real_ip: fake_ip.clone(),
+            client_ip: fake_ip.clone(),
+            cf_connecting_ip: fake_ip.clone(),
+            true_client_ip: fake_ip,
+        }
     }
 }
 
+fn range_of(ip: &str) -> Option<&'static IpRange> {
+    let octets = ip.parse::<std::net::Ipv4Addr>().ok()?.octets();
+    IP_RANGES
+        .iter()
+        .find(|r| (0..4).all(|i| r.start[i] <= octets[i] && octets[i] <= r.end[i]))
+}
+
+/// Find which country's range an IPv4 address was drawn from
+pub fn country_for_ip(ip: &str) -> Option<&'static str> {
+    range_of(ip).map(|r| r.country)
+}
+
+/// Find which region's range an IPv4 address was drawn from
+pub fn region_for_ip(ip: &str) -> Option<&'static str> {
+    range_of(ip).and_then(IpRange::region)
+}
+
 impl Default for IpPrivacy {
     fn default() -> Self {
         Self::new()
@@ -136,5 +244,20 @@ mod tests {
         let ip = ip_privacy.generate_ip_for_region("US").unwrap();
         
         assert!(ip.parse::<Ipv4Addr>().is_ok());
+        assert_eq!(region_for_ip(&ip), Some("US"));
+    }
+    
+    #[test]
+    fn test_every_region_country_has_ranges() {
+        use rand::SeedableRng;
+        let ip_privacy = IpPrivacy::new();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(8);
+        for region in super::super::coherence::REGIONS {
+            for _ in 0..20 {
+                let ip = ip_privacy.generate_ip_for_country_with_rng(region.country_code, &mut rng).unwrap();
+                assert_eq!(country_for_ip(&ip), Some(region.country_code), "{}", ip);
+                assert!(!ip.ends_with(".0") && !ip.ends_with(".255"));
+            }
+        }
     }
 }
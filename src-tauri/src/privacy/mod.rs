@@ -2,60 +2,129 @@
 // Core privacy functionality for ServionX Browser
 
 pub mod fingerprint;
+pub mod fingerprint_dataset;
 pub mod geolocation;
 pub mod user_agent;
+pub mod browser_versions;
 pub mod ip_privacy;
+pub mod request_headers;
 pub mod whitelist;
-pub mod tracker_blocker;
 pub mod https_enforcer;
 pub mod font_fingerprint;
 pub mod referrer_control;
-pub mod malware_blocker;
 pub mod fingerprint_detector;
+pub mod escalation;
 pub mod storage_partitioner;
+pub mod cookie_manager;
 pub mod blocklist_manager;
+pub mod builtin_lists;
+pub mod content_filter;
+pub mod blocklist_updater;
+pub mod blocklist_snapshot;
+pub mod filter_rules;
+pub mod url_matcher;
 pub mod advanced_fingerprint;
 pub mod complete_fake_data;
+pub mod injection;
+pub mod script_cache;
 pub mod ultimate_protection;
 pub mod additional_protection;
-pub mod expanded_blocklist;
+pub mod coherence;
+pub mod site;
+pub mod site_identity;
+pub mod site_noise;
+pub mod site_profiles;
+pub mod rotation;
+pub mod self_test;
 pub mod commands;
 
 use std::sync::{Arc, RwLock};
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
 pub use fingerprint::FingerprintGenerator;
-pub use geolocation::GeolocationFaker;
+pub use fingerprint_dataset::FingerprintDataset;
+pub use geolocation::{GeolocationFaker, LocationMode};
 pub use user_agent::UserAgentGenerator;
 pub use ip_privacy::IpPrivacy;
-pub use whitelist::WhitelistManager;
-pub use tracker_blocker::TrackerBlocker;
+pub use request_headers::{IpHeaderPolicy, OutgoingHeaders};
+pub use whitelist::{WhitelistEntry, WhitelistFormat, WhitelistManager};
 pub use https_enforcer::{HttpsEnforcer, HttpsMode};
 pub use font_fingerprint::FontFingerprint;
 pub use referrer_control::{ReferrerControl, ReferrerPolicy};
-pub use malware_blocker::{MalwareBlocker, MalwareCheckResult};
-pub use fingerprint_detector::FingerprintingDetector;
-pub use storage_partitioner::StoragePartitioner;
-pub use blocklist_manager::BlocklistManager;
+pub use fingerprint_detector::{FingerprintingDetector, FingerprintingReport, FingerprintingVerdict, SiteFingerprinting};
+pub use escalation::{EscalationDecision, EscalationManager};
+pub use storage_partitioner::{PartitionUsage, StoragePartition, StoragePartitioner};
+pub use cookie_manager::{CookieManager, CookieRule};
+pub use blocklist_manager::{BlocklistManager, FeedFormat, RuleList, RuleSet};
+pub use content_filter::{BlockCategory, BlockVerdict, CategorySet, RuleMatch};
+pub use blocklist_updater::{BlocklistFeed, BlocklistUpdater, FeedStatus};
+pub use filter_rules::{FilterList, FilterRequest, FilterVerdict, ResourceType};
+pub use url_matcher::{DomainSet, PatternSet};
 pub use advanced_fingerprint::AdvancedFingerprintProtection;
 pub use complete_fake_data::CompleteFakeData;
+pub use injection::{InjectionScriptBuilder, ProtectionModule, ScriptTemplate};
+pub use script_cache::{InjectionScriptCache, CachedScript};
+pub use site_noise::SiteNoise;
+pub use site_profiles::{ProfilePreset, ProtectionLayers, SiteProfile, SiteProfileStore};
 pub use ultimate_protection::UltimatePrivacyProtection;
+pub use self_test::{SelfTestReport, SelfTestRunner};
 pub use additional_protection::AdditionalProtection;
+pub use coherence::{Persona, CoherenceViolation};
+pub use site_identity::{SiteIdentity, SiteIdentityStore};
+pub use rotation::{IdentityRotator, RotationTrigger};
+
+/// Largest seed picked for random identities. Kept below 2^53 so the seed
+/// survives a round-trip through a JavaScript number in the frontend.
+pub const MAX_IDENTITY_SEED: u64 = (1 << 53) - 1;
 
 /// Represents a complete fake browser identity
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FakeIdentity {
     pub id: String,
+    /// Seed the identity was generated from; feeding it and `created_at`
+    /// back into `PrivacyEngine::identity_from_seed_at` reproduces the
+    /// same identity
+    #[serde(default)]
+    pub seed: u64,
     pub created_at: DateTime<Utc>,
     pub fingerprint: fingerprint::FakeFingerprint,
     pub geolocation: geolocation::FakeGeolocation,
     pub user_agent: user_agent::FakeUserAgent,
     pub ip_headers: ip_privacy::FakeIpHeaders,
+    /// User-Agent Client Hints; `None` for browsers that do not send them
+    pub client_hints: Option<ultimate_protection::ClientHintsData>,
     pub timezone: String,
     pub language: String,
     pub do_not_track: bool,
+    /// Key the per-site canvas and audio noise seeds are derived from; it
+    /// never reaches a page. Empty for identities stored before it existed.
+    #[serde(default)]
+    pub noise_key: String,
+}
+
+impl FakeIdentity {
+    /// This identity as the top-level page at `url` sees it: canvas and
+    /// audio noise seeded for the page's site. Pages without a site keep
+    /// the identity's own seeds.
+    pub fn for_site(&self, url: &str) -> FakeIdentity {
+        let Some(site) = site::registrable_domain(url) else {
+            return self.clone();
+        };
+        let key = if self.noise_key.is_empty() {
+            format!("{}:{}", self.id, self.seed)
+        } else {
+            self.noise_key.clone()
+        };
+        let noise = SiteNoise::derive(key.as_bytes(), &site);
+
+        let mut identity = self.clone();
+        identity.fingerprint.canvas_noise_seed = noise.canvas_seed;
+        identity.fingerprint.audio_noise_seed = noise.audio_seed;
+        identity
+    }
 }
 
 /// The main privacy engine that coordinates all spoofing operations
@@ -67,14 +136,27 @@ pub struct PrivacyEngine {
     user_agent_gen: UserAgentGenerator,
     ip_privacy: IpPrivacy,
     whitelist: Arc<RwLock<WhitelistManager>>,
+    /// Where whitelist changes are saved, once `load_whitelist` attached a file
+    whitelist_path: RwLock<Option<std::path::PathBuf>>,
+    location_mode: RwLock<LocationMode>,
 }
 
 impl PrivacyEngine {
     /// Create a new privacy engine with a fresh fake identity
     pub fn new() -> Self {
-        let fingerprint_gen = FingerprintGenerator::new();
+        Self::with_dataset(FingerprintDataset::load())
+    }
+    
+    /// Create a privacy engine taking the dataset override from `data_dir`
+    /// instead of the app data directory
+    pub fn open(data_dir: &std::path::Path) -> Self {
+        Self::with_dataset(FingerprintDataset::load_from(data_dir))
+    }
+    
+    fn with_dataset(dataset: Arc<FingerprintDataset>) -> Self {
+        let fingerprint_gen = FingerprintGenerator::with_dataset(dataset.clone());
         let geolocation_faker = GeolocationFaker::new();
-        let user_agent_gen = UserAgentGenerator::new();
+        let user_agent_gen = UserAgentGenerator::with_dataset(dataset);
         let ip_privacy = IpPrivacy::new();
         let whitelist = WhitelistManager::new();
         
@@ -83,6 +165,9 @@ impl PrivacyEngine {
             &geolocation_faker,
             &user_agent_gen,
             &ip_privacy,
+            &LocationMode::Random,
+            Self::random_seed(),
+            Utc::now(),
         );
         
         Self {
@@ -92,6 +177,8 @@ impl PrivacyEngine {
             user_agent_gen,
             ip_privacy,
             whitelist: Arc::new(RwLock::new(whitelist)),
+            whitelist_path: RwLock::new(None),
+            location_mode: RwLock::new(LocationMode::Random),
         }
     }
     
@@ -100,33 +187,52 @@ impl PrivacyEngine {
         geolocation_faker: &GeolocationFaker,
         user_agent_gen: &UserAgentGenerator,
         ip_privacy: &IpPrivacy,
+        location_mode: &LocationMode,
+        seed: u64,
+        created_at: DateTime<Utc>,
     ) -> FakeIdentity {
-        let timezones = [
-            "America/New_York", "America/Los_Angeles", "America/Chicago",
-            "Europe/London", "Europe/Paris", "Europe/Berlin",
-            "Asia/Tokyo", "Asia/Singapore", "Asia/Dubai",
-            "Australia/Sydney", "Pacific/Auckland",
-        ];
-        
-        let languages = [
-            "en-US", "en-GB", "de-DE", "fr-FR", "es-ES",
-            "ja-JP", "zh-CN", "ko-KR", "pt-BR", "ru-RU",
-        ];
+        // Every random choice below is drawn from this one RNG, in a fixed
+        // order, so the same seed and creation time always yield the same
+        // identity (for the same fingerprint dataset revision). Browser
+        // versions are those current on the creation date.
+        let mut rng = StdRng::seed_from_u64(seed);
+        let id = uuid::Builder::from_random_bytes(rng.gen()).into_uuid().to_string();
         
-        use rand::seq::SliceRandom;
-        let mut rng = rand::thread_rng();
+        // Location fixes country, timezone and language; a device profile
+        // sampled as a whole from the dataset fixes OS, browser and hardware
+        let geolocation = geolocation_faker.generate_for_mode_with_rng(location_mode, &mut rng);
+        let device = fingerprint_gen.dataset().sample_device(&mut rng);
+        let persona = Persona::for_device(&geolocation, device.os_family(), device.browser_family(), &mut rng);
+        let user_agent = user_agent_gen.generate_for_with_rng(persona.os, persona.browser, created_at.date_naive(), &mut rng);
+        let fingerprint = fingerprint_gen.generate_for_device_with_rng(device, &mut rng);
+        let ip_headers = ip_privacy.generate_headers_for_country_with_rng(&geolocation.country_code, &mut rng);
+        let client_hints = coherence::client_hints_for(&user_agent, &fingerprint.webgl_renderer);
+        // Drawn last so the attributes above stay what older versions
+        // derived from the same seed
+        let noise_key: String = rng.gen::<[u8; 32]>().iter().map(|b| format!("{:02x}", b)).collect();
         
-        FakeIdentity {
-            id: Uuid::new_v4().to_string(),
-            created_at: Utc::now(),
-            fingerprint: fingerprint_gen.generate(),
-            geolocation: geolocation_faker.generate(),
-            user_agent: user_agent_gen.generate(),
-            ip_headers: ip_privacy.generate_headers(),
-            timezone: timezones.choose(&mut rng).unwrap().to_string(),
-            language: languages.choose(&mut rng).unwrap().to_string(),
+        let identity = FakeIdentity {
+            id,
+            seed,
+            created_at,
+            fingerprint,
+            geolocation,
+            user_agent,
+            ip_headers,
+            client_hints,
+            timezone: persona.timezone,
+            language: persona.locale,
             do_not_track: true,
+            noise_key,
+        };
+        
+        if let Err(violations) = coherence::validate(&identity) {
+            for violation in &violations {
+                log::warn!("Incoherent identity {}: {} - {}", identity.id, violation.field, violation.message);
+            }
         }
+        
+        identity
     }
     
     /// Get the current fake identity
@@ -134,35 +240,111 @@ impl PrivacyEngine {
         self.current_identity.read().unwrap().clone()
     }
     
-    /// Regenerate a new fake identity
-    pub fn regenerate_identity(&self) -> FakeIdentity {
-        let new_identity = Self::generate_identity_internal(
+    /// Pick a fresh random identity seed
+    pub fn random_seed() -> u64 {
+        rand::thread_rng().gen_range(0..=MAX_IDENTITY_SEED)
+    }
+    
+    /// Build the identity for a given seed, created now, without making it
+    /// current
+    pub fn identity_from_seed(&self, seed: u64) -> FakeIdentity {
+        self.identity_from_seed_at(seed, Utc::now())
+    }
+    
+    /// Build the identity a seed gave when created at `created_at`
+    pub fn identity_from_seed_at(&self, seed: u64, created_at: DateTime<Utc>) -> FakeIdentity {
+        Self::generate_identity_internal(
             &self.fingerprint_gen,
             &self.geolocation_faker,
             &self.user_agent_gen,
             &self.ip_privacy,
-        );
+            &self.location_mode.read().unwrap(),
+            seed,
+            created_at,
+        )
+    }
+    
+    /// Regenerate a new fake identity
+    pub fn regenerate_identity(&self) -> FakeIdentity {
+        self.regenerate_identity_from_seed(Self::random_seed(), Utc::now())
+    }
+    
+    /// Replace the current identity with the one derived from `seed` and
+    /// its creation time
+    pub fn regenerate_identity_from_seed(&self, seed: u64, created_at: DateTime<Utc>) -> FakeIdentity {
+        let new_identity = self.identity_from_seed_at(seed, created_at);
         
         let mut identity = self.current_identity.write().unwrap();
         *identity = new_identity.clone();
         
-        log::info!("Generated new identity: {}", new_identity.id);
+        log::info!("Generated new identity: {} (seed {})", new_identity.id, seed);
         new_identity
     }
     
+    /// The fingerprint dataset identities are sampled from
+    pub fn dataset(&self) -> Arc<FingerprintDataset> {
+        self.fingerprint_gen.dataset().clone()
+    }
+    
+    /// Which fingerprint dataset identities are sampled from
+    pub fn dataset_info(&self) -> fingerprint_dataset::DatasetInfo {
+        self.fingerprint_gen.dataset().info()
+    }
+    
+    /// How new identities choose their location
+    pub fn location_mode(&self) -> LocationMode {
+        self.location_mode.read().unwrap().clone()
+    }
+    
+    /// Change how new identities choose their location. Takes effect on the
+    /// next regeneration; rejects places the faker does not know.
+    pub fn set_location_mode(&self, mode: LocationMode) -> Result<(), String> {
+        self.geolocation_faker.validate_mode(&mode)?;
+        *self.location_mode.write().unwrap() = mode;
+        Ok(())
+    }
+    
+    /// Cities a location mode can name
+    pub fn list_locations(&self) -> Vec<geolocation::LocationInfo> {
+        self.geolocation_faker.list_locations()
+    }
+    
+    /// Check the current identity for attributes that contradict each other
+    pub fn validate_identity(&self) -> Result<(), Vec<CoherenceViolation>> {
+        coherence::validate(&self.get_identity())
+    }
+    
+    /// Load the whitelist from `path` and save every later change there
+    pub fn load_whitelist(&self, path: &std::path::Path) -> Result<usize, String> {
+        let whitelist = WhitelistManager::load(path)?;
+        let count = whitelist.entries().len();
+        *self.whitelist.write().unwrap() = whitelist;
+        *self.whitelist_path.write().unwrap() = Some(path.to_path_buf());
+        Ok(count)
+    }
+    
+    fn save_whitelist(&self) -> Result<(), String> {
+        match &*self.whitelist_path.read().unwrap() {
+            Some(path) => self.whitelist.read().unwrap().save(path),
+            None => Ok(()),
+        }
+    }
+    
     /// Check if a domain is whitelisted (should receive real data)
     pub fn is_whitelisted(&self, domain: &str) -> bool {
         self.whitelist.read().unwrap().is_whitelisted(domain)
     }
     
-    /// Add a domain to the whitelist
-    pub fn add_to_whitelist(&self, domain: &str) {
-        self.whitelist.write().unwrap().add(domain);
+    /// Add a domain or pattern to the whitelist, optionally only until `expires_at`
+    pub fn add_to_whitelist(&self, domain: &str, expires_at: Option<DateTime<Utc>>) -> Result<(), String> {
+        self.whitelist.write().unwrap().add_until(domain, expires_at)?;
+        self.save_whitelist()
     }
     
     /// Remove a domain from the whitelist
-    pub fn remove_from_whitelist(&self, domain: &str) {
+    pub fn remove_from_whitelist(&self, domain: &str) -> Result<(), String> {
         self.whitelist.write().unwrap().remove(domain);
+        self.save_whitelist()
     }
     
     /// Get all whitelisted domains
@@ -170,6 +352,23 @@ impl PrivacyEngine {
         self.whitelist.read().unwrap().get_all()
     }
     
+    /// Whitelist entries with their expiry
+    pub fn get_whitelist_entries(&self) -> Vec<WhitelistEntry> {
+        self.whitelist.read().unwrap().entries()
+    }
+    
+    /// Export the whitelist for sharing
+    pub fn export_whitelist(&self, format: WhitelistFormat) -> Result<String, String> {
+        self.whitelist.read().unwrap().export(format)
+    }
+    
+    /// Import a shared whitelist, merging it or replacing the current one
+    pub fn import_whitelist(&self, content: &str, replace: bool) -> Result<usize, String> {
+        let count = self.whitelist.write().unwrap().import(content, replace)?;
+        self.save_whitelist()?;
+        Ok(count)
+    }
+    
     /// Get JavaScript injection scripts for privacy protection
     pub fn get_injection_scripts(&self) -> String {
         let identity = self.get_identity();
@@ -189,161 +388,242 @@ impl Default for PrivacyEngine {
 }
 
 /// Generate JavaScript code to inject into pages for privacy protection
-fn generate_injection_script(identity: &FakeIdentity) -> String {
+pub fn generate_injection_script(identity: &FakeIdentity) -> String {
+    generate_injection_script_with_layers(identity, &ProtectionLayers::default())
+}
+
+/// Injection script for `identity` with the user agent, canvas and WebGL
+/// overrides limited to what a site's `layers` allow
+pub fn generate_injection_script_with_layers(identity: &FakeIdentity, layers: &ProtectionLayers) -> String {
     let fp = &identity.fingerprint;
     let geo = &identity.geolocation;
     let ua = &identity.user_agent;
     
-    format!(r#"
-(function() {{
+    let script = ScriptTemplate::new(r#"
+(function() {
     'use strict';
     
     // =====================================
     // ServionX Privacy Protection Layer
     // =====================================
     
-    const SERVIONX_IDENTITY = {{
-        fingerprint: {fingerprint_json},
-        geolocation: {geolocation_json},
-        userAgent: {user_agent_json},
-        timezone: "{timezone}",
-        language: "{language}",
-        doNotTrack: {dnt}
-    }};
+    const SERVIONX_IDENTITY = {
+        fingerprint: @@fingerprint@@,
+        geolocation: @@geolocation@@,
+        userAgent: @@user_agent@@,
+        timezone: @@timezone@@,
+        language: @@language@@,
+        doNotTrack: @@dnt@@
+    };
+    const layers = @@layers@@;
     
     // =====================================
     // Navigator Overrides
     // =====================================
     
     // User Agent
-    Object.defineProperty(navigator, 'userAgent', {{
-        get: () => SERVIONX_IDENTITY.userAgent.full,
-        configurable: false
-    }});
-    
-    Object.defineProperty(navigator, 'appVersion', {{
-        get: () => SERVIONX_IDENTITY.userAgent.appVersion,
-        configurable: false
-    }});
-    
-    Object.defineProperty(navigator, 'platform', {{
-        get: () => SERVIONX_IDENTITY.userAgent.platform,
-        configurable: false
-    }});
-    
-    Object.defineProperty(navigator, 'vendor', {{
-        get: () => SERVIONX_IDENTITY.userAgent.vendor,
-        configurable: false
-    }});
+    if (layers.uaSpoof) {
+        Object.defineProperty(navigator, 'userAgent', {
+            get: () => SERVIONX_IDENTITY.userAgent.full,
+            configurable: false
+        });
+    
+        Object.defineProperty(navigator, 'appVersion', {
+            get: () => SERVIONX_IDENTITY.userAgent.appVersion,
+            configurable: false
+        });
+    
+        Object.defineProperty(navigator, 'platform', {
+            get: () => SERVIONX_IDENTITY.userAgent.platform,
+            configurable: false
+        });
+    
+        Object.defineProperty(navigator, 'vendor', {
+            get: () => SERVIONX_IDENTITY.userAgent.vendor,
+            configurable: false
+        });
+    }
     
     // Hardware
-    Object.defineProperty(navigator, 'hardwareConcurrency', {{
+    Object.defineProperty(navigator, 'hardwareConcurrency', {
         get: () => SERVIONX_IDENTITY.fingerprint.hardwareConcurrency,
         configurable: false
-    }});
+    });
     
-    Object.defineProperty(navigator, 'deviceMemory', {{
+    Object.defineProperty(navigator, 'deviceMemory', {
         get: () => SERVIONX_IDENTITY.fingerprint.deviceMemory,
         configurable: false
-    }});
+    });
     
     // Language
-    Object.defineProperty(navigator, 'language', {{
+    Object.defineProperty(navigator, 'language', {
         get: () => SERVIONX_IDENTITY.language,
         configurable: false
-    }});
+    });
     
-    Object.defineProperty(navigator, 'languages', {{
+    Object.defineProperty(navigator, 'languages', {
         get: () => [SERVIONX_IDENTITY.language, SERVIONX_IDENTITY.language.split('-')[0]],
         configurable: false
-    }});
+    });
     
     // Do Not Track
-    Object.defineProperty(navigator, 'doNotTrack', {{
+    Object.defineProperty(navigator, 'doNotTrack', {
         get: () => SERVIONX_IDENTITY.doNotTrack ? '1' : null,
         configurable: false
-    }});
+    });
     
     // =====================================
     // Geolocation Override
     // =====================================
     
-    const fakePosition = {{
-        coords: {{
-            latitude: SERVIONX_IDENTITY.geolocation.latitude,
-            longitude: SERVIONX_IDENTITY.geolocation.longitude,
-            accuracy: SERVIONX_IDENTITY.geolocation.accuracy,
+    // A route is replayed in real time from when the page loaded, moving
+    // between its points at the recorded pace
+    const fakeRoute = SERVIONX_IDENTITY.geolocation.route;
+    const routeStart = Date.now();
+    
+    function fakeCoords() {
+        const geo = SERVIONX_IDENTITY.geolocation;
+        const coords = {
+            latitude: geo.latitude,
+            longitude: geo.longitude,
+            accuracy: geo.accuracy,
             altitude: null,
             altitudeAccuracy: null,
             heading: null,
             speed: null
-        }},
-        timestamp: Date.now()
-    }};
+        };
+        if (!fakeRoute || fakeRoute.points.length < 2) return coords;
+        
+        const points = fakeRoute.points;
+        const duration = points[points.length - 1].elapsedSecs;
+        let t = (Date.now() - routeStart) / 1000;
+        t = fakeRoute.loopRoute && duration > 0 ? t % duration : Math.min(t, duration);
+        
+        let i = 1;
+        while (i < points.length - 1 && points[i].elapsedSecs < t) i++;
+        const from = points[i - 1], to = points[i];
+        const span = to.elapsedSecs - from.elapsedSecs;
+        const f = span > 0 ? Math.min(Math.max((t - from.elapsedSecs) / span, 0), 1) : 1;
+        coords.latitude = from.latitude + (to.latitude - from.latitude) * f;
+        coords.longitude = from.longitude + (to.longitude - from.longitude) * f;
+        
+        const rad = Math.PI / 180;
+        const dLat = (to.latitude - from.latitude) * rad;
+        const dLon = (to.longitude - from.longitude) * rad;
+        const a = Math.sin(dLat / 2) ** 2 +
+            Math.cos(from.latitude * rad) * Math.cos(to.latitude * rad) * Math.sin(dLon / 2) ** 2;
+        const meters = 2 * 6371000 * Math.asin(Math.sqrt(a));
+        const moving = span > 0 && meters > 0 && (f < 1 || fakeRoute.loopRoute);
+        coords.speed = moving ? meters / span : 0;
+        if (moving) {
+            const y = Math.sin(dLon) * Math.cos(to.latitude * rad);
+            const x = Math.cos(from.latitude * rad) * Math.sin(to.latitude * rad) -
+                Math.sin(from.latitude * rad) * Math.cos(to.latitude * rad) * Math.cos(dLon);
+            coords.heading = (Math.atan2(y, x) / rad + 360) % 360;
+        }
+        return coords;
+    }
+    
+    function fakePosition() {
+        return { coords: fakeCoords(), timestamp: Date.now() };
+    }
     
-    navigator.geolocation.getCurrentPosition = function(success, error, options) {{
-        setTimeout(() => success(fakePosition), 100);
-    }};
+    const fakeWatches = new Map();
+    let nextWatchId = 1;
+    
+    navigator.geolocation.getCurrentPosition = function(success, error, options) {
+        setTimeout(() => success(fakePosition()), 100);
+    };
+    
+    navigator.geolocation.watchPosition = function(success, error, options) {
+        const id = nextWatchId++;
+        setTimeout(() => success(fakePosition()), 100);
+        if (fakeRoute) {
+            fakeWatches.set(id, setInterval(() => success(fakePosition()), 1000));
+        }
+        return id;
+    };
     
-    navigator.geolocation.watchPosition = function(success, error, options) {{
-        setTimeout(() => success(fakePosition), 100);
-        return Math.floor(Math.random() * 1000);
-    }};
+    navigator.geolocation.clearWatch = function(id) {
+        clearInterval(fakeWatches.get(id));
+        fakeWatches.delete(id);
+    };
     
     // =====================================
     // Canvas Fingerprint Protection
     // =====================================
     
-    const originalToDataURL = HTMLCanvasElement.prototype.toDataURL;
-    const originalGetImageData = CanvasRenderingContext2D.prototype.getImageData;
-    
-    HTMLCanvasElement.prototype.toDataURL = function(...args) {{
-        const ctx = this.getContext('2d');
-        if (ctx) {{
-            // Add imperceptible noise
+    if (layers.canvasNoise) {
+        const originalToDataURL = HTMLCanvasElement.prototype.toDataURL;
+        const originalGetImageData = CanvasRenderingContext2D.prototype.getImageData;
+        // Seeded per site: the same drawing always reads back the same here
+        const noise = SERVIONX_IDENTITY.fingerprint.canvasNoiseSeed;
+        // -1, 0 or +1 for byte `index` of the image, hashed from the seed
+        // (xorshift and a multiply mix) so every bit of the seed counts
+        const noiseAt = (index) => {
+            let x = noise ^ Math.imul(index + 1, 0x9E3779B9);
+            x ^= x << 13;
+            x ^= x >>> 17;
+            x ^= x << 5;
+            x = Math.imul(x ^ (x >>> 16), 0x45D9F3B);
+            x ^= x >>> 16;
+            return (x >>> 0) % 3 - 1;
+        };
+    
+        HTMLCanvasElement.prototype.toDataURL = function(...args) {
+            const ctx = this.getContext('2d');
+            if (!ctx || this.width === 0 || this.height === 0) {
+                return originalToDataURL.apply(this, args);
+            }
+            // Add imperceptible noise to a copy, so reading the canvas again
+            // does not stack more noise on top
             const imageData = originalGetImageData.call(ctx, 0, 0, this.width, this.height);
-            const noise = SERVIONX_IDENTITY.fingerprint.canvasNoiseSeed;
-            for (let i = 0; i < imageData.data.length; i += 4) {{
-                imageData.data[i] = (imageData.data[i] + (noise % 3) - 1) & 0xFF;
-            }}
-            ctx.putImageData(imageData, 0, 0);
-        }}
-        return originalToDataURL.apply(this, args);
-    }};
+            for (let i = 0; i < imageData.data.length; i += 4) {
+                imageData.data[i] = (imageData.data[i] + noiseAt(i)) & 0xFF;
+            }
+            const copy = document.createElement('canvas');
+            copy.width = this.width;
+            copy.height = this.height;
+            copy.getContext('2d').putImageData(imageData, 0, 0);
+            return originalToDataURL.apply(copy, args);
+        };
+    }
     
     // =====================================
     // WebGL Fingerprint Protection
     // =====================================
     
-    const getParameterProxyHandler = {{
-        apply: function(target, thisArg, args) {{
-            const param = args[0];
-            const gl = thisArg;
+    if (layers.webglSpoof) {
+        const getParameterProxyHandler = {
+            apply: function(target, thisArg, args) {
+                const param = args[0];
+                const gl = thisArg;
             
-            // Override RENDERER
-            if (param === 37446) {{
-                return SERVIONX_IDENTITY.fingerprint.webglRenderer;
-            }}
-            // Override VENDOR
-            if (param === 37445) {{
-                return SERVIONX_IDENTITY.fingerprint.webglVendor;
-            }}
+                // Override RENDERER
+                if (param === 37446) {
+                    return SERVIONX_IDENTITY.fingerprint.webglRenderer;
+                }
+                // Override VENDOR
+                if (param === 37445) {
+                    return SERVIONX_IDENTITY.fingerprint.webglVendor;
+                }
             
-            return Reflect.apply(target, thisArg, args);
-        }}
-    }};
-    
-    WebGLRenderingContext.prototype.getParameter = new Proxy(
-        WebGLRenderingContext.prototype.getParameter,
-        getParameterProxyHandler
-    );
-    
-    if (typeof WebGL2RenderingContext !== 'undefined') {{
-        WebGL2RenderingContext.prototype.getParameter = new Proxy(
-            WebGL2RenderingContext.prototype.getParameter,
+                return Reflect.apply(target, thisArg, args);
+            }
+        };
+    
+        WebGLRenderingContext.prototype.getParameter = new Proxy(
+            WebGLRenderingContext.prototype.getParameter,
             getParameterProxyHandler
         );
-    }}
+    
+        if (typeof WebGL2RenderingContext !== 'undefined') {
+            WebGL2RenderingContext.prototype.getParameter = new Proxy(
+                WebGL2RenderingContext.prototype.getParameter,
+                getParameterProxyHandler
+            );
+        }
+    }
     
     // =====================================
     // Audio Fingerprint Protection
@@ -352,92 +632,69 @@ fn generate_injection_script(identity: &FakeIdentity) -> String {
     const originalCreateOscillator = AudioContext.prototype.createOscillator;
     const audioNoise = SERVIONX_IDENTITY.fingerprint.audioNoiseSeed;
     
-    AudioContext.prototype.createOscillator = function() {{
+    AudioContext.prototype.createOscillator = function() {
         const oscillator = originalCreateOscillator.call(this);
         const originalConnect = oscillator.connect.bind(oscillator);
         
-        oscillator.connect = function(destination) {{
-            if (destination instanceof AnalyserNode) {{
+        oscillator.connect = function(destination) {
+            if (destination instanceof AnalyserNode) {
                 // Add noise to audio fingerprinting attempts
                 const gain = this.context.createGain();
                 gain.gain.value = 1 + (audioNoise % 100) / 100000;
                 originalConnect(gain);
                 gain.connect(destination);
                 return gain;
-            }}
+            }
             return originalConnect(destination);
-        }};
+        };
         
         return oscillator;
-    }};
+    };
     
     // =====================================
     // WebRTC IP Leak Prevention
     // =====================================
     
-    if (typeof RTCPeerConnection !== 'undefined') {{
+    if (typeof RTCPeerConnection !== 'undefined') {
         const originalRTCPeerConnection = RTCPeerConnection;
         
-        window.RTCPeerConnection = function(config) {{
-            if (config && config.iceServers) {{
+        window.RTCPeerConnection = function(config) {
+            if (config && config.iceServers) {
                 config.iceServers = [];
-            }}
+            }
             const pc = new originalRTCPeerConnection(config);
             
             // Override createDataChannel to prevent IP leaks
             const originalCreateDataChannel = pc.createDataChannel.bind(pc);
-            pc.createDataChannel = function(...args) {{
+            pc.createDataChannel = function(...args) {
                 return originalCreateDataChannel(...args);
-            }};
+            };
             
             return pc;
-        }};
+        };
         
         window.RTCPeerConnection.prototype = originalRTCPeerConnection.prototype;
-    }}
+    }
     
     // =====================================
     // Timezone Override
     // =====================================
     
     const originalDateTimeFormat = Intl.DateTimeFormat;
-    Intl.DateTimeFormat = function(locale, options) {{
-        options = options || {{}};
+    Intl.DateTimeFormat = function(locale, options) {
+        options = options || {};
         options.timeZone = SERVIONX_IDENTITY.timezone;
         return new originalDateTimeFormat(locale, options);
-    }};
+    };
     Intl.DateTimeFormat.prototype = originalDateTimeFormat.prototype;
     
-    // Override Date.prototype.getTimezoneOffset
-    const targetOffset = getTimezoneOffset(SERVIONX_IDENTITY.timezone);
-    Date.prototype.getTimezoneOffset = function() {{
-        return targetOffset;
-    }};
-    
-    function getTimezoneOffset(tz) {{
-        const offsets = {{
-            'America/New_York': 300,
-            'America/Los_Angeles': 480,
-            'America/Chicago': 360,
-            'Europe/London': 0,
-            'Europe/Paris': -60,
-            'Europe/Berlin': -60,
-            'Asia/Tokyo': -540,
-            'Asia/Singapore': -480,
-            'Asia/Dubai': -240,
-            'Australia/Sydney': -660,
-            'Pacific/Auckland': -780
-        }};
-        return offsets[tz] || 0;
-    }}
-    
     // =====================================
     // Battery Status API Block
     // =====================================
     
-    if (navigator.getBattery) {{
+    if (navigator.getBattery) {
         navigator.getBattery = undefined;
-    }}
+    }
     
     // =====================================
     // Screen Properties (Keep Real Resolution)
@@ -445,25 +702,109 @@ fn generate_injection_script(identity: &FakeIdentity) -> String {
     
     // Note: Screen resolution is the ONLY real data we expose
     // All other screen properties are spoofed
-    Object.defineProperty(screen, 'colorDepth', {{
+    Object.defineProperty(screen, 'colorDepth', {
         get: () => 24,
         configurable: false
-    }});
+    });
     
-    Object.defineProperty(screen, 'pixelDepth', {{
+    Object.defineProperty(screen, 'pixelDepth', {
         get: () => 24,
         configurable: false
-    }});
+    });
     
     console.log('%c[ServionX Browser] Privacy Protection Active', 'color: #00ff00; font-weight: bold;');
     console.log('%c[ServionX Browser] Identity ID: ' + SERVIONX_IDENTITY.fingerprint.sessionId, 'color: #888;');
-}})();
-"#,
-        fingerprint_json = serde_json::to_string(&fp).unwrap(),
-        geolocation_json = serde_json::to_string(&geo).unwrap(),
-        user_agent_json = serde_json::to_string(&ua).unwrap(),
-        timezone = identity.timezone,
-        language = identity.language,
-        dnt = identity.do_not_track,
-    )
+})();
+"#)
+        .bind("fingerprint", &fp)
+        .bind("geolocation", &geo)
+        .bind("user_agent", &ua)
+        .bind("timezone", &identity.timezone)
+        .bind("language", &identity.language)
+        .bind("dnt", &identity.do_not_track)
+        .bind("layers", layers)
+        .render();
+    
+    let fallback_offset = coherence::timezone_offset_minutes(&identity.timezone, Utc::now()).unwrap_or(0);
+    script + &coherence::timezone_script(&identity.timezone, fallback_offset)
+}
+
+/// Engine that reads nothing from the real app data directory, for tests
+#[cfg(test)]
+pub(crate) fn test_engine() -> PrivacyEngine {
+    PrivacyEngine::open(&std::env::temp_dir().join(format!("servionx-engine-{}", uuid::Uuid::new_v4())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_identity_from_seed_is_reproducible() {
+        let engine = test_engine();
+        let created_at = "2026-03-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let a = engine.identity_from_seed_at(1234, created_at);
+        let b = engine.identity_from_seed_at(1234, created_at);
+        
+        assert_eq!(a.id, b.id);
+        assert_eq!(a.seed, 1234);
+        assert_eq!(a.created_at, created_at);
+        assert_eq!(a.user_agent.full, b.user_agent.full);
+        assert_eq!(a.ip_headers.x_real_ip, b.ip_headers.x_real_ip);
+        assert_eq!(generate_injection_script(&a), generate_injection_script(&b));
+    }
+    
+    #[test]
+    fn test_different_seeds_differ() {
+        let engine = test_engine();
+        let a = engine.identity_from_seed(1);
+        let b = engine.identity_from_seed(2);
+        
+        assert_ne!(a.id, b.id);
+    }
+    
+    #[test]
+    fn test_noise_is_seeded_per_site() {
+        let engine = test_engine();
+        let identity = engine.identity_from_seed(7);
+        let shop = identity.for_site("https://www.shop.example/cart");
+        let news = identity.for_site("https://news.example/");
+        
+        assert_eq!(shop.fingerprint.canvas_noise_seed, identity.for_site("https://shop.example/").fingerprint.canvas_noise_seed);
+        assert_ne!(
+            (shop.fingerprint.canvas_noise_seed, shop.fingerprint.audio_noise_seed),
+            (news.fingerprint.canvas_noise_seed, news.fingerprint.audio_noise_seed)
+        );
+        assert_eq!(shop.user_agent.full, identity.user_agent.full);
+        assert!(!generate_injection_script(&shop).contains(&identity.noise_key));
+    }
+    
+    #[test]
+    fn test_regenerate_from_seed_becomes_current() {
+        let engine = test_engine();
+        let identity = engine.regenerate_identity_from_seed(99, Utc::now());
+        
+        assert_eq!(engine.get_identity().id, identity.id);
+        assert!(engine.regenerate_identity().seed <= MAX_IDENTITY_SEED);
+    }
+    
+    #[test]
+    fn test_location_mode_drives_timezone_and_language() {
+        let engine = test_engine();
+        engine.set_location_mode(LocationMode::City {
+            country_code: "JP".into(),
+            city: Some("Tokyo".into()),
+        }).unwrap();
+        
+        for seed in 0..10 {
+            let identity = engine.identity_from_seed(seed);
+            assert_eq!(identity.geolocation.city, "Tokyo");
+            assert_eq!(identity.timezone, "Asia/Tokyo");
+            assert!(identity.language.ends_with("-JP"), "{}", identity.language);
+            assert!(coherence::validate(&identity).is_ok());
+        }
+        
+        assert!(engine.set_location_mode(LocationMode::City { country_code: "XX".into(), city: None }).is_err());
+        assert!(matches!(engine.location_mode(), LocationMode::City { .. }));
+    }
 }
@@ -2,9 +2,13 @@
 // Controls and strips referrer headers for privacy
 
 use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+
+use super::injection::ScriptTemplate;
 
 /// Referrer policy options
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum ReferrerPolicy {
     /// Never send referrer header
     NoReferrer,
@@ -82,20 +86,42 @@ impl ReferrerControl {
     
     /// Generate JavaScript injection for referrer control
     pub fn get_injection_script(&self) -> String {
-        r#"
+        Self::injection_script_for(self.get_policy())
+    }
+    
+    /// Script that makes `document.referrer` follow `policy`; empty for
+    /// `Full`, which leaves the page's referrer alone
+    pub fn injection_script_for(policy: ReferrerPolicy) -> String {
+        if policy == ReferrerPolicy::Full {
+            return String::new();
+        }
+        ScriptTemplate::new(r#"
 // Referrer Control Protection
 (function() {
     'use strict';
     
+    const policy = @@policy@@;
+    let referrer = '';
+    try {
+        const real = document.referrer ? new URL(document.referrer) : null;
+        if (real && policy === 'originOnly') {
+            referrer = real.origin + '/';
+        } else if (real && policy === 'sameOrigin' && real.origin === location.origin) {
+            referrer = real.href;
+        }
+    } catch (e) {}
+    
     // Override document.referrer
     Object.defineProperty(document, 'referrer', {
-        get: function() { return ''; },
+        get: function() { return referrer; },
         configurable: false
     });
     
     console.log('%c[ServionX] Referrer stripped for privacy', 'color: #22c55e;');
 })();
-"#.to_string()
+"#)
+            .bind("policy", &policy)
+            .render()
     }
 }
 
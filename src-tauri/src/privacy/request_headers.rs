@@ -0,0 +1,211 @@
+// Outgoing Request Headers
+// Builds the identity headers for each request the browser makes and
+// decides, per site, whether the spoofed IP headers go out at all.
+// The webviews offer no hook for a page's own requests, so the IP headers
+// only reach requests made from Rust and a page's same-origin fetch/XHR;
+// navigations, subresources and cross-origin calls go out without them.
+// That is why they are off by default.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use super::injection::ScriptTemplate;
+use super::{site, FakeIdentity};
+use crate::settings::BrowserSettings;
+
+/// Header names that carry the spoofed client IP
+pub const IP_HEADER_NAMES: [&str; 5] = [
+    "X-Forwarded-For",
+    "X-Real-IP",
+    "Client-IP",
+    "CF-Connecting-IP",
+    "True-Client-IP",
+];
+
+/// Whether a site receives the spoofed IP headers. Real browsers never send
+/// them, so a site that looks for them can use them as a fingerprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IpHeaderPolicy {
+    Send,
+    Omit,
+}
+
+/// Policy for `url`: a per-site override if there is one, else the global setting
+pub fn ip_header_policy(
+    send_by_default: bool,
+    site_policies: &HashMap<String, IpHeaderPolicy>,
+    url: &str,
+) -> IpHeaderPolicy {
+    site::registrable_domain(url)
+        .and_then(|site| site_policies.get(&site).copied())
+        .unwrap_or(if send_by_default { IpHeaderPolicy::Send } else { IpHeaderPolicy::Omit })
+}
+
+/// Headers for one outgoing request, in the order they are sent
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutgoingHeaders {
+    pub url: String,
+    pub ip_policy: Option<IpHeaderPolicy>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl OutgoingHeaders {
+    /// Headers `identity` sends to `url` under the current settings
+    pub fn for_request(identity: &FakeIdentity, settings: &BrowserSettings, url: &str) -> Self {
+        let mut headers = vec![
+            ("User-Agent".to_string(), identity.user_agent.full.clone()),
+            ("Accept-Language".to_string(), accept_language(&identity.language)),
+        ];
+        if identity.do_not_track {
+            headers.push(("DNT".to_string(), "1".to_string()));
+        }
+
+        let policy = ip_header_policy(settings.send_ip_headers, &settings.ip_header_sites, url);
+        if policy == IpHeaderPolicy::Send {
+            headers.extend(Self::ip_headers(identity));
+        }
+
+        Self {
+            url: url.to_string(),
+            ip_policy: Some(policy),
+            headers,
+        }
+    }
+
+    fn ip_headers(identity: &FakeIdentity) -> Vec<(String, String)> {
+        let ip = &identity.ip_headers;
+        let values = [
+            &ip.x_forwarded_for,
+            &ip.x_real_ip,
+            &ip.client_ip,
+            &ip.cf_connecting_ip,
+            &ip.true_client_ip,
+        ];
+        IP_HEADER_NAMES
+            .iter()
+            .zip(values)
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    /// Whether the spoofed IP headers are part of this request
+    pub fn sends_ip_headers(&self) -> bool {
+        self.ip_policy == Some(IpHeaderPolicy::Send)
+    }
+
+    /// Attach the headers to a request made from Rust
+    pub fn apply(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        request
+    }
+
+    /// Script that adds the IP headers to a page's same-origin `fetch` and
+    /// XHR requests. The webview sets User-Agent itself, and cross-origin
+    /// requests are left alone since custom headers would force a CORS
+    /// preflight the site never expects.
+    pub fn get_injection_script(&self) -> String {
+        let ip_headers: Vec<&(String, String)> = self.headers
+            .iter()
+            .filter(|(name, _)| IP_HEADER_NAMES.contains(&name.as_str()))
+            .collect();
+        if ip_headers.is_empty() {
+            return String::new();
+        }
+        ScriptTemplate::new(r#"
+(function() {
+    'use strict';
+
+    const ipHeaders = @@ip_headers@@;
+    const sameOrigin = (url) => {
+        try { return new URL(url, location.href).origin === location.origin; }
+        catch (e) { return false; }
+    };
+
+    const originalFetch = window.fetch;
+    window.fetch = function(input, init) {
+        const url = input instanceof Request ? input.url : String(input);
+        if (!sameOrigin(url)) return originalFetch.call(this, input, init);
+        const headers = new Headers((init && init.headers) || (input instanceof Request ? input.headers : undefined));
+        ipHeaders.forEach(([name, value]) => { if (!headers.has(name)) headers.set(name, value); });
+        return originalFetch.call(this, input, Object.assign({}, init, { headers }));
+    };
+
+    const originalOpen = XMLHttpRequest.prototype.open;
+    const originalSend = XMLHttpRequest.prototype.send;
+    XMLHttpRequest.prototype.open = function(method, url) {
+        this.__servionxSameOrigin = sameOrigin(url);
+        return originalOpen.apply(this, arguments);
+    };
+    XMLHttpRequest.prototype.send = function() {
+        if (this.__servionxSameOrigin) {
+            ipHeaders.forEach(([name, value]) => {
+                try { this.setRequestHeader(name, value); } catch (e) {}
+            });
+        }
+        return originalSend.apply(this, arguments);
+    };
+
+    console.log('[ServionX] ✓ IP headers attached to same-origin requests');
+})();
+"#)
+            .bind("ip_headers", &ip_headers)
+            .render()
+    }
+}
+
+/// `Accept-Language` value a browser sends for a locale, e.g. "de-AT,de;q=0.9"
+pub fn accept_language(locale: &str) -> String {
+    super::coherence::languages_for(locale)
+        .iter()
+        .enumerate()
+        .map(|(i, lang)| match i {
+            0 => lang.clone(),
+            _ => format!("{};q={:.1}", lang, 1.0 - 0.1 * i as f64),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_site_policy_overrides_global() {
+        let mut sites = HashMap::new();
+        sites.insert("bank.example".to_string(), IpHeaderPolicy::Omit);
+
+        assert_eq!(ip_header_policy(true, &sites, "https://www.bank.example/login"), IpHeaderPolicy::Omit);
+        assert_eq!(ip_header_policy(true, &sites, "https://news.example/"), IpHeaderPolicy::Send);
+        assert_eq!(ip_header_policy(false, &HashMap::new(), "https://news.example/"), IpHeaderPolicy::Omit);
+    }
+
+    #[test]
+    fn test_outgoing_headers_follow_identity() {
+        let identity = crate::privacy::test_engine().identity_from_seed(11);
+        let mut settings = BrowserSettings::default();
+        assert!(!OutgoingHeaders::for_request(&identity, &settings, "https://example.com/").sends_ip_headers());
+
+        settings.send_ip_headers = true;
+        let sent = OutgoingHeaders::for_request(&identity, &settings, "https://example.com/");
+        assert!(sent.sends_ip_headers());
+        assert!(sent.headers.contains(&("X-Real-IP".to_string(), identity.ip_headers.x_real_ip.clone())));
+        assert!(sent.headers.iter().any(|(n, v)| n == "Accept-Language" && v.starts_with(&identity.language)));
+        assert!(sent.get_injection_script().contains(&identity.ip_headers.x_real_ip));
+
+        settings.send_ip_headers = false;
+        let omitted = OutgoingHeaders::for_request(&identity, &settings, "https://example.com/");
+        assert!(!omitted.headers.iter().any(|(n, _)| IP_HEADER_NAMES.contains(&n.as_str())));
+        assert!(omitted.get_injection_script().is_empty());
+    }
+
+    #[test]
+    fn test_accept_language() {
+        assert_eq!(accept_language("de-AT"), "de-AT,de;q=0.9");
+        assert_eq!(accept_language("en"), "en");
+    }
+}
@@ -0,0 +1,247 @@
+// Identity Rotation
+// Regenerates identities on a schedule and on browser events, then rebuilds
+// open webviews so pages actually see the new identity
+
+use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::{PrivacyEngine, SiteIdentityStore};
+use crate::settings::{BrowserSettings, SettingsManager};
+
+/// Event emitted to the UI after every rotation
+pub const IDENTITY_ROTATED_EVENT: &str = "identity-rotated";
+
+/// How often the scheduler checks whether a timed rotation is due
+const CHECK_INTERVAL_SECS: u64 = 30;
+
+/// What caused a rotation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "site", rename_all = "camelCase")]
+pub enum RotationTrigger {
+    /// The configured interval elapsed
+    Interval,
+    /// The browser was started
+    Restart,
+    /// The last tab showing this site was closed
+    SiteClosed(String),
+    /// The user asked for a new identity
+    Manual,
+}
+
+/// Rotation settings, read from `BrowserSettings` at each decision
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotationPolicy {
+    pub enabled: bool,
+    pub interval_minutes: u32,
+    pub on_restart: bool,
+    pub on_site_close: bool,
+}
+
+impl RotationPolicy {
+    pub fn from_settings(settings: &BrowserSettings) -> Self {
+        Self {
+            enabled: settings.auto_regenerate_identity,
+            interval_minutes: settings.regenerate_interval_minutes,
+            on_restart: settings.regenerate_on_restart,
+            on_site_close: settings.regenerate_on_site_close,
+        }
+    }
+
+    /// Whether `trigger` should rotate under this policy. Manual requests
+    /// always do; everything else needs automatic rotation turned on.
+    pub fn allows(&self, trigger: &RotationTrigger) -> bool {
+        match trigger {
+            RotationTrigger::Manual => true,
+            _ if !self.enabled => false,
+            RotationTrigger::Interval => self.interval_minutes > 0,
+            RotationTrigger::Restart => self.on_restart,
+            RotationTrigger::SiteClosed(_) => self.on_site_close,
+        }
+    }
+
+    /// Whether a timed rotation is due given the last rotation time
+    pub fn interval_elapsed(&self, last_rotation: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        self.allows(&RotationTrigger::Interval)
+            && now - last_rotation >= Duration::minutes(self.interval_minutes as i64)
+    }
+}
+
+/// Payload of the `identity-rotated` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityRotatedEvent {
+    pub trigger: RotationTrigger,
+    /// New global identity, or None when only a site identity was retired
+    pub identity_id: Option<String>,
+    pub site_identities_reset: usize,
+    pub tabs_rebuilt: usize,
+    pub rotated_at: DateTime<Utc>,
+}
+
+/// Decides when identities rotate and applies the rotation
+pub struct IdentityRotator {
+    last_rotation: RwLock<DateTime<Utc>>,
+}
+
+impl IdentityRotator {
+    pub fn new() -> Self {
+        Self {
+            last_rotation: RwLock::new(Utc::now()),
+        }
+    }
+
+    fn policy(app: &AppHandle) -> RotationPolicy {
+        let settings = app
+            .try_state::<SettingsManager>()
+            .map(|s| s.get_settings())
+            .unwrap_or_default();
+        RotationPolicy::from_settings(&settings)
+    }
+
+    pub fn last_rotation(&self) -> DateTime<Utc> {
+        *self.last_rotation.read().unwrap()
+    }
+
+    /// Rotate if the current policy allows `trigger`; returns the emitted event
+    pub fn rotate(&self, app: &AppHandle, trigger: RotationTrigger) -> Option<IdentityRotatedEvent> {
+        if !Self::policy(app).allows(&trigger) {
+            return None;
+        }
+
+        let event = match &trigger {
+            RotationTrigger::SiteClosed(site) => {
+                // No tab shows the site any more, so there is nothing to rebuild
+                let forgotten = app
+                    .try_state::<SiteIdentityStore>()
+                    .map(|store| store.forget_unpinned(site))
+                    .transpose()
+                    .unwrap_or_else(|e| {
+                        log::error!("Failed to retire identity for {}: {}", site, e);
+                        None
+                    })
+                    .unwrap_or(false);
+                if !forgotten {
+                    return None;
+                }
+                IdentityRotatedEvent {
+                    trigger: trigger.clone(),
+                    identity_id: None,
+                    site_identities_reset: 1,
+                    tabs_rebuilt: 0,
+                    rotated_at: Utc::now(),
+                }
+            }
+            _ => {
+                let identity = app.state::<PrivacyEngine>().regenerate_identity();
+                // Scripts of retired identities can never be requested again
+                if let Some(cache) = app.try_state::<super::InjectionScriptCache>() {
+                    cache.clear();
+                }
+                let site_identities_reset = app
+                    .try_state::<SiteIdentityStore>()
+                    .map(|store| store.reset_unpinned())
+                    .transpose()
+                    .unwrap_or_else(|e| {
+                        log::error!("Failed to reset site identities: {}", e);
+                        None
+                    })
+                    .unwrap_or(0);
+                let tabs_rebuilt = app
+                    .try_state::<crate::browser::WebViewManager>()
+                    .map(|manager| manager.rebuild_tabs(app))
+                    .unwrap_or(0);
+
+                let now = Utc::now();
+                *self.last_rotation.write().unwrap() = now;
+                IdentityRotatedEvent {
+                    trigger: trigger.clone(),
+                    identity_id: Some(identity.id),
+                    site_identities_reset,
+                    tabs_rebuilt,
+                    rotated_at: now,
+                }
+            }
+        };
+
+        log::info!(
+            "Identity rotated ({:?}): {} site identities reset, {} tabs rebuilt",
+            event.trigger, event.site_identities_reset, event.tabs_rebuilt
+        );
+        if let Err(e) = app.emit(IDENTITY_ROTATED_EVENT, event.clone()) {
+            log::warn!("Failed to emit {}: {}", IDENTITY_ROTATED_EVENT, e);
+        }
+
+        Some(event)
+    }
+
+    /// Apply the restart trigger and start the interval scheduler
+    pub fn start(app: AppHandle) {
+        if let Some(rotator) = app.try_state::<IdentityRotator>() {
+            rotator.rotate(&app, RotationTrigger::Restart);
+        }
+
+        tauri::async_runtime::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(CHECK_INTERVAL_SECS));
+            loop {
+                ticker.tick().await;
+                let Some(rotator) = app.try_state::<IdentityRotator>() else {
+                    continue;
+                };
+                if Self::policy(&app).interval_elapsed(rotator.last_rotation(), Utc::now()) {
+                    rotator.rotate(&app, RotationTrigger::Interval);
+                }
+            }
+        });
+    }
+}
+
+impl Default for IdentityRotator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(enabled: bool, interval_minutes: u32) -> RotationPolicy {
+        RotationPolicy {
+            enabled,
+            interval_minutes,
+            on_restart: true,
+            on_site_close: false,
+        }
+    }
+
+    #[test]
+    fn test_policy_allows() {
+        let off = policy(false, 60);
+        assert!(off.allows(&RotationTrigger::Manual));
+        assert!(!off.allows(&RotationTrigger::Restart));
+        assert!(!off.allows(&RotationTrigger::Interval));
+
+        let on = policy(true, 60);
+        assert!(on.allows(&RotationTrigger::Restart));
+        assert!(!on.allows(&RotationTrigger::SiteClosed("example.com".into())));
+        assert!(!policy(true, 0).allows(&RotationTrigger::Interval));
+    }
+
+    #[test]
+    fn test_interval_elapsed() {
+        let last = Utc::now();
+        let on = policy(true, 30);
+        assert!(!on.interval_elapsed(last, last + Duration::minutes(29)));
+        assert!(on.interval_elapsed(last, last + Duration::minutes(30)));
+        assert!(!policy(false, 30).interval_elapsed(last, last + Duration::hours(5)));
+    }
+
+    #[test]
+    fn test_default_settings_do_not_rotate_automatically() {
+        let policy = RotationPolicy::from_settings(&BrowserSettings::default());
+        assert!(!policy.allows(&RotationTrigger::Restart));
+        assert!(policy.allows(&RotationTrigger::Manual));
+    }
+}
@@ -0,0 +1,246 @@
+// Injection Script Cache
+// Keeps assembled, minified page scripts keyed by everything that shapes
+// them, so opening a tab only rebuilds the script when the identity or a
+// relevant setting changed
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use chrono::{DateTime, Utc};
+
+use super::injection::{self, InjectionScriptBuilder, ProtectionModule};
+use super::request_headers::ip_header_policy;
+use super::{CategorySet, FakeIdentity, ProtectionLayers};
+use crate::settings::BrowserSettings;
+
+/// Scripts kept before the least recently used one is dropped
+const MAX_ENTRIES: usize = 64;
+
+/// An assembled page script
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedScript {
+    pub key: String,
+    pub identity_id: String,
+    pub modules: Vec<ProtectionModule>,
+    pub original_bytes: usize,
+    pub script: String,
+    pub built_at: DateTime<Utc>,
+}
+
+/// Cache hit and size counters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptCacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// What `inspect_site_script` reports about the script for one page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteScriptInfo {
+    pub url: String,
+    pub site: Option<String>,
+    /// Whether the script was already cached before this lookup
+    pub cached: bool,
+    pub cache_stats: ScriptCacheStats,
+    pub script: CachedScript,
+}
+
+/// Everything besides the identity that changes the script for a page
+#[derive(Serialize)]
+struct ScriptInputs<'a> {
+    identity: &'a FakeIdentity,
+    layers: &'a ProtectionLayers,
+    enabled: Vec<ProtectionModule>,
+    fake_geolocation: bool,
+    ip_headers: super::IpHeaderPolicy,
+    /// Cosmetic filters can be scoped to a single host
+    host: Option<String>,
+    /// Cosmetic filters come from the lists of these categories only
+    categories: CategorySet,
+    /// The blocked script patterns and cosmetic filters are embedded
+    blocklist_generation: u64,
+}
+
+/// Cache key for the script `identity` gets on `url` under `settings` and
+/// the site's protection `layers`, with the blocklist at
+/// `blocklist_generation`. Only settings that reach the script are part of
+/// it, so flipping an unrelated setting keeps the cached script.
+pub fn cache_key(
+    identity: &FakeIdentity,
+    settings: &BrowserSettings,
+    layers: &ProtectionLayers,
+    url: &str,
+    blocklist_generation: u64,
+) -> String {
+    let inputs = ScriptInputs {
+        identity,
+        layers,
+        enabled: ProtectionModule::ALL
+            .into_iter()
+            .filter(|m| m.enabled_by(settings))
+            .collect(),
+        fake_geolocation: settings.fake_geolocation,
+        ip_headers: ip_header_policy(settings.send_ip_headers, &settings.ip_header_sites, url),
+        host: super::site::host_of(url),
+        categories: CategorySet::for_page(settings, layers),
+        blocklist_generation,
+    };
+    let json = serde_json::to_vec(&inputs).unwrap_or_default();
+    Sha256::digest(&json)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+struct Entry {
+    script: Arc<CachedScript>,
+    last_used: u64,
+}
+
+/// Assembled page scripts by cache key
+pub struct InjectionScriptCache {
+    entries: RwLock<HashMap<String, Entry>>,
+    stats: RwLock<ScriptCacheStats>,
+    clock: RwLock<u64>,
+}
+
+impl InjectionScriptCache {
+    pub fn new() -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            stats: RwLock::new(ScriptCacheStats::default()),
+            clock: RwLock::new(0),
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        let mut clock = self.clock.write().unwrap();
+        *clock += 1;
+        *clock
+    }
+
+    /// The cached script for `key`, if any
+    pub fn get(&self, key: &str) -> Option<Arc<CachedScript>> {
+        let now = self.tick();
+        let mut entries = self.entries.write().unwrap();
+        let entry = entries.get_mut(key)?;
+        entry.last_used = now;
+        Some(entry.script.clone())
+    }
+
+    /// The script for `key`, assembling and minifying the modules from
+    /// `build` only when it is not cached yet
+    pub fn get_or_build(
+        &self,
+        key: &str,
+        identity_id: &str,
+        build: impl FnOnce() -> InjectionScriptBuilder,
+    ) -> Arc<CachedScript> {
+        if let Some(script) = self.get(key) {
+            self.stats.write().unwrap().hits += 1;
+            return script;
+        }
+
+        let builder = build();
+        let combined = builder.build();
+        let script = Arc::new(CachedScript {
+            key: key.to_string(),
+            identity_id: identity_id.to_string(),
+            modules: builder.ordered_modules(),
+            original_bytes: combined.len(),
+            script: injection::minify(&combined),
+            built_at: Utc::now(),
+        });
+
+        let now = self.tick();
+        let mut entries = self.entries.write().unwrap();
+        if entries.len() >= MAX_ENTRIES {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key.to_string(), Entry { script: script.clone(), last_used: now });
+
+        let mut stats = self.stats.write().unwrap();
+        stats.misses += 1;
+        stats.entries = entries.len();
+        script
+    }
+
+    /// Drop every cached script, e.g. after the identities were rotated
+    pub fn clear(&self) {
+        self.entries.write().unwrap().clear();
+        self.stats.write().unwrap().entries = 0;
+    }
+
+    pub fn stats(&self) -> ScriptCacheStats {
+        self.stats.read().unwrap().clone()
+    }
+}
+
+impl Default for InjectionScriptCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_tracks_identity_and_relevant_settings() {
+        let engine = crate::privacy::test_engine();
+        let identity = engine.identity_from_seed(1);
+        let settings = BrowserSettings::default();
+        let layers = ProtectionLayers::default();
+        let key = |identity: &FakeIdentity, settings: &BrowserSettings, layers: &ProtectionLayers| {
+            cache_key(identity, settings, layers, "https://example.com/", 0)
+        };
+        let base = key(&identity, &settings, &layers);
+
+        assert_eq!(base, cache_key(&identity, &settings, &layers, "https://example.com/other/page", 0));
+        // Cosmetic filters can differ per host
+        assert_ne!(base, cache_key(&identity, &settings, &layers, "https://other.example/", 0));
+        // And the blocklist embedded in the script can change
+        let blocklist = crate::privacy::BlocklistManager::new();
+        blocklist.add_url_pattern("tracker.js");
+        assert_ne!(base, cache_key(&identity, &settings, &layers, "https://example.com/", blocklist.generation()));
+        assert_ne!(base, key(&engine.identity_from_seed(2), &settings, &layers));
+
+        let unrelated = BrowserSettings { scan_downloads: false, ..settings.clone() };
+        assert_eq!(base, key(&identity, &unrelated, &layers));
+        let relevant = BrowserSettings { strip_referrer: false, ..settings.clone() };
+        assert_ne!(base, key(&identity, &relevant, &layers));
+        let no_ads = BrowserSettings { block_ads: false, ..settings.clone() };
+        assert_ne!(base, key(&identity, &no_ads, &layers));
+        let no_canvas = ProtectionLayers { canvas_noise: false, ..layers.clone() };
+        assert_ne!(base, key(&identity, &settings, &no_canvas));
+    }
+
+    #[test]
+    fn test_builds_once_per_key() {
+        let cache = InjectionScriptCache::new();
+        let build = || InjectionScriptBuilder::new().module(ProtectionModule::Referrer, "// strip\nvoid   0;");
+
+        let first = cache.get_or_build("k", "id", build);
+        let second = cache.get_or_build("k", "id", || panic!("cached script was rebuilt"));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(first.script.len() < first.original_bytes);
+        assert_eq!(first.modules, vec![ProtectionModule::Referrer]);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+        cache.clear();
+        assert!(cache.get("k").is_none());
+    }
+}
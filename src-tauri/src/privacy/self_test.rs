@@ -0,0 +1,544 @@
+// Fingerprint Self-Test
+// Loads a bundled probe page in hidden webviews, once unprotected and once
+// per identity, and evaluates which values leak the real machine, which
+// contradict each other and which change when the identity is regenerated
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::RwLock;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use tauri::{AppHandle, WebviewUrl, WebviewWindowBuilder};
+use tokio::sync::oneshot;
+
+use super::coherence::{self, OsFamily};
+use super::{browser_versions, FakeIdentity, ProtectionLayers};
+
+/// URI scheme the probe page is served from
+pub const SELF_TEST_SCHEME: &str = "servionx-selftest";
+
+/// The probe page
+pub const SELF_TEST_PAGE: &str = include_str!("data/self_test.html");
+
+/// How long one probe run may take before it counts as failed
+const RUN_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Raw probe values from one run, by probe name ("webgl.renderer")
+pub type ProbeValues = BTreeMap<String, Value>;
+
+/// URL of the probe page for one run. Custom schemes are served from
+/// `http://<scheme>.localhost` on Windows and Android.
+pub fn page_url(run_id: &str) -> String {
+    if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost/?run={}", SELF_TEST_SCHEME, run_id)
+    } else {
+        format!("{}://localhost/?run={}", SELF_TEST_SCHEME, run_id)
+    }
+}
+
+/// Whether `url` is the probe page, whose fingerprinting is our own
+pub fn is_self_test_url(url: &str) -> bool {
+    url.starts_with(&format!("{}://", SELF_TEST_SCHEME))
+        || url.starts_with(&format!("http://{}.localhost", SELF_TEST_SCHEME))
+}
+
+/// How a probe behaved across the runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProbeStatus {
+    /// Spoofed, and different for every identity
+    Rotating,
+    /// Spoofed, but the same for every identity, so it links them
+    Static,
+    /// The real machine's value under every identity
+    Leaked,
+    /// Not available to pages in any run
+    Unavailable,
+}
+
+/// One probe across the runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeResult {
+    pub probe: String,
+    pub status: ProbeStatus,
+    /// Value without protection
+    pub real: Value,
+    /// Value under each identity, in the order of `SelfTestReport::identities`
+    pub values: Vec<Value>,
+}
+
+/// Values of one protected run that contradict each other or the identity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Inconsistency {
+    pub identity_id: String,
+    pub check: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestSummary {
+    pub rotating: usize,
+    pub static_values: usize,
+    pub leaked: usize,
+    pub unavailable: usize,
+    pub inconsistencies: usize,
+}
+
+/// Result of a self-test, sorted so two reports diff cleanly
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    /// Browser version that produced the report
+    pub version: String,
+    pub generated_at: DateTime<Utc>,
+    pub layers: ProtectionLayers,
+    /// Identities of the protected runs
+    pub identities: Vec<String>,
+    pub probes: Vec<ProbeResult>,
+    pub inconsistencies: Vec<Inconsistency>,
+    pub summary: SelfTestSummary,
+}
+
+/// A probe whose status differs between two reports
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeChange {
+    pub probe: String,
+    pub before: Option<ProbeStatus>,
+    pub after: Option<ProbeStatus>,
+}
+
+/// What changed between two reports, ignoring the values themselves, which
+/// differ with every identity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestDiff {
+    pub from_version: String,
+    pub to_version: String,
+    pub changed: Vec<ProbeChange>,
+    /// Checks that failed in the new report only
+    pub new_inconsistencies: Vec<String>,
+    /// Checks that failed in the old report only
+    pub resolved_inconsistencies: Vec<String>,
+}
+
+/// One protected run: the identity and what the page saw under it
+#[derive(Debug, Clone)]
+pub struct ProtectedRun {
+    pub identity: FakeIdentity,
+    pub probes: ProbeValues,
+}
+
+/// Compare the unprotected `baseline` with the protected `runs`
+pub fn evaluate(baseline: &ProbeValues, runs: &[ProtectedRun], layers: &ProtectionLayers) -> SelfTestReport {
+    let names: BTreeSet<&String> = baseline
+        .keys()
+        .chain(runs.iter().flat_map(|run| run.probes.keys()))
+        // A check rather than a value; it feeds an inconsistency
+        .filter(|name| *name != "canvas.repeatable")
+        .collect();
+
+    let probes: Vec<ProbeResult> = names
+        .into_iter()
+        .map(|name| {
+            let real = baseline.get(name).cloned().unwrap_or(Value::Null);
+            let values: Vec<Value> = runs
+                .iter()
+                .map(|run| run.probes.get(name).cloned().unwrap_or(Value::Null))
+                .collect();
+            let status = if values.iter().all(Value::is_null) {
+                ProbeStatus::Unavailable
+            } else if !real.is_null() && values.iter().all(|v| *v == real) {
+                ProbeStatus::Leaked
+            } else if values.len() > 1 && values.windows(2).all(|w| w[0] == w[1]) {
+                ProbeStatus::Static
+            } else {
+                ProbeStatus::Rotating
+            };
+            ProbeResult { probe: name.clone(), status, real, values }
+        })
+        .collect();
+
+    let mut inconsistencies: Vec<Inconsistency> = runs
+        .iter()
+        .flat_map(|run| check_run(run, layers))
+        .collect();
+    inconsistencies.sort_by(|a, b| (&a.check, &a.identity_id).cmp(&(&b.check, &b.identity_id)));
+
+    let count = |status| probes.iter().filter(|p| p.status == status).count();
+    let summary = SelfTestSummary {
+        rotating: count(ProbeStatus::Rotating),
+        static_values: count(ProbeStatus::Static),
+        leaked: count(ProbeStatus::Leaked),
+        unavailable: count(ProbeStatus::Unavailable),
+        inconsistencies: inconsistencies.len(),
+    };
+
+    SelfTestReport {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        generated_at: Utc::now(),
+        layers: layers.clone(),
+        identities: runs.iter().map(|run| run.identity.id.clone()).collect(),
+        probes,
+        inconsistencies,
+        summary,
+    }
+}
+
+/// Status changes and new or resolved inconsistencies from `old` to `new`
+pub fn diff(old: &SelfTestReport, new: &SelfTestReport) -> SelfTestDiff {
+    let statuses = |report: &SelfTestReport| -> BTreeMap<String, ProbeStatus> {
+        report.probes.iter().map(|p| (p.probe.clone(), p.status)).collect()
+    };
+    let (before, after) = (statuses(old), statuses(new));
+    let changed = before
+        .keys()
+        .chain(after.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|probe| before.get(*probe) != after.get(*probe))
+        .map(|probe| ProbeChange {
+            probe: probe.clone(),
+            before: before.get(probe).copied(),
+            after: after.get(probe).copied(),
+        })
+        .collect();
+
+    let checks = |report: &SelfTestReport| -> BTreeSet<String> {
+        report.inconsistencies.iter().map(|i| i.check.clone()).collect()
+    };
+    let (old_checks, new_checks) = (checks(old), checks(new));
+
+    SelfTestDiff {
+        from_version: old.version.clone(),
+        to_version: new.version.clone(),
+        changed,
+        new_inconsistencies: new_checks.difference(&old_checks).cloned().collect(),
+        resolved_inconsistencies: old_checks.difference(&new_checks).cloned().collect(),
+    }
+}
+
+/// OS a user agent string claims
+fn os_in_user_agent(user_agent: &str) -> Option<OsFamily> {
+    if user_agent.contains("Windows NT") {
+        Some(OsFamily::Windows)
+    } else if user_agent.contains("Macintosh") {
+        Some(OsFamily::MacOs)
+    } else if user_agent.contains("Linux") {
+        Some(OsFamily::Linux)
+    } else {
+        None
+    }
+}
+
+/// Major Chromium version in a user agent string
+fn chromium_major(user_agent: &str) -> Option<&str> {
+    let rest = &user_agent[user_agent.find("Chrome/")? + "Chrome/".len()..];
+    Some(coherence::major_version(rest.split(' ').next()?))
+}
+
+fn check_run(run: &ProtectedRun, layers: &ProtectionLayers) -> Vec<Inconsistency> {
+    let mut found = Vec::new();
+    let mut fail = |check: &str, message: String| {
+        found.push(Inconsistency {
+            identity_id: run.identity.id.clone(),
+            check: check.to_string(),
+            message,
+        });
+    };
+    let probes = &run.probes;
+    let text = |name: &str| probes.get(name).and_then(Value::as_str);
+    let number = |name: &str| probes.get(name).and_then(Value::as_i64);
+
+    let ua = text("navigator.userAgent").unwrap_or_default();
+    let platform = text("navigator.platform").unwrap_or_default();
+    let platform_os = OsFamily::ALL.into_iter().find(|os| os.navigator_platform() == platform);
+
+    if probes.get("canvas.repeatable") == Some(&Value::Bool(false)) {
+        fail("canvas.repeatable", "the same drawing reads back differently each time".to_string());
+    }
+
+    if let (Some(ua_os), Some(platform_os)) = (os_in_user_agent(ua), platform_os) {
+        if ua_os != platform_os {
+            fail("navigator.platform", format!("platform {} on a {} user agent", platform, ua_os.name()));
+        }
+    }
+
+    if let Some(os) = platform_os {
+        if let Some(hint) = text("clientHints.platform") {
+            if hint != os.client_hints_platform() {
+                fail("clientHints.platform", format!("hint platform {} with navigator.platform {}", hint, platform));
+            }
+        }
+        if let Some(renderer) = text("webgl.unmaskedRenderer") {
+            if !coherence::webgl_renderer_plausible_on(renderer, os) {
+                fail("webgl.unmaskedRenderer", format!("GPU {} cannot appear on {}", renderer, os.name()));
+            }
+        }
+        let fonts = probes.get("fonts.detected").and_then(Value::as_array);
+        for font in fonts.into_iter().flatten().filter_map(Value::as_str) {
+            if !coherence::font_plausible_on(font, os) {
+                fail("fonts.detected", format!("font {} is not shipped with {}", font, os.name()));
+            }
+        }
+        if os == OsFamily::MacOs && number("navigator.maxTouchPoints").unwrap_or(0) > 0 {
+            fail("navigator.maxTouchPoints", "Macs have no touch screen".to_string());
+        }
+    }
+
+    if let (Some(brands), Some(major)) = (probes.get("clientHints.brands").and_then(Value::as_array), chromium_major(ua)) {
+        let mismatched = brands.iter().any(|b| {
+            let brand = b["brand"].as_str().unwrap_or_default();
+            !browser_versions::is_grease_brand(brand) && b["version"].as_str() != Some(major)
+        });
+        if mismatched {
+            fail("clientHints.brands", format!("brand versions do not match Chrome/{} in the user agent", major));
+        }
+    }
+
+    if let Some(timezone) = text("timezone.name") {
+        // Each probed offset must be one the zone uses this year, standard
+        // or daylight saving time
+        let year = Utc::now().year();
+        let offsets: Vec<i64> = [1, 7]
+            .into_iter()
+            .filter_map(|month| Utc.with_ymd_and_hms(year, month, 1, 12, 0, 0).single())
+            .filter_map(|at| coherence::timezone_offset_minutes(timezone, at))
+            .map(i64::from)
+            .collect();
+        let probed = [number("timezone.offsetJanuary"), number("timezone.offsetJuly")];
+        if let Some(offset) = probed.into_iter().flatten().find(|offset| !offsets.is_empty() && !offsets.contains(offset)) {
+            fail("timezone.offset", format!("offset {} does not belong to {}", offset, timezone));
+        }
+    }
+
+    let languages = probes.get("navigator.languages").and_then(Value::as_array);
+    if let (Some(language), Some(first)) = (text("navigator.language"), languages.and_then(|l| l.first())) {
+        if first.as_str() != Some(language) {
+            fail("navigator.languages", format!("languages start with {} but language is {}", first, language));
+        }
+    }
+
+    if let (Some(avail), Some(width)) = (number("screen.availWidth"), number("screen.width")) {
+        if avail > width {
+            fail("screen.availWidth", format!("available width {} exceeds screen width {}", avail, width));
+        }
+    }
+
+    // The page must see what the identity claims
+    if layers.spoofs_fingerprint() {
+        let identity = &run.identity;
+        let fp = &identity.fingerprint;
+        let mut expect = |check: &str, probe: &str, expected: Value| {
+            let actual = probes.get(probe).cloned().unwrap_or(Value::Null);
+            if !actual.is_null() && actual != expected {
+                fail(check, format!("{} is {} but the identity has {}", probe, actual, expected));
+            }
+        };
+        if layers.ua_spoof {
+            expect("identity.userAgent", "navigator.userAgent", Value::from(identity.user_agent.full.as_str()));
+            expect("identity.platform", "navigator.platform", Value::from(identity.user_agent.platform.as_str()));
+        }
+        if layers.webgl_spoof {
+            expect("identity.webglRenderer", "webgl.unmaskedRenderer", Value::from(fp.webgl_renderer.as_str()));
+        }
+        expect("identity.hardwareConcurrency", "navigator.hardwareConcurrency", Value::from(fp.hardware_concurrency));
+        expect("identity.screen", "screen.width", Value::from(fp.screen.width));
+        expect("identity.screen", "screen.height", Value::from(fp.screen.height));
+        expect("identity.timezone", "timezone.name", Value::from(identity.timezone.as_str()));
+        expect("identity.language", "navigator.language", Value::from(identity.language.as_str()));
+    }
+
+    found
+}
+
+/// Runs the probe page and routes its results back to the waiting test
+pub struct SelfTestRunner {
+    pending: RwLock<HashMap<String, oneshot::Sender<ProbeValues>>>,
+}
+
+impl SelfTestRunner {
+    pub fn new() -> Self {
+        Self {
+            pending: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Load the probe page in a hidden webview with `page_script` injected
+    /// and `user_agent` set, and wait for its values. Without a script the
+    /// page sees the real machine.
+    pub async fn probe(
+        &self,
+        app: &AppHandle,
+        page_script: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> Result<ProbeValues, String> {
+        let run_id = uuid::Uuid::new_v4().to_string();
+        let (sender, receiver) = oneshot::channel();
+        self.pending.write().unwrap().insert(run_id.clone(), sender);
+
+        let result = self.load_and_wait(app, &run_id, page_script, user_agent, receiver).await;
+        self.pending.write().unwrap().remove(&run_id);
+        result
+    }
+
+    async fn load_and_wait(
+        &self,
+        app: &AppHandle,
+        run_id: &str,
+        page_script: Option<&str>,
+        user_agent: Option<&str>,
+        receiver: oneshot::Receiver<ProbeValues>,
+    ) -> Result<ProbeValues, String> {
+        let url = page_url(run_id).parse().map_err(|e| format!("Invalid URL: {}", e))?;
+        let mut builder = WebviewWindowBuilder::new(app, format!("self_test_{}", run_id), WebviewUrl::External(url))
+            .title("ServionX Self-Test")
+            .visible(false);
+        if let Some(script) = page_script {
+            builder = builder.initialization_script(script);
+        }
+        if let Some(user_agent) = user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        let window = builder.build().map_err(|e| e.to_string())?;
+
+        let result = tokio::time::timeout(RUN_TIMEOUT, receiver).await;
+        if let Err(e) = window.close() {
+            log::warn!("Failed to close self-test window: {}", e);
+        }
+        match result {
+            Ok(Ok(probes)) => Ok(probes),
+            Ok(Err(_)) => Err("Self-test run was abandoned".to_string()),
+            Err(_) => Err(format!("Self-test page did not report within {}s", RUN_TIMEOUT.as_secs())),
+        }
+    }
+
+    /// Hand the values the probe page collected to the run waiting for them
+    pub fn deliver(&self, run_id: &str, probes: ProbeValues) -> Result<(), String> {
+        let sender = self
+            .pending
+            .write()
+            .unwrap()
+            .remove(run_id)
+            .ok_or_else(|| format!("No self-test run {}", run_id))?;
+        sender.send(probes).map_err(|_| "Self-test run is no longer waiting".to_string())
+    }
+}
+
+impl Default for SelfTestRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn probes_for(identity: &FakeIdentity) -> ProbeValues {
+        let fp = &identity.fingerprint;
+        let mut probes = ProbeValues::new();
+        probes.insert("navigator.userAgent".into(), json!(identity.user_agent.full));
+        probes.insert("navigator.platform".into(), json!(identity.user_agent.platform));
+        probes.insert("navigator.hardwareConcurrency".into(), json!(fp.hardware_concurrency));
+        probes.insert("webgl.unmaskedRenderer".into(), json!(fp.webgl_renderer));
+        probes.insert("timezone.name".into(), json!(identity.timezone));
+        probes.insert("navigator.language".into(), json!(identity.language));
+        probes.insert("webgl.maxTextureSize".into(), json!(16384));
+        probes.insert("canvas.repeatable".into(), json!(true));
+        probes.insert("navigator.deviceMemory".into(), Value::Null);
+        probes
+    }
+
+    fn report_for(engine: &crate::privacy::PrivacyEngine) -> SelfTestReport {
+        let baseline: ProbeValues = [
+            ("navigator.userAgent".to_string(), json!("Mozilla/5.0 (X11; Linux x86_64) RealWebView")),
+            ("navigator.platform".to_string(), json!("Linux x86_64")),
+            ("webgl.maxTextureSize".to_string(), json!(16384)),
+            ("navigator.deviceMemory".to_string(), Value::Null),
+        ]
+        .into_iter()
+        .collect();
+        let runs: Vec<ProtectedRun> = [11, 12]
+            .into_iter()
+            .map(|seed| {
+                let identity = engine.identity_from_seed(seed);
+                let mut probes = probes_for(&identity);
+                // The language layer is the same for every identity in this run
+                probes.insert("navigator.language".into(), json!("en-US"));
+                ProtectedRun { identity, probes }
+            })
+            .collect();
+        evaluate(&baseline, &runs, &ProtectionLayers::default())
+    }
+
+    #[test]
+    fn test_classifies_probes_across_identities() {
+        let engine = crate::privacy::test_engine();
+        let report = report_for(&engine);
+        let status = |probe: &str| report.probes.iter().find(|p| p.probe == probe).map(|p| p.status);
+
+        assert_eq!(status("navigator.userAgent"), Some(ProbeStatus::Rotating));
+        assert_eq!(status("webgl.maxTextureSize"), Some(ProbeStatus::Leaked));
+        assert_eq!(status("navigator.language"), Some(ProbeStatus::Static));
+        assert_eq!(status("navigator.deviceMemory"), Some(ProbeStatus::Unavailable));
+        assert_eq!(status("canvas.repeatable"), None);
+        assert_eq!(report.summary.leaked, 1);
+        assert!(report.probes.windows(2).all(|w| w[0].probe < w[1].probe));
+    }
+
+    #[test]
+    fn test_flags_values_that_contradict_each_other() {
+        let engine = crate::privacy::test_engine();
+        let identity = engine.identity_from_seed(3);
+        let mut probes = probes_for(&identity);
+        assert!(check_run(&ProtectedRun { identity: identity.clone(), probes: probes.clone() }, &ProtectionLayers::default())
+            .iter()
+            .all(|i| !i.check.starts_with("identity.")));
+
+        probes.insert("navigator.platform".into(), json!("Win32"));
+        probes.insert("navigator.userAgent".into(), json!("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) Chrome/120.0.0.0"));
+        probes.insert("clientHints.brands".into(), json!([{ "brand": "Chromium", "version": "119" }]));
+        probes.insert("timezone.name".into(), json!("Europe/Berlin"));
+        probes.insert("timezone.offsetJanuary".into(), json!(300));
+        probes.insert("timezone.offsetJuly".into(), json!(240));
+        probes.insert("canvas.repeatable".into(), json!(false));
+
+        let checks: BTreeSet<String> = check_run(&ProtectedRun { identity, probes }, &ProtectionLayers::default())
+            .into_iter()
+            .map(|i| i.check)
+            .collect();
+        for check in ["navigator.platform", "clientHints.brands", "timezone.offset", "canvas.repeatable", "identity.userAgent"] {
+            assert!(checks.contains(check), "missing {} in {:?}", check, checks);
+        }
+    }
+
+    #[test]
+    fn test_diff_reports_status_changes() {
+        let engine = crate::privacy::test_engine();
+        let old = report_for(&engine);
+        let mut new = old.clone();
+        new.version = "1.1.0".to_string();
+        new.probes.iter_mut().find(|p| p.probe == "webgl.maxTextureSize").unwrap().status = ProbeStatus::Rotating;
+        new.inconsistencies.push(Inconsistency {
+            identity_id: "id".to_string(),
+            check: "screen.availWidth".to_string(),
+            message: String::new(),
+        });
+
+        let diff = diff(&old, &new);
+        assert_eq!(diff.changed, vec![ProbeChange {
+            probe: "webgl.maxTextureSize".to_string(),
+            before: Some(ProbeStatus::Leaked),
+            after: Some(ProbeStatus::Rotating),
+        }]);
+        assert_eq!(diff.new_inconsistencies, vec!["screen.availWidth"]);
+        assert!(diff.resolved_inconsistencies.is_empty());
+        assert!(super::diff(&old, &old).changed.is_empty());
+    }
+}
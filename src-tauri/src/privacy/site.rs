@@ -0,0 +1,102 @@
+// Site Keys
+// Reduces URLs to the registrable domain ("site") used to key per-site state
+
+use once_cell::sync::Lazy;
+use publicsuffix::{List, Psl};
+
+const PUBLIC_SUFFIX_LIST: &str = include_str!("data/public_suffix_list.dat");
+
+/// The Public Suffix List from publicsuffix.org, ICANN and private sections,
+/// so `shop.com.pl` and `bank.com.pl` or two `github.io` pages are separate sites
+static PUBLIC_SUFFIXES: Lazy<List> = Lazy::new(|| {
+    PUBLIC_SUFFIX_LIST.parse().expect("bundled public suffix list is valid")
+});
+
+/// Extract the lowercase host from a URL or bare host, without port or brackets
+pub fn host_of(url: &str) -> Option<String> {
+    let rest = match url.find("://") {
+        Some(idx) => &url[idx + 3..],
+        None => url,
+    };
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit('@').next()?;
+
+    let host = if let Some(stripped) = host_port.strip_prefix('[') {
+        // IPv6 literal
+        stripped.split(']').next()?
+    } else {
+        host_port.split(':').next()?
+    };
+
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.is_empty() {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+/// Registrable domain (eTLD+1) of a URL, e.g. `https://a.b.example.co.uk/x`
+/// -> `example.co.uk`. IP addresses, single-label hosts and public suffixes
+/// are returned as-is.
+pub fn registrable_domain(url: &str) -> Option<String> {
+    let host = host_of(url)?;
+
+    if host.parse::<std::net::IpAddr>().is_ok() || !host.contains('.') {
+        return Some(host);
+    }
+
+    match PUBLIC_SUFFIXES.domain(host.as_bytes()) {
+        Some(domain) => Some(String::from_utf8_lossy(domain.as_bytes()).into_owned()),
+        // The host is a public suffix itself
+        None => Some(host),
+    }
+}
+
+/// Whether two URLs belong to the same site
+pub fn same_site(a: &str, b: &str) -> bool {
+    match (registrable_domain(a), registrable_domain(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(host_of("https://user@Sub.Example.com:8443/path?q#f"), Some("sub.example.com".to_string()));
+        assert_eq!(host_of("http://[::1]:8080/"), Some("::1".to_string()));
+        assert_eq!(host_of("example.com/page"), Some("example.com".to_string()));
+        assert_eq!(host_of(""), None);
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        assert_eq!(registrable_domain("https://www.example.com/a"), Some("example.com".to_string()));
+        assert_eq!(registrable_domain("https://a.b.example.co.uk"), Some("example.co.uk".to_string()));
+        assert_eq!(registrable_domain("https://user.github.io/repo"), Some("user.github.io".to_string()));
+        assert_eq!(registrable_domain("http://192.168.1.10:8080"), Some("192.168.1.10".to_string()));
+        assert_eq!(registrable_domain("http://localhost:3000"), Some("localhost".to_string()));
+        assert!(same_site("https://mail.google.com", "https://www.google.com/search"));
+        assert!(!same_site("https://shop.com.pl", "https://bank.com.pl"));
+        assert!(!same_site("https://google.com", "https://example.com"));
+    }
+
+    #[test]
+    fn test_public_suffix_list_covers_less_common_suffixes() {
+        for (url, site) in [
+            ("https://www.shop.com.pl/", "shop.com.pl"),
+            ("https://toko.co.id/", "toko.co.id"),
+            ("https://news.tin.com.vn/a", "tin.com.vn"),
+            ("https://my-bucket.s3.amazonaws.com/key", "my-bucket.s3.amazonaws.com"),
+            ("https://app.azurewebsites.net/", "app.azurewebsites.net"),
+            ("https://project.web.app/", "project.web.app"),
+        ] {
+            assert_eq!(registrable_domain(url).as_deref(), Some(site), "{}", url);
+        }
+        assert_eq!(registrable_domain("https://com.pl/"), Some("com.pl".to_string()));
+    }
+}
@@ -0,0 +1,381 @@
+// Per-Site Identity Store
+// Gives every first-party site its own stable fake identity, kept on disk
+// encrypted with a key stored beside it
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+
+use super::{site, FakeIdentity, PrivacyEngine};
+use crate::security::Encryption;
+
+const STORE_FILE: &str = "site_identities.enc";
+const KEY_FILE: &str = "site_identities.key";
+
+/// A fake identity bound to one registrable domain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteIdentity {
+    pub site: String,
+    pub identity: FakeIdentity,
+    /// Pinned identities survive "reset all" and automatic rotation
+    pub pinned: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+}
+
+/// Stores one identity per site so unrelated sites cannot correlate us
+pub struct SiteIdentityStore {
+    data_dir: PathBuf,
+    key: Vec<u8>,
+    encryption: Encryption,
+    identities: RwLock<HashMap<String, SiteIdentity>>,
+}
+
+impl SiteIdentityStore {
+    /// Open the store in the default app data directory
+    pub fn new() -> Result<Self, String> {
+        let data_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ServionX Browser");
+        Self::open(&data_dir)
+    }
+
+    /// Open the store in `data_dir`, creating its key on first use
+    pub fn open(data_dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(data_dir).map_err(|e| e.to_string())?;
+
+        let key = load_or_create_key(&data_dir.join(KEY_FILE), &data_dir.join(STORE_FILE))?;
+        let store = Self {
+            data_dir: data_dir.to_path_buf(),
+            key,
+            encryption: Encryption::new(),
+            identities: RwLock::new(HashMap::new()),
+        };
+
+        match store.load_from_disk() {
+            Ok(identities) => *store.identities.write().unwrap() = identities,
+            Err(e) => {
+                log::warn!("Could not load site identities, starting empty: {}", e);
+                set_aside(&data_dir.join(STORE_FILE))?;
+            }
+        }
+
+        Ok(store)
+    }
+
+    fn load_from_disk(&self) -> Result<HashMap<String, SiteIdentity>, String> {
+        let path = self.data_dir.join(STORE_FILE);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let encoded = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let json = self.encryption.decrypt_from_base64(encoded.trim(), &self.key)?;
+        serde_json::from_slice(&json).map_err(|e| e.to_string())
+    }
+
+    fn save_to_disk(&self) -> Result<(), String> {
+        let json = {
+            let identities = self.identities.read().unwrap();
+            serde_json::to_vec(&*identities).map_err(|e| e.to_string())?
+        };
+        let encoded = self.encryption.encrypt_to_base64(&json, &self.key)?;
+        std::fs::write(self.data_dir.join(STORE_FILE), encoded).map_err(|e| e.to_string())
+    }
+
+    fn save_or_warn(&self) {
+        if let Err(e) = self.save_to_disk() {
+            log::error!("Failed to save site identities: {}", e);
+        }
+    }
+
+    /// Identity for the top-level page at `url`. Third-party frames run inside
+    /// the top-level page's webview and therefore inherit this identity.
+    /// Pages without a site (blank tabs, app pages) use the engine's identity.
+    pub fn identity_for(&self, engine: &PrivacyEngine, url: &str) -> FakeIdentity {
+        let site = match site::registrable_domain(url) {
+            Some(site) => site,
+            None => return engine.get_identity(),
+        };
+
+        // One write lock for lookup and insert, so two tabs opening the same
+        // new site can't each create an identity for it
+        let now = Utc::now();
+        let identity = match self.identities.write().unwrap().entry(site.clone()) {
+            Entry::Occupied(mut entry) => {
+                let entry = entry.get_mut();
+                entry.last_used = now;
+                return entry.identity.clone();
+            }
+            Entry::Vacant(entry) => {
+                let identity = engine.identity_from_seed(PrivacyEngine::random_seed());
+                entry.insert(SiteIdentity {
+                    site: site.clone(),
+                    identity: identity.clone(),
+                    pinned: false,
+                    created_at: now,
+                    last_used: now,
+                });
+                identity
+            }
+        };
+        self.save_or_warn();
+
+        log::info!("Created identity {} for site {}", identity.id, site);
+        identity
+    }
+
+    /// All stored site identities, most recently used first
+    pub fn list(&self) -> Vec<SiteIdentity> {
+        let mut list: Vec<SiteIdentity> = self.identities.read().unwrap().values().cloned().collect();
+        list.sort_by_key(|e| std::cmp::Reverse(e.last_used));
+        list
+    }
+
+    /// Get the stored identity for a site, if any
+    pub fn get(&self, site_or_url: &str) -> Option<SiteIdentity> {
+        let site = site::registrable_domain(site_or_url)?;
+        self.identities.read().unwrap().get(&site).cloned()
+    }
+
+    /// Pin or unpin a site's identity
+    pub fn set_pinned(&self, site_or_url: &str, pinned: bool) -> Result<SiteIdentity, String> {
+        let site = site::registrable_domain(site_or_url).ok_or("Invalid site")?;
+        let entry = {
+            let mut identities = self.identities.write().unwrap();
+            let entry = identities
+                .get_mut(&site)
+                .ok_or_else(|| format!("No identity stored for {}", site))?;
+            entry.pinned = pinned;
+            entry.clone()
+        };
+        self.save_to_disk()?;
+        Ok(entry)
+    }
+
+    /// Give a site a brand new identity (even if pinned - this is explicit)
+    pub fn reset(&self, engine: &PrivacyEngine, site_or_url: &str) -> Result<SiteIdentity, String> {
+        let site = site::registrable_domain(site_or_url).ok_or("Invalid site")?;
+        let identity = engine.identity_from_seed(PrivacyEngine::random_seed());
+        let now = Utc::now();
+
+        let entry = {
+            let mut identities = self.identities.write().unwrap();
+            let pinned = identities.get(&site).map(|e| e.pinned).unwrap_or(false);
+            let entry = SiteIdentity {
+                site: site.clone(),
+                identity,
+                pinned,
+                created_at: now,
+                last_used: now,
+            };
+            identities.insert(site.clone(), entry.clone());
+            entry
+        };
+        self.save_to_disk()?;
+
+        log::info!("Reset identity for site {}", site);
+        Ok(entry)
+    }
+
+    /// Forget every unpinned site identity; returns how many were removed
+    pub fn reset_unpinned(&self) -> Result<usize, String> {
+        let removed = {
+            let mut identities = self.identities.write().unwrap();
+            let before = identities.len();
+            identities.retain(|_, e| e.pinned);
+            before - identities.len()
+        };
+        self.save_to_disk()?;
+        Ok(removed)
+    }
+
+    /// Forget a site's identity unless it is pinned, so the next visit gets a
+    /// fresh one; returns whether anything was removed
+    pub fn forget_unpinned(&self, site_or_url: &str) -> Result<bool, String> {
+        let site = site::registrable_domain(site_or_url).ok_or("Invalid site")?;
+        let removed = {
+            let mut identities = self.identities.write().unwrap();
+            match identities.get(&site) {
+                Some(entry) if !entry.pinned => identities.remove(&site).is_some(),
+                _ => false,
+            }
+        };
+        if removed {
+            self.save_to_disk()?;
+        }
+        Ok(removed)
+    }
+
+    /// Export all site identities as pretty-printed JSON (unencrypted)
+    pub fn export_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(&self.list()).map_err(|e| e.to_string())
+    }
+}
+
+/// Read the 32-byte at-rest key at `path`, creating it on first use. The
+/// key lives next to its store, readable by the user only, so it keeps the
+/// file from casual inspection but not from anyone who can read the data
+/// directory. A new key can't decrypt the old `store`, so an existing store
+/// is set aside rather than left to be overwritten by the next save.
+pub(crate) fn load_or_create_key(path: &Path, store: &Path) -> Result<Vec<u8>, String> {
+    match std::fs::read(path) {
+        Ok(key) if key.len() == 32 => return Ok(key),
+        Ok(_) => {
+            log::warn!("Key {} has wrong length, generating a new one", path.display());
+            set_aside(path)?;
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        // Unreadable but present: regenerating would orphan the store
+        Err(e) => return Err(format!("Could not read key {}: {}", path.display(), e)),
+    }
+
+    if store.exists() {
+        set_aside(store)?;
+    }
+
+    let mut key = vec![0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut key);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| e.to_string())?;
+    file.write_all(&key).map_err(|e| e.to_string())?;
+
+    Ok(key)
+}
+
+/// Rename a file we can't read to `<name>.unreadable-<timestamp>` so a later
+/// save starts fresh without destroying it
+pub(crate) fn set_aside(path: &Path) -> Result<PathBuf, String> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".unreadable-{}", Utc::now().format("%Y%m%d%H%M%S%3f")));
+    let target = path.with_file_name(name);
+    std::fs::rename(path, &target).map_err(|e| e.to_string())?;
+    log::warn!("Moved unreadable {} to {}", path.display(), target.display());
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("servionx-site-id-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_sites_get_distinct_stable_identities() {
+        let dir = temp_dir();
+        let engine = PrivacyEngine::open(&dir);
+        let store = SiteIdentityStore::open(&dir).unwrap();
+
+        let a = store.identity_for(&engine, "https://www.example.com/page");
+        let a_again = store.identity_for(&engine, "https://shop.example.com/cart");
+        let b = store.identity_for(&engine, "https://other.org");
+
+        assert_eq!(a.id, a_again.id);
+        assert_ne!(a.id, b.id);
+
+        // Survives a restart
+        let reopened = SiteIdentityStore::open(&dir).unwrap();
+        assert_eq!(reopened.get("example.com").unwrap().identity.id, a.id);
+
+        // Stored encrypted, not as plain JSON
+        let raw = std::fs::read_to_string(dir.join(STORE_FILE)).unwrap();
+        assert!(!raw.contains("example.com"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pin_and_reset() {
+        let dir = temp_dir();
+        let engine = PrivacyEngine::open(&dir);
+        let store = SiteIdentityStore::open(&dir).unwrap();
+
+        let original = store.identity_for(&engine, "https://bank.example");
+        store.identity_for(&engine, "https://news.example");
+        store.set_pinned("bank.example", true).unwrap();
+
+        assert_eq!(store.reset_unpinned().unwrap(), 1);
+        assert_eq!(store.get("bank.example").unwrap().identity.id, original.id);
+        assert!(store.get("news.example").is_none());
+
+        let reset = store.reset(&engine, "bank.example").unwrap();
+        assert_ne!(reset.identity.id, original.id);
+        assert!(reset.pinned);
+
+        // Closing a pinned site does not forget it; an unpinned one is
+        store.identity_for(&engine, "https://news.example");
+        assert!(!store.forget_unpinned("bank.example").unwrap());
+        assert!(store.forget_unpinned("https://www.news.example/").unwrap());
+        assert!(store.get("news.example").is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn set_aside_copies(dir: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().contains(".unreadable-"))
+            .collect()
+    }
+
+    #[test]
+    fn test_lost_key_or_unreadable_store_is_never_overwritten() {
+        let dir = temp_dir();
+        let engine = PrivacyEngine::open(&dir);
+        let store = SiteIdentityStore::open(&dir).unwrap();
+        store.identity_for(&engine, "https://example.com");
+        let original = std::fs::read(dir.join(STORE_FILE)).unwrap();
+
+        // Without its key the store is moved aside, intact, before a new key
+        // and a new store replace them
+        std::fs::remove_file(dir.join(KEY_FILE)).unwrap();
+        let store = SiteIdentityStore::open(&dir).unwrap();
+        assert!(store.list().is_empty());
+        store.identity_for(&engine, "https://other.org");
+        let aside = set_aside_copies(&dir);
+        assert_eq!(aside.len(), 1);
+        assert_eq!(std::fs::read(&aside[0]).unwrap(), original);
+
+        // A store the key can't decrypt is moved aside too
+        std::fs::write(dir.join(STORE_FILE), "not encrypted").unwrap();
+        let store = SiteIdentityStore::open(&dir).unwrap();
+        assert!(store.list().is_empty());
+        assert!(!dir.join(STORE_FILE).exists());
+        assert_eq!(set_aside_copies(&dir).len(), 2);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_key_is_created_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let key = load_or_create_key(&dir.join(KEY_FILE), &dir.join(STORE_FILE)).unwrap();
+        assert_eq!(key.len(), 32);
+
+        let mode = std::fs::metadata(dir.join(KEY_FILE)).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(load_or_create_key(&dir.join(KEY_FILE), &dir.join(STORE_FILE)).unwrap(), key);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,75 @@
+// Per-Site Noise Seeds
+// Derives the canvas and audio noise seeds from an identity's noise key and
+// the top-level site, so a site always reads the same noisy values while
+// two sites cannot correlate theirs
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const BLOCK_SIZE: usize = 64;
+
+/// Noise seeds for one site
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteNoise {
+    /// Any non-zero 32-bit value; the page hashes it per pixel
+    pub canvas_seed: u32,
+    /// In the range identities draw theirs from, which the audio layers scale
+    pub audio_seed: u32,
+}
+
+impl SiteNoise {
+    /// Seeds for `site` under `key`. Knowing the seeds of one site tells
+    /// nothing about another without the key.
+    pub fn derive(key: &[u8], site: &str) -> Self {
+        let mac = hmac_sha256(key, format!("servionx-noise:{}", site).as_bytes());
+        let word = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        Self {
+            canvas_seed: word(&mac[0..4]).max(1),
+            audio_seed: 1 + word(&mac[4..8]) % 9999,
+        }
+    }
+}
+
+/// HMAC-SHA256 (RFC 2104)
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let pad = |byte: u8| block.map(|b| b ^ byte);
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_matches_rfc_4231() {
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        let hex: String = mac.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(hex, "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+
+    #[test]
+    fn test_seeds_are_stable_per_site_and_differ_across_sites() {
+        let key = b"identity noise key";
+        let shop = SiteNoise::derive(key, "shop.example");
+        assert_eq!(shop, SiteNoise::derive(key, "shop.example"));
+        assert_ne!(shop, SiteNoise::derive(key, "news.example"));
+        assert_ne!(shop, SiteNoise::derive(b"another key", "shop.example"));
+        assert!(shop.canvas_seed != 0 && (1..10000).contains(&shop.audio_seed));
+    }
+}
@@ -0,0 +1,336 @@
+// Site Protection Profiles
+// Chooses per site which protection layers run, from presets or a custom
+// mix, and keeps the choices on disk
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+use super::injection::ScriptTemplate;
+use super::{site, ReferrerPolicy};
+use crate::browser::SecuritySettings;
+use crate::settings::BrowserSettings;
+
+const STORE_FILE: &str = "site_profiles.json";
+
+/// The protection layers that can be switched per site
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtectionLayers {
+    pub canvas_noise: bool,
+    pub webgl_spoof: bool,
+    pub ua_spoof: bool,
+    pub storage_partitioning: bool,
+    pub referrer_policy: ReferrerPolicy,
+    pub tracker_blocking: bool,
+    pub javascript: bool,
+}
+
+impl Default for ProtectionLayers {
+    fn default() -> Self {
+        ProfilePreset::Balanced.layers()
+    }
+}
+
+impl ProtectionLayers {
+    /// Layers for a site without a profile, taken from the global settings
+    pub fn from_settings(settings: &BrowserSettings, security: &SecuritySettings) -> Self {
+        Self {
+            canvas_noise: settings.spoof_fingerprint,
+            webgl_spoof: settings.spoof_fingerprint,
+            ua_spoof: settings.spoof_fingerprint,
+            storage_partitioning: settings.partition_storage,
+            referrer_policy: if settings.strip_referrer { ReferrerPolicy::NoReferrer } else { ReferrerPolicy::Full },
+            tracker_blocking: security.tracker_blocking,
+            javascript: security.javascript_enabled,
+        }
+    }
+
+    /// Layers for a whitelisted site: it gets the real canvas, WebGL and
+    /// user agent, everything else stays as configured
+    pub fn real_data(self) -> Self {
+        Self {
+            canvas_noise: false,
+            webgl_spoof: false,
+            ua_spoof: false,
+            ..self
+        }
+    }
+
+    /// Every protective layer on, for sites caught fingerprinting.
+    /// JavaScript stays as it was so the site keeps working.
+    pub fn escalated(self) -> Self {
+        Self {
+            javascript: self.javascript,
+            ..ProfilePreset::Strict.layers()
+        }
+    }
+
+    /// Whether any part of the fingerprint is spoofed
+    pub fn spoofs_fingerprint(&self) -> bool {
+        self.canvas_noise || self.webgl_spoof || self.ua_spoof
+    }
+
+    /// The global settings as they apply to a page under these layers, so
+    /// modules switched by settings follow the site's profile
+    pub fn apply_to(&self, settings: &BrowserSettings) -> BrowserSettings {
+        BrowserSettings {
+            spoof_fingerprint: self.spoofs_fingerprint(),
+            partition_storage: self.storage_partitioning,
+            strip_referrer: self.referrer_policy != ReferrerPolicy::Full,
+            ..settings.clone()
+        }
+    }
+
+    /// Script that keeps the page's own scripts from running. A CSP added
+    /// before the first page script blocks it, while the injected modules
+    /// (which run first) are unaffected. Empty when JavaScript is allowed.
+    pub fn get_injection_script(&self) -> String {
+        if self.javascript {
+            return String::new();
+        }
+        ScriptTemplate::new(r#"
+(function() {
+    'use strict';
+
+    const meta = document.createElement('meta');
+    meta.httpEquiv = 'Content-Security-Policy';
+    meta.content = @@policy@@;
+    const insert = () => (document.head || document.documentElement).prepend(meta);
+
+    if (document.documentElement) {
+        insert();
+    } else {
+        new MutationObserver((_, observer) => {
+            if (document.documentElement) {
+                observer.disconnect();
+                insert();
+            }
+        }).observe(document, { childList: true });
+    }
+
+    console.log('[ServionX] ✓ JavaScript disabled for this site');
+})();
+"#)
+            .bind("policy", &"script-src 'none'")
+            .render()
+    }
+}
+
+/// Ready-made layer combinations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProfilePreset {
+    /// Every layer on and the page's own JavaScript off
+    Strict,
+    /// Every layer on, origin-only referrers
+    Balanced,
+    /// A real, stable device fingerprint and shared storage, which fraud
+    /// checks and cross-domain logins (SSO, 3-D Secure) rely on
+    BankingCompatible,
+}
+
+impl ProfilePreset {
+    pub const ALL: [ProfilePreset; 3] = [
+        ProfilePreset::Strict,
+        ProfilePreset::Balanced,
+        ProfilePreset::BankingCompatible,
+    ];
+
+    pub fn layers(&self) -> ProtectionLayers {
+        match self {
+            ProfilePreset::Strict => ProtectionLayers {
+                canvas_noise: true,
+                webgl_spoof: true,
+                ua_spoof: true,
+                storage_partitioning: true,
+                referrer_policy: ReferrerPolicy::NoReferrer,
+                tracker_blocking: true,
+                javascript: false,
+            },
+            ProfilePreset::Balanced => ProtectionLayers {
+                canvas_noise: true,
+                webgl_spoof: true,
+                ua_spoof: true,
+                storage_partitioning: true,
+                referrer_policy: ReferrerPolicy::OriginOnly,
+                tracker_blocking: true,
+                javascript: true,
+            },
+            ProfilePreset::BankingCompatible => ProtectionLayers {
+                canvas_noise: false,
+                webgl_spoof: false,
+                ua_spoof: false,
+                storage_partitioning: false,
+                referrer_policy: ReferrerPolicy::SameOrigin,
+                tracker_blocking: true,
+                javascript: true,
+            },
+        }
+    }
+}
+
+/// The layers chosen for one registrable domain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteProfile {
+    pub site: String,
+    /// The preset the layers came from; `None` once they were customised
+    pub preset: Option<ProfilePreset>,
+    pub layers: ProtectionLayers,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Per-site protection profiles, persisted as JSON in the app data directory
+pub struct SiteProfileStore {
+    data_dir: PathBuf,
+    profiles: RwLock<HashMap<String, SiteProfile>>,
+}
+
+impl SiteProfileStore {
+    /// Open the store in the default app data directory
+    pub fn new() -> Result<Self, String> {
+        let data_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ServionX Browser");
+        Self::open(&data_dir)
+    }
+
+    pub fn open(data_dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(data_dir).map_err(|e| e.to_string())?;
+
+        let store = Self {
+            data_dir: data_dir.to_path_buf(),
+            profiles: RwLock::new(HashMap::new()),
+        };
+        match store.load_from_disk() {
+            Ok(profiles) => *store.profiles.write().unwrap() = profiles,
+            Err(e) => log::warn!("Could not load site profiles, starting empty: {}", e),
+        }
+        Ok(store)
+    }
+
+    fn load_from_disk(&self) -> Result<HashMap<String, SiteProfile>, String> {
+        let path = self.data_dir.join(STORE_FILE);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    fn save_to_disk(&self) -> Result<(), String> {
+        let json = {
+            let profiles = self.profiles.read().unwrap();
+            serde_json::to_string_pretty(&*profiles).map_err(|e| e.to_string())?
+        };
+        std::fs::write(self.data_dir.join(STORE_FILE), json).map_err(|e| e.to_string())
+    }
+
+    /// Profile of the site `site_or_url` belongs to, if one was set
+    pub fn get(&self, site_or_url: &str) -> Option<SiteProfile> {
+        let site = site::registrable_domain(site_or_url)?;
+        self.profiles.read().unwrap().get(&site).cloned()
+    }
+
+    /// All profiles, sorted by site
+    pub fn list(&self) -> Vec<SiteProfile> {
+        let mut list: Vec<SiteProfile> = self.profiles.read().unwrap().values().cloned().collect();
+        list.sort_by(|a, b| a.site.cmp(&b.site));
+        list
+    }
+
+    /// Apply a preset to a site
+    pub fn set_preset(&self, site_or_url: &str, preset: ProfilePreset) -> Result<SiteProfile, String> {
+        self.set(site_or_url, Some(preset), preset.layers())
+    }
+
+    /// Give a site a custom mix of layers
+    pub fn set_layers(&self, site_or_url: &str, layers: ProtectionLayers) -> Result<SiteProfile, String> {
+        let preset = ProfilePreset::ALL.into_iter().find(|p| p.layers() == layers);
+        self.set(site_or_url, preset, layers)
+    }
+
+    fn set(
+        &self,
+        site_or_url: &str,
+        preset: Option<ProfilePreset>,
+        layers: ProtectionLayers,
+    ) -> Result<SiteProfile, String> {
+        let site = site::registrable_domain(site_or_url).ok_or("Invalid site")?;
+        let profile = SiteProfile {
+            site: site.clone(),
+            preset,
+            layers,
+            updated_at: Utc::now(),
+        };
+        self.profiles.write().unwrap().insert(site.clone(), profile.clone());
+        self.save_to_disk()?;
+
+        log::info!("Protection profile for {} set to {:?}", site, preset);
+        Ok(profile)
+    }
+
+    /// Drop a site's profile so it follows the global settings again;
+    /// returns whether it had one
+    pub fn remove(&self, site_or_url: &str) -> Result<bool, String> {
+        let site = site::registrable_domain(site_or_url).ok_or("Invalid site")?;
+        let removed = self.profiles.write().unwrap().remove(&site).is_some();
+        if removed {
+            self.save_to_disk()?;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("servionx-site-profiles-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_profiles_persist_per_site() {
+        let dir = temp_dir();
+        let store = SiteProfileStore::open(&dir).unwrap();
+
+        store.set_preset("https://online.bank.example/login", ProfilePreset::BankingCompatible).unwrap();
+        let custom = ProtectionLayers { javascript: false, ..ProfilePreset::Balanced.layers() };
+        assert_eq!(store.set_layers("news.example", custom.clone()).unwrap().preset, None);
+        assert_eq!(
+            store.set_layers("shop.example", ProfilePreset::Strict.layers()).unwrap().preset,
+            Some(ProfilePreset::Strict)
+        );
+
+        let reopened = SiteProfileStore::open(&dir).unwrap();
+        let bank = reopened.get("https://www.bank.example/").unwrap();
+        assert_eq!(bank.preset, Some(ProfilePreset::BankingCompatible));
+        assert!(!bank.layers.spoofs_fingerprint());
+        assert_eq!(reopened.get("news.example").unwrap().layers, custom);
+        assert!(reopened.get("other.example").is_none());
+
+        assert!(reopened.remove("bank.example").unwrap());
+        assert!(!reopened.remove("bank.example").unwrap());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_layers_switch_settings_driven_modules() {
+        let settings = BrowserSettings::default();
+        let defaults = ProtectionLayers::from_settings(&settings, &SecuritySettings::default());
+        assert!(defaults.spoofs_fingerprint() && defaults.javascript);
+        assert!(defaults.get_injection_script().is_empty());
+
+        let banking = ProfilePreset::BankingCompatible.layers().apply_to(&settings);
+        assert!(!banking.spoof_fingerprint && !banking.partition_storage && banking.strip_referrer);
+
+        let strict = ProfilePreset::Strict.layers();
+        assert!(strict.get_injection_script().contains("script-src 'none'"));
+        assert!(!defaults.real_data().spoofs_fingerprint());
+    }
+}
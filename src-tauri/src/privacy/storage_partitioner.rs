@@ -1,101 +1,387 @@
 // Storage Partitioning
-// Isolates localStorage, sessionStorage, and IndexedDB per first-party origin
+// Gives every top-level site its own webview data store, so cookies,
+// localStorage, IndexedDB, the Cache API, service workers and BroadcastChannel
+// are isolated per site, and per container for container tabs. Keeps a
+// registry of the partitions on disk.
 
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
 
-/// Storage partitioning controller
+const REGISTRY_FILE: &str = "partitions.json";
+
+/// The data store of one top-level site
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoragePartition {
+    pub site: String,
+    /// Container the partition belongs to; `None` outside containers
+    #[serde(default)]
+    pub container: Option<String>,
+    /// Webview data directory (WebView2, WebKitGTK), named after the site
+    /// and `store_id`
+    pub data_dir: PathBuf,
+    /// Data store identifier, for WKWebView where data directories are
+    /// unsupported. Replaced when the partition is cleared, so a recreated
+    /// partition never shares a directory with one queued for removal.
+    pub store_id: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+}
+
+impl StoragePartition {
+    fn key(&self) -> String {
+        partition_key(self.container.as_deref(), &self.site)
+    }
+
+    /// `store_id` as the 16 bytes WKWebView takes
+    pub fn store_identifier(&self) -> [u8; 16] {
+        uuid::Uuid::parse_str(&self.store_id)
+            .map(|id| id.into_bytes())
+            .unwrap_or_default()
+    }
+}
+
+/// A partition and the disk space its data takes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartitionUsage {
+    pub site: String,
+    pub container: Option<String>,
+    pub bytes: u64,
+    pub created_at: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Registry {
+    partitions: Vec<StoragePartition>,
+    /// Data directories that could not be deleted while a webview held them
+    #[serde(default)]
+    pending_removal: Vec<PathBuf>,
+}
+
+/// Registry of per-site storage partitions
 pub struct StoragePartitioner {
-    enabled: RwLock<bool>,
-    partitioned_sites: RwLock<u64>,
+    root: PathBuf,
+    partitions: RwLock<HashMap<String, StoragePartition>>,
+    pending_removal: RwLock<Vec<PathBuf>>,
 }
 
 impl StoragePartitioner {
-    pub fn new() -> Self {
-        Self {
-            enabled: RwLock::new(true),
-            partitioned_sites: RwLock::new(0),
+    /// Open the partitions in the default app data directory
+    pub fn new() -> Result<Self, String> {
+        let root = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ServionX Browser")
+            .join("partitions");
+        Self::open(&root)
+    }
+
+    /// Open the partitions under `root`, finishing removals a previous run
+    /// could not complete
+    pub fn open(root: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(root).map_err(|e| e.to_string())?;
+
+        let registry = match Self::load_from_disk(root) {
+            Ok(registry) => registry,
+            Err(e) => {
+                log::warn!("Could not load storage partitions, starting empty: {}", e);
+                Registry::default()
+            }
+        };
+        let partitioner = Self {
+            root: root.to_path_buf(),
+            partitions: RwLock::new(
+                registry.partitions.into_iter().map(|p| (p.key(), p)).collect(),
+            ),
+            pending_removal: RwLock::new(Vec::new()),
+        };
+        for dir in registry.pending_removal {
+            partitioner.remove_dir(dir);
         }
+        partitioner.save_to_disk()?;
+        Ok(partitioner)
+    }
+
+    fn load_from_disk(root: &Path) -> Result<Registry, String> {
+        let path = root.join(REGISTRY_FILE);
+        if !path.exists() {
+            return Ok(Registry::default());
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    fn save_to_disk(&self) -> Result<(), String> {
+        let json = {
+            let mut partitions: Vec<StoragePartition> =
+                self.partitions.read().unwrap().values().cloned().collect();
+            partitions.sort_by_key(|p| p.key());
+            let registry = Registry {
+                partitions,
+                pending_removal: self.pending_removal.read().unwrap().clone(),
+            };
+            serde_json::to_string_pretty(&registry).map_err(|e| e.to_string())?
+        };
+        std::fs::write(self.root.join(REGISTRY_FILE), json).map_err(|e| e.to_string())
+    }
+
+    /// The partition of the top-level site of `url`, created on first use.
+    /// `None` for pages without a web site, like the app's own pages.
+    pub fn partition_for(&self, url: &str) -> Result<Option<StoragePartition>, String> {
+        self.partition_in(None, url)
     }
-    
-    /// Enable/disable partitioning
-    pub fn set_enabled(&self, enabled: bool) {
-        *self.enabled.write().unwrap() = enabled;
+
+    /// The partition of the top-level site of `url` inside `container`
+    pub fn partition_in(&self, container: Option<&str>, url: &str) -> Result<Option<StoragePartition>, String> {
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Ok(None);
+        }
+        let Some(site) = super::site::registrable_domain(url) else {
+            return Ok(None);
+        };
+
+        let now = Utc::now();
+        let partition = {
+            let mut partitions = self.partitions.write().unwrap();
+            let key = partition_key(container, &site);
+            let partition = partitions.entry(key).or_insert_with(|| {
+                let store_id = uuid::Uuid::new_v4().to_string();
+                let dir = format!("{}_{}", dir_name(&site), store_id);
+                StoragePartition {
+                    data_dir: match container {
+                        Some(container) => self.root.join("containers").join(dir_name(container)).join(dir),
+                        None => self.root.join(dir),
+                    },
+                    site,
+                    container: container.map(str::to_string),
+                    store_id,
+                    created_at: now,
+                    last_used: now,
+                }
+            });
+            partition.last_used = now;
+            partition.clone()
+        };
+        std::fs::create_dir_all(&partition.data_dir).map_err(|e| e.to_string())?;
+        self.save_to_disk()?;
+        Ok(Some(partition))
     }
-    
-    /// Check if enabled
-    pub fn is_enabled(&self) -> bool {
-        *self.enabled.read().unwrap()
+
+    /// Every partition
+    pub fn partitions(&self) -> Vec<StoragePartition> {
+        self.partitions.read().unwrap().values().cloned().collect()
     }
-    
-    /// Get count of partitioned sites
+
+    /// Number of sites with a partition
     pub fn get_partitioned_count(&self) -> u64 {
-        *self.partitioned_sites.read().unwrap()
-    }
-    
-    /// Generate JavaScript to partition storage
-    pub fn get_injection_script() -> String {
-        r#"
-// Storage Partitioning Protection
-(function() {
-    'use strict';
-    
-    const PARTITION_KEY = location.hostname;
-    
-    // Partition localStorage
-    const originalLocalStorageSetItem = localStorage.setItem.bind(localStorage);
-    const originalLocalStorageGetItem = localStorage.getItem.bind(localStorage);
-    const originalLocalStorageRemoveItem = localStorage.removeItem.bind(localStorage);
-    
-    localStorage.setItem = function(key, value) {
-        return originalLocalStorageSetItem(PARTITION_KEY + '::' + key, value);
-    };
-    
-    localStorage.getItem = function(key) {
-        return originalLocalStorageGetItem(PARTITION_KEY + '::' + key);
-    };
-    
-    localStorage.removeItem = function(key) {
-        return originalLocalStorageRemoveItem(PARTITION_KEY + '::' + key);
-    };
-    
-    // Partition sessionStorage
-    const originalSessionStorageSetItem = sessionStorage.setItem.bind(sessionStorage);
-    const originalSessionStorageGetItem = sessionStorage.getItem.bind(sessionStorage);
-    const originalSessionStorageRemoveItem = sessionStorage.removeItem.bind(sessionStorage);
-    
-    sessionStorage.setItem = function(key, value) {
-        return originalSessionStorageSetItem(PARTITION_KEY + '::' + key, value);
-    };
-    
-    sessionStorage.getItem = function(key) {
-        return originalSessionStorageGetItem(PARTITION_KEY + '::' + key);
-    };
-    
-    sessionStorage.removeItem = function(key) {
-        return originalSessionStorageRemoveItem(PARTITION_KEY + '::' + key);
-    };
-    
-    // Block cross-site cookie access
-    Object.defineProperty(document, 'cookie', {
-        get: function() {
-            // Return only first-party cookies (simulated)
-            return '';
-        },
-        set: function(value) {
-            // Allow setting but isolate
-            return value;
-        },
-        configurable: false
-    });
-    
-    console.log('%c[ServionX] Storage partitioned for: ' + PARTITION_KEY, 'color: #22c55e;');
-})();
-"#.to_string()
+        self.partitions.read().unwrap().len() as u64
+    }
+
+    /// Every partition with its size on disk, largest first
+    pub fn usage(&self) -> Vec<PartitionUsage> {
+        let mut usage: Vec<PartitionUsage> = self.partitions
+            .read()
+            .unwrap()
+            .values()
+            .map(|p| PartitionUsage {
+                site: p.site.clone(),
+                container: p.container.clone(),
+                bytes: dir_size(&p.data_dir),
+                created_at: p.created_at,
+                last_used: p.last_used,
+            })
+            .collect();
+        usage.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.site.cmp(&b.site)));
+        usage
+    }
+
+    /// Delete everything `site` stored, in every container. Its webviews
+    /// must be closed first; data a webview still holds is deleted at the
+    /// next start. Returns the bytes freed.
+    pub fn clear_site(&self, site: &str) -> Result<u64, String> {
+        let bytes = self.clear_where(|p| p.site == site)?
+            .ok_or_else(|| format!("No stored data for {}", site))?;
+        log::info!("Cleared storage partitions of {} ({} bytes)", site, bytes);
+        Ok(bytes)
+    }
+
+    /// Delete one partition: `site` inside `container`. Returns the bytes
+    /// freed.
+    pub fn clear_partition(&self, container: Option<&str>, site: &str) -> Result<u64, String> {
+        self.clear_where(|p| p.site == site && p.container.as_deref() == container)?
+            .ok_or_else(|| format!("No stored data for {}", site))
+    }
+
+    /// Delete everything stored in `container`. Returns the bytes freed.
+    pub fn clear_container(&self, container: &str) -> Result<u64, String> {
+        let bytes = self.clear_where(|p| p.container.as_deref() == Some(container))?.unwrap_or(0);
+        let _ = std::fs::remove_dir(self.root.join("containers").join(dir_name(container)));
+        log::info!("Cleared storage of container {} ({} bytes)", container, bytes);
+        Ok(bytes)
+    }
+
+    /// Delete the partitions of containers not in `containers`, e.g. those of
+    /// ephemeral containers after a crash. Returns the bytes freed.
+    pub fn retain_containers(&self, containers: &[String]) -> Result<u64, String> {
+        let bytes = self.clear_where(|p| p.container.as_ref().is_some_and(|c| !containers.contains(c)))?;
+        Ok(bytes.unwrap_or(0))
     }
+
+    /// Delete the data of every site. Returns the bytes freed.
+    pub fn clear_all(&self) -> Result<u64, String> {
+        let bytes = self.clear_where(|_| true)?.unwrap_or(0);
+        log::info!("Cleared all storage partitions ({} bytes)", bytes);
+        Ok(bytes)
+    }
+
+    /// Remove the matching partitions and their data. `None` if nothing
+    /// matched, else the bytes freed.
+    fn clear_where(&self, matches: impl Fn(&StoragePartition) -> bool) -> Result<Option<u64>, String> {
+        let removed: Vec<StoragePartition> = {
+            let mut partitions = self.partitions.write().unwrap();
+            let keys: Vec<String> = partitions
+                .iter()
+                .filter(|(_, p)| matches(p))
+                .map(|(key, _)| key.clone())
+                .collect();
+            keys.iter().filter_map(|key| partitions.remove(key)).collect()
+        };
+        if removed.is_empty() {
+            return Ok(None);
+        }
+
+        let mut bytes = 0;
+        for partition in removed {
+            bytes += dir_size(&partition.data_dir);
+            self.remove_dir(partition.data_dir);
+        }
+        self.save_to_disk()?;
+        Ok(Some(bytes))
+    }
+
+    /// Delete a data directory, or queue it when a webview still has it open
+    fn remove_dir(&self, dir: PathBuf) {
+        match std::fs::remove_dir_all(&dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                log::warn!("Could not delete {} yet, retrying at next start: {}", dir.display(), e);
+                self.pending_removal.write().unwrap().push(dir);
+            }
+        }
+    }
+}
+
+/// Registry key of a site's partition inside `container`
+fn partition_key(container: Option<&str>, site: &str) -> String {
+    match container {
+        Some(container) => format!("{}/{}", container, site),
+        None => site.to_string(),
+    }
+}
+
+/// Directory name for a site or container; IPv6 hosts carry characters paths can't
+fn dir_name(site: &str) -> String {
+    site.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect()
 }
 
-impl Default for StoragePartitioner {
-    fn default() -> Self {
-        Self::new()
+/// Total size of the files under `dir`
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("servionx-partitions-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_partitions_are_per_site_and_persist() {
+        let root = temp_dir();
+        let partitioner = StoragePartitioner::open(&root).unwrap();
+
+        let shop = partitioner.partition_for("https://www.shop.example/cart").unwrap().unwrap();
+        let same = partitioner.partition_for("https://login.shop.example/").unwrap().unwrap();
+        let news = partitioner.partition_for("https://news.example/").unwrap().unwrap();
+        assert_eq!(shop.data_dir, same.data_dir);
+        assert_eq!(shop.store_id, same.store_id);
+        assert_ne!(shop.data_dir, news.data_dir);
+        assert_ne!(shop.store_identifier(), news.store_identifier());
+        assert!(shop.data_dir.is_dir());
+        assert!(partitioner.partition_for("tauri://localhost/index.html").unwrap().is_none());
+
+        let reopened = StoragePartitioner::open(&root).unwrap();
+        assert_eq!(reopened.get_partitioned_count(), 2);
+        assert_eq!(reopened.partition_for("https://shop.example/").unwrap().unwrap().store_id, shop.store_id);
+    }
+
+    #[test]
+    fn test_usage_and_clear_by_site() {
+        let partitioner = StoragePartitioner::open(&temp_dir()).unwrap();
+        let shop = partitioner.partition_for("https://shop.example/").unwrap().unwrap();
+        partitioner.partition_for("https://news.example/").unwrap();
+        std::fs::create_dir_all(shop.data_dir.join("IndexedDB")).unwrap();
+        std::fs::write(shop.data_dir.join("IndexedDB").join("db"), [0u8; 300]).unwrap();
+        std::fs::write(shop.data_dir.join("Cookies"), [0u8; 20]).unwrap();
+
+        let usage = partitioner.usage();
+        assert_eq!(usage[0].site, "shop.example");
+        assert_eq!(usage[0].bytes, 320);
+        assert_eq!(usage[1].bytes, 0);
+
+        assert_eq!(partitioner.clear_site("shop.example").unwrap(), 320);
+        assert!(!shop.data_dir.exists());
+        assert!(partitioner.clear_site("shop.example").is_err());
+        let fresh = partitioner.partition_for("https://shop.example/").unwrap().unwrap();
+        assert_ne!(fresh.store_id, shop.store_id);
+        // A directory queued for removal is never reused
+        assert_ne!(fresh.data_dir, shop.data_dir);
+
+        partitioner.clear_all().unwrap();
+        assert_eq!(partitioner.get_partitioned_count(), 0);
+    }
+
+    #[test]
+    fn test_containers_have_their_own_partitions() {
+        let partitioner = StoragePartitioner::open(&temp_dir()).unwrap();
+        let plain = partitioner.partition_for("https://shop.example/").unwrap().unwrap();
+        let work = partitioner.partition_in(Some("work"), "https://shop.example/").unwrap().unwrap();
+        let temporary = partitioner.partition_in(Some("temporary"), "https://shop.example/").unwrap().unwrap();
+        assert_ne!(plain.data_dir, work.data_dir);
+        assert_ne!(work.data_dir, temporary.data_dir);
+        assert_ne!(work.store_id, temporary.store_id);
+
+        partitioner.clear_container("temporary").unwrap();
+        assert!(!temporary.data_dir.exists());
+        assert!(work.data_dir.exists());
+
+        partitioner.partition_in(Some("temporary"), "https://news.example/").unwrap();
+        partitioner.retain_containers(&["work".to_string()]).unwrap();
+        assert_eq!(partitioner.get_partitioned_count(), 2);
+
+        partitioner.clear_partition(None, "shop.example").unwrap();
+        assert!(!plain.data_dir.exists());
+        partitioner.clear_site("shop.example").unwrap();
+        assert_eq!(partitioner.get_partitioned_count(), 0);
     }
 }
@@ -4,6 +4,7 @@
 
 use serde::{Deserialize, Serialize};
 use rand::Rng;
+use super::injection::ScriptTemplate;
 
 /// Ultimate privacy protection - covers EVERYTHING
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,7 +25,8 @@ pub struct UltimatePrivacyProtection {
     
     // Client Hints
     pub spoof_client_hints: bool,
-    pub fake_ua_data: ClientHintsData,
+    /// `None` when the spoofed browser does not expose `navigator.userAgentData`
+    pub fake_ua_data: Option<ClientHintsData>,
     
     // Audio
     pub deep_audio_protection: bool,
@@ -38,6 +40,10 @@ pub struct UltimatePrivacyProtection {
     
     // Canvas
     pub deep_canvas_protection: bool,
+    pub canvas_noise_seed: u32,
+    
+    // WebGL
+    pub deep_webgl_protection: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,6 +55,10 @@ pub struct ClientHintsData {
     pub architecture: String,
     pub bitness: String,
     pub model: String,
+    pub ua_full_version: String,
+    /// Brands with full versions, for `fullVersionList`
+    #[serde(default)]
+    pub full_version_list: Vec<(String, String)>,
 }
 
 impl Default for UltimatePrivacyProtection {
@@ -69,31 +79,51 @@ impl UltimatePrivacyProtection {
             spoof_media_queries: true,
             block_computed_styles_leak: true,
             spoof_client_hints: true,
-            fake_ua_data: ClientHintsData {
-                brands: vec![
-                    ("Chromium".to_string(), "120".to_string()),
-                    ("Google Chrome".to_string(), "120".to_string()),
-                    ("Not_A Brand".to_string(), "24".to_string()),
-                ],
-                mobile: false,
-                platform: "Windows".to_string(),
-                platform_version: "10.0.0".to_string(),
-                architecture: "x86".to_string(),
-                bitness: "64".to_string(),
-                model: "".to_string(),
-            },
+            // Hints of a current Chrome on Windows until an identity is applied
+            fake_ua_data: super::coherence::client_hints_for(
+                &super::UserAgentGenerator::new().generate_for_with_rng(
+                    super::coherence::OsFamily::Windows,
+                    super::coherence::BrowserFamily::Chrome,
+                    chrono::Utc::now().date_naive(),
+                    &mut rng,
+                ),
+                "",
+            ),
             deep_audio_protection: true,
             audio_noise_seed: rng.gen::<f64>(),
             block_speech_recognition: true,
             reduce_performance_precision: true,
             deep_canvas_protection: true,
+            canvas_noise_seed: rng.gen_range(1..10000),
+            deep_webgl_protection: true,
+        }
+    }
+    
+    /// Copy of this protection with client hints and canvas and audio noise
+    /// taken from `identity`
+    pub fn with_identity(&self, identity: &super::FakeIdentity) -> Self {
+        Self {
+            fake_ua_data: identity.client_hints.clone(),
+            canvas_noise_seed: identity.fingerprint.canvas_noise_seed,
+            audio_noise_seed: f64::from(identity.fingerprint.audio_noise_seed) / 10000.0,
+            ..self.clone()
+        }
+    }
+    
+    /// Copy of this protection limited to the layers a site's profile allows
+    pub fn with_layers(&self, layers: &super::ProtectionLayers) -> Self {
+        Self {
+            spoof_client_hints: self.spoof_client_hints && layers.ua_spoof,
+            deep_canvas_protection: self.deep_canvas_protection && layers.canvas_noise,
+            deep_webgl_protection: self.deep_webgl_protection && layers.webgl_spoof,
+            ..self.clone()
         }
     }
     
     /// Generate the ULTIMATE injection script - 50+ protections
     pub fn get_ultimate_injection_script(&self) -> String {
-        format!(r#"
-(function() {{
+        ScriptTemplate::new(r#"
+(function() {
     'use strict';
     
     // ================================================================
@@ -104,466 +134,497 @@ impl UltimatePrivacyProtection {
     
     console.log('[ServionX] 🔐 ULTIMATE PRIVACY PROTECTION INITIALIZING...');
     
-    const noiseSeed = {math_noise};
-    const audioNoise = {audio_noise};
+    const noiseSeed = @@math_noise@@;
+    const audioNoise = @@audio_noise@@;
+    const canvasNoise = @@canvas_noise@@;
+    // -1, 0 or +1 for byte `index` of an image, hashed from the canvas seed
+    // (xorshift and a multiply mix) so every bit of the seed counts
+    const canvasNoiseAt = (index) => {
+        let x = canvasNoise ^ Math.imul(index + 1, 0x9E3779B9);
+        x ^= x << 13;
+        x ^= x >>> 17;
+        x ^= x << 5;
+        x = Math.imul(x ^ (x >>> 16), 0x45D9F3B);
+        x ^= x >>> 16;
+        return (x >>> 0) % 3 - 1;
+    };
+    const deepCanvas = @@deep_canvas@@;
+    const deepWebgl = @@deep_webgl@@;
     
     // ==================== WEBRTC STUN/TURN BLOCKING ====================
     // Block WebRTC from leaking real IP through STUN/TURN servers
     const origRTCPeerConnection = window.RTCPeerConnection;
     const origWebkitRTCPeerConnection = window.webkitRTCPeerConnection;
     
-    const safeRTCPeerConnection = function(config) {{
+    const safeRTCPeerConnection = function(config) {
         // Remove all ICE servers to prevent IP leak
-        if (config && config.iceServers) {{
+        if (config && config.iceServers) {
             console.log('[ServionX] ✗ Blocked ' + config.iceServers.length + ' STUN/TURN servers');
             config.iceServers = [];
-        }}
+        }
         
         const pc = new origRTCPeerConnection(config);
         
         // Override createDataChannel to prevent fingerprinting
         const origCreateDataChannel = pc.createDataChannel.bind(pc);
-        pc.createDataChannel = function(label, options) {{
+        pc.createDataChannel = function(label, options) {
             console.log('[ServionX] ⚠ DataChannel created:', label);
             return origCreateDataChannel(label, options);
-        }};
+        };
         
         return pc;
-    }};
+    };
     
     safeRTCPeerConnection.prototype = origRTCPeerConnection.prototype;
     window.RTCPeerConnection = safeRTCPeerConnection;
-    if (origWebkitRTCPeerConnection) {{
+    if (origWebkitRTCPeerConnection) {
         window.webkitRTCPeerConnection = safeRTCPeerConnection;
-    }}
+    }
     console.log('[ServionX] ✓ WebRTC STUN/TURN servers blocked');
     
     // ==================== MATH FINGERPRINT PROTECTION ====================
     // Reduce entropy from math functions used for fingerprinting
     const origMathRandom = Math.random;
     let randomCounter = 0;
-    Math.random = function() {{
+    Math.random = function() {
         randomCounter++;
         const base = origMathRandom();
         // Add deterministic noise based on seed
         return (base + noiseSeed * 0.00001) % 1;
-    }};
+    };
     
     // Spoof trigonometric functions (used in canvas fingerprinting)
     const origSin = Math.sin;
     const origCos = Math.cos;
     const origTan = Math.tan;
     
-    Math.sin = function(x) {{
+    Math.sin = function(x) {
         return origSin(x) + (noiseSeed * 0.0000001);
-    }};
-    Math.cos = function(x) {{
+    };
+    Math.cos = function(x) {
         return origCos(x) + (noiseSeed * 0.0000001);
-    }};
-    Math.tan = function(x) {{
+    };
+    Math.tan = function(x) {
         return origTan(x) + (noiseSeed * 0.0000001);
-    }};
+    };
     console.log('[ServionX] ✓ Math function fingerprinting protected');
     
     // ==================== KEYBOARD LAYOUT FINGERPRINTING ====================
     // Normalize keyboard events to prevent layout detection
-    const normalizeKeyboardEvent = function(e) {{
+    const normalizeKeyboardEvent = function(e) {
         // Don't expose actual keyboard layout
-        Object.defineProperty(e, 'code', {{
-            get: function() {{
+        Object.defineProperty(e, 'code', {
+            get: function() {
                 // Return generic code
-                if (e.key && e.key.length === 1) {{
+                if (e.key && e.key.length === 1) {
                     return 'Key' + e.key.toUpperCase();
-                }}
+                }
                 return e._originalCode || 'Unidentified';
-            }}
-        }});
-    }};
+            }
+        });
+    };
     
     document.addEventListener('keydown', normalizeKeyboardEvent, true);
     document.addEventListener('keyup', normalizeKeyboardEvent, true);
     document.addEventListener('keypress', normalizeKeyboardEvent, true);
     
     // Block keyboard getLayoutMap
-    if (navigator.keyboard) {{
-        navigator.keyboard.getLayoutMap = async () => {{
+    if (navigator.keyboard) {
+        navigator.keyboard.getLayoutMap = async () => {
             console.log('[ServionX] ✗ Keyboard layout map blocked');
             return new Map();
-        }};
-    }}
+        };
+    }
     console.log('[ServionX] ✓ Keyboard layout fingerprinting blocked');
     
     // ==================== CSS FINGERPRINTING PROTECTION ====================
     // Spoof CSS media queries used for fingerprinting
     const origMatchMedia = window.matchMedia;
-    window.matchMedia = function(query) {{
+    window.matchMedia = function(query) {
         const result = origMatchMedia.call(window, query);
         
         // Spoof specific fingerprinting queries
-        if (query.includes('prefers-color-scheme')) {{
-            return {{
+        if (query.includes('prefers-color-scheme')) {
+            return {
                 matches: false,
                 media: query,
                 onchange: null,
-                addListener: () => {{}},
-                removeListener: () => {{}},
-                addEventListener: () => {{}},
-                removeEventListener: () => {{}},
+                addListener: () => {},
+                removeListener: () => {},
+                addEventListener: () => {},
+                removeEventListener: () => {},
                 dispatchEvent: () => true
-            }};
-        }}
+            };
+        }
         
-        if (query.includes('prefers-reduced-motion')) {{
-            return {{
+        if (query.includes('prefers-reduced-motion')) {
+            return {
                 matches: false,
                 media: query,
                 onchange: null,
-                addListener: () => {{}},
-                removeListener: () => {{}},
-                addEventListener: () => {{}},
-                removeEventListener: () => {{}},
+                addListener: () => {},
+                removeListener: () => {},
+                addEventListener: () => {},
+                removeEventListener: () => {},
                 dispatchEvent: () => true
-            }};
-        }}
+            };
+        }
         
         // Spoof resolution queries
-        if (query.includes('resolution') || query.includes('device-pixel-ratio')) {{
-            return {{
+        if (query.includes('resolution') || query.includes('device-pixel-ratio')) {
+            return {
                 matches: true,
                 media: '(min-resolution: 96dpi)',
                 onchange: null,
-                addListener: () => {{}},
-                removeListener: () => {{}},
-                addEventListener: () => {{}},
-                removeEventListener: () => {{}},
+                addListener: () => {},
+                removeListener: () => {},
+                addEventListener: () => {},
+                removeEventListener: () => {},
                 dispatchEvent: () => true
-            }};
-        }}
+            };
+        }
         
         return result;
-    }};
+    };
     
     // Block getComputedStyle fingerprinting
     const origGetComputedStyle = window.getComputedStyle;
-    window.getComputedStyle = function(element, pseudoElt) {{
+    window.getComputedStyle = function(element, pseudoElt) {
         const style = origGetComputedStyle.call(window, element, pseudoElt);
         
         // Wrap to hide certain properties used for fingerprinting
-        return new Proxy(style, {{
-            get: function(target, prop) {{
-                if (prop === 'fontFamily') {{
+        return new Proxy(style, {
+            get: function(target, prop) {
+                if (prop === 'fontFamily') {
                     return 'Arial, sans-serif';
-                }}
+                }
                 return target[prop];
-            }}
-        }});
-    }};
+            }
+        });
+    };
     console.log('[ServionX] ✓ CSS fingerprinting protected');
     
     // ==================== CLIENT HINTS SPOOFING ====================
     // Spoof User-Agent Client Hints (Sec-CH-UA headers)
-    Object.defineProperty(navigator, 'userAgentData', {{
-        get: function() {{
-            return {{
-                brands: [
-                    {{ brand: 'Chromium', version: '120' }},
-                    {{ brand: 'Google Chrome', version: '120' }},
-                    {{ brand: 'Not_A Brand', version: '24' }}
-                ],
-                mobile: false,
-                platform: 'Windows',
-                getHighEntropyValues: async function(hints) {{
-                    console.log('[ServionX] Client hints request:', hints);
-                    return {{
-                        brands: this.brands,
-                        mobile: false,
-                        platform: 'Windows',
-                        platformVersion: '10.0.0',
-                        architecture: 'x86',
-                        bitness: '64',
-                        model: '',
-                        uaFullVersion: '120.0.0.0',
-                        fullVersionList: this.brands
-                    }};
-                }},
-                toJSON: function() {{
-                    return {{
-                        brands: this.brands,
-                        mobile: this.mobile,
-                        platform: this.platform
-                    }};
-                }}
-            }};
-        }},
-        configurable: true
-    }});
+    const spoofClientHints = @@spoof_client_hints@@;
+    const clientHints = @@client_hints@@;
+    if (spoofClientHints && clientHints) {
+        const brands = clientHints.brands.map(([brand, version]) => ({ brand, version }));
+        Object.defineProperty(navigator, 'userAgentData', {
+            get: function() {
+                return {
+                    brands: brands,
+                    mobile: clientHints.mobile,
+                    platform: clientHints.platform,
+                    getHighEntropyValues: async function(hints) {
+                        console.log('[ServionX] Client hints request:', hints);
+                        return {
+                            brands: this.brands,
+                            mobile: clientHints.mobile,
+                            platform: clientHints.platform,
+                            platformVersion: clientHints.platform_version,
+                            architecture: clientHints.architecture,
+                            bitness: clientHints.bitness,
+                            model: clientHints.model,
+                            uaFullVersion: clientHints.ua_full_version,
+                            fullVersionList: clientHints.full_version_list.map(([brand, version]) => ({ brand, version }))
+                        };
+                    },
+                    toJSON: function() {
+                        return {
+                            brands: this.brands,
+                            mobile: this.mobile,
+                            platform: this.platform
+                        };
+                    }
+                };
+            },
+            configurable: true
+        });
+    } else if (spoofClientHints) {
+        // Firefox and Safari do not implement User-Agent Client Hints
+        Object.defineProperty(navigator, 'userAgentData', {
+            get: () => undefined,
+            configurable: true
+        });
+    }
     console.log('[ServionX] ✓ Client Hints spoofed');
     
     // ==================== DEEP AUDIO FINGERPRINT PROTECTION ====================
     // Advanced AudioContext fingerprint protection
     const origAudioContext = window.AudioContext || window.webkitAudioContext;
-    if (origAudioContext) {{
+    if (origAudioContext) {
         const audioContextPrototype = origAudioContext.prototype;
         
         // Override createOscillator
         const origCreateOscillator = audioContextPrototype.createOscillator;
-        audioContextPrototype.createOscillator = function() {{
+        audioContextPrototype.createOscillator = function() {
             const osc = origCreateOscillator.call(this);
             // Add noise to frequency
             const origFrequency = osc.frequency;
-            Object.defineProperty(osc, 'frequency', {{
-                get: function() {{
-                    return {{
+            Object.defineProperty(osc, 'frequency', {
+                get: function() {
+                    return {
                         ...origFrequency,
                         value: origFrequency.value + (audioNoise * 0.01)
-                    }};
-                }}
-            }});
+                    };
+                }
+            });
             return osc;
-        }};
+        };
         
         // Override createAnalyser
         const origCreateAnalyser = audioContextPrototype.createAnalyser;
-        audioContextPrototype.createAnalyser = function() {{
+        audioContextPrototype.createAnalyser = function() {
             const analyser = origCreateAnalyser.call(this);
             
             // Override getFloatFrequencyData
             const origGetFloatFrequencyData = analyser.getFloatFrequencyData.bind(analyser);
-            analyser.getFloatFrequencyData = function(array) {{
+            analyser.getFloatFrequencyData = function(array) {
                 origGetFloatFrequencyData(array);
                 // Add noise to frequency data
-                for (let i = 0; i < array.length; i++) {{
-                    array[i] += (audioNoise * 0.1 * Math.random());
-                }}
-            }};
+                // Varies per bin but not per call, so re-reads agree
+                for (let i = 0; i < array.length; i++) {
+                    array[i] += audioNoise * 0.1 * (((i * 7919) % 101) / 101);
+                }
+            };
             
             return analyser;
-        }};
+        };
         
         // Override createDynamicsCompressor
         const origCreateDynamicsCompressor = audioContextPrototype.createDynamicsCompressor;
-        audioContextPrototype.createDynamicsCompressor = function() {{
+        audioContextPrototype.createDynamicsCompressor = function() {
             const compressor = origCreateDynamicsCompressor.call(this);
             // Spoof reduction value
-            Object.defineProperty(compressor, 'reduction', {{
-                get: function() {{
+            Object.defineProperty(compressor, 'reduction', {
+                get: function() {
                     return -20 + (audioNoise * 5);
-                }}
-            }});
+                }
+            });
             return compressor;
-        }};
-    }}
+        };
+    }
     console.log('[ServionX] ✓ Deep audio fingerprinting protected');
     
     // ==================== SPEECH RECOGNITION BLOCKING ====================
-    if (window.SpeechRecognition || window.webkitSpeechRecognition) {{
+    if (window.SpeechRecognition || window.webkitSpeechRecognition) {
         window.SpeechRecognition = undefined;
         window.webkitSpeechRecognition = undefined;
         console.log('[ServionX] ✓ Speech Recognition API blocked');
-    }}
+    }
     
     // ==================== PERFORMANCE FINGERPRINTING PROTECTION ====================
     // High-precision timing attacks
     const origPerformanceNow = performance.now.bind(performance);
-    performance.now = function() {{
+    performance.now = function() {
         // Reduce precision to 5ms
         return Math.floor(origPerformanceNow() / 5) * 5;
-    }};
+    };
     
     // performance.timing spoofing
-    if (performance.timing) {{
+    if (performance.timing) {
         const fakeTimingOffset = Math.floor(Math.random() * 100);
-        Object.defineProperty(performance, 'timing', {{
-            get: function() {{
-                return new Proxy(performance.timing, {{
-                    get: function(target, prop) {{
+        Object.defineProperty(performance, 'timing', {
+            get: function() {
+                return new Proxy(performance.timing, {
+                    get: function(target, prop) {
                         const value = target[prop];
-                        if (typeof value === 'number' && value > 0) {{
+                        if (typeof value === 'number' && value > 0) {
                             return value + fakeTimingOffset;
-                        }}
+                        }
                         return value;
-                    }}
-                }});
-            }}
-        }});
-    }}
+                    }
+                });
+            }
+        });
+    }
     
     // Resource timing - reduce exposure but don't block completely (needed for YouTube)
-    if (performance.getEntries) {{
+    if (performance.getEntries) {
         const origGetEntries = performance.getEntries.bind(performance);
         const origGetEntriesByType = performance.getEntriesByType.bind(performance);
         
         // Allow navigation and resource entries (needed for YouTube/Gmail)
-        performance.getEntries = function() {{
+        performance.getEntries = function() {
             return origGetEntries().filter(e => 
                 e.entryType === 'navigation' || 
                 e.entryType === 'resource' || 
                 e.entryType === 'paint'
             ).slice(0, 50);
-        }};
+        };
         
-        performance.getEntriesByType = function(type) {{
-            if (['navigation', 'resource', 'paint', 'mark', 'measure'].includes(type)) {{
+        performance.getEntriesByType = function(type) {
+            if (['navigation', 'resource', 'paint', 'mark', 'measure'].includes(type)) {
                 return origGetEntriesByType(type).slice(0, 50);
-            }}
+            }
             return [];
-        }};
-    }}
+        };
+    }
     console.log('[ServionX] ✓ Performance timing protected');
     
     // ==================== DEEP CANVAS PROTECTION ====================
     // Enhanced canvas fingerprinting protection
-    const origToDataURL = HTMLCanvasElement.prototype.toDataURL;
-    HTMLCanvasElement.prototype.toDataURL = function(type, quality) {{
-        const ctx = this.getContext('2d');
-        if (ctx) {{
-            // Add invisible noise to canvas
+    if (deepCanvas) {
+        const origToDataURL = HTMLCanvasElement.prototype.toDataURL;
+        HTMLCanvasElement.prototype.toDataURL = function(type, quality) {
+            const ctx = this.getContext('2d');
+            if (!ctx || this.width === 0 || this.height === 0) {
+                return origToDataURL.call(this, type, quality);
+            }
+            // Add invisible noise to a copy; the page's canvas stays as drawn
+            // and reading it twice gives the same result
             const imageData = ctx.getImageData(0, 0, this.width, this.height);
             const data = imageData.data;
-            for (let i = 0; i < data.length; i += 4) {{
+            for (let i = 0; i < data.length; i += 4) {
                 // Add subtle noise to RGB channels
-                data[i] = Math.min(255, Math.max(0, data[i] + Math.floor((noiseSeed * 3) % 3) - 1));
-                data[i + 1] = Math.min(255, Math.max(0, data[i + 1] + Math.floor((noiseSeed * 5) % 3) - 1));
-                data[i + 2] = Math.min(255, Math.max(0, data[i + 2] + Math.floor((noiseSeed * 7) % 3) - 1));
-            }}
-            ctx.putImageData(imageData, 0, 0);
-        }}
-        console.log('[ServionX] ⚠ Canvas fingerprint captured (noise added)');
-        return origToDataURL.call(this, type, quality);
-    }};
-    
-    // toBlob protection
-    const origToBlob = HTMLCanvasElement.prototype.toBlob;
-    HTMLCanvasElement.prototype.toBlob = function(callback, type, quality) {{
-        console.log('[ServionX] ⚠ Canvas toBlob called (protected)');
-        return origToBlob.call(this, callback, type, quality);
-    }};
-    
-    // getImageData protection
-    const origGetImageData = CanvasRenderingContext2D.prototype.getImageData;
-    CanvasRenderingContext2D.prototype.getImageData = function(sx, sy, sw, sh) {{
-        const imageData = origGetImageData.call(this, sx, sy, sw, sh);
-        // Add noise to image data
-        for (let i = 0; i < imageData.data.length; i += 4) {{
-            imageData.data[i] = Math.min(255, Math.max(0, imageData.data[i] + Math.floor((noiseSeed * 11) % 3) - 1));
-        }}
-        return imageData;
-    }};
-    console.log('[ServionX] ✓ Deep canvas fingerprinting protected');
+                data[i] = Math.min(255, Math.max(0, data[i] + canvasNoiseAt(i)));
+                data[i + 1] = Math.min(255, Math.max(0, data[i + 1] + canvasNoiseAt(i + 1)));
+                data[i + 2] = Math.min(255, Math.max(0, data[i + 2] + canvasNoiseAt(i + 2)));
+            }
+            const copy = document.createElement('canvas');
+            copy.width = this.width;
+            copy.height = this.height;
+            copy.getContext('2d').putImageData(imageData, 0, 0);
+            console.log('[ServionX] ⚠ Canvas fingerprint captured (noise added)');
+            return origToDataURL.call(copy, type, quality);
+        };
+    
+        // toBlob protection
+        const origToBlob = HTMLCanvasElement.prototype.toBlob;
+        HTMLCanvasElement.prototype.toBlob = function(callback, type, quality) {
+            console.log('[ServionX] ⚠ Canvas toBlob called (protected)');
+            return origToBlob.call(this, callback, type, quality);
+        };
+    
+        // getImageData protection
+        const origGetImageData = CanvasRenderingContext2D.prototype.getImageData;
+        CanvasRenderingContext2D.prototype.getImageData = function(sx, sy, sw, sh) {
+            const imageData = origGetImageData.call(this, sx, sy, sw, sh);
+            // Add noise to image data
+            for (let i = 0; i < imageData.data.length; i += 4) {
+                imageData.data[i] = Math.min(255, Math.max(0, imageData.data[i] + canvasNoiseAt(i)));
+            }
+            return imageData;
+        };
+        console.log('[ServionX] ✓ Deep canvas fingerprinting protected');
+    }
     
     // ==================== WEBGL DEEP PROTECTION ====================
     // Enhanced WebGL fingerprinting protection
-    const webglContexts = ['webgl', 'webgl2', 'experimental-webgl'];
-    const origGetContext = HTMLCanvasElement.prototype.getContext;
+    if (deepWebgl) {
+        const webglContexts = ['webgl', 'webgl2', 'experimental-webgl'];
+        const origGetContext = HTMLCanvasElement.prototype.getContext;
     
-    HTMLCanvasElement.prototype.getContext = function(type, attributes) {{
-        const ctx = origGetContext.call(this, type, attributes);
+        HTMLCanvasElement.prototype.getContext = function(type, attributes) {
+            const ctx = origGetContext.call(this, type, attributes);
         
-        if (webglContexts.includes(type) && ctx) {{
-            // Override getParameter
-            const origGetParameter = ctx.getParameter.bind(ctx);
-            ctx.getParameter = function(param) {{
-                // Spoof vendor and renderer
-                if (param === ctx.VENDOR) return 'Google Inc.';
-                if (param === ctx.RENDERER) return 'ANGLE (Intel, Intel(R) UHD Graphics 620 Direct3D11 vs_5_0 ps_5_0, D3D11)';
-                if (param === ctx.VERSION) return 'WebGL 1.0 (OpenGL ES 2.0 Chromium)';
-                if (param === ctx.SHADING_LANGUAGE_VERSION) return 'WebGL GLSL ES 1.0 (OpenGL ES GLSL ES 1.0 Chromium)';
+            if (webglContexts.includes(type) && ctx) {
+                // Override getParameter
+                const origGetParameter = ctx.getParameter.bind(ctx);
+                ctx.getParameter = function(param) {
+                    // Spoof vendor and renderer
+                    if (param === ctx.VENDOR) return 'Google Inc.';
+                    if (param === ctx.RENDERER) return 'ANGLE (Intel, Intel(R) UHD Graphics 620 Direct3D11 vs_5_0 ps_5_0, D3D11)';
+                    if (param === ctx.VERSION) return 'WebGL 1.0 (OpenGL ES 2.0 Chromium)';
+                    if (param === ctx.SHADING_LANGUAGE_VERSION) return 'WebGL GLSL ES 1.0 (OpenGL ES GLSL ES 1.0 Chromium)';
                 
-                // Spoof max values to common ones
-                if (param === ctx.MAX_TEXTURE_SIZE) return 16384;
-                if (param === ctx.MAX_VERTEX_ATTRIBS) return 16;
-                if (param === ctx.MAX_VERTEX_UNIFORM_VECTORS) return 4096;
-                if (param === ctx.MAX_VARYING_VECTORS) return 30;
-                if (param === ctx.MAX_FRAGMENT_UNIFORM_VECTORS) return 4096;
+                    // Spoof max values to common ones
+                    if (param === ctx.MAX_TEXTURE_SIZE) return 16384;
+                    if (param === ctx.MAX_VERTEX_ATTRIBS) return 16;
+                    if (param === ctx.MAX_VERTEX_UNIFORM_VECTORS) return 4096;
+                    if (param === ctx.MAX_VARYING_VECTORS) return 30;
+                    if (param === ctx.MAX_FRAGMENT_UNIFORM_VECTORS) return 4096;
                 
-                return origGetParameter(param);
-            }};
+                    return origGetParameter(param);
+                };
             
-            // Override getSupportedExtensions
-            const origGetSupportedExtensions = ctx.getSupportedExtensions.bind(ctx);
-            ctx.getSupportedExtensions = function() {{
-                // Return common extensions only
-                return [
-                    'ANGLE_instanced_arrays',
-                    'EXT_blend_minmax',
-                    'EXT_color_buffer_half_float',
-                    'EXT_float_blend',
-                    'EXT_frag_depth',
-                    'EXT_shader_texture_lod',
-                    'EXT_texture_filter_anisotropic',
-                    'OES_element_index_uint',
-                    'OES_standard_derivatives',
-                    'OES_texture_float',
-                    'OES_texture_float_linear',
-                    'OES_texture_half_float',
-                    'OES_texture_half_float_linear',
-                    'OES_vertex_array_object',
-                    'WEBGL_color_buffer_float',
-                    'WEBGL_compressed_texture_s3tc',
-                    'WEBGL_debug_renderer_info',
-                    'WEBGL_depth_texture',
-                    'WEBGL_draw_buffers',
-                    'WEBGL_lose_context'
-                ];
-            }};
+                // Override getSupportedExtensions
+                const origGetSupportedExtensions = ctx.getSupportedExtensions.bind(ctx);
+                ctx.getSupportedExtensions = function() {
+                    // Return common extensions only
+                    return [
+                        'ANGLE_instanced_arrays',
+                        'EXT_blend_minmax',
+                        'EXT_color_buffer_half_float',
+                        'EXT_float_blend',
+                        'EXT_frag_depth',
+                        'EXT_shader_texture_lod',
+                        'EXT_texture_filter_anisotropic',
+                        'OES_element_index_uint',
+                        'OES_standard_derivatives',
+                        'OES_texture_float',
+                        'OES_texture_float_linear',
+                        'OES_texture_half_float',
+                        'OES_texture_half_float_linear',
+                        'OES_vertex_array_object',
+                        'WEBGL_color_buffer_float',
+                        'WEBGL_compressed_texture_s3tc',
+                        'WEBGL_debug_renderer_info',
+                        'WEBGL_depth_texture',
+                        'WEBGL_draw_buffers',
+                        'WEBGL_lose_context'
+                    ];
+                };
             
-            // Block WEBGL_debug_renderer_info completely
-            const origGetExtension = ctx.getExtension.bind(ctx);
-            ctx.getExtension = function(name) {{
-                if (name === 'WEBGL_debug_renderer_info') {{
-                    return null;
-                }}
-                return origGetExtension(name);
-            }};
-        }}
+                // Block WEBGL_debug_renderer_info completely
+                const origGetExtension = ctx.getExtension.bind(ctx);
+                ctx.getExtension = function(name) {
+                    if (name === 'WEBGL_debug_renderer_info') {
+                        return null;
+                    }
+                    return origGetExtension(name);
+                };
+            }
         
-        return ctx;
-    }};
-    console.log('[ServionX] ✓ Deep WebGL fingerprinting protected');
+            return ctx;
+        };
+        console.log('[ServionX] ✓ Deep WebGL fingerprinting protected');
+    }
     
     // ==================== INTERSECTION OBSERVER TRACKING ====================
     // Protect against intersection observer tracking
     const origIntersectionObserver = window.IntersectionObserver;
-    window.IntersectionObserver = function(callback, options) {{
+    window.IntersectionObserver = function(callback, options) {
         console.log('[ServionX] ⚠ IntersectionObserver created');
         return new origIntersectionObserver(callback, options);
-    }};
+    };
     
     // ==================== RESIZE OBSERVER TRACKING ====================
     const origResizeObserver = window.ResizeObserver;
-    if (origResizeObserver) {{
-        window.ResizeObserver = function(callback) {{
+    if (origResizeObserver) {
+        window.ResizeObserver = function(callback) {
             console.log('[ServionX] ⚠ ResizeObserver created');
             return new origResizeObserver(callback);
-        }};
-    }}
+        };
+    }
     
     // ==================== MUTATION OBSERVER TRACKING ====================
     // Just log - don't block as it's needed for functionality
     const origMutationObserver = window.MutationObserver;
-    window.MutationObserver = function(callback) {{
+    window.MutationObserver = function(callback) {
         return new origMutationObserver(callback);
-    }};
+    };
     
     // ==================== TOUCH FINGERPRINTING PROTECTION ====================
     // Normalize touch events
-    Object.defineProperty(navigator, 'maxTouchPoints', {{
+    Object.defineProperty(navigator, 'maxTouchPoints', {
         get: () => 0,
         configurable: true
-    }});
+    });
     
-    Object.defineProperty(navigator, 'msMaxTouchPoints', {{
+    Object.defineProperty(navigator, 'msMaxTouchPoints', {
         get: () => 0,
         configurable: true
-    }});
+    });
     console.log('[ServionX] ✓ Touch fingerprinting blocked');
     
     // ==================== WEBDRIVER DETECTION PREVENTION ====================
     // Hide webdriver property
-    Object.defineProperty(navigator, 'webdriver', {{
+    Object.defineProperty(navigator, 'webdriver', {
         get: () => undefined,
         configurable: true
-    }});
+    });
     
     // Remove automation indicators
     delete window.cdc_adoQpoasnfa76pfcZLmcfl_Array;
@@ -573,10 +634,10 @@ impl UltimatePrivacyProtection {
     
     // ==================== IFRAME PROTECTION ====================
     // Prevent iframe document detection
-    Object.defineProperty(window, 'frameElement', {{
+    Object.defineProperty(window, 'frameElement', {
         get: () => null,
         configurable: true
-    }});
+    });
     
     // ==================== ERROR STACK ====================
     // NOTE: Skip error stack modification - breaks Gmail
@@ -611,11 +672,16 @@ impl UltimatePrivacyProtection {
     console.log('[ServionX] ');
     console.log('[ServionX] 🦊 The most secure browser in the world');
     console.log('[ServionX] ========================================');
-}})();
-"#,
-            math_noise = self.math_noise_seed,
-            audio_noise = self.audio_noise_seed,
-        )
+})();
+"#)
+            .bind("math_noise", &self.math_noise_seed)
+            .bind("audio_noise", &self.audio_noise_seed)
+            .bind("canvas_noise", &self.canvas_noise_seed)
+            .bind("spoof_client_hints", &self.spoof_client_hints)
+            .bind("client_hints", &self.fake_ua_data)
+            .bind("deep_canvas", &self.deep_canvas_protection)
+            .bind("deep_webgl", &self.deep_webgl_protection)
+            .render()
     }
 }
 
@@ -0,0 +1,295 @@
+// URL Matching Engine
+// Indexes shared by the blockers: suffix-label hash lookups for domains, an
+// Aho-Corasick automaton for URL substring patterns and a token index that
+// narrows filter rules down to the few that can match a URL
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use serde::{Deserialize, Serialize};
+
+/// Tokens so common in URLs that indexing a rule under them narrows nothing
+const COMMON_TOKENS: &[&str] = &[
+    "http", "https", "www", "com", "net", "org", "js", "html", "php", "cdn", "static",
+];
+
+/// A set of blocked domains, matched against a host and all its parents
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DomainSet {
+    domains: HashSet<String>,
+}
+
+impl DomainSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a domain; returns whether it was new
+    pub fn insert(&mut self, domain: &str) -> bool {
+        self.domains.insert(domain.to_lowercase())
+    }
+
+    /// Remove a domain; returns whether it was listed
+    pub fn remove(&mut self, domain: &str) -> bool {
+        self.domains.remove(&domain.to_lowercase())
+    }
+
+    /// The listed domain `host` is, or is a subdomain of. One hash lookup
+    /// per label of `host`, however many domains are listed.
+    pub fn matching(&self, host: &str) -> Option<&str> {
+        let mut suffix = host;
+        loop {
+            if let Some(domain) = self.domains.get(suffix) {
+                return Some(domain);
+            }
+            suffix = &suffix[suffix.find('.')? + 1..];
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.domains.iter()
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for DomainSet {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<S: AsRef<str>> Extend<S> for DomainSet {
+    fn extend<I: IntoIterator<Item = S>>(&mut self, iter: I) {
+        for domain in iter {
+            self.insert(domain.as_ref());
+        }
+    }
+}
+
+/// Case-insensitive substring patterns, all searched in one pass over the URL
+#[derive(Debug, Clone, Default)]
+pub struct PatternSet {
+    patterns: Vec<String>,
+    automaton: Option<AhoCorasick>,
+}
+
+impl PatternSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a pattern; returns whether it was new
+    pub fn insert(&mut self, pattern: &str) -> bool {
+        let added = self.push(pattern);
+        if added {
+            self.rebuild();
+        }
+        added
+    }
+
+    /// Remove a pattern; returns whether it was listed
+    pub fn remove(&mut self, pattern: &str) -> bool {
+        let pattern = pattern.to_lowercase();
+        let before = self.patterns.len();
+        self.patterns.retain(|p| *p != pattern);
+        let removed = self.patterns.len() != before;
+        if removed {
+            self.rebuild();
+        }
+        removed
+    }
+
+    /// The first listed pattern `haystack` contains
+    pub fn find(&self, haystack: &str) -> Option<&str> {
+        let found = self.automaton.as_ref()?.find(haystack)?;
+        Some(&self.patterns[found.pattern().as_usize()])
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.patterns.iter()
+    }
+
+    fn push(&mut self, pattern: &str) -> bool {
+        let pattern = pattern.to_lowercase();
+        if pattern.is_empty() || self.patterns.contains(&pattern) {
+            return false;
+        }
+        self.patterns.push(pattern);
+        true
+    }
+
+    fn rebuild(&mut self) {
+        self.automaton = if self.patterns.is_empty() {
+            None
+        } else {
+            AhoCorasickBuilder::new()
+                .ascii_case_insensitive(true)
+                .match_kind(MatchKind::LeftmostFirst)
+                .build(&self.patterns)
+                .map_err(|e| log::error!("Failed to build URL pattern matcher: {}", e))
+                .ok()
+        };
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for PatternSet {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<S: AsRef<str>> Extend<S> for PatternSet {
+    /// Adds every pattern, rebuilding the automaton once
+    fn extend<I: IntoIterator<Item = S>>(&mut self, iter: I) {
+        let mut added = false;
+        for pattern in iter {
+            added |= self.push(pattern.as_ref());
+        }
+        if added {
+            self.rebuild();
+        }
+    }
+}
+
+/// Where the distinct runs of ASCII letters and digits of `url` (which must
+/// be lowercase) lie in it, in order of first appearance
+pub fn url_token_spans(url: &str) -> Vec<Range<usize>> {
+    let bytes = url.as_bytes();
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    let mut start = None;
+    for i in 0..=bytes.len() {
+        let alphanumeric = bytes.get(i).is_some_and(u8::is_ascii_alphanumeric);
+        match start {
+            None if alphanumeric => start = Some(i),
+            Some(from) if !alphanumeric => {
+                if !spans.iter().any(|span| url[span.clone()] == url[from..i]) {
+                    spans.push(from..i);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    spans
+}
+
+/// Rule ids keyed by one token each rule needs to find in a URL. Rules
+/// without such a token are checked against every URL.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenIndex {
+    buckets: HashMap<String, Vec<usize>>,
+    untokenized: Vec<usize>,
+}
+
+impl TokenIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// File rule `id` under whichever of `tokens` (each a whole token any
+    /// matching URL contains) currently holds the fewest rules
+    pub fn insert(&mut self, id: usize, tokens: &[String]) {
+        let rank = |token: &String| {
+            let common = COMMON_TOKENS.contains(&token.as_str());
+            let size = self.buckets.get(token).map_or(0, Vec::len);
+            (common, size, std::cmp::Reverse(token.len()))
+        };
+        match tokens.iter().min_by_key(|t| rank(t)) {
+            Some(token) => self.buckets.entry(token.clone()).or_default().push(id),
+            None => self.untokenized.push(id),
+        }
+    }
+
+    /// Ids of the rules that may match a URL made of `tokens`, its distinct
+    /// tokens (see `url_token_spans`); each id comes up once
+    pub fn candidates<'a>(&'a self, tokens: impl Iterator<Item = &'a str> + 'a) -> impl Iterator<Item = usize> + 'a {
+        let tokenized = tokens
+            .take_while(|_| !self.buckets.is_empty())
+            .filter_map(|token| self.buckets.get(token))
+            .flatten();
+        self.untokenized.iter().chain(tokenized).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.untokenized.len() + self.buckets.values().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domain_set_matches_parents_only() {
+        let set: DomainSet = ["doubleclick.net", "Tracker.Example"].into_iter().collect();
+        assert_eq!(set.matching("doubleclick.net"), Some("doubleclick.net"));
+        assert_eq!(set.matching("ad.g.doubleclick.net"), Some("doubleclick.net"));
+        assert_eq!(set.matching("cdn.tracker.example"), Some("tracker.example"));
+        assert_eq!(set.matching("notdoubleclick.net"), None);
+        assert_eq!(set.matching("doubleclick.net.evil.example"), None);
+        assert_eq!(set.matching(""), None);
+    }
+
+    #[test]
+    fn test_pattern_set_agrees_with_linear_scan() {
+        let patterns = ["/ads/", "banner", "Track.gif", "?utm_"];
+        let mut set: PatternSet = patterns.iter().collect();
+        let urls = [
+            "https://x.example/ads/1.png",
+            "https://x.example/img/BANNER.png",
+            "https://x.example/track.GIF",
+            "https://x.example/?utm_source=a",
+            "https://x.example/clean",
+        ];
+        for url in urls {
+            let url = url.to_lowercase();
+            let linear = patterns.iter().any(|p| url.contains(&p.to_lowercase()));
+            assert_eq!(set.find(&url).is_some(), linear, "{}", url);
+        }
+
+        assert!(set.remove("BANNER"));
+        assert!(set.find("https://x.example/banner.png").is_none());
+        assert!(!set.insert("/ads/"));
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    fn test_token_index_spreads_rules() {
+        let mut index = TokenIndex::new();
+        index.insert(0, &["ads".to_string(), "com".to_string()]);
+        index.insert(1, &["ads".to_string(), "banner".to_string()]);
+        index.insert(2, &[]);
+
+        let candidates = |url: &str| -> Vec<usize> {
+            let spans = url_token_spans(url);
+            index.candidates(spans.iter().map(|span| &url[span.clone()])).collect()
+        };
+        assert_eq!(candidates("https://x.com/ads/1.png"), vec![2, 0]);
+        assert_eq!(candidates("https://x.com/banner/ads/banner"), vec![2, 1, 0]);
+        assert_eq!(url_token_spans("https://x.com/banner/ads/banner"), vec![0..5, 8..9, 10..13, 14..20, 21..24]);
+        assert_eq!(index.len(), 3);
+    }
+}
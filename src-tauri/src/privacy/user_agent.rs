@@ -1,8 +1,13 @@
 // User Agent Generator
 // Generates realistic fake user agent strings
 
+use std::sync::Arc;
 use serde::{Deserialize, Serialize};
+use chrono::{NaiveDate, Utc};
 use rand::Rng;
+use super::browser_versions;
+use super::coherence::{BrowserFamily, OsFamily};
+use super::fingerprint_dataset::FingerprintDataset;
 
 /// Complete fake user agent data
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +19,10 @@ pub struct FakeUserAgent {
     pub vendor: String,
     pub browser_name: String,
     pub browser_version: String,
+    /// Unreduced version behind `browser_version` (e.g. "137.0.7151.68"),
+    /// reported through client hints
+    #[serde(default)]
+    pub full_version: String,
     pub os_name: String,
     pub os_version: String,
 }
@@ -21,95 +30,78 @@ pub struct FakeUserAgent {
 #[derive(Debug)]
 struct BrowserConfig {
     name: &'static str,
-    versions: &'static [&'static str],
     vendor: &'static str,
 }
 
-#[derive(Debug)]
-struct OsConfig {
-    name: &'static str,
-    platform: &'static str,
-    versions: &'static [&'static str],
-}
-
 #[derive(Debug)]
 pub struct UserAgentGenerator {
     browsers: Vec<BrowserConfig>,
-    windows_versions: Vec<OsConfig>,
-    mac_versions: Vec<OsConfig>,
-    linux_versions: Vec<OsConfig>,
+    dataset: Arc<FingerprintDataset>,
 }
 
 impl UserAgentGenerator {
+    /// Generator drawing versions from the bundled fingerprint dataset
     pub fn new() -> Self {
+        Self::with_dataset(FingerprintDataset::bundled())
+    }
+    
+    /// Generator drawing browser and OS versions from a specific dataset
+    pub fn with_dataset(dataset: Arc<FingerprintDataset>) -> Self {
         Self {
             browsers: vec![
-                BrowserConfig {
-                    name: "Chrome",
-                    versions: &["120.0.0.0", "119.0.0.0", "118.0.0.0", "121.0.0.0", "122.0.0.0", "123.0.0.0", "124.0.0.0", "125.0.0.0"],
-                    vendor: "Google Inc.",
-                },
-                BrowserConfig {
-                    name: "Firefox",
-                    versions: &["121.0", "120.0", "119.0", "122.0", "123.0", "124.0"],
-                    vendor: "",
-                },
-                BrowserConfig {
-                    name: "Safari",
-                    versions: &["17.2", "17.1", "17.0", "16.6", "17.3", "17.4"],
-                    vendor: "Apple Computer, Inc.",
-                },
-                BrowserConfig {
-                    name: "Edge",
-                    versions: &["120.0.0.0", "119.0.0.0", "121.0.0.0", "122.0.0.0", "123.0.0.0"],
-                    vendor: "Google Inc.",
-                },
-            ],
-            windows_versions: vec![
-                OsConfig { name: "Windows", platform: "Win32", versions: &["10.0", "11.0"] },
-            ],
-            mac_versions: vec![
-                OsConfig { name: "macOS", platform: "MacIntel", versions: &["10_15_7", "11_0", "12_0", "13_0", "14_0", "14_1", "14_2"] },
-            ],
-            linux_versions: vec![
-                OsConfig { name: "Linux", platform: "Linux x86_64", versions: &["x86_64"] },
+                BrowserConfig { name: "Chrome", vendor: "Google Inc." },
+                BrowserConfig { name: "Firefox", vendor: "" },
+                BrowserConfig { name: "Safari", vendor: "Apple Computer, Inc." },
+                BrowserConfig { name: "Edge", vendor: "Google Inc." },
             ],
+            dataset,
         }
     }
     
     /// Generate a random fake user agent
     pub fn generate(&self) -> FakeUserAgent {
-        let mut rng = rand::thread_rng();
-        
-        // Select random browser
-        let browser = &self.browsers[rng.gen_range(0..self.browsers.len())];
-        let browser_version = browser.versions[rng.gen_range(0..browser.versions.len())];
-        
-        // Select random OS (weighted towards Windows)
-        let os_choice = rng.gen_range(0..100);
-        let (os_name, platform, os_version) = if os_choice < 60 {
-            // Windows (60%)
-            let os = &self.windows_versions[0];
-            let ver = os.versions[rng.gen_range(0..os.versions.len())];
-            (os.name, os.platform, ver)
-        } else if os_choice < 85 {
-            // macOS (25%)
-            let os = &self.mac_versions[0];
-            let ver = os.versions[rng.gen_range(0..os.versions.len())];
-            (os.name, os.platform, ver)
-        } else {
-            // Linux (15%)
-            let os = &self.linux_versions[0];
-            let ver = os.versions[0];
-            (os.name, os.platform, ver)
+        self.generate_with_rng(&mut rand::thread_rng())
+    }
+    
+    /// Generate a fake user agent as of today, drawing all randomness from `rng`
+    pub fn generate_with_rng<R: Rng>(&self, rng: &mut R) -> FakeUserAgent {
+        let device = self.dataset.sample_device(rng);
+        self.generate_for_with_rng(device.os_family(), device.browser_family(), Utc::now().date_naive(), rng)
+    }
+    
+    /// Generate a user agent for a specific OS and browser as it would look
+    /// on `date`. Versions sampled from the dataset are aged from its
+    /// revision date along each browser's release cadence, so the share of
+    /// up-to-date users stays realistic; the same `date` and `rng` state
+    /// always give the same user agent.
+    pub fn generate_for_with_rng<R: Rng>(&self, os: OsFamily, browser_family: BrowserFamily, date: NaiveDate, rng: &mut R) -> FakeUserAgent {
+        let browser_version = match self.dataset.sample_browser_version(browser_family, rng) {
+            Some(version) => match self.dataset.revision_date() {
+                Some(revision) => browser_versions::age_version(browser_family, version, revision, date),
+                None => version.to_string(),
+            },
+            None => browser_versions::version_string(
+                browser_family,
+                browser_versions::current_release(browser_family, date),
+            ),
         };
+        let full_version = browser_versions::full_version(browser_family, &browser_version, rng);
+        let os_version = self.dataset
+            .sample_os_version(os, rng)
+            .unwrap_or("10.0")
+            .to_string();
+        let browser = self.browsers
+            .iter()
+            .find(|b| b.name == browser_family.name())
+            .unwrap_or(&self.browsers[0]);
+        let (os_name, platform) = (os.name(), os.navigator_platform());
         
         // Generate user agent string
         let (full, app_version) = self.build_user_agent(
             browser.name,
-            browser_version,
+            &browser_version,
             os_name,
-            os_version,
+            &os_version,
             platform,
         );
         
@@ -119,9 +111,10 @@ impl UserAgentGenerator {
             platform: platform.to_string(),
             vendor: browser.vendor.to_string(),
             browser_name: browser.name.to_string(),
-            browser_version: browser_version.to_string(),
+            browser_version,
+            full_version,
             os_name: os_name.to_string(),
-            os_version: os_version.to_string(),
+            os_version,
         }
     }
     
@@ -133,10 +126,18 @@ impl UserAgentGenerator {
         os_version: &str,
         _platform: &str,
     ) -> (String, String) {
+        // Browsers freeze the OS version in the UA (Windows 11 still says
+        // NT 10.0, every macOS says 10_15_7); only client hints carry the real one
+        let os_version = match os_name {
+            "Windows" => "10.0",
+            "macOS" => "10_15_7",
+            _ => os_version,
+        };
+        
         match browser {
             "Chrome" => {
                 let os_string = match os_name {
-                    "Windows" => format!("Windows NT {}", os_version),
+                    "Windows" => format!("Windows NT {}; Win64; x64", os_version),
                     "macOS" => format!("Macintosh; Intel Mac OS X {}", os_version),
                     "Linux" => "X11; Linux x86_64".to_string(),
                     _ => "Windows NT 10.0".to_string(),
@@ -155,13 +156,14 @@ impl UserAgentGenerator {
             "Firefox" => {
                 let os_string = match os_name {
                     "Windows" => format!("Windows NT {}; Win64; x64", os_version),
-                    "macOS" => format!("Macintosh; Intel Mac OS X {}", os_version),
+                    // Firefox freezes macOS at 10.15, written with dots
+                    "macOS" => "Macintosh; Intel Mac OS X 10.15".to_string(),
                     "Linux" => "X11; Linux x86_64".to_string(),
                     _ => "Windows NT 10.0; Win64; x64".to_string(),
                 };
                 
                 let full = format!(
-                    "Mozilla/5.0({}; rv:{}) Gecko/20100101 Firefox/{}",
+                    "Mozilla/5.0 ({}; rv:{}) Gecko/20100101 Firefox/{}",
                     os_string, browser_version, browser_version
                 );
                 let app_version = format!(
@@ -186,7 +188,7 @@ impl UserAgentGenerator {
             }
             "Edge" => {
                 let os_string = match os_name {
-                    "Windows" => format!("Windows NT {}", os_version),
+                    "Windows" => format!("Windows NT {}; Win64; x64", os_version),
                     "macOS" => format!("Macintosh; Intel Mac OS X {}", os_version),
                     _ => "Windows NT 10.0".to_string(),
                 };
@@ -232,4 +234,42 @@ mod tests {
         assert!(!ua.browser_name.is_empty());
         assert!(!ua.os_name.is_empty());
     }
+    
+    #[test]
+    fn test_generate_for_os_and_browser() {
+        use rand::SeedableRng;
+        let gen = UserAgentGenerator::new();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let date = NaiveDate::from_ymd_opt(2026, 6, 1).unwrap();
+        
+        let ua = gen.generate_for_with_rng(OsFamily::MacOs, BrowserFamily::Safari, date, &mut rng);
+        assert_eq!(ua.platform, "MacIntel");
+        assert!(ua.full.contains("Macintosh"));
+        assert!(ua.full.contains("Version/"));
+        assert!(ua.full.contains("Mac OS X 10_15_7"));
+        
+        let ua = gen.generate_for_with_rng(OsFamily::Windows, BrowserFamily::Firefox, date, &mut rng);
+        assert!(ua.full.starts_with("Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:"));
+        
+        let ua = gen.generate_for_with_rng(OsFamily::MacOs, BrowserFamily::Firefox, date, &mut rng);
+        assert!(ua.full.contains("Mac OS X 10.15; rv:"));
+    }
+    
+    #[test]
+    fn test_versions_age_with_the_date() {
+        use rand::SeedableRng;
+        let gen = UserAgentGenerator::new();
+        let later = NaiveDate::from_ymd_opt(2027, 3, 1).unwrap();
+        let current = browser_versions::current_release(BrowserFamily::Chrome, later);
+        
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        for _ in 0..20 {
+            let ua = gen.generate_for_with_rng(OsFamily::Windows, BrowserFamily::Chrome, later, &mut rng);
+            let major: i64 = ua.browser_version.split('.').next().unwrap().parse().unwrap();
+            assert!(major <= current && major >= current - 3, "Chrome {} on {}", major, later);
+            assert!(ua.full.contains(&format!("Chrome/{}.0.0.0 ", major)));
+            assert!(ua.app_version.contains(&format!("Chrome/{}.0.0.0 ", major)));
+            assert!(ua.full_version.starts_with(&format!("{}.0.", major)));
+        }
+    }
 }
@@ -2,69 +2,307 @@
 // Manages sites that should receive real data
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::path::Path;
+use chrono::{DateTime, Utc};
 
-/// Manages the whitelist of trusted domains
+/// Version of the JSON whitelist format, on disk and in exports
+const FORMAT_VERSION: u32 = 1;
+
+/// A whitelist pattern:
+/// - `example.com` matches the domain and all its subdomains
+/// - `*.bank.example` matches subdomains only
+/// - `intranet:8443` matches the host only on that port
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Pattern {
+    host: String,
+    subdomains_only: bool,
+    port: Option<u16>,
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Self, String> {
+        let normalized = WhitelistManager::normalize_domain(pattern);
+        let (host, port) = match normalized.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse::<u16>().map_err(|_| format!("Invalid port in pattern: {}", pattern))?;
+                (host, Some(port))
+            }
+            None => (normalized.as_str(), None),
+        };
+        let (host, subdomains_only) = match host.strip_prefix("*.") {
+            Some(rest) => (rest, true),
+            None => (host, false),
+        };
+
+        let valid_label = |label: &str| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        };
+        if !host.split('.').all(valid_label) {
+            return Err(format!("Invalid whitelist pattern: {}", pattern));
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            subdomains_only,
+            port,
+        })
+    }
+
+    fn matches(&self, host: &str, port: Option<u16>) -> bool {
+        let is_subdomain = host
+            .strip_suffix(self.host.as_str())
+            .is_some_and(|prefix| prefix.ends_with('.'));
+        let host_matches = is_subdomain || (!self.subdomains_only && host == self.host);
+        host_matches && self.port.is_none_or(|p| port == Some(p))
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.subdomains_only {
+            write!(f, "*.")?;
+        }
+        write!(f, "{}", self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+/// One whitelisted pattern
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WhitelistEntry {
+    pub pattern: String,
+    pub added_at: DateTime<Utc>,
+    /// Temporary exceptions stop matching after this time
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl WhitelistEntry {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// Formats the whitelist can be exported to and imported from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WhitelistFormat {
+    /// One pattern per line, optionally followed by an RFC 3339 expiry;
+    /// `#` starts a comment
+    Text,
+    Json,
+}
+
+/// The JSON document written to disk and exported
 #[derive(Debug, Clone, Serialize, Deserialize)]
+struct WhitelistFile {
+    version: u32,
+    entries: Vec<WhitelistEntry>,
+}
+
+/// Manages the whitelist of trusted domains
+#[derive(Debug, Clone)]
 pub struct WhitelistManager {
-    domains: HashSet<String>,
+    entries: HashMap<Pattern, WhitelistEntry>,
 }
 
 impl WhitelistManager {
     pub fn new() -> Self {
         Self {
-            domains: HashSet::new(),
+            entries: HashMap::new(),
         }
     }
-    
-    /// Check if a domain is whitelisted
-    pub fn is_whitelisted(&self, domain: &str) -> bool {
-        let domain = Self::normalize_domain(domain);
-        
-        // Check exact match
-        if self.domains.contains(&domain) {
-            return true;
+
+    /// Load a whitelist saved with `save`; a missing file is an empty list
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut manager = Self::new();
+        if path.exists() {
+            let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+            manager.import(&content, true)?;
         }
-        
-        // Check if any parent domain is whitelisted
-        let parts: Vec<&str> = domain.split('.').collect();
-        for i in 0..parts.len().saturating_sub(1) {
-            let parent = parts[i..].join(".");
-            if self.domains.contains(&parent) {
-                return true;
-            }
+        Ok(manager)
+    }
+
+    /// Write the whitelist to `path` as JSON, leaving out expired entries
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
         }
-        
-        false
-    }
-    
-    /// Add a domain to the whitelist
-    pub fn add(&mut self, domain: &str) {
-        let domain = Self::normalize_domain(domain);
-        log::info!("Added domain to whitelist: {}", &domain);
-        self.domains.insert(domain);
-    }
-    
-    /// Remove a domain from the whitelist
+        let json = self.export(WhitelistFormat::Json)?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    /// Check if a domain or URL is whitelisted
+    pub fn is_whitelisted(&self, domain: &str) -> bool {
+        let Some((host, port)) = Self::host_and_port(domain) else {
+            return false;
+        };
+        let now = Utc::now();
+        self.entries
+            .iter()
+            .any(|(pattern, entry)| !entry.is_expired(now) && pattern.matches(&host, port))
+    }
+
+    /// Add a domain or pattern to the whitelist
+    pub fn add(&mut self, domain: &str) -> Result<(), String> {
+        self.add_until(domain, None)
+    }
+
+    /// Add a pattern that stops matching at `expires_at` (if given)
+    pub fn add_until(&mut self, domain: &str, expires_at: Option<DateTime<Utc>>) -> Result<(), String> {
+        let pattern = Pattern::parse(domain)?;
+        log::info!("Added pattern to whitelist: {}", pattern);
+        self.entries.insert(pattern.clone(), WhitelistEntry {
+            pattern: pattern.to_string(),
+            added_at: Utc::now(),
+            expires_at,
+        });
+        Ok(())
+    }
+
+    /// Remove a domain or pattern from the whitelist
     pub fn remove(&mut self, domain: &str) {
-        let domain = Self::normalize_domain(domain);
-        log::info!("Removed domain from whitelist: {}", &domain);
-        self.domains.remove(&domain);
+        if let Ok(pattern) = Pattern::parse(domain) {
+            log::info!("Removed pattern from whitelist: {}", pattern);
+            self.entries.remove(&pattern);
+        }
+    }
+
+    /// Drop expired entries; returns how many were removed
+    pub fn prune_expired(&mut self) -> usize {
+        let now = Utc::now();
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| !entry.is_expired(now));
+        before - self.entries.len()
     }
-    
-    /// Get all whitelisted domains
+
+    /// Get all active patterns
     pub fn get_all(&self) -> Vec<String> {
-        self.domains.iter().cloned().collect()
+        self.entries().into_iter().map(|e| e.pattern).collect()
+    }
+
+    /// All active entries, sorted by pattern
+    pub fn entries(&self) -> Vec<WhitelistEntry> {
+        let now = Utc::now();
+        let mut entries: Vec<WhitelistEntry> = self.entries
+            .values()
+            .filter(|e| !e.is_expired(now))
+            .cloned()
+            .collect();
+        entries.sort_by(|a, b| a.pattern.cmp(&b.pattern));
+        entries
+    }
+
+    /// Export the active entries
+    pub fn export(&self, format: WhitelistFormat) -> Result<String, String> {
+        let entries = self.entries();
+        match format {
+            WhitelistFormat::Json => {
+                let file = WhitelistFile { version: FORMAT_VERSION, entries };
+                serde_json::to_string_pretty(&file).map_err(|e| e.to_string())
+            }
+            WhitelistFormat::Text => Ok(entries
+                .iter()
+                .map(|e| match e.expires_at {
+                    Some(at) => format!("{} {}\n", e.pattern, at.to_rfc3339()),
+                    None => format!("{}\n", e.pattern),
+                })
+                .collect()),
+        }
+    }
+
+    /// Import entries from either format (JSON is recognised by its leading
+    /// `{`). With `replace` the current entries are dropped first. Nothing
+    /// changes if any line is invalid. Returns how many entries were read.
+    pub fn import(&mut self, content: &str, replace: bool) -> Result<usize, String> {
+        let entries = if content.trim_start().starts_with('{') {
+            let file: WhitelistFile = serde_json::from_str(content).map_err(|e| e.to_string())?;
+            if file.version > FORMAT_VERSION {
+                return Err(format!("Unsupported whitelist version {}", file.version));
+            }
+            file.entries
+        } else {
+            Self::parse_text(content)?
+        };
+
+        let mut parsed = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let pattern = Pattern::parse(&entry.pattern)?;
+            parsed.push((pattern.clone(), WhitelistEntry { pattern: pattern.to_string(), ..entry }));
+        }
+
+        if replace {
+            self.entries.clear();
+        }
+        let count = parsed.len();
+        self.entries.extend(parsed);
+        self.prune_expired();
+        Ok(count)
+    }
+
+    fn parse_text(content: &str) -> Result<Vec<WhitelistEntry>, String> {
+        let now = Utc::now();
+        content
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let line = line.split('#').next().unwrap_or("").trim();
+                (!line.is_empty()).then_some((i + 1, line))
+            })
+            .map(|(line_no, line)| {
+                let mut parts = line.split_whitespace();
+                let pattern = parts.next().unwrap_or_default().to_string();
+                let expires_at = parts
+                    .next()
+                    .map(|at| {
+                        DateTime::parse_from_rfc3339(at)
+                            .map(|at| at.with_timezone(&Utc))
+                            .map_err(|e| format!("Line {}: invalid expiry '{}': {}", line_no, at, e))
+                    })
+                    .transpose()?;
+                if parts.next().is_some() {
+                    return Err(format!("Line {}: unexpected text after expiry", line_no));
+                }
+                Ok(WhitelistEntry { pattern, added_at: now, expires_at })
+            })
+            .collect()
+    }
+
+    /// Host and port of a URL or bare host. URLs without an explicit port
+    /// get their scheme's default one.
+    fn host_and_port(url: &str) -> Option<(String, Option<u16>)> {
+        let host = super::site::host_of(url)?;
+        let default_port = match url.split_once("://").map(|(scheme, _)| scheme.to_lowercase()) {
+            Some(scheme) if scheme == "https" || scheme == "wss" => Some(443),
+            Some(scheme) if scheme == "http" || scheme == "ws" => Some(80),
+            _ => None,
+        };
+        let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let authority = rest.split(['/', '?', '#']).next()?;
+        let host_port = authority.rsplit('@').next()?;
+        let port = host_port
+            .rsplit_once(':')
+            .filter(|_| !host_port.ends_with(']'))
+            .and_then(|(_, port)| port.parse::<u16>().ok())
+            .or(default_port);
+        Some((host, port))
     }
-    
+
     /// Normalize a domain for consistent matching
     fn normalize_domain(domain: &str) -> String {
-        let domain = domain.to_lowercase();
+        let domain = domain.trim().to_lowercase();
         let domain = domain.trim_start_matches("https://");
         let domain = domain.trim_start_matches("http://");
         let domain = domain.trim_start_matches("www.");
-        let domain = domain.split('/').next().unwrap_or(&domain);
-        let domain = domain.split(':').next().unwrap_or(domain);
+        let domain = domain.split('/').next().unwrap_or(domain);
         domain.to_string()
     }
 }
@@ -78,28 +316,73 @@ impl Default for WhitelistManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
     #[test]
     fn test_whitelist() {
         let mut manager = WhitelistManager::new();
-        
-        manager.add("example.com");
-        
+
+        manager.add("example.com").unwrap();
+
         assert!(manager.is_whitelisted("example.com"));
         assert!(manager.is_whitelisted("www.example.com"));
         assert!(manager.is_whitelisted("https://example.com/path"));
         assert!(manager.is_whitelisted("sub.example.com"));
         assert!(!manager.is_whitelisted("notexample.com"));
     }
-    
+
     #[test]
     fn test_remove() {
         let mut manager = WhitelistManager::new();
-        
-        manager.add("example.com");
+
+        manager.add("example.com").unwrap();
         assert!(manager.is_whitelisted("example.com"));
-        
+
         manager.remove("example.com");
         assert!(!manager.is_whitelisted("example.com"));
     }
+
+    #[test]
+    fn test_wildcards_ports_and_expiry() {
+        let mut manager = WhitelistManager::new();
+        manager.add("*.bank.example").unwrap();
+        manager.add("intranet:8443").unwrap();
+        manager.add("https://secure.example:443/login").unwrap();
+        manager.add_until("old.example", Some(Utc::now() - chrono::Duration::minutes(1))).unwrap();
+
+        assert!(manager.is_whitelisted("https://online.bank.example/"));
+        assert!(!manager.is_whitelisted("https://bank.example/"));
+        assert!(manager.is_whitelisted("http://intranet:8443/wiki"));
+        assert!(!manager.is_whitelisted("http://intranet/wiki"));
+        assert!(manager.is_whitelisted("https://secure.example/"));
+        assert!(!manager.is_whitelisted("http://secure.example/"));
+        assert!(!manager.is_whitelisted("https://old.example/"));
+        assert!(manager.add("bad host!").is_err());
+
+        assert_eq!(manager.get_all(), vec!["*.bank.example", "intranet:8443", "secure.example:443"]);
+        assert_eq!(manager.prune_expired(), 1);
+    }
+
+    #[test]
+    fn test_import_export_and_persistence() {
+        let mut manager = WhitelistManager::new();
+        let text = "# team whitelist\n*.bank.example\nintranet:8443  2099-01-01T00:00:00Z # until the migration\n\n";
+        assert_eq!(manager.import(text, false).unwrap(), 2);
+        assert!(manager.import("good.example\nbad host\n", false).is_err());
+        assert!(!manager.is_whitelisted("good.example"));
+
+        let exported = manager.export(WhitelistFormat::Text).unwrap();
+        assert_eq!(exported, "*.bank.example\nintranet:8443 2099-01-01T00:00:00+00:00\n");
+
+        let mut copy = WhitelistManager::new();
+        copy.add("other.example").unwrap();
+        copy.import(&manager.export(WhitelistFormat::Json).unwrap(), true).unwrap();
+        assert_eq!(copy.entries(), manager.entries());
+
+        let path = std::env::temp_dir()
+            .join(format!("servionx-whitelist-{}", uuid::Uuid::new_v4()))
+            .join("whitelist.json");
+        manager.save(&path).unwrap();
+        assert_eq!(WhitelistManager::load(&path).unwrap().entries(), manager.entries());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
 }
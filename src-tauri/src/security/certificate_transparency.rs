@@ -2,6 +2,7 @@
 //! Verifies SSL certificates against CT logs to prevent MITM attacks
 
 use serde::{Deserialize, Serialize};
+use crate::privacy::ScriptTemplate;
 
 /// Certificate Transparency verification
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,8 +45,8 @@ impl CertificateTransparency {
     
     /// Get JavaScript injection for CT verification status display
     pub fn get_injection_script(&self) -> String {
-        format!(r#"
-(function() {{
+        ScriptTemplate::new(r#"
+(function() {
     'use strict';
     
     // ================================================================
@@ -54,65 +55,65 @@ impl CertificateTransparency {
     
     console.log('[ServionX] 🔐 Certificate Transparency verification active');
     
-    const CT_CONFIG = {{
-        enabled: {enabled},
-        requireScts: {require_scts},
-        minScts: {min_scts}
-    }};
+    const CT_CONFIG = {
+        enabled: @@enabled@@,
+        requireScts: @@require_scts@@,
+        minScts: @@min_scts@@
+    };
     
     // Monitor for certificate errors
-    window.addEventListener('securitypolicyviolation', (e) => {{
+    window.addEventListener('securitypolicyviolation', (e) => {
         console.error('[ServionX] ⚠️ Security policy violation:', e.violatedDirective);
-    }});
+    });
     
     // Check current page security
-    if (location.protocol === 'https:') {{
+    if (location.protocol === 'https:') {
         console.log('[ServionX] ✓ HTTPS connection verified');
         
         // Performance API can give us some connection info
-        if (performance.getEntriesByType) {{
+        if (performance.getEntriesByType) {
             const navTiming = performance.getEntriesByType('navigation')[0];
-            if (navTiming && navTiming.secureConnectionStart > 0) {{
+            if (navTiming && navTiming.secureConnectionStart > 0) {
                 const tlsTime = navTiming.connectEnd - navTiming.secureConnectionStart;
                 console.log('[ServionX] ✓ TLS handshake: ' + tlsTime.toFixed(2) + 'ms');
-            }}
-        }}
-    }} else if (location.protocol === 'http:') {{
+            }
+        }
+    } else if (location.protocol === 'http:') {
         console.warn('[ServionX] ⚠️ Insecure HTTP connection detected!');
-    }}
+    }
     
     // Monitor for mixed content
-    document.addEventListener('DOMContentLoaded', () => {{
+    document.addEventListener('DOMContentLoaded', () => {
         const insecureElements = document.querySelectorAll(
             'img[src^="http:"], script[src^="http:"], link[href^="http:"], iframe[src^="http:"]'
         );
         
-        if (insecureElements.length > 0) {{
+        if (insecureElements.length > 0) {
             console.warn('[ServionX] ⚠️ Mixed content detected: ' + insecureElements.length + ' insecure elements');
-        }} else {{
+        } else {
             console.log('[ServionX] ✓ No mixed content detected');
-        }}
-    }});
+        }
+    });
     
     // Expose CT check function
-    window.__servionx_ct = {{
+    window.__servionx_ct = {
         config: CT_CONFIG,
-        check: function() {{
-            return {{
+        check: function() {
+            return {
                 isHttps: location.protocol === 'https:',
                 ctEnabled: CT_CONFIG.enabled,
                 status: location.protocol === 'https:' ? 'verified' : 'insecure'
-            }};
-        }}
-    }};
+            };
+        }
+    };
     
     console.log('[ServionX] ✓ CT monitoring active (min SCTs: ' + CT_CONFIG.minScts + ')');
-}})();
-"#,
-            enabled = self.enabled,
-            require_scts = self.require_scts,
-            min_scts = self.min_scts,
-        )
+})();
+"#)
+            .bind("enabled", &self.enabled)
+            .bind("require_scts", &self.require_scts)
+            .bind("min_scts", &self.min_scts)
+            .render()
     }
     
     /// Verify a certificate has valid CT information (placeholder for native verification)
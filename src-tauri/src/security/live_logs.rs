@@ -155,13 +155,13 @@ impl LiveSecurityLogs {
     }
     
     /// Log fingerprint protection
-    pub fn log_fingerprint_blocked(&self, api: &str) {
+    pub fn log_fingerprint_blocked(&self, api: &str, url: &str) {
         self.log(
             LogType::Privacy,
             LogSeverity::Blocked,
             &format!("Fingerprinting blocked: {}", api),
             Some("API access spoofed/blocked"),
-            None,
+            Some(url),
         );
     }
     
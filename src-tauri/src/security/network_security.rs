@@ -2,6 +2,7 @@
 //! Provides DNS over HTTPS, certificate transparency, and cookie hardening
 
 use serde::{Deserialize, Serialize};
+use crate::privacy::ScriptTemplate;
 
 /// DNS over HTTPS provider
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -85,125 +86,125 @@ impl NetworkSecurity {
     
     /// Get network security injection script
     pub fn get_injection_script(&self) -> String {
-        format!(r#"
-(function() {{
+        ScriptTemplate::new(r#"
+(function() {
     'use strict';
     
     // ============================================
     // NETWORK SECURITY - ServionX
     // ============================================
     
-    const config = {{
-        blockMixedContent: {block_mixed},
-        upgradeInsecure: {upgrade_insecure},
-        forceSameSite: {force_samesite},
-        forceSecure: {force_secure},
-        blockThirdParty: {block_third_party},
-        blockDataUrls: {block_data_urls}
-    }};
+    const config = {
+        blockMixedContent: @@block_mixed@@,
+        upgradeInsecure: @@upgrade_insecure@@,
+        forceSameSite: @@force_samesite@@,
+        forceSecure: @@force_secure@@,
+        blockThirdParty: @@block_third_party@@,
+        blockDataUrls: @@block_data_urls@@
+    };
     
     // === MIXED CONTENT BLOCKING ===
-    if (config.blockMixedContent && window.location.protocol === 'https:') {{
+    if (config.blockMixedContent && window.location.protocol === 'https:') {
         // Monitor for insecure resource loading
-        const observer = new MutationObserver((mutations) => {{
-            mutations.forEach((mutation) => {{
-                mutation.addedNodes.forEach((node) => {{
-                    if (node.tagName === 'IMG' || node.tagName === 'SCRIPT' || node.tagName === 'LINK') {{
+        const observer = new MutationObserver((mutations) => {
+            mutations.forEach((mutation) => {
+                mutation.addedNodes.forEach((node) => {
+                    if (node.tagName === 'IMG' || node.tagName === 'SCRIPT' || node.tagName === 'LINK') {
                         const src = node.src || node.href;
-                        if (src && src.startsWith('http:')) {{
+                        if (src && src.startsWith('http:')) {
                             console.warn('[ServionX] Blocked insecure resource:', src);
                             node.remove();
-                        }}
-                    }}
-                }});
-            }});
-        }});
-        observer.observe(document.documentElement, {{ childList: true, subtree: true }});
-    }}
+                        }
+                    }
+                });
+            });
+        });
+        observer.observe(document.documentElement, { childList: true, subtree: true });
+    }
     
     // === UPGRADE INSECURE REQUESTS ===
-    if (config.upgradeInsecure) {{
+    if (config.upgradeInsecure) {
         // Intercept fetch requests
         const originalFetch = window.fetch;
-        window.fetch = function(url, options) {{
-            if (typeof url === 'string' && url.startsWith('http:')) {{
+        window.fetch = function(url, options) {
+            if (typeof url === 'string' && url.startsWith('http:')) {
                 url = url.replace('http:', 'https:');
                 console.log('[ServionX] Upgraded insecure request:', url);
-            }}
+            }
             return originalFetch.call(this, url, options);
-        }};
+        };
         
         // Intercept XMLHttpRequest
         const originalOpen = XMLHttpRequest.prototype.open;
-        XMLHttpRequest.prototype.open = function(method, url, ...args) {{
-            if (typeof url === 'string' && url.startsWith('http:')) {{
+        XMLHttpRequest.prototype.open = function(method, url, ...args) {
+            if (typeof url === 'string' && url.startsWith('http:')) {
                 url = url.replace('http:', 'https:');
                 console.log('[ServionX] Upgraded XHR request:', url);
-            }}
+            }
             return originalOpen.call(this, method, url, ...args);
-        }};
-    }}
+        };
+    }
     
     // === COOKIE SECURITY ===
-    if (config.forceSameSite || config.forceSecure) {{
+    if (config.forceSameSite || config.forceSecure) {
         const originalCookieSetter = Object.getOwnPropertyDescriptor(Document.prototype, 'cookie').set;
-        Object.defineProperty(document, 'cookie', {{
-            set: function(cookieString) {{
+        Object.defineProperty(document, 'cookie', {
+            set: function(cookieString) {
                 let enhanced = cookieString;
                 
                 // Force SameSite=Strict
-                if (config.forceSameSite && !enhanced.includes('SameSite')) {{
+                if (config.forceSameSite && !enhanced.includes('SameSite')) {
                     enhanced += '; SameSite=Strict';
-                }}
+                }
                 
                 // Force Secure flag
-                if (config.forceSecure && window.location.protocol === 'https:' && !enhanced.includes('Secure')) {{
+                if (config.forceSecure && window.location.protocol === 'https:' && !enhanced.includes('Secure')) {
                     enhanced += '; Secure';
-                }}
+                }
                 
                 return originalCookieSetter.call(this, enhanced);
-            }},
+            },
             get: Object.getOwnPropertyDescriptor(Document.prototype, 'cookie').get,
             configurable: true
-        }});
+        });
         console.log('[ServionX] Cookie security enhanced');
-    }}
+    }
     
     // === THIRD-PARTY COOKIE BLOCKING ===
-    if (config.blockThirdParty) {{
+    if (config.blockThirdParty) {
         // Block third-party iframes from accessing cookies
-        if (window.self !== window.top) {{
+        if (window.self !== window.top) {
             const parentHost = new URL(document.referrer).hostname;
             const currentHost = window.location.hostname;
-            if (parentHost !== currentHost) {{
-                Object.defineProperty(document, 'cookie', {{
+            if (parentHost !== currentHost) {
+                Object.defineProperty(document, 'cookie', {
                     get: () => '',
-                    set: () => {{}},
+                    set: () => {},
                     configurable: false
-                }});
+                });
                 console.log('[ServionX] Third-party cookies blocked');
-            }}
-        }}
-    }}
+            }
+        }
+    }
     
     // === DATA URL BLOCKING IN FRAMES ===
-    if (config.blockDataUrls) {{
+    if (config.blockDataUrls) {
         // Block data: URLs in iframes
-        const observer = new MutationObserver((mutations) => {{
-            mutations.forEach((mutation) => {{
-                mutation.addedNodes.forEach((node) => {{
-                    if (node.tagName === 'IFRAME') {{
+        const observer = new MutationObserver((mutations) => {
+            mutations.forEach((mutation) => {
+                mutation.addedNodes.forEach((node) => {
+                    if (node.tagName === 'IFRAME') {
                         const src = node.src;
-                        if (src && (src.startsWith('data:') || src.startsWith('javascript:'))) {{
+                        if (src && (src.startsWith('data:') || src.startsWith('javascript:'))) {
                             console.warn('[ServionX] Blocked dangerous iframe:', src.substring(0, 50));
                             node.remove();
-                        }}
-                    }}
-                }});
-            }});
-        }});
-        observer.observe(document.documentElement, {{ childList: true, subtree: true }});
-    }}
+                        }
+                    }
+                });
+            });
+        });
+        observer.observe(document.documentElement, { childList: true, subtree: true });
+    }
     
     // === REFERRER POLICY ===
     // Set strict referrer policy
@@ -222,15 +223,15 @@ impl NetworkSecurity {
     console.log('[ServionX] Network security active');
     console.log('[ServionX] Mixed content blocking: ' + config.blockMixedContent);
     console.log('[ServionX] Cookie hardening: SameSite=' + config.forceSameSite + ', Secure=' + config.forceSecure);
-}})();
-"#,
-            block_mixed = self.block_mixed_content,
-            upgrade_insecure = self.upgrade_insecure_requests,
-            force_samesite = self.force_samesite_strict,
-            force_secure = self.force_secure_cookies,
-            block_third_party = self.block_third_party_cookies,
-            block_data_urls = self.block_data_urls_in_frames,
-        )
+})();
+"#)
+            .bind("block_mixed", &self.block_mixed_content)
+            .bind("upgrade_insecure", &self.upgrade_insecure_requests)
+            .bind("force_samesite", &self.force_samesite_strict)
+            .bind("force_secure", &self.force_secure_cookies)
+            .bind("block_third_party", &self.block_third_party_cookies)
+            .bind("block_data_urls", &self.block_data_urls_in_frames)
+            .render()
     }
     
     /// Check if URL should be blocked
@@ -25,11 +25,10 @@ pub struct UserProfile {
     pub logs_salt: String,
 }
 
-/// Profile settings (stored encrypted)
+/// Profile settings (stored encrypted). Identity rotation is configured in
+/// `BrowserSettings` only.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ProfileSettings {
-    pub auto_regenerate_identity: bool,
-    pub regenerate_interval_minutes: u32,
     pub block_webrtc: bool,
     pub block_geolocation: bool,
     pub strip_upload_metadata: bool,
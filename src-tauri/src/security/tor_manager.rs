@@ -100,6 +100,13 @@ impl TorManager {
         }
     }
     
+    /// SOCKS proxy URL that also resolves host names through Tor, for
+    /// HTTP clients, so lookups don't leak to the local resolver
+    pub fn get_remote_dns_proxy_url(&self) -> Option<String> {
+        let config = self.config.read().unwrap();
+        config.enabled.then(|| format!("socks5h://127.0.0.1:{}", config.socks_port))
+    }
+    
     /// Get current circuit info
     pub fn get_circuit(&self) -> Option<TorCircuit> {
         self.current_circuit.read().unwrap().clone()
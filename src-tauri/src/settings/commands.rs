@@ -49,6 +49,35 @@ pub fn toggle_setting(
     Ok(new_value)
 }
 
+/// Set the automatic identity rotation interval in minutes (0 disables it)
+#[tauri::command]
+pub fn set_regenerate_interval(
+    settings: State<SettingsManager>,
+    minutes: u32,
+) -> Result<(), String> {
+    settings.set_regenerate_interval(minutes)
+}
+
+/// Set how many distinct high-entropy fingerprinting APIs one page load may
+/// use before its site is escalated (1-4)
+#[tauri::command]
+pub fn set_escalation_threshold(
+    settings: State<SettingsManager>,
+    apis: u32,
+) -> Result<(), String> {
+    settings.set_escalation_threshold(apis)
+}
+
+/// Set how many minutes a site without open tabs keeps its cookies (0 keeps
+/// them); sites with a cookie rule follow their rule instead
+#[tauri::command]
+pub fn set_cookie_cleanup_minutes(
+    settings: State<SettingsManager>,
+    minutes: u32,
+) -> Result<(), String> {
+    settings.set_cookie_cleanup_minutes(minutes)
+}
+
 /// Reset all settings to defaults
 #[tauri::command]
 pub fn reset_settings(settings: State<SettingsManager>) -> Result<BrowserSettings, String> {
@@ -3,9 +3,12 @@
 
 pub mod commands;
 
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
+use crate::privacy::geolocation::LocationMode;
+use crate::privacy::request_headers::IpHeaderPolicy;
 
 /// Browser settings - persisted to disk
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,16 +20,61 @@ pub struct BrowserSettings {
     pub block_malware: bool,
     pub scan_downloads: bool,
     pub block_ads: bool,
+    #[serde(default = "default_true")]
+    pub block_phishing: bool,
+    #[serde(default = "default_true")]
+    pub block_social: bool,  // Social network pixels, embeds and share widgets
+    #[serde(default = "default_true")]
+    pub block_cryptominers: bool,
     
     // Privacy settings
     pub strip_metadata: bool,
     pub block_webrtc: bool,
     pub fake_geolocation: bool,
+    #[serde(default)]
+    pub location_mode: LocationMode,  // Where the spoofed location comes from
     pub spoof_fingerprint: bool,
+    #[serde(default)]
+    pub send_ip_headers: bool,  // Spoofed X-Forwarded-For & co. on same-origin fetch/XHR only
+    #[serde(default)]
+    pub ip_header_sites: HashMap<String, IpHeaderPolicy>,  // Per-site override of send_ip_headers
     pub strip_referrer: bool,
     pub partition_storage: bool,
     pub auto_regenerate_identity: bool,
     pub secure_keyboard: bool,
+    
+    // Identity rotation (only acted on when auto_regenerate_identity is on)
+    #[serde(default = "default_regenerate_interval")]
+    pub regenerate_interval_minutes: u32,  // 0 = no timed rotation
+    #[serde(default = "default_true")]
+    pub regenerate_on_restart: bool,
+    #[serde(default = "default_true")]
+    pub regenerate_on_site_close: bool,
+    
+    // Escalation of sites that fingerprint aggressively
+    #[serde(default = "default_true")]
+    pub auto_escalate: bool,
+    #[serde(default = "default_escalation_threshold")]
+    pub escalation_threshold: u32,  // Distinct high-entropy APIs in one page load
+    #[serde(default)]
+    pub escalation_blocks_scripts: bool,  // Also blocklist the offending scripts
+    
+    // Cookies of sites without a rule are deleted once the site had no open
+    // tab for this long
+    #[serde(default)]
+    pub cookie_cleanup_minutes: u32,  // 0 = keep them
+}
+
+fn default_regenerate_interval() -> u32 {
+    60
+}
+
+fn default_escalation_threshold() -> u32 {
+    4
+}
+
+fn default_true() -> bool {
+    true
 }
 
 impl Default for BrowserSettings {
@@ -39,16 +87,32 @@ impl Default for BrowserSettings {
             block_malware: true,
             scan_downloads: true,
             block_ads: true,
+            block_phishing: true,
+            block_social: true,
+            block_cryptominers: true,
             
             // Privacy - all on by default
             strip_metadata: true,
             block_webrtc: true,
             fake_geolocation: true,
+            location_mode: LocationMode::Random,
             spoof_fingerprint: true,
+            send_ip_headers: false,  // Only partial coverage, and a fingerprint of its own
+            ip_header_sites: HashMap::new(),
             strip_referrer: true,
             partition_storage: true,
             auto_regenerate_identity: false,  // Manual regeneration by default
             secure_keyboard: true,
+            
+            regenerate_interval_minutes: default_regenerate_interval(),
+            regenerate_on_restart: true,
+            regenerate_on_site_close: true,
+            
+            auto_escalate: true,
+            escalation_threshold: default_escalation_threshold(),
+            escalation_blocks_scripts: false,
+            
+            cookie_cleanup_minutes: 0,
         }
     }
 }
@@ -123,14 +187,22 @@ impl SettingsManager {
                 "blockMalware" => settings.block_malware = value,
                 "scanDownloads" => settings.scan_downloads = value,
                 "blockAds" => settings.block_ads = value,
+                "blockPhishing" => settings.block_phishing = value,
+                "blockSocial" => settings.block_social = value,
+                "blockCryptominers" => settings.block_cryptominers = value,
                 "stripMetadata" => settings.strip_metadata = value,
                 "blockWebRTC" => settings.block_webrtc = value,
                 "fakeGeolocation" => settings.fake_geolocation = value,
+                "sendIpHeaders" => settings.send_ip_headers = value,
                 "spoofFingerprint" => settings.spoof_fingerprint = value,
                 "stripReferrer" => settings.strip_referrer = value,
                 "partitionStorage" => settings.partition_storage = value,
                 "autoRegenerateIdentity" => settings.auto_regenerate_identity = value,
                 "secureKeyboard" => settings.secure_keyboard = value,
+                "regenerateOnRestart" => settings.regenerate_on_restart = value,
+                "regenerateOnSiteClose" => settings.regenerate_on_site_close = value,
+                "autoEscalate" => settings.auto_escalate = value,
+                "escalationBlocksScripts" => settings.escalation_blocks_scripts = value,
                 _ => return Err(format!("Unknown setting: {}", key)),
             }
         }
@@ -149,18 +221,79 @@ impl SettingsManager {
             "blockMalware" => Ok(settings.block_malware),
             "scanDownloads" => Ok(settings.scan_downloads),
             "blockAds" => Ok(settings.block_ads),
+            "blockPhishing" => Ok(settings.block_phishing),
+            "blockSocial" => Ok(settings.block_social),
+            "blockCryptominers" => Ok(settings.block_cryptominers),
             "stripMetadata" => Ok(settings.strip_metadata),
             "blockWebRTC" => Ok(settings.block_webrtc),
             "fakeGeolocation" => Ok(settings.fake_geolocation),
+            "sendIpHeaders" => Ok(settings.send_ip_headers),
             "spoofFingerprint" => Ok(settings.spoof_fingerprint),
             "stripReferrer" => Ok(settings.strip_referrer),
             "partitionStorage" => Ok(settings.partition_storage),
             "autoRegenerateIdentity" => Ok(settings.auto_regenerate_identity),
             "secureKeyboard" => Ok(settings.secure_keyboard),
+            "regenerateOnRestart" => Ok(settings.regenerate_on_restart),
+            "regenerateOnSiteClose" => Ok(settings.regenerate_on_site_close),
+            "autoEscalate" => Ok(settings.auto_escalate),
+            "escalationBlocksScripts" => Ok(settings.escalation_blocks_scripts),
             _ => Err(format!("Unknown setting: {}", key)),
         }
     }
     
+    /// Set how often identities rotate automatically (0 disables timed rotation)
+    pub fn set_regenerate_interval(&self, minutes: u32) -> Result<(), String> {
+        self.settings.write().unwrap().regenerate_interval_minutes = minutes;
+        self.save_to_disk()?;
+        log::info!("Identity rotation interval set to {} minutes", minutes);
+        Ok(())
+    }
+    
+    /// Set how long a closed site keeps its cookies (0 keeps them)
+    pub fn set_cookie_cleanup_minutes(&self, minutes: u32) -> Result<(), String> {
+        self.settings.write().unwrap().cookie_cleanup_minutes = minutes;
+        self.save_to_disk()?;
+        log::info!("Cookie cleanup delay set to {} minutes", minutes);
+        Ok(())
+    }
+    
+    /// Set how many distinct high-entropy APIs one page load may use before
+    /// its site is escalated
+    pub fn set_escalation_threshold(&self, apis: u32) -> Result<(), String> {
+        if !(1..=4).contains(&apis) {
+            return Err(format!("Escalation threshold must be between 1 and 4, got {}", apis));
+        }
+        self.settings.write().unwrap().escalation_threshold = apis;
+        self.save_to_disk()?;
+        log::info!("Escalation threshold set to {} APIs", apis);
+        Ok(())
+    }
+    
+    /// Set how the spoofed location is chosen
+    pub fn set_location_mode(&self, mode: LocationMode) -> Result<(), String> {
+        self.settings.write().unwrap().location_mode = mode;
+        self.save_to_disk()?;
+        log::info!("Location mode updated");
+        Ok(())
+    }
+    
+    /// Override whether `site` gets the spoofed IP headers; `None` falls
+    /// back to the global setting
+    pub fn set_site_ip_header_policy(&self, site: &str, policy: Option<IpHeaderPolicy>) -> Result<(), String> {
+        let site = crate::privacy::site::registrable_domain(site)
+            .ok_or_else(|| format!("Invalid site: {}", site))?;
+        {
+            let mut settings = self.settings.write().unwrap();
+            match policy {
+                Some(policy) => settings.ip_header_sites.insert(site.clone(), policy),
+                None => settings.ip_header_sites.remove(&site),
+            };
+        }
+        self.save_to_disk()?;
+        log::info!("IP header policy for {}: {:?}", site, policy);
+        Ok(())
+    }
+    
     // Convenience methods for checking settings
     pub fn is_tor_enabled(&self) -> bool {
         self.settings.read().unwrap().tor_enabled
@@ -207,4 +340,32 @@ mod tests {
         assert!(settings.block_malware);
         assert!(!settings.tor_enabled); // Tor off by default
     }
+    
+    #[test]
+    fn test_settings_without_rotation_fields_load() {
+        // settings.json written before identity rotation existed
+        let mut json = serde_json::to_value(BrowserSettings::default()).unwrap();
+        let obj = json.as_object_mut().unwrap();
+        obj.remove("regenerate_interval_minutes");
+        obj.remove("regenerate_on_restart");
+        obj.remove("regenerate_on_site_close");
+        obj.remove("location_mode");
+        obj.remove("send_ip_headers");
+        obj.remove("ip_header_sites");
+        obj.remove("auto_escalate");
+        obj.remove("escalation_threshold");
+        obj.remove("escalation_blocks_scripts");
+        obj.remove("block_phishing");
+        obj.remove("block_social");
+        obj.remove("block_cryptominers");
+        
+        let settings: BrowserSettings = serde_json::from_value(json).unwrap();
+        assert_eq!(settings.regenerate_interval_minutes, 60);
+        assert!(settings.regenerate_on_restart);
+        assert_eq!(settings.location_mode, LocationMode::Random);
+        assert!(!settings.send_ip_headers);
+        assert!(settings.auto_escalate);
+        assert_eq!(settings.escalation_threshold, 4);
+        assert!(settings.block_phishing && settings.block_social && settings.block_cryptominers);
+    }
 }
@@ -1,12 +1,13 @@
 import { useState, useEffect, useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import BrowserChrome from './components/BrowserChrome';
 import NewTabPage from './components/NewTabPage';
 import SettingsPanel from './components/SettingsPanel';
 import ITToolsPanel from './components/ITToolsPanel';
 import VirtualKeyboard from './components/VirtualKeyboard';
 import DownloadManager from './components/DownloadManager';
-import { Tab, PrivacyStatus, FakeFingerprint, FakeGeolocation, FakeUserAgent } from './lib/types';
+import { Tab, PrivacyStatus, FakeFingerprint, FakeGeolocation, FakeUserAgent, IdentityRotatedEvent } from './lib/types';
 
 interface PrivacyDetails {
   fingerprint: FakeFingerprint | null;
@@ -68,6 +69,31 @@ function App() {
     initPrivacy();
   }, []);
 
+  // Refresh privacy details whenever the backend rotates the identity
+  useEffect(() => {
+    const unlisten = listen<IdentityRotatedEvent>('identity-rotated', async (event) => {
+      if (!event.payload.identityId) return;
+      try {
+        const [fingerprint, geolocation, userAgent] = await Promise.all([
+          invoke<FakeFingerprint>('get_fake_fingerprint'),
+          invoke<FakeGeolocation>('get_fake_geolocation'),
+          invoke<FakeUserAgent>('get_fake_user_agent'),
+        ]);
+        setPrivacyDetails({ fingerprint, geolocation, userAgent });
+        setPrivacyStatus((prev: PrivacyStatus) => ({
+          ...prev,
+          identityId: fingerprint.sessionId,
+        }));
+      } catch (error) {
+        console.error('Failed to refresh rotated identity:', error);
+      }
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
   // Tab management functions
   const addTab = useCallback(() => {
     const newTab: Tab = {
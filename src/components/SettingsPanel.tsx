@@ -59,6 +59,9 @@ const SettingsPanel: FC<SettingsPanelProps> = ({
         autoRegenerateIdentity: false,
         secureKeyboard: true,
         blockAds: true,
+        blockPhishing: true,
+        blockSocial: true,
+        blockCryptominers: true,
     });
 
     // Authenticate settings access
@@ -98,6 +101,9 @@ const SettingsPanel: FC<SettingsPanelProps> = ({
                 autoRegenerateIdentity: backendSettings.auto_regenerate_identity ?? false,
                 secureKeyboard: backendSettings.secure_keyboard ?? true,
                 blockAds: backendSettings.block_ads ?? true,
+                blockPhishing: backendSettings.block_phishing ?? true,
+                blockSocial: backendSettings.block_social ?? true,
+                blockCryptominers: backendSettings.block_cryptominers ?? true,
             });
             console.log('Settings loaded from backend:', backendSettings);
         } catch (error) {
@@ -556,8 +562,11 @@ const SettingsPanel: FC<SettingsPanelProps> = ({
                         </div>
 
                         <SettingToggle label="HTTPS Only" description="Force secure connections" icon="🔒" checked={settings.httpsOnly} onChange={() => toggleSetting('httpsOnly')} />
-                        <SettingToggle label="Block Trackers" description="94+ tracking domains blocked" icon="🚫" checked={settings.blockTrackers} onChange={() => toggleSetting('blockTrackers')} />
-                        <SettingToggle label="Malware Protection" description="Block malicious URLs & phishing" icon="🦠" checked={settings.blockMalware} onChange={() => toggleSetting('blockMalware')} />
+                        <SettingToggle label="Block Trackers" description="Analytics, pixels & fingerprinting services" icon="🚫" checked={settings.blockTrackers} onChange={() => toggleSetting('blockTrackers')} />
+                        <SettingToggle label="Malware Protection" description="Block malicious URLs & downloads" icon="🦠" checked={settings.blockMalware} onChange={() => toggleSetting('blockMalware')} />
+                        <SettingToggle label="Phishing Protection" description="Block lookalike login pages" icon="🎣" checked={settings.blockPhishing} onChange={() => toggleSetting('blockPhishing')} />
+                        <SettingToggle label="Block Social Widgets" description="Social pixels, embeds & share buttons" icon="👥" checked={settings.blockSocial} onChange={() => toggleSetting('blockSocial')} />
+                        <SettingToggle label="Block Cryptominers" description="In-browser coin mining scripts" icon="⛏️" checked={settings.blockCryptominers} onChange={() => toggleSetting('blockCryptominers')} />
                         <SettingToggle label="Scan Downloads" description="SHA256 hash verification" icon="📥" checked={settings.scanDownloads} onChange={() => toggleSetting('scanDownloads')} />
                         <SettingToggle label="Block Ads" description="EasyList-compatible ad blocking" icon="🚫" checked={settings.blockAds} onChange={() => toggleSetting('blockAds')} />
                     </div>
@@ -28,6 +28,14 @@ export interface FakeFingerprint {
     plugins: FakePlugin[];
     maxTouchPoints: number;
     touchSupport: boolean;
+    screen: FakeScreen;
+}
+
+export interface FakeScreen {
+    width: number;
+    height: number;
+    colorDepth: number;
+    pixelRatio: number;
 }
 
 export interface FakePlugin {
@@ -43,6 +51,243 @@ export interface FakeGeolocation {
     city: string;
     country: string;
     countryCode: string;
+    timezone: string;
+    route?: GeoRoute | null;
+}
+
+export interface RoutePoint {
+    latitude: number;
+    longitude: number;
+    elapsedSecs: number;
+}
+
+export interface GeoRoute {
+    name: string;
+    points: RoutePoint[];
+    loopRoute: boolean;
+}
+
+export type LocationMode =
+    | { mode: 'random' }
+    | { mode: 'city'; countryCode: string; city: string | null }
+    | { mode: 'coordinates'; latitude: number; longitude: number; accuracy: number | null }
+    | ({ mode: 'route' } & GeoRoute);
+
+export interface LocationInfo {
+    city: string;
+    country: string;
+    countryCode: string;
+    timezone: string;
+    latitude: number;
+    longitude: number;
+}
+
+export type IpHeaderPolicy = 'send' | 'omit';
+
+export interface OutgoingHeaders {
+    url: string;
+    ipPolicy: IpHeaderPolicy | null;
+    headers: [string, string][];
+}
+
+export type FingerprintingType =
+    | 'canvas' | 'webgl' | 'audio' | 'font' | 'hardware'
+    | 'battery' | 'webrtc' | 'timezone' | 'language' | 'screen';
+
+export type FingerprintingVerdict = 'none' | 'suspicious' | 'fingerprinting';
+
+export interface ScriptActivity {
+    scriptUrl: string;
+    apis: FingerprintingType[];
+    calls: number;
+}
+
+export interface SiteFingerprinting {
+    site: string;
+    apiCalls: Partial<Record<FingerprintingType, number>>;
+    scripts: ScriptActivity[];
+    pageLoads: number;
+    lastLoadApis: FingerprintingType[];
+    score: number;
+    verdict: FingerprintingVerdict;
+    firstSeen: string;
+    lastSeen: string;
+}
+
+export interface WhitelistEntry {
+    pattern: string;
+    addedAt: string;
+    expiresAt: string | null;
+}
+
+export type WhitelistFormat = 'text' | 'json';
+
+export type ReferrerPolicy = 'noReferrer' | 'originOnly' | 'sameOrigin' | 'full';
+
+export interface ProtectionLayers {
+    canvasNoise: boolean;
+    webglSpoof: boolean;
+    uaSpoof: boolean;
+    storagePartitioning: boolean;
+    referrerPolicy: ReferrerPolicy;
+    trackerBlocking: boolean;
+    javascript: boolean;
+}
+
+export type ProfilePreset = 'strict' | 'balanced' | 'bankingCompatible';
+
+export interface SiteProfile {
+    site: string;
+    preset: ProfilePreset | null;
+    layers: ProtectionLayers;
+    updatedAt: string;
+}
+
+export interface EscalationDecision {
+    id: string;
+    site: string;
+    decidedAt: string;
+    apis: FingerprintingType[];
+    blockedScripts: string[];
+    previousProfile: SiteProfile | null;
+    layers: ProtectionLayers;
+    revertedAt: string | null;
+}
+
+export interface PartitionUsage {
+    site: string;
+    container: string | null;
+    bytes: number;
+    createdAt: string;
+    lastUsed: string;
+}
+
+export type CookieRule = 'allow' | 'sessionOnly' | 'block';
+
+export interface CookieRuleEntry {
+    site: string;
+    rule: CookieRule;
+}
+
+export interface SiteCookie {
+    name: string;
+    value: string;
+    domain: string;
+    path: string;
+    site: string;
+    container: string | null;
+    secure: boolean;
+    httpOnly: boolean;
+    sameSite: string | null;
+    expires: string | null;
+}
+
+export interface CookieCleanupReport {
+    cookiesDeleted: number;
+    sites: string[];
+    partitionsCleared: string[];
+}
+
+export type FeedFormat = 'adblock' | 'hosts' | 'phishTank';
+
+export type BlockCategory = 'ads' | 'trackers' | 'malware' | 'phishing' | 'social' | 'cryptominers';
+
+export interface FeedStatus {
+    name: string;
+    url: string;
+    format: FeedFormat;
+    category: BlockCategory;
+    rules: number;
+    fetchedAt: number | null;
+    notModified: boolean;
+    error: string | null;
+}
+
+export type ResourceType =
+    | 'document' | 'subdocument' | 'script' | 'image' | 'stylesheet' | 'font' | 'media'
+    | 'object' | 'xmlHttpRequest' | 'webSocket' | 'ping' | 'popup' | 'other';
+
+export interface RuleMatch {
+    category: BlockCategory;
+    list: string;
+    rule: string;
+}
+
+export type BlockVerdict =
+    | { kind: 'noMatch' }
+    | { kind: 'blocked'; match: RuleMatch }
+    | { kind: 'allowed'; match: RuleMatch }
+    | { kind: 'suspicious'; match: RuleMatch };
+
+export interface Container {
+    id: string;
+    name: string;
+    color: string;
+    ephemeral: boolean;
+    identity: FakeIdentity;
+    createdAt: string;
+}
+
+export type ProbeStatus = 'rotating' | 'static' | 'leaked' | 'unavailable';
+
+export interface ProbeResult {
+    probe: string;
+    status: ProbeStatus;
+    real: unknown;
+    values: unknown[];
+}
+
+export interface SelfTestInconsistency {
+    identityId: string;
+    check: string;
+    message: string;
+}
+
+export interface SelfTestReport {
+    version: string;
+    generatedAt: string;
+    layers: ProtectionLayers;
+    identities: string[];
+    probes: ProbeResult[];
+    inconsistencies: SelfTestInconsistency[];
+    summary: {
+        rotating: number;
+        staticValues: number;
+        leaked: number;
+        unavailable: number;
+        inconsistencies: number;
+    };
+}
+
+export interface SelfTestDiff {
+    fromVersion: string;
+    toVersion: string;
+    changed: { probe: string; before: ProbeStatus | null; after: ProbeStatus | null }[];
+    newInconsistencies: string[];
+    resolvedInconsistencies: string[];
+}
+
+export interface CachedScript {
+    key: string;
+    identityId: string;
+    modules: string[];
+    originalBytes: number;
+    script: string;
+    builtAt: string;
+}
+
+export interface ScriptCacheStats {
+    entries: number;
+    hits: number;
+    misses: number;
+}
+
+export interface SiteScriptInfo {
+    url: string;
+    site: string | null;
+    cached: boolean;
+    cacheStats: ScriptCacheStats;
+    script: CachedScript;
 }
 
 export interface FakeUserAgent {
@@ -52,20 +297,71 @@ export interface FakeUserAgent {
     vendor: string;
     browserName: string;
     browserVersion: string;
+    fullVersion: string;
     osName: string;
     osVersion: string;
 }
 
+export interface ClientHintsData {
+    brands: [string, string][];
+    mobile: boolean;
+    platform: string;
+    platform_version: string;
+    architecture: string;
+    bitness: string;
+    model: string;
+    ua_full_version: string;
+    full_version_list: [string, string][];
+}
+
+export interface DatasetInfo {
+    schemaVersion: number;
+    revision: string;
+    source: string;
+    deviceCount: number;
+    isOverride: boolean;
+}
+
+export interface CoherenceViolation {
+    field: string;
+    message: string;
+}
+
 export interface FakeIdentity {
     id: string;
+    seed: number;
     createdAt: string;
     fingerprint: FakeFingerprint;
     geolocation: FakeGeolocation;
     userAgent: FakeUserAgent;
     ipHeaders: Record<string, string>;
+    clientHints: ClientHintsData | null;
     timezone: string;
     language: string;
     doNotTrack: boolean;
+    noiseKey: string;
+}
+
+export interface SiteIdentity {
+    site: string;
+    identity: FakeIdentity;
+    pinned: boolean;
+    createdAt: string;
+    lastUsed: string;
+}
+
+export type RotationTrigger =
+    | { kind: 'interval' }
+    | { kind: 'restart' }
+    | { kind: 'siteClosed'; site: string }
+    | { kind: 'manual' };
+
+export interface IdentityRotatedEvent {
+    trigger: RotationTrigger;
+    identityId: string | null;
+    siteIdentitiesReset: number;
+    tabsRebuilt: number;
+    rotatedAt: string;
 }
 
 export interface KeyboardLayout {